@@ -0,0 +1,283 @@
+use crate::money::{Money, Rounding};
+use crate::Plan;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddonKind {
+    /// Extra NVMe, adding `storage_gb` per unit
+    Storage,
+    /// RAM upgrade, adding `ram_gb` per unit
+    Ram,
+    PublicIp,
+    /// Bandwidth tier; an order takes at most one
+    Bandwidth,
+    Backup,
+}
+
+impl AddonKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AddonKind::Storage => "storage",
+            AddonKind::Ram => "ram",
+            AddonKind::PublicIp => "public_ip",
+            AddonKind::Bandwidth => "bandwidth",
+            AddonKind::Backup => "backup",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "storage" => Some(AddonKind::Storage),
+            "ram" => Some(AddonKind::Ram),
+            "public_ip" => Some(AddonKind::PublicIp),
+            "bandwidth" => Some(AddonKind::Bandwidth),
+            "backup" => Some(AddonKind::Backup),
+            _ => None,
+        }
+    }
+}
+
+/// An optional extra sold with a package. Prices are per unit; setup is
+/// charged once, monthly with hosting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageAddon {
+    pub id: i32,
+    pub package_id: Uuid,
+    pub code: String,
+    pub kind: AddonKind,
+    pub name: String,
+    pub storage_gb: u32,
+    pub ram_gb: u16,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+    pub max_quantity: u32,
+    pub is_active: bool,
+}
+
+/// Admin request to offer an add-on with a package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateAddonRequest {
+    pub code: String,
+    pub kind: AddonKind,
+    pub name: String,
+    #[serde(default)]
+    pub storage_gb: u32,
+    #[serde(default)]
+    pub ram_gb: u16,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+    #[serde(default = "default_quantity")]
+    pub max_quantity: u32,
+}
+
+impl CreateAddonRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_addon_code(&self.code)?;
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        match self.kind {
+            AddonKind::Storage if self.storage_gb == 0 || self.ram_gb != 0 => {
+                return Err("a storage add-on adds storage_gb and no RAM".to_string());
+            }
+            AddonKind::Ram if self.ram_gb == 0 || self.storage_gb != 0 => {
+                return Err("a RAM add-on adds ram_gb and no storage".to_string());
+            }
+            AddonKind::PublicIp | AddonKind::Bandwidth | AddonKind::Backup
+                if self.storage_gb != 0 || self.ram_gb != 0 =>
+            {
+                return Err(format!(
+                    "a {} add-on adds no storage or RAM",
+                    self.kind.as_str()
+                ));
+            }
+            _ => {}
+        }
+        check_addon_terms(
+            self.kind,
+            self.setup_price_usdc,
+            self.monthly_price_usdc,
+            self.max_quantity,
+        )
+    }
+}
+
+/// Admin changes to an add-on; code, kind and sizes are fixed once sold
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateAddonRequest {
+    pub name: Option<String>,
+    pub setup_price_usdc: Option<Money>,
+    pub monthly_price_usdc: Option<Money>,
+    pub max_quantity: Option<u32>,
+    pub is_active: Option<bool>,
+}
+
+impl UpdateAddonRequest {
+    /// The add-on with the changes applied, checked
+    pub fn apply(&self, current: &PackageAddon) -> Result<PackageAddon, String> {
+        let updated = PackageAddon {
+            name: self
+                .name
+                .as_deref()
+                .map(str::trim)
+                .unwrap_or(&current.name)
+                .to_string(),
+            setup_price_usdc: self.setup_price_usdc.unwrap_or(current.setup_price_usdc),
+            monthly_price_usdc: self
+                .monthly_price_usdc
+                .unwrap_or(current.monthly_price_usdc),
+            max_quantity: self.max_quantity.unwrap_or(current.max_quantity),
+            is_active: self.is_active.unwrap_or(current.is_active),
+            ..current.clone()
+        };
+        if updated.name.is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        check_addon_terms(
+            updated.kind,
+            updated.setup_price_usdc,
+            updated.monthly_price_usdc,
+            updated.max_quantity,
+        )?;
+        Ok(updated)
+    }
+}
+
+fn check_addon_terms(
+    kind: AddonKind,
+    setup_price: Money,
+    monthly_price: Money,
+    max_quantity: u32,
+) -> Result<(), String> {
+    if setup_price.is_negative() || monthly_price.is_negative() {
+        return Err("prices must not be negative".to_string());
+    }
+    if max_quantity == 0 {
+        return Err("max_quantity must be at least 1".to_string());
+    }
+    if kind == AddonKind::Bandwidth && max_quantity != 1 {
+        return Err("bandwidth tiers are taken once; max_quantity must be 1".to_string());
+    }
+    Ok(())
+}
+
+/// Add-on codes are lowercase letters and digits in dash separated groups,
+/// e.g. `nvme-4tb`
+pub fn validate_addon_code(code: &str) -> Result<(), String> {
+    let valid = !code.is_empty()
+        && code.split('-').all(|group| {
+            !group.is_empty()
+                && group
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        });
+    if !valid {
+        return Err(format!(
+            "add-on code {code:?} must be lowercase letters and digits in dash separated groups"
+        ));
+    }
+    Ok(())
+}
+
+/// An add-on picked for each server of a quote item or order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddonSelection {
+    pub code: String,
+    #[serde(default = "default_quantity")]
+    pub quantity: u32,
+}
+
+fn default_quantity() -> u32 {
+    1
+}
+
+/// A selection checked against the package's add-ons
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectedAddon {
+    pub addon: PackageAddon,
+    pub quantity: u32,
+}
+
+impl SelectedAddon {
+    pub fn monthly_usdc(&self) -> Money {
+        self.addon
+            .monthly_price_usdc
+            .mul_ratio(i64::from(self.quantity), 1, Rounding::HalfUp)
+    }
+}
+
+/// An add-on line item of a placed order, at the terms it was ordered on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderAddon {
+    pub code: String,
+    pub kind: AddonKind,
+    pub name: String,
+    pub quantity: u32,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+}
+
+/// Match selections to the active add-ons of a package
+pub fn select_addons(
+    available: &[PackageAddon],
+    selections: &[AddonSelection],
+) -> Result<Vec<SelectedAddon>, String> {
+    let mut selected: Vec<SelectedAddon> = Vec::new();
+    for selection in selections {
+        let addon = available
+            .iter()
+            .find(|a| a.code == selection.code && a.is_active)
+            .ok_or_else(|| format!("add-on {} is not offered with this package", selection.code))?;
+        if selected.iter().any(|s| s.addon.code == addon.code) {
+            return Err(format!("add-on {} is selected twice", addon.code));
+        }
+        if selection.quantity == 0 || selection.quantity > addon.max_quantity {
+            return Err(format!(
+                "add-on {} can be taken 1 to {} times",
+                addon.code, addon.max_quantity
+            ));
+        }
+        selected.push(SelectedAddon {
+            addon: addon.clone(),
+            quantity: selection.quantity,
+        });
+    }
+
+    if selected
+        .iter()
+        .filter(|s| s.addon.kind == AddonKind::Bandwidth)
+        .count()
+        > 1
+    {
+        return Err("only one bandwidth tier can be selected".to_string());
+    }
+
+    Ok(selected)
+}
+
+/// The plan with storage and RAM upgrades added
+pub fn upgraded_plan(plan: &Plan, selected: &[SelectedAddon]) -> Result<Plan, String> {
+    let mut storage_gb = plan.storage_gb;
+    let mut ram_gb = plan.ram_gb;
+    for s in selected {
+        storage_gb = s
+            .addon
+            .storage_gb
+            .checked_mul(s.quantity)
+            .and_then(|extra| storage_gb.checked_add(extra))
+            .ok_or("storage upgrades are out of range")?;
+        ram_gb = u32::from(s.addon.ram_gb)
+            .checked_mul(s.quantity)
+            .and_then(|extra| u16::try_from(extra).ok())
+            .and_then(|extra| ram_gb.checked_add(extra))
+            .ok_or("RAM upgrades are out of range")?;
+    }
+
+    Ok(Plan {
+        storage_gb,
+        ram_gb,
+        ..plan.clone()
+    })
+}
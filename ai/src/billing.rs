@@ -0,0 +1,388 @@
+use crate::money::{Money, Rounding};
+use crate::vat::VatAssessment;
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use uuid::Uuid;
+
+/// Line kind for the recurring monthly hosting fee of a server
+pub const LINE_KIND_HOSTING: &str = "hosting";
+/// Line kind for a monthly instalment of a financed setup price
+pub const LINE_KIND_INSTALMENT: &str = "instalment";
+
+/// A calendar month that invoices are generated for. `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BillingPeriod {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl BillingPeriod {
+    /// Billing period for the given calendar month
+    pub fn month(year: i32, month: u32) -> Option<Self> {
+        let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+        let end = start.checked_add_months(Months::new(1))?;
+        Some(Self { start, end })
+    }
+
+    /// Billing period for the month containing `date`
+    pub fn containing(date: NaiveDate) -> Self {
+        Self::month(date.year(), date.month()).expect("first day of a valid date's month")
+    }
+
+    /// Parse a period written as `YYYY-MM`
+    pub fn parse(value: &str) -> Option<Self> {
+        let (year, month) = value.split_once('-')?;
+        Self::month(year.parse().ok()?, month.parse().ok()?)
+    }
+
+    /// The billing period immediately after this one
+    pub fn next(&self) -> Self {
+        Self::containing(self.end)
+    }
+
+    /// The billing period immediately before this one
+    pub fn previous(&self) -> Self {
+        Self::containing(self.start - Days::new(1))
+    }
+
+    /// Number of days in the period
+    pub fn days(&self) -> u32 {
+        (self.end - self.start).num_days() as u32
+    }
+}
+
+impl fmt::Display for BillingPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}", self.start.year(), self.start.month())
+    }
+}
+
+/// A provisioned server that accrues the monthly hosting fee of its package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BillableServer {
+    pub server_id: Uuid,
+    pub org_id: Uuid,
+    pub hostname: String,
+    pub package_name: String,
+    pub monthly_price_usdc: Money,
+    pub activated_at: DateTime<Utc>,
+    pub decommissioned_at: Option<DateTime<Utc>>,
+}
+
+impl BillableServer {
+    /// Days of `period` the server is billable for, as a half-open date range.
+    /// Both the activation day and the decommission day are billed.
+    pub fn billable_range(&self, period: &BillingPeriod) -> Option<(NaiveDate, NaiveDate)> {
+        billable_range(self.activated_at, self.decommissioned_at, period)
+    }
+
+    /// Build the hosting line for `period`, prorated by activation and
+    /// decommission dates. Returns `None` if the server is not billable in it.
+    pub fn invoice_line(&self, period: &BillingPeriod) -> Option<InvoiceLine> {
+        let (service_start, service_end) = self.billable_range(period)?;
+        let billed_days = (service_end - service_start).num_days() as u32;
+        let period_days = period.days();
+
+        let description = if billed_days == period_days {
+            format!(
+                "{} ({}) hosting {}",
+                self.package_name, self.hostname, period
+            )
+        } else {
+            format!(
+                "{} ({}) hosting {}, prorated {}/{} days",
+                self.package_name, self.hostname, period, billed_days, period_days
+            )
+        };
+
+        Some(InvoiceLine {
+            server_id: Some(self.server_id),
+            kind: LINE_KIND_HOSTING.to_string(),
+            description,
+            service_start,
+            service_end,
+            billed_days,
+            period_days,
+            amount_usdc: self.monthly_price_usdc.mul_ratio(
+                billed_days as i64,
+                period_days as i64,
+                Rounding::HalfUp,
+            ),
+        })
+    }
+}
+
+/// A server whose setup price is being paid in instalments, with the next
+/// instalment due
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinancedServer {
+    pub server_id: Uuid,
+    pub org_id: Uuid,
+    pub hostname: String,
+    pub package_name: String,
+    pub instalment_number: u32,
+    pub term_months: u32,
+    pub amount_usdc: Money,
+    pub activated_at: DateTime<Utc>,
+}
+
+impl FinancedServer {
+    /// Build the instalment line for `period`. Instalments are not prorated:
+    /// one falls due in every period from the month the server is activated
+    /// until the term ends. Decommissioning the server does not stop them,
+    /// since the hardware is still owed.
+    pub fn invoice_line(&self, period: &BillingPeriod) -> Option<InvoiceLine> {
+        if self.instalment_number == 0 || self.instalment_number > self.term_months {
+            return None;
+        }
+        let (service_start, service_end) = billable_range(self.activated_at, None, period)?;
+
+        Some(InvoiceLine {
+            server_id: Some(self.server_id),
+            kind: LINE_KIND_INSTALMENT.to_string(),
+            description: format!(
+                "{} ({}) setup instalment {}/{}",
+                self.package_name, self.hostname, self.instalment_number, self.term_months
+            ),
+            service_start,
+            service_end,
+            billed_days: (service_end - service_start).num_days() as u32,
+            period_days: period.days(),
+            amount_usdc: self.amount_usdc,
+        })
+    }
+}
+
+/// Days of `period` between activation and decommissioning, as a half-open
+/// date range. Both the activation day and the decommission day count.
+fn billable_range(
+    activated_at: DateTime<Utc>,
+    decommissioned_at: Option<DateTime<Utc>>,
+    period: &BillingPeriod,
+) -> Option<(NaiveDate, NaiveDate)> {
+    let start = activated_at.date_naive().max(period.start);
+    let end = match decommissioned_at {
+        Some(at) => (at.date_naive() + Days::new(1)).min(period.end),
+        None => period.end,
+    };
+
+    (start < end).then_some((start, end))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceLine {
+    pub server_id: Option<Uuid>,
+    pub kind: String,
+    pub description: String,
+    pub service_start: NaiveDate,
+    pub service_end: NaiveDate, // Exclusive
+    pub billed_days: u32,
+    pub period_days: u32,
+    pub amount_usdc: Money,
+}
+
+/// An invoice computed by the billing engine but not yet persisted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DraftInvoice {
+    pub org_id: Uuid,
+    pub period: BillingPeriod,
+    pub lines: Vec<InvoiceLine>,
+    pub subtotal_usdc: Money,
+    pub vat_usdc: Money,
+    pub vat: Option<VatAssessment>,
+    pub total_usdc: Money,
+}
+
+impl DraftInvoice {
+    /// Add VAT on the subtotal according to `assessment`
+    pub fn apply_vat(&mut self, assessment: VatAssessment) {
+        self.vat_usdc = assessment.vat_on(self.subtotal_usdc);
+        self.total_usdc = self.subtotal_usdc + self.vat_usdc;
+        self.vat = Some(assessment);
+    }
+}
+
+/// Group the hosting lines of `servers` and the instalment lines of
+/// `financed` for `period` into one draft invoice per organization. Servers
+/// with nothing to bill in the period are skipped.
+pub fn draft_invoices(
+    period: &BillingPeriod,
+    servers: &[BillableServer],
+    financed: &[FinancedServer],
+) -> Vec<DraftInvoice> {
+    let mut by_org: BTreeMap<Uuid, Vec<InvoiceLine>> = BTreeMap::new();
+
+    for server in servers {
+        if let Some(line) = server.invoice_line(period) {
+            by_org.entry(server.org_id).or_default().push(line);
+        }
+    }
+
+    for server in financed {
+        if let Some(line) = server.invoice_line(period) {
+            by_org.entry(server.org_id).or_default().push(line);
+        }
+    }
+
+    by_org
+        .into_iter()
+        .map(|(org_id, lines)| {
+            let subtotal: Money = lines.iter().map(|l| l.amount_usdc).sum();
+            DraftInvoice {
+                org_id,
+                period: *period,
+                lines,
+                subtotal_usdc: subtotal,
+                vat_usdc: Money::ZERO,
+                vat: None,
+                total_usdc: subtotal,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invoice {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub period: BillingPeriod,
+    pub status: String,
+    pub subtotal_usdc: Money,
+    pub vat_usdc: Money,
+    /// `None` for invoices issued before VAT was charged
+    pub vat: Option<VatAssessment>,
+    pub total_usdc: Money,
+    /// Part of the total settled from account credit
+    pub credit_applied_usdc: Money,
+    pub issued_at: DateTime<Utc>,
+    pub due_at: DateTime<Utc>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub reminders_sent: u32,
+    pub suspended_at: Option<DateTime<Utc>>,
+    pub lines: Vec<InvoiceLine>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap()
+    }
+
+    fn server(
+        activated_at: DateTime<Utc>,
+        decommissioned_at: Option<DateTime<Utc>>,
+    ) -> BillableServer {
+        BillableServer {
+            server_id: Uuid::new_v4(),
+            org_id: Uuid::new_v4(),
+            hostname: "gpu-1".to_string(),
+            package_name: "Workstation".to_string(),
+            monthly_price_usdc: Money::from_usdc(310),
+            activated_at,
+            decommissioned_at,
+        }
+    }
+
+    #[test]
+    fn periods_cross_year_boundaries() {
+        let january = BillingPeriod::containing(NaiveDate::from_ymd_opt(2027, 1, 15).unwrap());
+        assert_eq!(january.previous(), BillingPeriod::month(2026, 12).unwrap());
+        assert_eq!(january.previous().next(), january);
+        assert_eq!(january.previous().to_string(), "2026-12");
+
+        assert_eq!(BillingPeriod::month(2028, 2).unwrap().days(), 29);
+        assert_eq!(BillingPeriod::month(2027, 2).unwrap().days(), 28);
+        assert_eq!(BillingPeriod::parse("2027-13"), None);
+    }
+
+    #[test]
+    fn a_server_running_all_month_pays_the_full_fee() {
+        let period = BillingPeriod::month(2027, 3).unwrap();
+        let line = server(at(2027, 1, 20), None).invoice_line(&period).unwrap();
+
+        assert_eq!((line.billed_days, line.period_days), (31, 31));
+        assert_eq!(line.service_start, period.start);
+        assert_eq!(line.service_end, period.end);
+        assert_eq!(line.amount_usdc, Money::from_usdc(310));
+        assert!(!line.description.contains("prorated"));
+    }
+
+    #[test]
+    fn activation_and_decommission_days_are_both_billed() {
+        let period = BillingPeriod::month(2027, 3).unwrap();
+
+        // Activated on the last day of the month
+        let line = server(at(2027, 3, 31), None).invoice_line(&period).unwrap();
+        assert_eq!(line.billed_days, 1);
+        assert_eq!(line.amount_usdc, Money::from_usdc(10));
+
+        // Decommissioned on the first day of the month
+        let line = server(at(2027, 1, 5), Some(at(2027, 3, 1)))
+            .invoice_line(&period)
+            .unwrap();
+        assert_eq!(line.billed_days, 1);
+        assert_eq!(
+            line.service_end,
+            NaiveDate::from_ymd_opt(2027, 3, 2).unwrap()
+        );
+
+        // Activated and decommissioned the same day
+        let line = server(at(2027, 3, 10), Some(at(2027, 3, 10)))
+            .invoice_line(&period)
+            .unwrap();
+        assert_eq!(line.billed_days, 1);
+        assert!(line.description.contains("prorated 1/31 days"));
+    }
+
+    #[test]
+    fn servers_outside_the_period_are_not_billed() {
+        let period = BillingPeriod::month(2027, 3).unwrap();
+
+        assert!(server(at(2027, 4, 1), None).invoice_line(&period).is_none());
+        assert!(server(at(2027, 1, 5), Some(at(2027, 2, 28)))
+            .invoice_line(&period)
+            .is_none());
+    }
+
+    #[test]
+    fn partial_months_are_prorated_by_days_and_rounded_half_up() {
+        // 21 of 31 days: 310 * 21 / 31
+        let march = BillingPeriod::month(2027, 3).unwrap();
+        let line = server(at(2027, 3, 11), None).invoice_line(&march).unwrap();
+        assert_eq!(line.billed_days, 21);
+        assert_eq!(line.amount_usdc, Money::from_usdc(210));
+
+        // 15 of 29 days in a leap February: 310 * 15 / 29 = 160.3448275...
+        let february = BillingPeriod::month(2028, 2).unwrap();
+        let line = server(at(2028, 2, 15), None)
+            .invoice_line(&february)
+            .unwrap();
+        assert_eq!((line.billed_days, line.period_days), (15, 29));
+        assert_eq!(line.amount_usdc, Money::from_micro(160_344_828));
+    }
+
+    #[test]
+    fn draft_invoices_group_lines_by_organization() {
+        let period = BillingPeriod::month(2027, 3).unwrap();
+        let first = server(at(2027, 1, 1), None);
+        let second = BillableServer {
+            server_id: Uuid::new_v4(),
+            activated_at: at(2027, 3, 11),
+            ..first.clone()
+        };
+        let other_org = server(at(2027, 1, 1), Some(at(2027, 2, 1)));
+
+        let drafts = draft_invoices(&period, &[first.clone(), second, other_org], &[]);
+
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(drafts[0].org_id, first.org_id);
+        assert_eq!(drafts[0].lines.len(), 2);
+        assert_eq!(drafts[0].subtotal_usdc, Money::from_usdc(520));
+        assert_eq!(drafts[0].total_usdc, drafts[0].subtotal_usdc);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::money::{Money, Rounding};
+use crate::DepreciationRule;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Physical condition of returned hardware, graded at inspection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionGrade {
+    /// No visible wear, all parts and packaging present
+    A,
+    /// Light cosmetic wear
+    B,
+    /// Heavy wear or replaced parts
+    C,
+    /// Working but needs refurbishment before resale
+    D,
+}
+
+impl ConditionGrade {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConditionGrade::A => "a",
+            ConditionGrade::B => "b",
+            ConditionGrade::C => "c",
+            ConditionGrade::D => "d",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "a" => Some(ConditionGrade::A),
+            "b" => Some(ConditionGrade::B),
+            "c" => Some(ConditionGrade::C),
+            "d" => Some(ConditionGrade::D),
+            _ => None,
+        }
+    }
+
+    /// Share of the depreciated value a box in this condition is worth
+    pub fn value_percentage(&self) -> Decimal {
+        Decimal::from(match self {
+            ConditionGrade::A => 100,
+            ConditionGrade::B => 90,
+            ConditionGrade::C => 75,
+            ConditionGrade::D => 50,
+        })
+    }
+}
+
+/// How much of the resale value we pay out, and for how long an offer stands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuybackPolicy {
+    /// Kept back from the resale value to cover refurbishment and margin
+    #[serde(with = "rust_decimal::serde::float")]
+    pub margin_percent: Decimal,
+    pub offer_valid_days: u32,
+}
+
+impl Default for BuybackPolicy {
+    fn default() -> Self {
+        Self {
+            margin_percent: Decimal::from(30),
+            offer_valid_days: 14,
+        }
+    }
+}
+
+impl BuybackPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.margin_percent < Decimal::ZERO || self.margin_percent >= Decimal::ONE_HUNDRED {
+            return Err(format!(
+                "margin {} must be at least 0 and below 100 percent",
+                self.margin_percent
+            ));
+        }
+        if self.offer_valid_days == 0 {
+            return Err("offers must be valid for at least one day".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuybackQuoteRequest {
+    pub server_id: Uuid,
+    pub condition: ConditionGrade,
+}
+
+/// What a returned box is worth to us
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuybackValuation {
+    pub usage_hours: u32,
+    /// Price the box will be listed at as a used provenance
+    pub resale_price_usdc: Money,
+    /// Resale price adjusted for condition, less the buyback margin
+    pub offer_usdc: Money,
+}
+
+/// Value hardware at its depreciated catalog price for the recorded hours,
+/// scaled by condition, keeping the policy margin. Offers round down.
+pub fn value_buyback(
+    rule: &DepreciationRule,
+    setup_price: Money,
+    usage_hours: u32,
+    condition: ConditionGrade,
+    policy: &BuybackPolicy,
+) -> BuybackValuation {
+    let resale_price = rule.calculate_depreciated_price(setup_price, usage_hours);
+    let payout_percentage = condition.value_percentage()
+        * (Decimal::ONE_HUNDRED - policy.margin_percent)
+        / Decimal::ONE_HUNDRED;
+
+    BuybackValuation {
+        usage_hours,
+        resale_price_usdc: resale_price,
+        offer_usdc: resale_price.percent(payout_percentage, Rounding::Down),
+    }
+}
+
+/// Whole hours a server has run between activation and `until`
+pub fn recorded_usage_hours(activated_at: DateTime<Utc>, until: DateTime<Utc>) -> u32 {
+    u32::try_from((until - activated_at).num_hours().max(0)).unwrap_or(u32::MAX)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuybackStatus {
+    Offered,
+    Accepted,
+    /// Another offer for the same server was accepted
+    Withdrawn,
+    /// Not accepted before `expires_at`
+    Expired,
+}
+
+impl BuybackStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuybackStatus::Offered => "offered",
+            BuybackStatus::Accepted => "accepted",
+            BuybackStatus::Withdrawn => "withdrawn",
+            BuybackStatus::Expired => "expired",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "offered" => Some(BuybackStatus::Offered),
+            "accepted" => Some(BuybackStatus::Accepted),
+            "withdrawn" => Some(BuybackStatus::Withdrawn),
+            "expired" => Some(BuybackStatus::Expired),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuybackOffer {
+    pub id: Uuid,
+    pub server_id: Uuid,
+    pub package_id: Uuid,
+    pub sku: String,
+    pub usage_hours: u32,
+    pub condition: ConditionGrade,
+    pub resale_price_usdc: Money,
+    pub offer_usdc: Money,
+    pub status: BuybackStatus,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    /// Catalog provenance the box was listed under once accepted
+    pub provenance_id: Option<i32>,
+}
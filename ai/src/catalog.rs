@@ -0,0 +1,250 @@
+use crate::gpu::GpuMemory;
+use crate::images::ImageVariant;
+use crate::{Availability, GpuClass, Money, Provenance};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Longest SKU accepted
+pub const MAX_SKU_LENGTH: usize = 64;
+
+/// Everything about a package except its SKU and prices, which have their
+/// own lifecycle (SKUs are permanent, prices are versioned)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageDetails {
+    pub name: String,
+    pub description: String,
+    pub hardware_description: String,
+    pub cpu_cores: u16,
+    pub ram_gb: u16,
+    pub storage_gb: u32,
+    pub gpu_class: GpuClass,
+    pub gpu_count: u16,
+    pub vram_gb: u16,
+    pub availability: Availability,
+}
+
+impl PackageDetails {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        if self.description.trim().is_empty() || self.hardware_description.trim().is_empty() {
+            return Err("description and hardware description must not be empty".to_string());
+        }
+        if self.cpu_cores == 0 || self.ram_gb == 0 || self.storage_gb == 0 {
+            return Err("CPU cores, RAM and storage must be positive".to_string());
+        }
+        let small = [self.cpu_cores, self.ram_gb, self.gpu_count, self.vram_gb];
+        if small.iter().any(|v| *v > i16::MAX as u16) || self.storage_gb > i32::MAX as u32 {
+            return Err("hardware figures are out of range".to_string());
+        }
+        if let Availability::Build { hours: 0 } = self.availability {
+            return Err("build time must be positive".to_string());
+        }
+        check_gpu_memory(&self.gpu_class, self.gpu_count, self.vram_gb)
+    }
+}
+
+/// VRAM must be what `gpu_count` cards of the class carry. Integrated GPUs
+/// have no fixed VRAM; their share of unified memory is configurable up to
+/// a ceiling.
+pub fn check_gpu_memory(gpu_class: &GpuClass, gpu_count: u16, vram_gb: u16) -> Result<(), String> {
+    let Some(spec) = gpu_class.spec() else {
+        if gpu_count != 0 || vram_gb != 0 {
+            return Err("a package without GPU must have GPU count and VRAM 0".to_string());
+        }
+        return Ok(());
+    };
+
+    match spec.memory {
+        GpuMemory::Unified { max_gb } => {
+            if gpu_count != 1 {
+                return Err(format!("{gpu_class} is integrated; GPU count must be 1"));
+            }
+            if vram_gb == 0 || vram_gb > max_gb {
+                return Err(format!(
+                    "{gpu_class} VRAM must be between 1 and {max_gb} GB"
+                ));
+            }
+        }
+        GpuMemory::Dedicated { gb } => {
+            if gpu_count == 0 {
+                return Err(format!("GPU count must be positive for {gpu_class}"));
+            }
+            let expected = u32::from(gb) * u32::from(gpu_count);
+            if u32::from(vram_gb) != expected {
+                return Err(format!(
+                    "{gpu_count} x {gpu_class} have {expected} GB VRAM, not {vram_gb}"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check a SKU is lowercase letters and digits in dash separated groups,
+/// e.g. `2x-h100-160`
+pub fn validate_sku(sku: &str) -> Result<(), String> {
+    let valid = !sku.is_empty()
+        && sku.len() <= MAX_SKU_LENGTH
+        && sku.split('-').all(|group| {
+            !group.is_empty()
+                && group
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        });
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "SKU {sku:?} must be at most {MAX_SKU_LENGTH} lowercase letters and digits in dash separated groups"
+        ))
+    }
+}
+
+/// Admin request for a new package. Packages are created inactive so images
+/// and provenance can be added before they are listed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatePackageRequest {
+    pub sku: String,
+    #[serde(flatten)]
+    pub details: PackageDetails,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+}
+
+impl CreatePackageRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_sku(&self.sku)?;
+        self.details.validate()?;
+        check_prices(self.setup_price_usdc, self.monthly_price_usdc)
+    }
+}
+
+pub(crate) fn check_prices(
+    setup_price_usdc: Money,
+    monthly_price_usdc: Money,
+) -> Result<(), String> {
+    if !setup_price_usdc.is_positive() || monthly_price_usdc.is_negative() {
+        return Err("setup price must be positive and monthly price not negative".to_string());
+    }
+    Ok(())
+}
+
+/// Fields to change on a package; the rest keep their value. Prices are
+/// changed through price versions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdatePackageRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub hardware_description: Option<String>,
+    pub cpu_cores: Option<u16>,
+    pub ram_gb: Option<u16>,
+    pub storage_gb: Option<u32>,
+    pub gpu_class: Option<GpuClass>,
+    pub gpu_count: Option<u16>,
+    pub vram_gb: Option<u16>,
+    pub availability: Option<Availability>,
+}
+
+impl UpdatePackageRequest {
+    /// The package details after the update
+    pub fn apply(self, current: PackageDetails) -> PackageDetails {
+        PackageDetails {
+            name: self.name.unwrap_or(current.name),
+            description: self.description.unwrap_or(current.description),
+            hardware_description: self
+                .hardware_description
+                .unwrap_or(current.hardware_description),
+            cpu_cores: self.cpu_cores.unwrap_or(current.cpu_cores),
+            ram_gb: self.ram_gb.unwrap_or(current.ram_gb),
+            storage_gb: self.storage_gb.unwrap_or(current.storage_gb),
+            gpu_class: self.gpu_class.unwrap_or(current.gpu_class),
+            gpu_count: self.gpu_count.unwrap_or(current.gpu_count),
+            vram_gb: self.vram_gb.unwrap_or(current.vram_gb),
+            availability: self.availability.unwrap_or(current.availability),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageImageRequest {
+    pub filename: String,
+    pub title: String,
+    pub description: String,
+}
+
+impl PackageImageRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.filename.trim().is_empty() || self.title.trim().is_empty() {
+            return Err("image filename and title must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdatePackageImageRequest {
+    pub filename: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+/// New display order of a package's images; must list each of them once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReorderImagesRequest {
+    pub image_ids: Vec<Uuid>,
+}
+
+/// A provenance with 0 usage hours is new, anything else used
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvenanceRequest {
+    pub usage_hours: u32,
+    pub quantity_available: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateProvenanceRequest {
+    pub usage_hours: Option<u32>,
+    pub quantity_available: Option<u32>,
+    pub is_active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogImage {
+    pub id: Uuid,
+    pub filename: String,
+    pub title: String,
+    pub description: String,
+    pub sort_order: i32,
+    #[serde(default)]
+    pub variants: Vec<ImageVariant>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogProvenance {
+    pub id: i32,
+    pub provenance_type: Provenance,
+    pub quantity_available: u32,
+    pub is_active: bool,
+    /// `None` while the cached price is stale
+    pub calculated_price_usdc: Option<Money>,
+}
+
+/// A package as administrators see it, including inactive packages and
+/// provenance rows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogPackage {
+    pub id: Uuid,
+    pub sku: String,
+    #[serde(flatten)]
+    pub details: PackageDetails,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub images: Vec<CatalogImage>,
+    pub provenances: Vec<CatalogProvenance>,
+}
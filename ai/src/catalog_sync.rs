@@ -0,0 +1,522 @@
+use crate::catalog::{
+    check_prices, validate_sku, CatalogPackage, PackageDetails, PackageImageRequest,
+    ProvenanceRequest,
+};
+use crate::depreciation::DepreciationRuleRequest;
+use crate::{DepreciationCurve, DepreciationRule, Money, Provenance};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use uuid::Uuid;
+
+/// The whole package catalog as declared in a catalog file. The file is
+/// authoritative: packages missing from it are unlisted (never deleted, as
+/// orders and servers refer to them), and so are missing provenance rows.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogFile {
+    #[serde(default, rename = "package")]
+    pub packages: Vec<PackageSpec>,
+}
+
+/// One package of a catalog file. Images are identified by filename and
+/// provenance rows by usage hours.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageSpec {
+    pub sku: String,
+    #[serde(flatten)]
+    pub details: PackageDetails,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+    #[serde(default = "listed")]
+    pub active: bool,
+    /// In display order
+    #[serde(default, rename = "image")]
+    pub images: Vec<PackageImageRequest>,
+    #[serde(default, rename = "provenance")]
+    pub provenances: Vec<ProvenanceRequest>,
+    /// The default rule when omitted
+    pub depreciation: Option<DepreciationRuleRequest>,
+}
+
+fn listed() -> bool {
+    true
+}
+
+impl PackageSpec {
+    fn depreciation_rule(&self, package_id: Uuid) -> DepreciationRule {
+        self.depreciation
+            .clone()
+            .unwrap_or(DepreciationRuleRequest {
+                final_depreciated_percentage: None,
+                full_depreciation_hours: None,
+                depreciation_curve: DepreciationCurve::Linear,
+            })
+            .into_rule(package_id)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_sku(&self.sku)?;
+        let context = |e: String| format!("{}: {e}", self.sku);
+
+        self.details.validate().map_err(context)?;
+        check_prices(self.setup_price_usdc, self.monthly_price_usdc).map_err(context)?;
+        self.depreciation_rule(Uuid::nil())
+            .validate()
+            .map_err(context)?;
+
+        let mut filenames = HashSet::new();
+        for image in &self.images {
+            image.validate().map_err(context)?;
+            if !filenames.insert(image.filename.trim()) {
+                return Err(context(format!("image {} is listed twice", image.filename)));
+            }
+        }
+
+        let mut hours = HashSet::new();
+        for provenance in &self.provenances {
+            if provenance.usage_hours > i32::MAX as u32
+                || provenance.quantity_available > i32::MAX as u32
+            {
+                return Err(context("provenance figures are out of range".to_string()));
+            }
+            if !hours.insert(provenance.usage_hours) {
+                return Err(context(format!(
+                    "provenance at {} hours is listed twice",
+                    provenance.usage_hours
+                )));
+            }
+        }
+        if self.active && self.provenances.is_empty() {
+            return Err(context(
+                "an active package needs at least one provenance".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl CatalogFile {
+    pub fn validate(&self) -> Result<(), String> {
+        let mut skus = HashSet::new();
+        let mut names = HashSet::new();
+        for spec in &self.packages {
+            spec.validate()?;
+            if !skus.insert(spec.sku.as_str()) {
+                return Err(format!("SKU {} is listed twice", spec.sku));
+            }
+            if !names.insert(spec.details.name.trim().to_lowercase()) {
+                return Err(format!(
+                    "package name {} is listed twice",
+                    spec.details.name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One step of bringing the database in line with a catalog file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum CatalogChange {
+    /// New packages are created unlisted; a later `SetActive` lists them
+    CreatePackage {
+        sku: String,
+        details: PackageDetails,
+        setup_price_usdc: Money,
+        monthly_price_usdc: Money,
+    },
+    UpdatePackage {
+        sku: String,
+        fields: Vec<String>,
+        details: PackageDetails,
+    },
+    /// A new price version effective immediately
+    ChangePrices {
+        sku: String,
+        setup_price_usdc: Money,
+        monthly_price_usdc: Money,
+    },
+    SetDepreciationRule {
+        sku: String,
+        rule: DepreciationRuleRequest,
+    },
+    AddImage {
+        sku: String,
+        image: PackageImageRequest,
+    },
+    UpdateImage {
+        sku: String,
+        image: PackageImageRequest,
+    },
+    RemoveImage {
+        sku: String,
+        filename: String,
+    },
+    OrderImages {
+        sku: String,
+        filenames: Vec<String>,
+    },
+    AddProvenance {
+        sku: String,
+        usage_hours: u32,
+        quantity_available: u32,
+    },
+    UpdateProvenance {
+        sku: String,
+        usage_hours: u32,
+        quantity_available: u32,
+        active: bool,
+    },
+    SetActive {
+        sku: String,
+        active: bool,
+    },
+}
+
+impl fmt::Display for CatalogChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CreatePackage {
+                sku,
+                details,
+                setup_price_usdc,
+                monthly_price_usdc,
+            } => write!(
+                f,
+                "+ package {sku} \"{}\" at {setup_price_usdc} + {monthly_price_usdc}/month USDC",
+                details.name
+            ),
+            Self::UpdatePackage { sku, fields, .. } => {
+                write!(f, "~ package {sku}: {}", fields.join(", "))
+            }
+            Self::ChangePrices {
+                sku,
+                setup_price_usdc,
+                monthly_price_usdc,
+            } => write!(
+                f,
+                "~ prices {sku}: {setup_price_usdc} + {monthly_price_usdc}/month USDC"
+            ),
+            Self::SetDepreciationRule { sku, rule } => {
+                write!(
+                    f,
+                    "~ depreciation {sku}: {}",
+                    rule.depreciation_curve.as_str()
+                )
+            }
+            Self::AddImage { sku, image } => write!(f, "+ image {sku} {}", image.filename),
+            Self::UpdateImage { sku, image } => write!(f, "~ image {sku} {}", image.filename),
+            Self::RemoveImage { sku, filename } => write!(f, "- image {sku} {filename}"),
+            Self::OrderImages { sku, filenames } => {
+                write!(f, "~ image order {sku}: {}", filenames.join(", "))
+            }
+            Self::AddProvenance {
+                sku,
+                usage_hours,
+                quantity_available,
+            } => write!(
+                f,
+                "+ provenance {sku} at {usage_hours} h, {quantity_available} available"
+            ),
+            Self::UpdateProvenance {
+                sku,
+                usage_hours,
+                quantity_available,
+                active: true,
+            } => write!(
+                f,
+                "~ provenance {sku} at {usage_hours} h, {quantity_available} available"
+            ),
+            Self::UpdateProvenance {
+                sku, usage_hours, ..
+            } => write!(f, "- provenance {sku} at {usage_hours} h"),
+            Self::SetActive { sku, active: true } => write!(f, "+ list {sku}"),
+            Self::SetActive { sku, active: false } => write!(f, "- unlist {sku}"),
+        }
+    }
+}
+
+/// Changes that make the database match a catalog file, per package in file
+/// order, followed by the packages to unlist
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncPlan {
+    pub changes: Vec<CatalogChange>,
+}
+
+impl SyncPlan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for SyncPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "The catalog is up to date.");
+        }
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        writeln!(f, "{} changes", self.changes.len())
+    }
+}
+
+/// Diff `file` against the `current` catalog, whose depreciation rules are
+/// in `rules` by package (packages without one use the default rule)
+pub fn plan_sync(
+    file: &CatalogFile,
+    current: &[CatalogPackage],
+    rules: &HashMap<Uuid, DepreciationRule>,
+) -> Result<SyncPlan, String> {
+    file.validate()?;
+
+    let mut changes = Vec::new();
+    for spec in &file.packages {
+        match current.iter().find(|p| p.sku == spec.sku) {
+            Some(package) => {
+                let rule = rules
+                    .get(&package.id)
+                    .cloned()
+                    .unwrap_or_else(|| DepreciationRule::default_for(package.id));
+                plan_package(spec, package, &rule, &mut changes);
+            }
+            None => plan_new_package(spec, &mut changes),
+        }
+    }
+
+    for package in current {
+        if package.is_active && !file.packages.iter().any(|s| s.sku == package.sku) {
+            changes.push(CatalogChange::SetActive {
+                sku: package.sku.clone(),
+                active: false,
+            });
+        }
+    }
+
+    Ok(SyncPlan { changes })
+}
+
+fn plan_new_package(spec: &PackageSpec, changes: &mut Vec<CatalogChange>) {
+    let sku = || spec.sku.clone();
+
+    changes.push(CatalogChange::CreatePackage {
+        sku: sku(),
+        details: spec.details.clone(),
+        setup_price_usdc: spec.setup_price_usdc,
+        monthly_price_usdc: spec.monthly_price_usdc,
+    });
+    if let Some(rule) = &spec.depreciation {
+        changes.push(CatalogChange::SetDepreciationRule {
+            sku: sku(),
+            rule: rule.clone(),
+        });
+    }
+    for image in &spec.images {
+        changes.push(CatalogChange::AddImage {
+            sku: sku(),
+            image: image.clone(),
+        });
+    }
+    for provenance in &spec.provenances {
+        changes.push(CatalogChange::AddProvenance {
+            sku: sku(),
+            usage_hours: provenance.usage_hours,
+            quantity_available: provenance.quantity_available,
+        });
+    }
+    if spec.active {
+        changes.push(CatalogChange::SetActive {
+            sku: sku(),
+            active: true,
+        });
+    }
+}
+
+fn plan_package(
+    spec: &PackageSpec,
+    package: &CatalogPackage,
+    rule: &DepreciationRule,
+    changes: &mut Vec<CatalogChange>,
+) {
+    let sku = || spec.sku.clone();
+
+    let fields = changed_fields(&package.details, &spec.details);
+    if !fields.is_empty() {
+        changes.push(CatalogChange::UpdatePackage {
+            sku: sku(),
+            fields,
+            details: spec.details.clone(),
+        });
+    }
+
+    if package.setup_price_usdc != spec.setup_price_usdc
+        || package.monthly_price_usdc != spec.monthly_price_usdc
+    {
+        changes.push(CatalogChange::ChangePrices {
+            sku: sku(),
+            setup_price_usdc: spec.setup_price_usdc,
+            monthly_price_usdc: spec.monthly_price_usdc,
+        });
+    }
+
+    let wanted = spec.depreciation_rule(package.id);
+    if !same_rule(rule, &wanted) {
+        changes.push(CatalogChange::SetDepreciationRule {
+            sku: sku(),
+            rule: DepreciationRuleRequest {
+                final_depreciated_percentage: Some(wanted.final_depreciated_percentage),
+                full_depreciation_hours: Some(wanted.full_depreciation_hours),
+                depreciation_curve: wanted.depreciation_curve,
+            },
+        });
+    }
+
+    plan_images(spec, package, changes);
+    plan_provenances(spec, package, changes);
+
+    if package.is_active != spec.active {
+        changes.push(CatalogChange::SetActive {
+            sku: sku(),
+            active: spec.active,
+        });
+    }
+}
+
+fn plan_images(spec: &PackageSpec, package: &CatalogPackage, changes: &mut Vec<CatalogChange>) {
+    let mut current: Vec<_> = package.images.iter().collect();
+    current.sort_by_key(|i| i.sort_order);
+
+    // Images keep their place when kept and new ones are appended, so the
+    // order only needs setting if that differs from the file
+    let mut resulting_order = Vec::new();
+    for image in &current {
+        match spec
+            .images
+            .iter()
+            .find(|i| i.filename.trim() == image.filename)
+        {
+            Some(wanted) => {
+                if wanted.title.trim() != image.title
+                    || wanted.description.trim() != image.description
+                {
+                    changes.push(CatalogChange::UpdateImage {
+                        sku: spec.sku.clone(),
+                        image: wanted.clone(),
+                    });
+                }
+                resulting_order.push(image.filename.as_str());
+            }
+            None => changes.push(CatalogChange::RemoveImage {
+                sku: spec.sku.clone(),
+                filename: image.filename.clone(),
+            }),
+        }
+    }
+
+    for image in &spec.images {
+        if !current.iter().any(|i| i.filename == image.filename.trim()) {
+            changes.push(CatalogChange::AddImage {
+                sku: spec.sku.clone(),
+                image: image.clone(),
+            });
+            resulting_order.push(image.filename.trim());
+        }
+    }
+
+    let wanted_order: Vec<&str> = spec.images.iter().map(|i| i.filename.trim()).collect();
+    if resulting_order != wanted_order {
+        changes.push(CatalogChange::OrderImages {
+            sku: spec.sku.clone(),
+            filenames: wanted_order.iter().map(|f| f.to_string()).collect(),
+        });
+    }
+}
+
+fn plan_provenances(
+    spec: &PackageSpec,
+    package: &CatalogPackage,
+    changes: &mut Vec<CatalogChange>,
+) {
+    for wanted in &spec.provenances {
+        let existing = package
+            .provenances
+            .iter()
+            .find(|p| usage_hours(&p.provenance_type) == wanted.usage_hours);
+        match existing {
+            Some(p) if p.is_active && p.quantity_available == wanted.quantity_available => {}
+            Some(_) => changes.push(CatalogChange::UpdateProvenance {
+                sku: spec.sku.clone(),
+                usage_hours: wanted.usage_hours,
+                quantity_available: wanted.quantity_available,
+                active: true,
+            }),
+            None => changes.push(CatalogChange::AddProvenance {
+                sku: spec.sku.clone(),
+                usage_hours: wanted.usage_hours,
+                quantity_available: wanted.quantity_available,
+            }),
+        }
+    }
+
+    for p in &package.provenances {
+        let hours = usage_hours(&p.provenance_type);
+        if p.is_active && !spec.provenances.iter().any(|w| w.usage_hours == hours) {
+            changes.push(CatalogChange::UpdateProvenance {
+                sku: spec.sku.clone(),
+                usage_hours: hours,
+                quantity_available: p.quantity_available,
+                active: false,
+            });
+        }
+    }
+}
+
+fn usage_hours(provenance: &Provenance) -> u32 {
+    match provenance {
+        Provenance::New => 0,
+        Provenance::Used { hours } => *hours,
+    }
+}
+
+/// Names of the fields that differ between two sets of package details
+fn changed_fields(current: &PackageDetails, wanted: &PackageDetails) -> Vec<String> {
+    let checks = [
+        ("name", current.name != wanted.name.trim()),
+        (
+            "description",
+            current.description != wanted.description.trim(),
+        ),
+        (
+            "hardware_description",
+            current.hardware_description != wanted.hardware_description.trim(),
+        ),
+        ("cpu_cores", current.cpu_cores != wanted.cpu_cores),
+        ("ram_gb", current.ram_gb != wanted.ram_gb),
+        ("storage_gb", current.storage_gb != wanted.storage_gb),
+        ("gpu_class", current.gpu_class != wanted.gpu_class),
+        ("gpu_count", current.gpu_count != wanted.gpu_count),
+        ("vram_gb", current.vram_gb != wanted.vram_gb),
+        ("availability", current.availability != wanted.availability),
+    ];
+
+    checks
+        .iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(field, _)| field.to_string())
+        .collect()
+}
+
+/// A custom schedule sets the rule's final percentage and hours itself, so
+/// only the schedule is compared
+fn same_rule(current: &DepreciationRule, wanted: &DepreciationRule) -> bool {
+    match (&current.depreciation_curve, &wanted.depreciation_curve) {
+        (DepreciationCurve::Custom(a), DepreciationCurve::Custom(b)) => a == b,
+        (a, b) => {
+            a == b
+                && current.final_depreciated_percentage == wanted.final_depreciated_percentage
+                && current.full_depreciation_hours == wanted.full_depreciation_hours
+        }
+    }
+}
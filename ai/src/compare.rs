@@ -0,0 +1,128 @@
+use crate::{Availability, GpuClass, Money, Package, Provenance, Rounding};
+use serde::{Deserialize, Serialize};
+
+/// Fewest and most packages compared at once
+pub const MIN_COMPARED_PACKAGES: usize = 2;
+pub const MAX_COMPARED_PACKAGES: usize = 4;
+
+/// Parse the comma separated `skus` of a comparison, dropping repeats
+pub fn parse_skus(skus: &str) -> Result<Vec<String>, String> {
+    let mut parsed: Vec<String> = Vec::new();
+    for sku in skus.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        if !parsed.iter().any(|p| p == sku) {
+            parsed.push(sku.to_string());
+        }
+    }
+
+    if !(MIN_COMPARED_PACKAGES..=MAX_COMPARED_PACKAGES).contains(&parsed.len()) {
+        return Err(format!(
+            "compare {MIN_COMPARED_PACKAGES} to {MAX_COMPARED_PACKAGES} packages, got {}",
+            parsed.len()
+        ));
+    }
+    Ok(parsed)
+}
+
+/// One package in a comparison. Unit prices are of the cheapest
+/// provenance, which is what the package can be had for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComparedPackage {
+    pub sku: String,
+    pub name: String,
+    pub cpu_cores: u16,
+    pub ram_gb: u16,
+    pub storage_gb: u32,
+    pub gpu_class: GpuClass,
+    pub gpu_count: u16,
+    pub vram_gb: u16,
+    pub availability: Availability,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+    pub cheapest_provenance: Provenance,
+    pub cheapest_price_usdc: Money,
+    /// `None` for packages without VRAM
+    pub price_per_vram_gb_usdc: Option<Money>,
+    pub price_per_core_usdc: Money,
+}
+
+/// SKUs of the packages that lead on each measure; ties go to the first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ComparisonLeaders {
+    pub cheapest: Option<String>,
+    pub lowest_price_per_vram_gb: Option<String>,
+    pub lowest_price_per_core: Option<String>,
+    pub most_vram: Option<String>,
+    pub most_cores: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageComparison {
+    /// In the order requested
+    pub packages: Vec<ComparedPackage>,
+    pub leaders: ComparisonLeaders,
+}
+
+/// Compare `packages`, keeping their order
+pub fn compare(packages: &[Package]) -> PackageComparison {
+    let compared: Vec<ComparedPackage> = packages.iter().map(compared_package).collect();
+
+    let leader = |key: &dyn Fn(&ComparedPackage) -> Option<Money>| {
+        compared
+            .iter()
+            .filter_map(|p| Some((key(p)?, p)))
+            .reduce(|best, next| if next.0 < best.0 { next } else { best })
+            .map(|(_, p)| p.sku.clone())
+    };
+    let most = |key: &dyn Fn(&ComparedPackage) -> u16| {
+        compared
+            .iter()
+            .filter(|p| key(p) > 0)
+            .reduce(|best, next| if key(next) > key(best) { next } else { best })
+            .map(|p| p.sku.clone())
+    };
+
+    let leaders = ComparisonLeaders {
+        cheapest: leader(&|p| Some(p.cheapest_price_usdc)),
+        lowest_price_per_vram_gb: leader(&|p| p.price_per_vram_gb_usdc),
+        lowest_price_per_core: leader(&|p| Some(p.price_per_core_usdc)),
+        most_vram: most(&|p| p.vram_gb),
+        most_cores: most(&|p| p.cpu_cores),
+    };
+
+    PackageComparison {
+        packages: compared,
+        leaders,
+    }
+}
+
+fn compared_package(package: &Package) -> ComparedPackage {
+    let (cheapest_provenance, cheapest_price) = package
+        .provenances
+        .iter()
+        .min_by_key(|o| o.calculated_price)
+        .map(|o| (o.provenance_type.clone(), o.calculated_price))
+        .unwrap_or((Provenance::New, package.setup_price_usdc));
+
+    ComparedPackage {
+        sku: package.sku.clone(),
+        name: package.name.clone(),
+        cpu_cores: package.cpu_cores,
+        ram_gb: package.ram_gb,
+        storage_gb: package.storage_gb,
+        gpu_class: package.gpu_class.clone(),
+        gpu_count: package.gpu_count,
+        vram_gb: package.vram_gb,
+        availability: package.availability.clone(),
+        setup_price_usdc: package.setup_price_usdc,
+        monthly_price_usdc: package.monthly_price_usdc,
+        cheapest_provenance,
+        cheapest_price_usdc: cheapest_price,
+        price_per_vram_gb_usdc: (package.vram_gb > 0)
+            .then(|| cheapest_price.mul_ratio(1, i64::from(package.vram_gb), Rounding::HalfUp)),
+        price_per_core_usdc: cheapest_price.mul_ratio(
+            1,
+            i64::from(package.cpu_cores.max(1)),
+            Rounding::HalfUp,
+        ),
+    }
+}
@@ -0,0 +1,163 @@
+use crate::money::{Money, Rounding};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Why money is handed back on an invoice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CreditNoteReason {
+    Cancellation,
+    SlaBreach,
+    BillingError,
+    Goodwill,
+}
+
+impl CreditNoteReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CreditNoteReason::Cancellation => "cancellation",
+            CreditNoteReason::SlaBreach => "sla_breach",
+            CreditNoteReason::BillingError => "billing_error",
+            CreditNoteReason::Goodwill => "goodwill",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "cancellation" => Some(CreditNoteReason::Cancellation),
+            "sla_breach" => Some(CreditNoteReason::SlaBreach),
+            "billing_error" => Some(CreditNoteReason::BillingError),
+            "goodwill" => Some(CreditNoteReason::Goodwill),
+            _ => None,
+        }
+    }
+}
+
+/// A credit against an issued invoice. The amount becomes account credit,
+/// which is applied to future invoices or paid out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreditNote {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub invoice_id: Uuid,
+    pub reason: CreditNoteReason,
+    pub description: String,
+    /// Gross amount credited, VAT included
+    pub amount_usdc: Money,
+    /// Share of the VAT on the original invoice being reversed
+    pub vat_usdc: Money,
+    pub issued_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateCreditNoteRequest {
+    pub invoice_id: Uuid,
+    pub reason: CreditNoteReason,
+    #[serde(default)]
+    pub description: String,
+    pub amount_usdc: Money,
+}
+
+/// VAT contained in `amount` when crediting part of an invoice that charged
+/// `invoice_vat` out of `invoice_total`
+pub fn vat_share(amount: Money, invoice_total: Money, invoice_vat: Money) -> Money {
+    if invoice_total.is_zero() || invoice_vat.is_zero() {
+        return Money::ZERO;
+    }
+    Money::from_decimal(
+        amount.as_decimal() * invoice_vat.as_decimal() / invoice_total.as_decimal(),
+        Rounding::HalfUp,
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutStatus {
+    /// Waiting for staff review
+    Requested,
+    /// Approved and waiting to be sent
+    Approved,
+    Rejected,
+    /// Sent on chain and posted to the ledger
+    Paid,
+}
+
+impl PayoutStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayoutStatus::Requested => "requested",
+            PayoutStatus::Approved => "approved",
+            PayoutStatus::Rejected => "rejected",
+            PayoutStatus::Paid => "paid",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "requested" => Some(PayoutStatus::Requested),
+            "approved" => Some(PayoutStatus::Approved),
+            "rejected" => Some(PayoutStatus::Rejected),
+            "paid" => Some(PayoutStatus::Paid),
+            _ => None,
+        }
+    }
+
+    /// Whether the payout still holds back account credit
+    pub fn is_pending(&self) -> bool {
+        matches!(self, PayoutStatus::Requested | PayoutStatus::Approved)
+    }
+}
+
+/// Account credit paid back out in USDC to an address the customer supplied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefundPayout {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub amount_usdc: Money,
+    pub destination_address: String,
+    pub status: PayoutStatus,
+    pub requested_at: DateTime<Utc>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub review_note: Option<String>,
+    pub tx_hash: Option<String>,
+    pub paid_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefundPayoutRequest {
+    pub amount_usdc: Money,
+    pub destination_address: String,
+}
+
+/// Staff decision on a requested payout. The reviewer is the admin whose
+/// token made the request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayoutReview {
+    pub note: Option<String>,
+}
+
+/// Proof that an approved payout was sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayoutSettlement {
+    pub tx_hash: String,
+}
+
+/// Offline sanity check of a USDC destination: an EVM address (`0x` and 40
+/// hex digits) or a Solana address (32 to 44 base58 characters)
+pub fn validate_usdc_address(address: &str) -> Result<(), String> {
+    if let Some(hex) = address.strip_prefix("0x") {
+        if hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(());
+        }
+        return Err(format!("{address} is not a valid EVM address"));
+    }
+
+    const BASE58: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    if (32..=44).contains(&address.len()) && address.chars().all(|c| BASE58.contains(c)) {
+        return Ok(());
+    }
+
+    Err(format!("{address} is not a supported USDC address"))
+}
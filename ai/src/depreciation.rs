@@ -0,0 +1,492 @@
+use crate::{DepreciationCurve, DepreciationRule, Money};
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Most breakpoints a schedule may have
+pub const MAX_BREAKPOINTS: usize = 100;
+
+/// How a schedule moves between breakpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleInterpolation {
+    /// Straight line from one breakpoint to the next
+    Linear,
+    /// Hold each breakpoint's value until the next one is reached
+    Step,
+}
+
+impl ScheduleInterpolation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleInterpolation::Linear => "linear",
+            ScheduleInterpolation::Step => "step",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "linear" => Some(ScheduleInterpolation::Linear),
+            "step" => Some(ScheduleInterpolation::Step),
+            _ => None,
+        }
+    }
+}
+
+/// Percentage of the original price remaining once `usage_hours` is reached
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleBreakpoint {
+    pub usage_hours: u32,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub remaining_percentage: Decimal,
+}
+
+impl ScheduleBreakpoint {
+    pub fn new(usage_hours: u32, remaining_percentage: Decimal) -> Self {
+        Self {
+            usage_hours,
+            remaining_percentage,
+        }
+    }
+}
+
+/// A depreciation curve given as a table of breakpoints. The curve starts at
+/// 100% at zero hours unless the first breakpoint says otherwise, and stays
+/// at the last breakpoint's value beyond it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepreciationSchedule {
+    pub interpolation: ScheduleInterpolation,
+    pub breakpoints: Vec<ScheduleBreakpoint>,
+}
+
+impl DepreciationSchedule {
+    /// Straight line from 100% down to `final_percentage` at `full_hours`
+    pub fn linear(final_percentage: Decimal, full_hours: u32) -> Self {
+        Self {
+            interpolation: ScheduleInterpolation::Linear,
+            breakpoints: vec![ScheduleBreakpoint::new(full_hours, final_percentage)],
+        }
+    }
+
+    /// `steps` equal drops from 100% to `final_percentage`, the last one at
+    /// `full_hours`
+    pub fn stepped(final_percentage: Decimal, full_hours: u32, steps: u32) -> Self {
+        let steps = steps.max(1);
+        let range = Decimal::ONE_HUNDRED - final_percentage;
+        let breakpoints = (1..=steps)
+            .map(|i| {
+                // First whole hour at which the usage ratio reaches i / steps
+                let hours = (full_hours as u64 * i as u64).div_ceil(steps as u64) as u32;
+                ScheduleBreakpoint::new(
+                    hours,
+                    Decimal::ONE_HUNDRED - range * Decimal::from(i) / Decimal::from(steps),
+                )
+            })
+            .collect();
+
+        Self {
+            interpolation: ScheduleInterpolation::Step,
+            breakpoints,
+        }
+    }
+
+    /// Exponential decay to `final_percentage` at `full_hours`, approximated
+    /// by `segments` straight lines
+    pub fn exponential(final_percentage: Decimal, full_hours: u32, segments: u32) -> Self {
+        let segments = segments.clamp(1, MAX_BREAKPOINTS as u32);
+        let mut breakpoints: Vec<ScheduleBreakpoint> = Vec::new();
+        for i in 1..=segments {
+            let hours = (full_hours as u64 * i as u64 / segments as u64) as u32;
+            if hours == 0 || breakpoints.last().is_some_and(|b| b.usage_hours == hours) {
+                continue;
+            }
+            let ratio = Decimal::from(i) / Decimal::from(segments);
+            breakpoints.push(ScheduleBreakpoint::new(
+                hours,
+                exponential_remaining(final_percentage, ratio),
+            ));
+        }
+
+        Self {
+            interpolation: ScheduleInterpolation::Linear,
+            breakpoints,
+        }
+    }
+
+    /// Breakpoints must be in strictly increasing hours, within 0-100% and
+    /// never rise
+    pub fn validate(&self) -> Result<(), String> {
+        if self.breakpoints.is_empty() {
+            return Err("a depreciation schedule needs at least one breakpoint".to_string());
+        }
+        if self.breakpoints.len() > MAX_BREAKPOINTS {
+            return Err(format!(
+                "a depreciation schedule has at most {MAX_BREAKPOINTS} breakpoints"
+            ));
+        }
+
+        for point in &self.breakpoints {
+            if point.remaining_percentage < Decimal::ZERO
+                || point.remaining_percentage > Decimal::ONE_HUNDRED
+            {
+                return Err(format!(
+                    "remaining percentage {} at {} hours must be between 0 and 100",
+                    point.remaining_percentage, point.usage_hours
+                ));
+            }
+        }
+        for pair in self.breakpoints.windows(2) {
+            if pair[1].usage_hours <= pair[0].usage_hours {
+                return Err(format!(
+                    "breakpoint at {} hours must come after {} hours",
+                    pair[1].usage_hours, pair[0].usage_hours
+                ));
+            }
+            if pair[1].remaining_percentage > pair[0].remaining_percentage {
+                return Err(format!(
+                    "remaining percentage rises from {} to {} at {} hours",
+                    pair[0].remaining_percentage, pair[1].remaining_percentage, pair[1].usage_hours
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Percentage of the original price remaining after `usage_hours`
+    pub fn remaining_percentage(&self, usage_hours: u32) -> Decimal {
+        let (mut prev_hours, mut prev_pct) = (0u32, Decimal::ONE_HUNDRED);
+
+        for point in &self.breakpoints {
+            if usage_hours < point.usage_hours {
+                return match self.interpolation {
+                    ScheduleInterpolation::Step => prev_pct,
+                    ScheduleInterpolation::Linear => {
+                        let ratio = Decimal::from(usage_hours - prev_hours)
+                            / Decimal::from(point.usage_hours - prev_hours);
+                        prev_pct - (prev_pct - point.remaining_percentage) * ratio
+                    }
+                };
+            }
+            (prev_hours, prev_pct) = (point.usage_hours, point.remaining_percentage);
+        }
+
+        prev_pct
+    }
+
+    /// Hours at which the schedule reaches its final value
+    pub fn full_depreciation_hours(&self) -> u32 {
+        self.breakpoints.last().map_or(0, |b| b.usage_hours)
+    }
+
+    /// Value the schedule ends at
+    pub fn final_percentage(&self) -> Decimal {
+        self.breakpoints
+            .last()
+            .map_or(Decimal::ONE_HUNDRED, |b| b.remaining_percentage)
+    }
+}
+
+/// Exponential decay from 100% to `final_percentage` at `ratio` of the way
+/// to full depreciation. A final percentage of zero has no exponential
+/// curve, so it falls back to linear.
+pub(crate) fn exponential_remaining(final_percentage: Decimal, ratio: Decimal) -> Decimal {
+    (final_percentage / Decimal::ONE_HUNDRED)
+        .checked_ln()
+        .and_then(|k| (k * ratio).checked_exp())
+        .map(|e| Decimal::ONE_HUNDRED * e)
+        .unwrap_or(Decimal::ONE_HUNDRED - (Decimal::ONE_HUNDRED - final_percentage) * ratio)
+}
+
+/// Default spacing of sampled points: one month of continuous use
+pub const DEFAULT_SAMPLE_STEP_HOURS: u32 = 720;
+/// Most points returned by one curve preview
+pub const MAX_SAMPLES: usize = 1000;
+
+/// Price of a package after a given number of usage hours
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepreciationPoint {
+    pub usage_hours: u32,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub remaining_percentage: Decimal,
+    pub price_usdc: Money,
+}
+
+/// Sampled depreciation curve of a package, for pricing charts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepreciationPreview {
+    pub sku: String,
+    pub setup_price_usdc: Money,
+    pub rule: DepreciationRule,
+    /// Whether `rule` is a what-if rule rather than the saved one
+    pub what_if: bool,
+    pub points: Vec<DepreciationPoint>,
+}
+
+/// A depreciation rule as submitted by an admin. The final percentage and
+/// hours default to those of `DepreciationRule::default_for` and are not
+/// used by custom schedules.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepreciationRuleRequest {
+    #[serde(default, with = "rust_decimal::serde::float_option")]
+    pub final_depreciated_percentage: Option<Decimal>,
+    pub full_depreciation_hours: Option<u32>,
+    pub depreciation_curve: DepreciationCurve,
+}
+
+impl DepreciationRuleRequest {
+    /// The rule for `package_id`. A custom schedule's final percentage and
+    /// hours are taken from its last breakpoint.
+    pub fn into_rule(self, package_id: Uuid) -> DepreciationRule {
+        let default = DepreciationRule::default_for(package_id);
+        let (final_depreciated_percentage, full_depreciation_hours) = match &self.depreciation_curve
+        {
+            DepreciationCurve::Custom(schedule) => (
+                schedule.final_percentage(),
+                schedule.full_depreciation_hours().max(1),
+            ),
+            _ => (
+                self.final_depreciated_percentage
+                    .unwrap_or(default.final_depreciated_percentage),
+                self.full_depreciation_hours
+                    .unwrap_or(default.full_depreciation_hours),
+            ),
+        };
+
+        DepreciationRule {
+            package_id,
+            final_depreciated_percentage,
+            full_depreciation_hours,
+            depreciation_curve: self.depreciation_curve,
+        }
+    }
+}
+
+/// Sample `rule` from `from` to `to` hours inclusive every `step` hours.
+/// `to` is always included so the chart ends where it was asked to.
+pub fn sample_curve(
+    rule: &DepreciationRule,
+    setup_price: Money,
+    from: u32,
+    to: u32,
+    step: u32,
+) -> Result<Vec<DepreciationPoint>, String> {
+    if step == 0 {
+        return Err("step must be at least 1 hour".to_string());
+    }
+    if from > to {
+        return Err(format!("from ({from}) must not be after to ({to})"));
+    }
+    let count = ((to - from) / step) as usize + 1;
+    if count > MAX_SAMPLES {
+        return Err(format!(
+            "{count} points requested, at most {MAX_SAMPLES} are returned; use a larger step"
+        ));
+    }
+
+    let point = |usage_hours: u32| DepreciationPoint {
+        usage_hours,
+        remaining_percentage: rule.remaining_percentage(usage_hours),
+        price_usdc: rule.calculate_depreciated_price(setup_price, usage_hours),
+    };
+
+    let mut points: Vec<DepreciationPoint> =
+        (from..=to).step_by(step as usize).map(point).collect();
+    if points.last().is_some_and(|p| p.usage_hours != to) {
+        points.push(point(to));
+    }
+    Ok(points)
+}
+
+/// Admin request to replace a package's depreciation rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepreciationRuleChange {
+    pub rule: DepreciationRuleRequest,
+    /// Apply the rule from this moment instead of immediately
+    pub effective_at: Option<DateTime<Utc>>,
+    /// Only report the impact, change nothing
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// How a rule change moves the price of one provenance row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvenancePriceImpact {
+    pub provenance_id: i32,
+    pub usage_hours: u32,
+    pub old_price_usdc: Money,
+    pub new_price_usdc: Money,
+    /// New minus old; negative when the row gets cheaper
+    pub delta_usdc: Money,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub old_discount_percentage: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub new_discount_percentage: Decimal,
+}
+
+impl ProvenancePriceImpact {
+    pub fn between(
+        old_rule: &DepreciationRule,
+        new_rule: &DepreciationRule,
+        provenance_id: i32,
+        setup_price: Money,
+        usage_hours: u32,
+    ) -> Self {
+        let old_price = old_rule.calculate_depreciated_price(setup_price, usage_hours);
+        let new_price = new_rule.calculate_depreciated_price(setup_price, usage_hours);
+        Self {
+            provenance_id,
+            usage_hours,
+            old_price_usdc: old_price,
+            new_price_usdc: new_price,
+            delta_usdc: new_price - old_price,
+            old_discount_percentage: old_rule.discount_percentage(usage_hours),
+            new_discount_percentage: new_rule.discount_percentage(usage_hours),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleChangeOutcome {
+    /// Dry run: nothing was changed
+    Previewed,
+    /// The rule is in effect and prices have been recomputed
+    Applied,
+    /// The rule takes effect at `effective_at`
+    Scheduled,
+}
+
+/// Old and new price of every provenance row of a package under a rule
+/// change, reported before and when the change is made
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepricingImpact {
+    pub sku: String,
+    pub package_id: Uuid,
+    pub current_rule: DepreciationRule,
+    pub new_rule: DepreciationRule,
+    pub outcome: RuleChangeOutcome,
+    pub effective_at: Option<DateTime<Utc>>,
+    /// Set when the change was scheduled
+    pub scheduled_id: Option<i32>,
+    pub provenances: Vec<ProvenancePriceImpact>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledRuleStatus {
+    Pending,
+    Applied,
+    Cancelled,
+}
+
+impl ScheduledRuleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduledRuleStatus::Pending => "pending",
+            ScheduledRuleStatus::Applied => "applied",
+            ScheduledRuleStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(ScheduledRuleStatus::Pending),
+            "applied" => Some(ScheduledRuleStatus::Applied),
+            "cancelled" => Some(ScheduledRuleStatus::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+/// A depreciation rule that replaces a package's rule at `effective_at`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledDepreciationRule {
+    pub id: i32,
+    pub rule: DepreciationRule,
+    pub effective_at: DateTime<Utc>,
+    pub status: ScheduledRuleStatus,
+    pub created_at: DateTime<Utc>,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_rule_with_zero_hours_prices_without_panicking() {
+        let package_id = Uuid::new_v4();
+        let schedule = DepreciationSchedule {
+            interpolation: ScheduleInterpolation::Linear,
+            breakpoints: vec![
+                ScheduleBreakpoint::new(0, Decimal::ONE_HUNDRED),
+                ScheduleBreakpoint::new(10_000, Decimal::from(40)),
+            ],
+        };
+        let rule = DepreciationRuleRequest {
+            final_depreciated_percentage: None,
+            full_depreciation_hours: Some(0),
+            depreciation_curve: DepreciationCurve::Custom(schedule),
+        }
+        .into_rule(package_id);
+
+        assert_eq!(rule.full_depreciation_hours, 10_000);
+        assert_eq!(rule.final_depreciated_percentage, Decimal::from(40));
+        assert!(rule.validate().is_ok());
+
+        let setup_price = Money::from_usdc(10_000);
+        let impact = ProvenancePriceImpact::between(
+            &DepreciationRule::default_for(package_id),
+            &rule,
+            1,
+            setup_price,
+            5_000,
+        );
+        assert_eq!(impact.new_price_usdc, Money::from_usdc(7_000));
+        assert!(sample_curve(&rule, setup_price, 0, 20_000, 1_000).is_ok());
+
+        // Even a rule that skipped the request normalization uses its schedule
+        let unnormalized = DepreciationRule {
+            full_depreciation_hours: 0,
+            ..rule
+        };
+        assert_eq!(
+            unnormalized.calculate_depreciated_price(setup_price, 5_000),
+            Money::from_usdc(7_000)
+        );
+        assert!(unnormalized.validate().is_err());
+    }
+
+    #[test]
+    fn preset_curves_price_as_their_schedules() {
+        let package_id = Uuid::new_v4();
+        let setup_price = Money::from_usdc(10_000);
+
+        for curve in [
+            DepreciationCurve::Linear,
+            DepreciationCurve::Exponential,
+            DepreciationCurve::Stepped,
+        ] {
+            let rule = DepreciationRule {
+                package_id,
+                final_depreciated_percentage: Decimal::from(30),
+                full_depreciation_hours: 17_520,
+                depreciation_curve: curve,
+            };
+            let as_custom = DepreciationRule {
+                depreciation_curve: DepreciationCurve::Custom(rule.schedule()),
+                ..rule.clone()
+            };
+
+            for hours in (0..=20_000).step_by(250) {
+                assert_eq!(
+                    rule.calculate_depreciated_price(setup_price, hours),
+                    as_custom.calculate_depreciated_price(setup_price, hours),
+                    "{} at {hours} hours",
+                    rule.depreciation_curve.as_str()
+                );
+            }
+        }
+    }
+}
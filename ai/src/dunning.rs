@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Controls how unpaid invoices are chased: reminders are sent a number of
+/// days after the due date, and once the grace period has passed the servers
+/// on the invoice are suspended until it is paid.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DunningPolicy {
+    pub reminder_days: Vec<u32>, // Days past the due date, ascending (e.g. [3, 7, 14])
+    pub grace_period_days: u32,  // Days past the due date before suspension
+}
+
+impl Default for DunningPolicy {
+    fn default() -> Self {
+        Self {
+            reminder_days: vec![3, 7, 14],
+            grace_period_days: 21,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DunningAction {
+    SendReminder { number: u32 },
+    Suspend,
+}
+
+impl DunningPolicy {
+    /// Check that reminders are strictly ascending and all fall inside the
+    /// grace period
+    pub fn validate(&self) -> Result<(), String> {
+        if self.reminder_days.windows(2).any(|w| w[0] >= w[1]) {
+            return Err("reminder days must be strictly ascending".to_string());
+        }
+        if let Some(last) = self.reminder_days.last() {
+            if *last >= self.grace_period_days {
+                return Err(format!(
+                    "last reminder (day {}) must come before the grace period ends (day {})",
+                    last, self.grace_period_days
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Decide what to do next for an unpaid invoice. Reminders that were
+    /// missed (e.g. the scheduler was down) collapse into the latest one due.
+    pub fn next_action(
+        &self,
+        due_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+        reminders_sent: u32,
+        suspended: bool,
+    ) -> Option<DunningAction> {
+        if now <= due_at || suspended {
+            return None;
+        }
+
+        let days_overdue = (now - due_at).num_days();
+        if days_overdue >= self.grace_period_days as i64 {
+            return Some(DunningAction::Suspend);
+        }
+
+        let reminders_due = self
+            .reminder_days
+            .iter()
+            .filter(|days| days_overdue >= **days as i64)
+            .count() as u32;
+
+        (reminders_due > reminders_sent).then_some(DunningAction::SendReminder {
+            number: reminders_due,
+        })
+    }
+}
+
+/// An action taken by a dunning run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DunningOutcome {
+    pub invoice_id: Uuid,
+    pub org_id: Uuid,
+    pub action: DunningAction,
+    pub affected_servers: Vec<Uuid>,
+}
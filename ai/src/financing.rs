@@ -0,0 +1,275 @@
+use crate::money::{Money, Rounding};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Longest term a financing plan can run
+pub const MAX_FINANCING_TERM_MONTHS: u32 = 60;
+
+/// Terms on which a package's setup price can be paid in monthly instalments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinancingPlan {
+    pub id: i32,
+    pub name: String,
+    pub term_months: u32,
+    /// Nominal yearly interest, charged monthly at a twelfth of the rate
+    #[serde(with = "rust_decimal::serde::float")]
+    pub annual_rate_percent: Decimal,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateFinancingPlanRequest {
+    pub name: String,
+    pub term_months: u32,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub annual_rate_percent: Decimal,
+}
+
+impl CreateFinancingPlanRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("plan name must not be empty".to_string());
+        }
+        if self.term_months < 2 || self.term_months > MAX_FINANCING_TERM_MONTHS {
+            return Err(format!(
+                "term must be between 2 and {MAX_FINANCING_TERM_MONTHS} months"
+            ));
+        }
+        if self.annual_rate_percent < Decimal::ZERO
+            || self.annual_rate_percent > Decimal::ONE_HUNDRED
+        {
+            return Err(format!(
+                "annual rate {} must be between 0 and 100 percent",
+                self.annual_rate_percent
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// One monthly payment; `balance_usdc` is what is still owed after it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Instalment {
+    pub number: u32,
+    pub payment_usdc: Money,
+    pub principal_usdc: Money,
+    pub interest_usdc: Money,
+    pub balance_usdc: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmortizationSchedule {
+    pub principal_usdc: Money,
+    pub term_months: u32,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub annual_rate_percent: Decimal,
+    /// Regular monthly payment; the last one may differ by rounding
+    pub instalment_usdc: Money,
+    pub total_interest_usdc: Money,
+    pub total_paid_usdc: Money,
+    pub instalments: Vec<Instalment>,
+}
+
+impl AmortizationSchedule {
+    /// Payment due for instalment `number` (1-based)
+    pub fn payment(&self, number: u32) -> Option<Money> {
+        let index = usize::try_from(number.checked_sub(1)?).ok()?;
+        self.instalments.get(index).map(|i| i.payment_usdc)
+    }
+}
+
+/// Repay `principal` in `term_months` equal monthly payments (an annuity) at
+/// `annual_rate_percent`. Interest accrues on the outstanding balance each
+/// month; the final payment absorbs rounding so the balance ends at zero.
+pub fn amortize(
+    principal: Money,
+    annual_rate_percent: Decimal,
+    term_months: u32,
+) -> AmortizationSchedule {
+    let term_months = term_months.max(1);
+    let monthly_rate = annual_rate_percent / Decimal::from(1200);
+
+    let payment = if monthly_rate.is_zero() {
+        principal.mul_ratio(1, term_months as i64, Rounding::Up)
+    } else {
+        // P * r / (1 - (1 + r)^-n), written with (1 + r)^n to stay exact
+        let growth =
+            (0..term_months).fold(Decimal::ONE, |acc, _| acc * (Decimal::ONE + monthly_rate));
+        Money::from_decimal(
+            principal.as_decimal() * monthly_rate * growth / (growth - Decimal::ONE),
+            Rounding::Up,
+        )
+    };
+
+    let mut balance = principal;
+    let mut instalments = Vec::with_capacity(term_months as usize);
+    for number in 1..=term_months {
+        let interest = balance.mul_decimal(monthly_rate, Rounding::HalfUp);
+        let principal_part = if number == term_months {
+            balance
+        } else {
+            (payment - interest).min(balance)
+        };
+        balance -= principal_part;
+
+        instalments.push(Instalment {
+            number,
+            payment_usdc: principal_part + interest,
+            principal_usdc: principal_part,
+            interest_usdc: interest,
+            balance_usdc: balance,
+        });
+    }
+
+    let total_interest: Money = instalments.iter().map(|i| i.interest_usdc).sum();
+
+    AmortizationSchedule {
+        principal_usdc: principal,
+        term_months,
+        annual_rate_percent,
+        instalment_usdc: payment,
+        total_interest_usdc: total_interest,
+        total_paid_usdc: principal + total_interest,
+        instalments,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinancingOffer {
+    pub plan: FinancingPlan,
+    pub schedule: AmortizationSchedule,
+}
+
+/// Financing available for a package's setup price
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageFinancing {
+    pub sku: String,
+    pub setup_price_usdc: Money,
+    pub offers: Vec<FinancingOffer>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinancingStatus {
+    /// Instalments are still being billed or paid
+    Active,
+    /// Every instalment is paid and the hardware belongs to the customer
+    Completed,
+}
+
+impl FinancingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FinancingStatus::Active => "active",
+            FinancingStatus::Completed => "completed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "active" => Some(FinancingStatus::Active),
+            "completed" => Some(FinancingStatus::Completed),
+            _ => None,
+        }
+    }
+}
+
+/// A financed order, on the plan's terms at the time it was placed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinancingAgreement {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    /// Set once the order's server has been provisioned
+    pub server_id: Option<Uuid>,
+    pub plan_id: i32,
+    pub plan_name: String,
+    pub sku: String,
+    pub instalments_billed: u32,
+    pub status: FinancingStatus,
+    pub created_at: DateTime<Utc>,
+    pub ownership_transferred_at: Option<DateTime<Utc>>,
+    pub schedule: AmortizationSchedule,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every schedule pays the principal off exactly, each payment being its
+    /// principal and interest parts
+    fn assert_pays_off(schedule: &AmortizationSchedule) {
+        let principal_paid: Money = schedule.instalments.iter().map(|i| i.principal_usdc).sum();
+        assert_eq!(principal_paid, schedule.principal_usdc);
+        assert_eq!(
+            schedule.instalments.last().map(|i| i.balance_usdc),
+            Some(Money::ZERO)
+        );
+        for instalment in &schedule.instalments {
+            assert_eq!(
+                instalment.payment_usdc,
+                instalment.principal_usdc + instalment.interest_usdc
+            );
+        }
+        let paid: Money = schedule.instalments.iter().map(|i| i.payment_usdc).sum();
+        assert_eq!(paid, schedule.total_paid_usdc);
+    }
+
+    #[test]
+    fn interest_free_plans_split_the_principal_and_round_up() {
+        let schedule = amortize(Money::from_usdc(1_000), Decimal::ZERO, 3);
+
+        assert_eq!(schedule.instalment_usdc, Money::from_micro(333_333_334));
+        assert_eq!(schedule.payment(1), Some(Money::from_micro(333_333_334)));
+        assert_eq!(schedule.payment(2), Some(Money::from_micro(333_333_334)));
+        // The last payment absorbs the rounding
+        assert_eq!(schedule.payment(3), Some(Money::from_micro(333_333_332)));
+        assert_eq!(schedule.total_interest_usdc, Money::ZERO);
+        assert_pays_off(&schedule);
+    }
+
+    #[test]
+    fn annuity_payments_are_level_until_the_last() {
+        // 10000 at 12% a year over 12 months: 1% a month
+        let schedule = amortize(Money::from_usdc(10_000), Decimal::from(12), 12);
+
+        assert_eq!(schedule.instalment_usdc, Money::from_micro(888_487_887));
+        assert_eq!(schedule.instalments[0].interest_usdc, Money::from_usdc(100));
+        for instalment in &schedule.instalments[..11] {
+            assert_eq!(instalment.payment_usdc, schedule.instalment_usdc);
+        }
+        let last = schedule.payment(12).unwrap();
+        assert!((last - schedule.instalment_usdc).abs() < Money::from_micro(100));
+        assert_pays_off(&schedule);
+    }
+
+    #[test]
+    fn every_term_and_rate_ends_at_zero() {
+        for term in [1, 2, 7, 24, MAX_FINANCING_TERM_MONTHS] {
+            for rate in [0, 1, 9, 35, 100] {
+                let schedule =
+                    amortize(Money::from_micro(12_345_678_901), Decimal::from(rate), term);
+                assert_eq!(schedule.instalments.len(), term as usize);
+                assert_pays_off(&schedule);
+            }
+        }
+    }
+
+    #[test]
+    fn payment_numbers_are_one_based() {
+        let schedule = amortize(Money::from_usdc(600), Decimal::from(6), 6);
+
+        assert_eq!(schedule.payment(0), None);
+        assert!(schedule.payment(1).is_some());
+        assert!(schedule.payment(6).is_some());
+        assert_eq!(schedule.payment(7), None);
+
+        // A zero term is paid in one instalment
+        let single = amortize(Money::from_usdc(600), Decimal::from(6), 0);
+        assert_eq!(single.term_months, 1);
+        assert_eq!(single.payment(1), Some(Money::from_usdc(603)));
+        assert_pays_off(&single);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::Availability;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Workshop capacity builds are scheduled on. Work is spread over the whole
+/// day, so a technician on 8 hours a day needs 24 hours for an 8 hour build.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildCapacity {
+    pub technicians: u32,
+    pub hours_per_day: u32, // Hours of build work per technician per day
+}
+
+impl Default for BuildCapacity {
+    fn default() -> Self {
+        Self {
+            technicians: 1,
+            hours_per_day: 8,
+        }
+    }
+}
+
+impl BuildCapacity {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.technicians == 0 {
+            return Err("at least one technician is needed".to_string());
+        }
+        if !(1..=24).contains(&self.hours_per_day) {
+            return Err(format!(
+                "hours per day must be between 1 and 24, not {}",
+                self.hours_per_day
+            ));
+        }
+        Ok(())
+    }
+
+    /// Calendar hours a technician takes for `work_hours` of work
+    fn calendar_hours(&self, work_hours: f64) -> f64 {
+        work_hours * 24.0 / f64::from(self.hours_per_day)
+    }
+
+    /// Hours of work a technician gets done in `calendar_hours`
+    fn work_hours(&self, calendar_hours: f64) -> f64 {
+        calendar_hours * f64::from(self.hours_per_day) / 24.0
+    }
+}
+
+/// Parts on hand for a package's builds. The default, no kits and no lead
+/// time, means parts never hold a build up.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PartsStock {
+    /// Complete sets of parts for one build, not yet taken by a started build
+    pub kits_in_stock: u32,
+    /// Hours from ordering the parts for a build to having them
+    pub lead_time_hours: u32,
+}
+
+/// An order waiting for or being built
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedBuild {
+    pub order_id: Uuid,
+    pub package_id: Uuid,
+    /// Hands-on hours the package takes to build
+    pub build_hours: u32,
+    pub queued_at: DateTime<Utc>,
+    /// When a technician started on it, having taken its parts kit
+    pub started_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledBuild {
+    #[serde(flatten)]
+    pub build: QueuedBuild,
+    pub ready_at: DateTime<Utc>,
+    pub waiting_for_parts: bool,
+}
+
+/// When a build ordered now would ship
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildEstimate {
+    /// Hours from now until the build is done
+    pub hours: u32,
+    pub ships_at: DateTime<Utc>,
+    /// Builds queued or in progress before it
+    pub builds_ahead: u32,
+    /// The package is out of parts kits, and waiting for new ones delays it
+    pub waiting_for_parts: bool,
+}
+
+impl BuildEstimate {
+    pub fn availability(&self) -> Availability {
+        Availability::Build {
+            hours: u16::try_from(self.hours).unwrap_or(u16::MAX),
+        }
+    }
+}
+
+/// The build queue worked through in order by whichever technician is free
+/// first. Builds in progress keep their technician; waiting builds start
+/// once a technician and a parts kit are available.
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildSchedule {
+    pub capacity: BuildCapacity,
+    pub builds: Vec<ScheduledBuild>,
+    #[serde(skip)]
+    now: DateTime<Utc>,
+    /// Hours from now at which each technician is free
+    #[serde(skip)]
+    technicians_free: Vec<f64>,
+    #[serde(skip)]
+    parts: HashMap<Uuid, PartsStock>,
+    /// Kits reserved by waiting builds, per package
+    #[serde(skip)]
+    kits_reserved: HashMap<Uuid, u32>,
+}
+
+impl BuildSchedule {
+    /// Schedule `queue`, given in the order it was placed, from `now`
+    pub fn plan(
+        capacity: BuildCapacity,
+        queue: Vec<QueuedBuild>,
+        parts: HashMap<Uuid, PartsStock>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let mut schedule = Self {
+            technicians_free: vec![0.0; capacity.technicians.max(1) as usize],
+            capacity,
+            builds: Vec::new(),
+            now,
+            parts,
+            kits_reserved: HashMap::new(),
+        };
+
+        let (started, waiting): (Vec<_>, Vec<_>) =
+            queue.into_iter().partition(|b| b.started_at.is_some());
+
+        for build in started {
+            let elapsed = build
+                .started_at
+                .map_or(0.0, |started| hours_between(started, now));
+            // An overrun build is taken to be about done
+            let remaining =
+                (f64::from(build.build_hours) - schedule.capacity.work_hours(elapsed)).max(0.0);
+            let duration = schedule.capacity.calendar_hours(remaining);
+            let technician = schedule.next_technician();
+            schedule.technicians_free[technician] += duration;
+            let ready = schedule.technicians_free[technician];
+            schedule.push(build, ready, false);
+        }
+
+        for build in waiting {
+            let parts_ready = schedule.parts_ready(build.package_id);
+            *schedule.kits_reserved.entry(build.package_id).or_default() += 1;
+
+            let technician = schedule.next_technician();
+            let free = schedule.technicians_free[technician];
+            let ready = free.max(parts_ready)
+                + schedule
+                    .capacity
+                    .calendar_hours(f64::from(build.build_hours));
+            schedule.technicians_free[technician] = ready;
+            schedule.push(build, ready, parts_ready > free);
+        }
+
+        schedule
+    }
+
+    /// When a build of a package taking `build_hours` would be done if it
+    /// were ordered now
+    pub fn estimate(&self, package_id: Uuid, build_hours: u32) -> BuildEstimate {
+        let parts_ready = self.parts_ready(package_id);
+        let free = self.technicians_free[self.next_technician()];
+        let ready = free.max(parts_ready) + self.capacity.calendar_hours(f64::from(build_hours));
+        let hours = ready.ceil() as u32;
+
+        BuildEstimate {
+            hours,
+            ships_at: self.now + Duration::hours(i64::from(hours)),
+            builds_ahead: self.builds.len() as u32,
+            waiting_for_parts: parts_ready > free,
+        }
+    }
+
+    fn next_technician(&self) -> usize {
+        self.technicians_free
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(b.1))
+            .map_or(0, |(i, _)| i)
+    }
+
+    /// Hours from now until the parts for the package's next build are in
+    fn parts_ready(&self, package_id: Uuid) -> f64 {
+        let Some(stock) = self.parts.get(&package_id) else {
+            return 0.0;
+        };
+        let reserved = self.kits_reserved.get(&package_id).copied().unwrap_or(0);
+        if reserved < stock.kits_in_stock {
+            0.0
+        } else {
+            f64::from(stock.lead_time_hours)
+        }
+    }
+
+    fn push(&mut self, build: QueuedBuild, ready: f64, waiting_for_parts: bool) {
+        self.builds.push(ScheduledBuild {
+            build,
+            ready_at: self.now + Duration::hours(ready.ceil() as i64),
+            waiting_for_parts,
+        });
+    }
+}
+
+fn hours_between(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    ((to - from).num_minutes() as f64 / 60.0).max(0.0)
+}
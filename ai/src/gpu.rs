@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Declares `GpuClass` with `ALL`, `as_str` and `spec` from one table of
+/// variants and their specs, so none of them can miss a GPU
+macro_rules! gpu_classes {
+    ($($class:ident => $spec:expr,)*) => {
+        /// GPU model a package is built with. Variant names are the labels of
+        /// the Postgres `gpu_class` enum. A new GPU is a row in the
+        /// `gpu_classes!` table plus an `ALTER TYPE gpu_class ADD VALUE`
+        /// migration.
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[cfg_attr(feature = "sqlx", derive(sqlx::Type), sqlx(type_name = "gpu_class"))]
+        pub enum GpuClass {
+            $($class,)*
+        }
+
+        impl GpuClass {
+            /// Every class, in the order of the Postgres enum
+            pub const ALL: [GpuClass; [$(stringify!($class)),*].len()] =
+                [$(GpuClass::$class),*];
+
+            /// The Postgres enum label
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(GpuClass::$class => stringify!($class),)*
+                }
+            }
+
+            /// Hardware figures; `None` for CPU only packages
+            pub fn spec(&self) -> Option<&'static GpuSpec> {
+                match self {
+                    $(GpuClass::$class => $spec,)*
+                }
+            }
+        }
+    };
+}
+
+gpu_classes! {
+    None => None,
+    L4 => Some(&L4),
+    A100_40G => Some(&A100_40G),
+    A100_80G => Some(&A100_80G),
+    H100_80G => Some(&H100_80G),
+    RTX_4090 => Some(&RTX_4090),
+    RTX_5090 => Some(&RTX_5090),
+    Radeon_8060S => Some(&RADEON_8060S),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GpuVendor {
+    Nvidia,
+    Amd,
+}
+
+/// How a card's memory is provisioned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GpuMemory {
+    /// Fixed VRAM on each card
+    Dedicated { gb: u16 },
+    /// Integrated GPU sharing system memory; the share given to the GPU is
+    /// configurable up to `max_gb`
+    Unified { max_gb: u16 },
+}
+
+/// How cards in one server talk to each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interconnect {
+    Pcie4,
+    Pcie5,
+    NvLink3,
+    NvLink4,
+    /// Single integrated GPU, nothing to connect
+    OnChip,
+}
+
+/// Hardware figures of a GPU model. Throughput is dense tensor TFLOPS,
+/// without sparsity.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GpuSpec {
+    pub class: GpuClass,
+    pub name: &'static str,
+    pub vendor: GpuVendor,
+    pub memory: GpuMemory,
+    pub memory_bandwidth_gb_s: u32,
+    pub fp16_tflops: f64,
+    /// `None` where the card has no FP8 tensor support
+    pub fp8_tflops: Option<f64>,
+    pub tdp_watts: u32,
+    pub interconnect: Interconnect,
+}
+
+const L4: GpuSpec = GpuSpec {
+    class: GpuClass::L4,
+    name: "NVIDIA L4",
+    vendor: GpuVendor::Nvidia,
+    memory: GpuMemory::Dedicated { gb: 24 },
+    memory_bandwidth_gb_s: 300,
+    fp16_tflops: 121.0,
+    fp8_tflops: Some(242.0),
+    tdp_watts: 72,
+    interconnect: Interconnect::Pcie4,
+};
+
+const A100_40G: GpuSpec = GpuSpec {
+    class: GpuClass::A100_40G,
+    name: "NVIDIA A100 40GB",
+    vendor: GpuVendor::Nvidia,
+    memory: GpuMemory::Dedicated { gb: 40 },
+    memory_bandwidth_gb_s: 1555,
+    fp16_tflops: 312.0,
+    fp8_tflops: None,
+    tdp_watts: 400,
+    interconnect: Interconnect::NvLink3,
+};
+
+const A100_80G: GpuSpec = GpuSpec {
+    class: GpuClass::A100_80G,
+    name: "NVIDIA A100 80GB",
+    vendor: GpuVendor::Nvidia,
+    memory: GpuMemory::Dedicated { gb: 80 },
+    memory_bandwidth_gb_s: 2039,
+    fp16_tflops: 312.0,
+    fp8_tflops: None,
+    tdp_watts: 400,
+    interconnect: Interconnect::NvLink3,
+};
+
+const H100_80G: GpuSpec = GpuSpec {
+    class: GpuClass::H100_80G,
+    name: "NVIDIA H100 80GB",
+    vendor: GpuVendor::Nvidia,
+    memory: GpuMemory::Dedicated { gb: 80 },
+    memory_bandwidth_gb_s: 3350,
+    fp16_tflops: 989.0,
+    fp8_tflops: Some(1979.0),
+    tdp_watts: 700,
+    interconnect: Interconnect::NvLink4,
+};
+
+const RTX_4090: GpuSpec = GpuSpec {
+    class: GpuClass::RTX_4090,
+    name: "NVIDIA GeForce RTX 4090",
+    vendor: GpuVendor::Nvidia,
+    memory: GpuMemory::Dedicated { gb: 24 },
+    memory_bandwidth_gb_s: 1008,
+    fp16_tflops: 165.0,
+    fp8_tflops: Some(330.0),
+    tdp_watts: 450,
+    interconnect: Interconnect::Pcie4,
+};
+
+const RTX_5090: GpuSpec = GpuSpec {
+    class: GpuClass::RTX_5090,
+    name: "NVIDIA GeForce RTX 5090",
+    vendor: GpuVendor::Nvidia,
+    memory: GpuMemory::Dedicated { gb: 32 },
+    memory_bandwidth_gb_s: 1792,
+    fp16_tflops: 209.5,
+    fp8_tflops: Some(419.0),
+    tdp_watts: 575,
+    interconnect: Interconnect::Pcie5,
+};
+
+const RADEON_8060S: GpuSpec = GpuSpec {
+    class: GpuClass::Radeon_8060S,
+    name: "AMD Radeon 8060S",
+    vendor: GpuVendor::Amd,
+    memory: GpuMemory::Unified { max_gb: 96 },
+    memory_bandwidth_gb_s: 256,
+    fp16_tflops: 59.4,
+    fp8_tflops: None,
+    tdp_watts: 120,
+    interconnect: Interconnect::OnChip,
+};
+
+/// Specs of every GPU model, in the order of `GpuClass::ALL`
+pub fn gpu_specs() -> Vec<&'static GpuSpec> {
+    GpuClass::ALL.iter().filter_map(GpuClass::spec).collect()
+}
+
+impl fmt::Display for GpuClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for GpuClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GpuClass::ALL
+            .into_iter()
+            .find(|class| class.as_str() == s)
+            .ok_or_else(|| format!("unknown GPU class {s}"))
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+/// Largest photo an admin can upload
+pub const MAX_IMAGE_UPLOAD_BYTES: usize = 20 * 1024 * 1024;
+
+/// Largest width or height of an uploaded photo, in pixels
+pub const MAX_IMAGE_DIMENSION: u32 = 12_000;
+
+/// Widths resized variants are generated at, for images wider than that
+pub const IMAGE_VARIANT_WIDTHS: [u32; 3] = [320, 640, 1280];
+
+/// Formats photos can be uploaded in and variants are stored as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl ImageFormat {
+    /// Recognize a format by its signature rather than trusting the
+    /// uploaded filename or content type
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(ImageFormat::Webp)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "png" => Some(ImageFormat::Png),
+            "jpeg" => Some(ImageFormat::Jpeg),
+            "webp" => Some(ImageFormat::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+        }
+    }
+}
+
+/// A stored rendition of an uploaded image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageVariant {
+    pub url: String,
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A photo uploaded for a package
+#[derive(Debug, Clone)]
+pub struct ImageUpload {
+    pub title: String,
+    pub description: String,
+    pub bytes: Vec<u8>,
+}
+
+impl ImageUpload {
+    /// Check the upload and tell its format
+    pub fn validate(&self) -> Result<ImageFormat, String> {
+        if self.title.trim().is_empty() {
+            return Err("image title must not be empty".to_string());
+        }
+        if self.bytes.is_empty() {
+            return Err("image file is empty".to_string());
+        }
+        if self.bytes.len() > MAX_IMAGE_UPLOAD_BYTES {
+            return Err(format!(
+                "image must be at most {} MB",
+                MAX_IMAGE_UPLOAD_BYTES / (1024 * 1024)
+            ));
+        }
+        ImageFormat::detect(&self.bytes)
+            .ok_or_else(|| "image must be a PNG, JPEG or WebP file".to_string())
+    }
+}
+
+/// Widths to render an image `width` pixels wide at: the standard widths
+/// below it, then its own
+pub fn variant_widths(width: u32) -> Vec<u32> {
+    IMAGE_VARIANT_WIDTHS
+        .into_iter()
+        .filter(|w| *w < width)
+        .chain(std::iter::once(width))
+        .collect()
+}
@@ -0,0 +1,317 @@
+use crate::money::Money;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Accounting classification of a ledger account. Assets and expenses
+/// increase with debits; liabilities, equity and revenue with credits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountKind {
+    Asset,
+    Liability,
+    Equity,
+    Revenue,
+    Expense,
+}
+
+impl AccountKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountKind::Asset => "asset",
+            AccountKind::Liability => "liability",
+            AccountKind::Equity => "equity",
+            AccountKind::Revenue => "revenue",
+            AccountKind::Expense => "expense",
+        }
+    }
+
+    /// Whether a debit increases the balance of this kind of account
+    pub fn is_debit_normal(&self) -> bool {
+        matches!(self, AccountKind::Asset | AccountKind::Expense)
+    }
+}
+
+/// The chart of accounts. System accounts are shared; customer accounts are
+/// kept per organization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Account {
+    /// USDC held by us
+    Cash,
+    /// Monthly hosting fees earned
+    HostingRevenue,
+    /// Credits and goodwill granted to customers (contra revenue)
+    RevenueAdjustments,
+    /// VAT charged to customers and owed to the tax authority
+    VatPayable,
+    /// What an organization owes us
+    Receivable { org_id: Uuid },
+    /// What we owe an organization: prepayments and account credit
+    CustomerCredit { org_id: Uuid },
+}
+
+impl Account {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Account::Cash => "cash:usdc",
+            Account::HostingRevenue => "revenue:hosting",
+            Account::RevenueAdjustments => "revenue:adjustments",
+            Account::VatPayable => "liability:vat",
+            Account::Receivable { .. } => "receivable",
+            Account::CustomerCredit { .. } => "customer_credit",
+        }
+    }
+
+    pub fn org_id(&self) -> Option<Uuid> {
+        match self {
+            Account::Receivable { org_id } | Account::CustomerCredit { org_id } => Some(*org_id),
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> AccountKind {
+        match self {
+            Account::Cash | Account::Receivable { .. } => AccountKind::Asset,
+            Account::CustomerCredit { .. } | Account::VatPayable => AccountKind::Liability,
+            Account::HostingRevenue | Account::RevenueAdjustments => AccountKind::Revenue,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Account::Cash => "USDC holdings",
+            Account::HostingRevenue => "Hosting revenue",
+            Account::RevenueAdjustments => "Credits and adjustments",
+            Account::VatPayable => "VAT payable",
+            Account::Receivable { .. } => "Accounts receivable",
+            Account::CustomerCredit { .. } => "Customer credit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Debit,
+    Credit,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Posting {
+    pub account: Account,
+    pub side: Side,
+    pub amount_usdc: Money, // Always positive
+}
+
+/// A balanced set of postings recording one business event. `source_type`
+/// and `source_id` identify the event so it is never posted twice.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub description: String,
+    pub source_type: String,
+    pub source_id: Uuid,
+    pub postings: Vec<Posting>,
+}
+
+impl JournalEntry {
+    /// A two-legged entry moving `amount` from `credit` to `debit`
+    pub fn transfer(
+        description: impl Into<String>,
+        source_type: &str,
+        source_id: Uuid,
+        debit: Account,
+        credit: Account,
+        amount_usdc: Money,
+    ) -> Self {
+        Self {
+            description: description.into(),
+            source_type: source_type.to_string(),
+            source_id,
+            postings: vec![
+                Posting {
+                    account: debit,
+                    side: Side::Debit,
+                    amount_usdc,
+                },
+                Posting {
+                    account: credit,
+                    side: Side::Credit,
+                    amount_usdc,
+                },
+            ],
+        }
+    }
+
+    /// An invoice was issued: the organization owes us its total, of which
+    /// the net amount is revenue and the VAT is owed to the tax authority
+    pub fn invoice_issued(
+        org_id: Uuid,
+        invoice_id: Uuid,
+        net_usdc: Money,
+        vat_usdc: Money,
+    ) -> Self {
+        let mut entry = Self::transfer(
+            format!("Invoice {invoice_id} issued"),
+            "invoice",
+            invoice_id,
+            Account::Receivable { org_id },
+            Account::HostingRevenue,
+            net_usdc,
+        );
+
+        if vat_usdc.is_positive() {
+            entry.postings[0].amount_usdc += vat_usdc;
+            entry.postings.push(Posting {
+                account: Account::VatPayable,
+                side: Side::Credit,
+                amount_usdc: vat_usdc,
+            });
+        }
+
+        entry
+    }
+
+    /// Payment for an invoice arrived
+    pub fn invoice_paid(org_id: Uuid, invoice_id: Uuid, amount_usdc: Money) -> Self {
+        Self::transfer(
+            format!("Payment for invoice {invoice_id}"),
+            "invoice_payment",
+            invoice_id,
+            Account::Cash,
+            Account::Receivable { org_id },
+            amount_usdc,
+        )
+    }
+
+    /// Account credit granted to an organization
+    pub fn credit_granted(org_id: Uuid, credit_id: Uuid, amount_usdc: Money, reason: &str) -> Self {
+        Self::transfer(
+            format!("Credit granted: {reason}"),
+            "credit",
+            credit_id,
+            Account::RevenueAdjustments,
+            Account::CustomerCredit { org_id },
+            amount_usdc,
+        )
+    }
+
+    /// A credit note turned part of an invoice into account credit, reversing
+    /// its share of revenue and VAT
+    pub fn credit_note_issued(
+        org_id: Uuid,
+        credit_note_id: Uuid,
+        net_usdc: Money,
+        vat_usdc: Money,
+    ) -> Self {
+        let mut entry = Self::transfer(
+            format!("Credit note {credit_note_id}"),
+            "credit_note",
+            credit_note_id,
+            Account::RevenueAdjustments,
+            Account::CustomerCredit { org_id },
+            net_usdc,
+        );
+
+        if vat_usdc.is_positive() {
+            entry.postings[1].amount_usdc += vat_usdc;
+            entry.postings.push(Posting {
+                account: Account::VatPayable,
+                side: Side::Debit,
+                amount_usdc: vat_usdc,
+            });
+        }
+
+        entry
+    }
+
+    /// Account credit settled (part of) an invoice
+    pub fn credit_applied(org_id: Uuid, invoice_id: Uuid, amount_usdc: Money) -> Self {
+        Self::transfer(
+            format!("Account credit applied to invoice {invoice_id}"),
+            "credit_application",
+            invoice_id,
+            Account::CustomerCredit { org_id },
+            Account::Receivable { org_id },
+            amount_usdc,
+        )
+    }
+
+    /// Account credit paid back out to the organization in USDC
+    pub fn refund_paid(org_id: Uuid, refund_id: Uuid, amount_usdc: Money, reason: &str) -> Self {
+        Self::transfer(
+            format!("Refund paid: {reason}"),
+            "refund",
+            refund_id,
+            Account::CustomerCredit { org_id },
+            Account::Cash,
+            amount_usdc,
+        )
+    }
+
+    pub fn total_debits(&self) -> Money {
+        self.side_total(Side::Debit)
+    }
+
+    pub fn total_credits(&self) -> Money {
+        self.side_total(Side::Credit)
+    }
+
+    fn side_total(&self, side: Side) -> Money {
+        self.postings
+            .iter()
+            .filter(|p| p.side == side)
+            .map(|p| p.amount_usdc)
+            .sum()
+    }
+
+    /// Check the double-entry invariant before an entry is posted
+    pub fn validate(&self) -> Result<(), String> {
+        if self.postings.len() < 2 {
+            return Err("a journal entry needs at least two postings".to_string());
+        }
+        if self.postings.iter().any(|p| !p.amount_usdc.is_positive()) {
+            return Err("posting amounts must be positive".to_string());
+        }
+
+        let (debits, credits) = (self.total_debits(), self.total_credits());
+        if debits != credits {
+            return Err(format!(
+                "unbalanced entry: debits {debits} != credits {credits}"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Ledger position of one organization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrgBalance {
+    pub org_id: Uuid,
+    pub receivable_usdc: Money,
+    pub credit_usdc: Money,
+    /// Account credit held back by payouts awaiting approval or sending
+    pub pending_payouts_usdc: Money,
+    /// Receivable minus account credit; negative when we owe the organization
+    pub balance_due_usdc: Money,
+}
+
+impl OrgBalance {
+    /// Account credit that can still be applied or paid out
+    pub fn available_credit_usdc(&self) -> Money {
+        self.credit_usdc - self.pending_payouts_usdc
+    }
+}
+
+/// Result of checking that the whole ledger balances
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerCheck {
+    pub total_debits_usdc: Money,
+    pub total_credits_usdc: Money,
+    pub unbalanced_entries: Vec<Uuid>,
+    pub balanced: bool,
+}
+
+/// Manual credit or refund against an organization's account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerAdjustmentRequest {
+    pub amount_usdc: Money,
+    pub reason: String,
+}
@@ -1,80 +1,183 @@
+use rust_decimal::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum GpuClass {
-    None,
-    L4,
-    A100_40G,
-    A100_80G,
-    H100_80G,
-    RTX_4090,
-    RTX_5090,
-    Radeon_8060S,
-}
+pub mod addons;
+pub mod billing;
+pub mod buyback;
+pub mod catalog;
+pub mod catalog_sync;
+pub mod compare;
+pub mod credit_note;
+pub mod depreciation;
+pub mod dunning;
+pub mod financing;
+pub mod fulfilment;
+pub mod gpu;
+pub mod images;
+pub mod ledger;
+pub mod locale;
+pub mod money;
+pub mod price_history;
+pub mod quote;
+pub mod recommend;
+pub mod search;
+pub mod tco;
+pub mod vat;
+
+pub use depreciation::{DepreciationSchedule, ScheduleBreakpoint, ScheduleInterpolation};
+pub use gpu::{GpuClass, GpuSpec};
+pub use money::{Money, Rounding};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PackageImage {
+    pub id: Uuid,
     pub filename: String,
     pub title: String,
     pub description: String,
+    /// Resized and WebP renditions of uploaded photos; empty for static
+    /// assets
+    #[serde(default)]
+    pub variants: Vec<images::ImageVariant>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DepreciationRule {
     pub package_id: Uuid,
-    pub final_depreciated_percentage: f64, // Percentage of original price after full depreciation (e.g., 25.0)
-    pub full_depreciation_hours: u32,      // Hours for full depreciation (e.g., 26280 for 3 years)
+    #[serde(with = "rust_decimal::serde::float")]
+    pub final_depreciated_percentage: Decimal, // Percentage of original price after full depreciation (e.g., 25.0)
+    pub full_depreciation_hours: u32, // Hours for full depreciation (e.g., 26280 for 3 years)
     pub depreciation_curve: DepreciationCurve,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Straight segments the exponential curve is priced with
+pub const EXPONENTIAL_SEGMENTS: u32 = 48;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DepreciationCurve {
     Linear,
+    /// Decay towards the final percentage, in `EXPONENTIAL_SEGMENTS`
+    /// straight segments
     Exponential,
+    /// Six equal steps; `DepreciationSchedule::stepped` gives other counts
     Stepped,
+    /// Arbitrary breakpoints, which replace the rule's final percentage and
+    /// full depreciation hours
+    Custom(DepreciationSchedule),
 }
 
-impl DepreciationRule {
-    /// Calculate the depreciated price based on usage hours
-    pub fn calculate_depreciated_price(&self, original_price: u32, usage_hours: u32) -> u32 {
-        if usage_hours == 0 {
-            return original_price;
+impl DepreciationCurve {
+    /// Name stored in `package_depreciation_rules.depreciation_curve`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DepreciationCurve::Linear => "linear",
+            DepreciationCurve::Exponential => "exponential",
+            DepreciationCurve::Stepped => "stepped",
+            DepreciationCurve::Custom(_) => "custom",
         }
+    }
+}
 
-        if usage_hours >= self.full_depreciation_hours {
-            // At or beyond full depreciation
-            return (original_price as f64 * self.final_depreciated_percentage / 100.0) as u32;
+impl DepreciationRule {
+    /// Rule applied to packages that have none configured: linear down to
+    /// 25% over three years
+    pub fn default_for(package_id: Uuid) -> Self {
+        Self {
+            package_id,
+            final_depreciated_percentage: Decimal::from(25),
+            full_depreciation_hours: 26280,
+            depreciation_curve: DepreciationCurve::Linear,
         }
+    }
 
-        let depreciation_factor = match self.depreciation_curve {
-            DepreciationCurve::Linear => {
-                // Linear depreciation: price decreases linearly with usage
-                let usage_ratio = usage_hours as f64 / self.full_depreciation_hours as f64;
-                let price_range = 100.0 - self.final_depreciated_percentage;
-                100.0 - (price_range * usage_ratio)
-            }
+    /// The rule as an explicit breakpoint table, which is also what it is
+    /// priced from. The exponential curve is drawn as straight segments
+    /// between exact points of the decay.
+    pub fn schedule(&self) -> DepreciationSchedule {
+        let (final_pct, hours) = (
+            self.final_depreciated_percentage,
+            self.full_depreciation_hours,
+        );
+        match &self.depreciation_curve {
+            DepreciationCurve::Linear => DepreciationSchedule::linear(final_pct, hours),
             DepreciationCurve::Exponential => {
-                // Exponential depreciation: faster initial depreciation
-                let usage_ratio = usage_hours as f64 / self.full_depreciation_hours as f64;
-                let k = -(1.0_f64.ln() - (self.final_depreciated_percentage / 100.0).ln());
-                100.0 * (-k * usage_ratio).exp()
+                DepreciationSchedule::exponential(final_pct, hours, EXPONENTIAL_SEGMENTS)
             }
-            DepreciationCurve::Stepped => {
-                // Stepped depreciation: discrete steps (e.g., every 6 months)
-                let steps = 6; // Number of depreciation steps
-                let usage_ratio = usage_hours as f64 / self.full_depreciation_hours as f64;
-                let step = (usage_ratio * steps as f64).floor() as u32;
-                let price_range = 100.0 - self.final_depreciated_percentage;
-                100.0 - (price_range * step as f64 / steps as f64)
-            }
-        };
+            DepreciationCurve::Stepped => DepreciationSchedule::stepped(final_pct, hours, 6),
+            DepreciationCurve::Custom(schedule) => schedule.clone(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let DepreciationCurve::Custom(schedule) = &self.depreciation_curve {
+            schedule.validate()?;
+        }
+        if self.final_depreciated_percentage < Decimal::ZERO
+            || self.final_depreciated_percentage > Decimal::ONE_HUNDRED
+        {
+            return Err(format!(
+                "final depreciated percentage {} must be between 0 and 100",
+                self.final_depreciated_percentage
+            ));
+        }
+        if self.full_depreciation_hours == 0 {
+            return Err("full depreciation hours must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    /// Percentage of the original price remaining after `usage_hours`, read
+    /// off the rule's `schedule` so it charges what its breakpoints show.
+    /// New equipment is always full price.
+    pub fn remaining_percentage(&self, usage_hours: u32) -> Decimal {
+        if usage_hours == 0 {
+            return Decimal::ONE_HUNDRED;
+        }
+
+        match &self.depreciation_curve {
+            DepreciationCurve::Custom(schedule) => schedule.remaining_percentage(usage_hours),
+            _ => self.schedule().remaining_percentage(usage_hours),
+        }
+    }
 
-        (original_price as f64 * depreciation_factor / 100.0) as u32
+    /// Calculate the depreciated price based on usage hours, rounded half up
+    /// to USDC precision
+    pub fn calculate_depreciated_price(&self, original_price: Money, usage_hours: u32) -> Money {
+        original_price.percent(self.remaining_percentage(usage_hours), Rounding::HalfUp)
     }
+
+    /// Discount off the original price after `usage_hours`, rounded to the
+    /// two decimals stored with cached provenance prices
+    pub fn discount_percentage(&self, usage_hours: u32) -> Decimal {
+        (Decimal::ONE_HUNDRED - self.remaining_percentage(usage_hours))
+            .round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero)
+    }
+}
+
+/// A cached provenance price that does not match what the depreciation rule
+/// gives. A missing cached price means it is stale and awaiting recompute.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceDiscrepancy {
+    pub provenance_id: i32,
+    pub package_id: Uuid,
+    pub usage_hours: u32,
+    pub cached_price_usdc: Option<Money>,
+    pub expected_price_usdc: Money,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub cached_discount_percentage: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub expected_discount_percentage: Decimal,
 }
 
+/// Result of comparing every cached provenance price with the Rust pricing
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceCheck {
+    pub checked: usize,
+    pub discrepancies: Vec<PriceDiscrepancy>,
+    pub consistent: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Availability {
     Preorder,
     InStock,
@@ -91,8 +194,9 @@ pub enum Provenance {
 pub struct ProvenanceOption {
     pub provenance_type: Provenance,
     pub quantity_available: u32,
-    pub calculated_price: u32,
-    pub discount_percentage: Option<f64>,
+    pub calculated_price: Money,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub discount_percentage: Option<Decimal>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -108,14 +212,18 @@ pub struct Package {
     pub gpu_class: GpuClass,
     pub gpu_count: u16,
     pub vram_gb: u16,
-    pub setup_price_usdc: u32,   // Original price for new equipment
-    pub monthly_price_usdc: u32, // Monthly hosting price (not affected by depreciation)
+    pub setup_price_usdc: Money,   // Original price for new equipment
+    pub monthly_price_usdc: Money, // Monthly hosting price (not affected by depreciation)
     pub depreciation_rule: Option<DepreciationRule>, // Depreciation rule for this package
     pub images: Vec<PackageImage>,
     pub availability: Availability,
     pub provenances: Vec<ProvenanceOption>, // Multiple provenance options available
-    pub min_price_usdc: Option<u32>,        // Lowest price among all provenance options
-    pub max_price_usdc: Option<u32>,        // Highest price (usually new equipment)
+    pub min_price_usdc: Option<Money>,      // Lowest price among all provenance options
+    pub max_price_usdc: Option<Money>,      // Highest price (usually new equipment)
+    /// Ship date of a build ordered now, from the build queue; its hours
+    /// are the ones in `availability`
+    #[serde(default)]
+    pub build_estimate: Option<fulfilment::BuildEstimate>,
 }
 
 impl Package {
@@ -127,7 +235,7 @@ impl Package {
             return;
         }
 
-        let prices: Vec<u32> = self
+        let prices: Vec<Money> = self
             .provenances
             .iter()
             .map(|p| p.calculated_price)
@@ -137,12 +245,12 @@ impl Package {
     }
 
     /// Get the lowest available price
-    pub fn get_min_price(&self) -> u32 {
+    pub fn get_min_price(&self) -> Money {
         self.min_price_usdc.unwrap_or(self.setup_price_usdc)
     }
 
     /// Get the highest available price (usually new equipment)
-    pub fn get_max_price(&self) -> u32 {
+    pub fn get_max_price(&self) -> Money {
         self.max_price_usdc.unwrap_or(self.setup_price_usdc)
     }
 
@@ -172,9 +280,34 @@ pub struct Plan {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateOrderRequest {
-    pub plan: Plan,
+    /// Specs of a custom server. Required without a `sku`; an order of a
+    /// catalog package gets the package's specs and ignores it.
+    #[serde(default)]
+    pub plan: Option<Plan>,
     pub pq_enabled: bool,
     pub notes: Option<String>,
+    /// Redeemed when the order is placed
+    #[serde(default)]
+    pub promo_code: Option<String>,
+    /// Catalog package ordered; the order keeps its current price version
+    #[serde(default)]
+    pub sku: Option<String>,
+    /// Pay the setup price in instalments on this financing plan
+    #[serde(default)]
+    pub financing_plan_id: Option<i32>,
+    /// Add-ons of the catalog package; storage and RAM upgrades are added
+    /// to the plan
+    #[serde(default)]
+    pub addons: Vec<addons::AddonSelection>,
+}
+
+impl CreateOrderRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sku.is_none() && self.plan.is_none() {
+            return Err("an order needs a catalog package (sku) or a plan".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -205,4 +338,7 @@ pub struct OrderSummary {
     pub id: Uuid,
     pub plan: Plan,
     pub status: String,
+    /// Promo discount off the setup price
+    pub discount_usdc: Money,
+    pub addons: Vec<addons::OrderAddon>,
 }
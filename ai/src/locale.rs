@@ -0,0 +1,199 @@
+use crate::Package;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Languages catalog content can be translated into: the official languages
+/// of the EU
+pub const SUPPORTED_LOCALES: [&str; 24] = [
+    "bg", "cs", "da", "de", "el", "en", "es", "et", "fi", "fr", "ga", "hr", "hu", "it", "lt", "lv",
+    "mt", "nl", "pl", "pt", "ro", "sk", "sl", "sv",
+];
+
+/// A language of catalog content, by its ISO 639-1 code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct Locale(&'static str);
+
+impl Locale {
+    /// The language packages are written in and translations fall back to
+    pub const DEFAULT: Locale = Locale("en");
+
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::DEFAULT
+    }
+
+    /// The supported locale of a language tag such as `de` or `de-AT`
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_']).next()?.trim();
+        SUPPORTED_LOCALES
+            .iter()
+            .find(|l| l.eq_ignore_ascii_case(language))
+            .copied()
+            .map(Locale)
+    }
+
+    /// The locale to answer in: `requested` if given, otherwise the
+    /// client's most preferred supported language from an
+    /// `Accept-Language` header, otherwise English. Fails only for an
+    /// unsupported `requested` locale.
+    pub fn negotiate(
+        requested: Option<&str>,
+        accept_language: Option<&str>,
+    ) -> Result<Self, String> {
+        if let Some(requested) = requested {
+            return requested.parse();
+        }
+
+        let mut preferences: Vec<(&str, u16)> = accept_language
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map_or(Some(1000), parse_quality)?;
+                (!tag.is_empty() && quality > 0).then_some((tag, quality))
+            })
+            .collect();
+        // Stable, so equally preferred languages keep the client's order
+        preferences.sort_by_key(|(_, quality)| std::cmp::Reverse(*quality));
+
+        Ok(preferences
+            .into_iter()
+            .find_map(|(tag, _)| Self::from_tag(tag))
+            .unwrap_or(Self::DEFAULT))
+    }
+}
+
+/// A quality value in thousandths; `None` if malformed
+fn parse_quality(q: &str) -> Option<u16> {
+    let q: f32 = q.parse().ok()?;
+    (0.0..=1.0)
+        .contains(&q)
+        .then(|| (q * 1000.0).round() as u16)
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_tag(s).ok_or_else(|| {
+            format!(
+                "unsupported locale {s:?}; expected one of {}",
+                SUPPORTED_LOCALES.join(", ")
+            )
+        })
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Locale {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// A package's text in one locale. Fields left out fall back to English.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageTranslation {
+    pub locale: Locale,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub hardware_description: Option<String>,
+    pub images: Vec<ImageTranslation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageTranslation {
+    pub image_id: Uuid,
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Admin request replacing a package's translation into one locale
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TranslationRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub hardware_description: Option<String>,
+    #[serde(default)]
+    pub images: Vec<ImageTranslation>,
+}
+
+impl TranslationRequest {
+    /// Check the request for a package with the given image ids
+    pub fn validate(&self, locale: Locale, image_ids: &[Uuid]) -> Result<(), String> {
+        if locale.is_default() {
+            return Err(format!(
+                "{locale} is the package's own text; edit the package instead"
+            ));
+        }
+
+        let mut texts = vec![
+            ("name", &self.name),
+            ("description", &self.description),
+            ("hardware_description", &self.hardware_description),
+        ];
+        for image in &self.images {
+            texts.push(("image title", &image.title));
+            texts.push(("image description", &image.description));
+        }
+        if let Some((field, _)) = texts
+            .iter()
+            .find(|(_, text)| text.as_deref().is_some_and(|t| t.trim().is_empty()))
+        {
+            return Err(format!("{field} must be left out rather than empty"));
+        }
+
+        for (i, image) in self.images.iter().enumerate() {
+            if !image_ids.contains(&image.image_id) {
+                return Err(format!(
+                    "image {} is not one of the package's",
+                    image.image_id
+                ));
+            }
+            if self.images[..i]
+                .iter()
+                .any(|o| o.image_id == image.image_id)
+            {
+                return Err(format!("image {} is translated twice", image.image_id));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Put a translation's text in place of the English, field by field
+pub fn localize(package: &mut Package, translation: &PackageTranslation) {
+    fn replace(text: &mut String, translated: &Option<String>) {
+        if let Some(translated) = translated {
+            text.clone_from(translated);
+        }
+    }
+
+    replace(&mut package.name, &translation.name);
+    replace(&mut package.description, &translation.description);
+    replace(
+        &mut package.hardware_description,
+        &translation.hardware_description,
+    );
+    for image in &mut package.images {
+        if let Some(translated) = translation.images.iter().find(|t| t.image_id == image.id) {
+            replace(&mut image.title, &translated.title);
+            replace(&mut image.description, &translated.description);
+        }
+    }
+}
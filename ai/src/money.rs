@@ -0,0 +1,306 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+/// Number of decimal places carried by every amount; USDC has 6 on-chain
+pub const USDC_DECIMALS: u32 = 6;
+
+/// How to round when a calculation produces more precision than an amount
+/// can hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rounding {
+    /// Round half away from zero (commercial rounding)
+    HalfUp,
+    /// Round half to even (banker's rounding)
+    HalfEven,
+    /// Truncate toward zero
+    Down,
+    /// Round away from zero
+    Up,
+}
+
+impl Rounding {
+    fn strategy(self) -> RoundingStrategy {
+        match self {
+            Rounding::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            Rounding::HalfEven => RoundingStrategy::MidpointNearestEven,
+            Rounding::Down => RoundingStrategy::ToZero,
+            Rounding::Up => RoundingStrategy::AwayFromZero,
+        }
+    }
+}
+
+/// An exact amount of USDC with 6 decimal places. Serialized as a decimal
+/// string and stored in Postgres as `numeric`, so quotes, orders, invoices
+/// and ledger entries all agree to the last micro-USDC.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type), sqlx(transparent))]
+pub struct Money(Decimal);
+
+impl Money {
+    pub const ZERO: Money = Money(Decimal::ZERO);
+
+    /// A whole number of USDC
+    pub fn from_usdc(usdc: i64) -> Self {
+        Self::from_decimal(Decimal::from(usdc), Rounding::HalfUp)
+    }
+
+    /// An amount given in micro-USDC (integer minor units)
+    pub fn from_micro(micro: i64) -> Self {
+        Self(Decimal::new(micro, USDC_DECIMALS))
+    }
+
+    /// Round an arbitrary decimal to USDC precision
+    pub fn from_decimal(value: Decimal, rounding: Rounding) -> Self {
+        let mut value = value.round_dp_with_strategy(USDC_DECIMALS, rounding.strategy());
+        value.rescale(USDC_DECIMALS);
+        Self(value)
+    }
+
+    /// The amount in micro-USDC (integer minor units)
+    pub fn to_micro(&self) -> i64 {
+        (self.0 * Decimal::from(10i64.pow(USDC_DECIMALS)))
+            .trunc()
+            .try_into()
+            .expect("USDC amount fits in i64 micro units")
+    }
+
+    pub fn as_decimal(&self) -> Decimal {
+        self.0
+    }
+
+    /// Multiply by `numerator / denominator`, e.g. to prorate by days
+    pub fn mul_ratio(&self, numerator: i64, denominator: i64, rounding: Rounding) -> Self {
+        if denominator == 0 {
+            return Self::ZERO;
+        }
+        Self::from_decimal(
+            self.0 * Decimal::from(numerator) / Decimal::from(denominator),
+            rounding,
+        )
+    }
+
+    /// Multiply by an arbitrary decimal factor
+    pub fn mul_decimal(&self, factor: Decimal, rounding: Rounding) -> Self {
+        Self::from_decimal(self.0 * factor, rounding)
+    }
+
+    /// `percent`% of this amount
+    pub fn percent(&self, percent: Decimal, rounding: Rounding) -> Self {
+        Self::from_decimal(self.0 * percent / Decimal::ONE_HUNDRED, rounding)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.0.is_sign_positive() && !self.0.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0.is_sign_negative() && !self.0.is_zero()
+    }
+
+    pub fn abs(&self) -> Self {
+        Self(self.0.abs())
+    }
+}
+
+impl fmt::Display for Money {
+    /// Full precision with trailing zeros trimmed down to cents,
+    /// e.g. `20000.00` or `1666.666667`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut value = self.0.normalize();
+        if value.scale() < 2 {
+            value.rescale(2);
+        }
+        write!(f, "{value}")
+    }
+}
+
+impl FromStr for Money {
+    type Err = String;
+
+    /// Parse a decimal string. More than 6 decimal places is an error rather
+    /// than being silently rounded.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value =
+            Decimal::from_str_exact(s.trim()).map_err(|e| format!("invalid amount: {e}"))?;
+        if value.normalize().scale() > USDC_DECIMALS {
+            return Err(format!(
+                "amount {s} has more than {USDC_DECIMALS} decimal places"
+            ));
+        }
+        Ok(Self::from_decimal(value, Rounding::HalfUp))
+    }
+}
+
+impl From<u32> for Money {
+    fn from(usdc: u32) -> Self {
+        Self::from_usdc(usdc as i64)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, rhs: Money) -> Money {
+        Money(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, rhs: Money) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, rhs: Money) -> Money {
+        Money(self.0 - rhs.0)
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, rhs: Money) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Money {
+        iter.copied().sum()
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    /// Accepts decimal strings and, for convenience, JSON integers
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Text(String),
+            Whole(i64),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Text(s) => s.parse().map_err(serde::de::Error::custom),
+            Repr::Whole(usdc) => Ok(Money::from_usdc(usdc)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str_exact(s).unwrap()
+    }
+
+    #[test]
+    fn rounding_modes_at_the_sixth_decimal() {
+        let cases = [
+            ("0.0000005", Rounding::HalfUp, 1),
+            ("0.0000005", Rounding::HalfEven, 0),
+            ("0.0000015", Rounding::HalfEven, 2),
+            ("-0.0000005", Rounding::HalfUp, -1),
+            ("0.0000019", Rounding::Down, 1),
+            ("-0.0000019", Rounding::Down, -1),
+            ("0.0000001", Rounding::Up, 1),
+            ("-0.0000001", Rounding::Up, -1),
+        ];
+        for (value, rounding, micro) in cases {
+            assert_eq!(
+                Money::from_decimal(dec(value), rounding),
+                Money::from_micro(micro),
+                "{value} rounded {rounding:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn ratios_and_percentages_round_once() {
+        let amount = Money::from_usdc(100);
+        assert_eq!(
+            amount.mul_ratio(1, 3, Rounding::HalfUp),
+            Money::from_micro(33_333_333)
+        );
+        assert_eq!(
+            amount.mul_ratio(2, 3, Rounding::HalfUp),
+            Money::from_micro(66_666_667)
+        );
+        assert_eq!(
+            amount.mul_ratio(2, 3, Rounding::Down),
+            Money::from_micro(66_666_666)
+        );
+        assert_eq!(amount.mul_ratio(5, 0, Rounding::HalfUp), Money::ZERO);
+        assert_eq!(
+            Money::from_micro(1).percent(Decimal::from(50), Rounding::HalfUp),
+            Money::from_micro(1)
+        );
+        assert_eq!(
+            Money::from_micro(1).percent(Decimal::from(50), Rounding::HalfEven),
+            Money::ZERO
+        );
+    }
+
+    #[test]
+    fn to_micro_covers_the_whole_i64_range() {
+        for micro in [i64::MIN, -1, 0, 1, i64::MAX] {
+            assert_eq!(Money::from_micro(micro).to_micro(), micro);
+        }
+        assert_eq!(Money::from_usdc(5).to_micro(), 5_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "USDC amount fits in i64 micro units")]
+    fn to_micro_panics_beyond_i64() {
+        (Money::from_micro(i64::MAX) + Money::from_micro(1)).to_micro();
+    }
+
+    #[test]
+    fn parsing_rejects_sub_micro_precision() {
+        assert_eq!("12.5".parse::<Money>(), Ok(Money::from_micro(12_500_000)));
+        assert_eq!(" 0.000001 ".parse::<Money>(), Ok(Money::from_micro(1)));
+        assert_eq!(
+            "1.1000000".parse::<Money>(),
+            Ok(Money::from_micro(1_100_000))
+        );
+        assert!("0.0000001".parse::<Money>().is_err());
+        assert!("twelve".parse::<Money>().is_err());
+    }
+
+    #[test]
+    fn display_trims_to_cents() {
+        assert_eq!(Money::from_usdc(20_000).to_string(), "20000.00");
+        assert_eq!(Money::from_micro(1_666_666_667).to_string(), "1666.666667");
+        assert_eq!(Money::from_micro(-1_500_000).to_string(), "-1.50");
+    }
+}
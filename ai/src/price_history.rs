@@ -0,0 +1,93 @@
+use crate::Money;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Where a price version stands relative to now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceVersionState {
+    /// Replaced by a later version; still billed to servers bought at it
+    Superseded,
+    /// What new orders are placed at
+    Current,
+    /// Takes effect at `effective_from`
+    Scheduled,
+}
+
+/// Setup and monthly price of a package from `effective_from` until the
+/// next version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceVersion {
+    pub id: i32,
+    pub package_id: Uuid,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+    pub effective_from: DateTime<Utc>,
+    pub note: String,
+    pub state: PriceVersionState,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceHistory {
+    pub sku: String,
+    pub package_id: Uuid,
+    /// Newest first
+    pub versions: Vec<PriceVersion>,
+}
+
+impl PriceHistory {
+    pub fn current(&self) -> Option<&PriceVersion> {
+        self.versions
+            .iter()
+            .find(|v| v.state == PriceVersionState::Current)
+    }
+}
+
+/// State of each version given their effective dates, newest first
+pub fn version_states(
+    effective_from_newest_first: &[DateTime<Utc>],
+    now: DateTime<Utc>,
+) -> Vec<PriceVersionState> {
+    let mut current_seen = false;
+    effective_from_newest_first
+        .iter()
+        .map(|from| {
+            if *from > now {
+                PriceVersionState::Scheduled
+            } else if !current_seen {
+                current_seen = true;
+                PriceVersionState::Current
+            } else {
+                PriceVersionState::Superseded
+            }
+        })
+        .collect()
+}
+
+/// Admin request for a new price version of a package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceChangeRequest {
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+    /// Defaults to now
+    pub effective_from: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub note: String,
+}
+
+impl PriceChangeRequest {
+    pub fn validate(&self, now: DateTime<Utc>) -> Result<(), String> {
+        if !self.setup_price_usdc.is_positive() {
+            return Err("setup price must be positive".to_string());
+        }
+        if self.monthly_price_usdc < Money::ZERO {
+            return Err("monthly price must not be negative".to_string());
+        }
+        if self.effective_from.is_some_and(|from| from < now) {
+            return Err("price changes cannot take effect in the past".to_string());
+        }
+        Ok(())
+    }
+}
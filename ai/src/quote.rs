@@ -0,0 +1,475 @@
+use crate::addons::{AddonSelection, SelectedAddon};
+use crate::money::{Money, Rounding};
+use crate::Provenance;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Line kind for the hardware and setup price of a package
+pub const LINE_KIND_HARDWARE: &str = "hardware";
+/// Line kind for the setup price of an add-on
+pub const LINE_KIND_ADDON: &str = "addon";
+/// Line kind for the tiered discount on the number of servers
+pub const LINE_KIND_VOLUME_DISCOUNT: &str = "volume_discount";
+/// Line kind for a redeemed promo code
+pub const LINE_KIND_PROMO: &str = "promo";
+
+/// What a promo code takes off
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PromoDiscount {
+    Percentage {
+        #[serde(with = "rust_decimal::serde::float")]
+        percent: Decimal,
+    },
+    Fixed {
+        amount_usdc: Money,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromoCode {
+    pub code: String,
+    pub description: String,
+    pub discount: PromoDiscount,
+    /// Packages the code is valid for; empty means every package
+    pub package_ids: Vec<Uuid>,
+    pub max_redemptions: Option<u32>,
+    pub redemptions: u32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub is_active: bool,
+}
+
+impl PromoCode {
+    /// Promo codes are matched case-insensitively and stored uppercase
+    pub fn normalize_code(code: &str) -> String {
+        code.trim().to_uppercase()
+    }
+
+    /// Why the code cannot be redeemed at `now`, if it cannot
+    pub fn check_redeemable(&self, now: DateTime<Utc>) -> Result<(), String> {
+        if !self.is_active {
+            return Err(format!("promo code {} is no longer active", self.code));
+        }
+        if self.expires_at.is_some_and(|at| at <= now) {
+            return Err(format!("promo code {} has expired", self.code));
+        }
+        if self
+            .max_redemptions
+            .is_some_and(|max| self.redemptions >= max)
+        {
+            return Err(format!("promo code {} has been fully redeemed", self.code));
+        }
+        Ok(())
+    }
+
+    pub fn applies_to(&self, package_id: Uuid) -> bool {
+        self.package_ids.is_empty() || self.package_ids.contains(&package_id)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.code.is_empty()
+            || !self
+                .code
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err("promo codes may only contain letters, digits, '-' and '_'".to_string());
+        }
+        match &self.discount {
+            PromoDiscount::Percentage { percent } => {
+                if *percent <= Decimal::ZERO || *percent > Decimal::ONE_HUNDRED {
+                    return Err(format!(
+                        "percentage {percent} must be above 0 and at most 100"
+                    ));
+                }
+            }
+            PromoDiscount::Fixed { amount_usdc } => {
+                if !amount_usdc.is_positive() {
+                    return Err("fixed discount must be positive".to_string());
+                }
+            }
+        }
+        if self.max_redemptions == Some(0) {
+            return Err("max_redemptions must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Admin request to create a promo code
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatePromoCodeRequest {
+    pub code: String,
+    #[serde(default)]
+    pub description: String,
+    pub discount: PromoDiscount,
+    #[serde(default)]
+    pub package_ids: Vec<Uuid>,
+    pub max_redemptions: Option<u32>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Percentage off the hardware subtotal once an order reaches `min_quantity`
+/// servers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeDiscountTier {
+    pub min_quantity: u32,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub percent: Decimal,
+}
+
+/// The best tier reached by `quantity` servers
+pub fn volume_tier(tiers: &[VolumeDiscountTier], quantity: u32) -> Option<&VolumeDiscountTier> {
+    tiers
+        .iter()
+        .filter(|t| quantity >= t.min_quantity)
+        .max_by_key(|t| t.min_quantity)
+}
+
+pub fn validate_volume_tiers(tiers: &[VolumeDiscountTier]) -> Result<(), String> {
+    let mut sorted: Vec<_> = tiers.iter().collect();
+    sorted.sort_by_key(|t| t.min_quantity);
+
+    for tier in &sorted {
+        if tier.min_quantity < 2 {
+            return Err("volume discounts start at 2 servers".to_string());
+        }
+        if tier.percent <= Decimal::ZERO || tier.percent >= Decimal::ONE_HUNDRED {
+            return Err(format!(
+                "volume discount {}% must be between 0 and 100",
+                tier.percent
+            ));
+        }
+    }
+    for pair in sorted.windows(2) {
+        if pair[0].min_quantity == pair[1].min_quantity {
+            return Err(format!(
+                "duplicate tier for {} servers",
+                pair[0].min_quantity
+            ));
+        }
+        if pair[1].percent <= pair[0].percent {
+            return Err("larger tiers must give a larger discount".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// One package in a quote request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteItem {
+    pub sku: String,
+    pub provenance: Provenance,
+    pub quantity: u32,
+    /// Add-ons for each server of the item
+    #[serde(default)]
+    pub addons: Vec<AddonSelection>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteRequest {
+    pub items: Vec<QuoteItem>,
+    pub promo_code: Option<String>,
+}
+
+/// A quote item resolved against the catalog
+#[derive(Debug, Clone)]
+pub struct PricedItem {
+    pub package_id: Uuid,
+    pub description: String,
+    pub quantity: u32,
+    /// Price of the chosen provenance, after depreciation
+    pub unit_price_usdc: Money,
+    /// Depreciation discount of the chosen provenance
+    pub discount_percentage: Option<Decimal>,
+    pub monthly_price_usdc: Money,
+    /// Add-ons for each server
+    pub addons: Vec<SelectedAddon>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteLine {
+    pub kind: String,
+    pub description: String,
+    pub package_id: Option<Uuid>,
+    pub quantity: u32,
+    pub unit_price_usdc: Money,
+    /// Negative for discounts
+    pub amount_usdc: Money,
+    /// Depreciation discount already included in the unit price
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub discount_percentage: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quote {
+    pub lines: Vec<QuoteLine>,
+    pub subtotal_usdc: Money,
+    pub discount_usdc: Money,
+    /// One-time hardware and setup total
+    pub total_usdc: Money,
+    /// Recurring hosting, which discounts do not apply to
+    pub monthly_usdc: Money,
+    pub promo_code: Option<String>,
+}
+
+/// Price a set of items. The volume discount is taken off the one-time
+/// subtotal, add-on setup included, first, then the promo code off what
+/// remains of the packages it is valid for. Both appear as negative lines.
+pub fn build_quote(
+    items: &[PricedItem],
+    tiers: &[VolumeDiscountTier],
+    promo: Option<&PromoCode>,
+) -> Result<Quote, String> {
+    if items.is_empty() {
+        return Err("a quote needs at least one item".to_string());
+    }
+    if items.iter().any(|i| i.quantity == 0) {
+        return Err("quantities must be at least 1".to_string());
+    }
+
+    let mut lines: Vec<QuoteLine> = Vec::new();
+    for item in items {
+        lines.push(QuoteLine {
+            kind: LINE_KIND_HARDWARE.to_string(),
+            description: item.description.clone(),
+            package_id: Some(item.package_id),
+            quantity: item.quantity,
+            unit_price_usdc: item.unit_price_usdc,
+            amount_usdc: item
+                .unit_price_usdc
+                .mul_ratio(item.quantity as i64, 1, Rounding::HalfUp),
+            discount_percentage: item.discount_percentage,
+        });
+        for selected in &item.addons {
+            let quantity = item
+                .quantity
+                .checked_mul(selected.quantity)
+                .ok_or_else(|| format!("too many {} add-ons", selected.addon.name))?;
+            lines.push(QuoteLine {
+                kind: LINE_KIND_ADDON.to_string(),
+                description: selected.addon.name.clone(),
+                package_id: Some(item.package_id),
+                quantity,
+                unit_price_usdc: selected.addon.setup_price_usdc,
+                amount_usdc: selected.addon.setup_price_usdc.mul_ratio(
+                    quantity as i64,
+                    1,
+                    Rounding::HalfUp,
+                ),
+                discount_percentage: None,
+            });
+        }
+    }
+
+    let subtotal: Money = lines.iter().map(|l| l.amount_usdc).sum();
+    let quantity = items
+        .iter()
+        .try_fold(0u32, |total, i| total.checked_add(i.quantity))
+        .ok_or("too many servers in one quote")?;
+
+    let volume_percent = volume_tier(tiers, quantity).map(|t| t.percent);
+    if let Some(percent) = volume_percent {
+        let discount = subtotal.percent(percent, Rounding::HalfUp);
+        lines.push(discount_line(
+            LINE_KIND_VOLUME_DISCOUNT,
+            format!("Volume discount {percent}% for {quantity} servers"),
+            discount,
+        ));
+    }
+
+    if let Some(promo) = promo {
+        let eligible: Money = lines
+            .iter()
+            .filter(|l| l.kind == LINE_KIND_HARDWARE)
+            .filter(|l| l.package_id.is_some_and(|id| promo.applies_to(id)))
+            .map(|l| l.amount_usdc)
+            .sum();
+        if eligible.is_zero() {
+            return Err(format!(
+                "promo code {} is not valid for these packages",
+                promo.code
+            ));
+        }
+
+        // The promo applies to what is left after the volume discount
+        let eligible = match volume_percent {
+            Some(percent) => eligible - eligible.percent(percent, Rounding::HalfUp),
+            None => eligible,
+        };
+        let discount = match &promo.discount {
+            PromoDiscount::Percentage { percent } => eligible.percent(*percent, Rounding::HalfUp),
+            PromoDiscount::Fixed { amount_usdc } => (*amount_usdc).min(eligible),
+        };
+        lines.push(discount_line(
+            LINE_KIND_PROMO,
+            format!("Promo {}: {}", promo.code, promo.description),
+            discount,
+        ));
+    }
+
+    let total: Money = lines.iter().map(|l| l.amount_usdc).sum();
+
+    Ok(Quote {
+        lines,
+        subtotal_usdc: subtotal,
+        discount_usdc: subtotal - total,
+        total_usdc: total,
+        monthly_usdc: items
+            .iter()
+            .map(|i| {
+                let per_server = i.monthly_price_usdc
+                    + i.addons
+                        .iter()
+                        .map(SelectedAddon::monthly_usdc)
+                        .sum::<Money>();
+                per_server.mul_ratio(i.quantity as i64, 1, Rounding::HalfUp)
+            })
+            .sum(),
+        promo_code: promo.map(|p| p.code.clone()),
+    })
+}
+
+fn discount_line(kind: &str, description: String, discount: Money) -> QuoteLine {
+    QuoteLine {
+        kind: kind.to_string(),
+        description,
+        package_id: None,
+        quantity: 1,
+        unit_price_usdc: -discount,
+        amount_usdc: -discount,
+        discount_percentage: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::addons::{AddonKind, PackageAddon};
+
+    fn item(package_id: Uuid, quantity: u32, unit_usdc: i64) -> PricedItem {
+        PricedItem {
+            package_id,
+            description: "Workstation".to_string(),
+            quantity,
+            unit_price_usdc: Money::from_usdc(unit_usdc),
+            discount_percentage: None,
+            monthly_price_usdc: Money::from_usdc(100),
+            addons: Vec::new(),
+        }
+    }
+
+    fn backup(package_id: Uuid, quantity: u32) -> SelectedAddon {
+        SelectedAddon {
+            addon: PackageAddon {
+                id: 1,
+                package_id,
+                code: "backup".to_string(),
+                kind: AddonKind::Backup,
+                name: "Managed backup".to_string(),
+                storage_gb: 0,
+                ram_gb: 0,
+                setup_price_usdc: Money::from_usdc(50),
+                monthly_price_usdc: Money::from_usdc(10),
+                max_quantity: 1,
+                is_active: true,
+            },
+            quantity,
+        }
+    }
+
+    #[test]
+    fn empty_quotes_and_zero_quantities_are_rejected() {
+        assert!(build_quote(&[], &[], None).is_err());
+        assert!(build_quote(&[item(Uuid::new_v4(), 0, 1_000)], &[], None).is_err());
+    }
+
+    #[test]
+    fn overflowing_quantities_are_rejected() {
+        let package_id = Uuid::new_v4();
+
+        let mut many = item(package_id, 70_000, 1);
+        many.addons.push(backup(package_id, 70_000));
+        assert_eq!(
+            build_quote(&[many], &[], None).unwrap_err(),
+            "too many Managed backup add-ons"
+        );
+
+        let items = [item(package_id, u32::MAX, 1), item(package_id, 1, 1)];
+        assert_eq!(
+            build_quote(&items, &[], None).unwrap_err(),
+            "too many servers in one quote"
+        );
+    }
+
+    #[test]
+    fn volume_discount_comes_before_the_promo_on_eligible_hardware() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut first = item(a, 2, 1_000);
+        first.addons.push(backup(a, 1));
+        let items = [first, item(b, 1, 500)];
+        let tiers = [VolumeDiscountTier {
+            min_quantity: 3,
+            percent: Decimal::from(10),
+        }];
+        let promo = PromoCode {
+            code: "LAUNCH".to_string(),
+            description: "20% off".to_string(),
+            discount: PromoDiscount::Percentage {
+                percent: Decimal::from(20),
+            },
+            package_ids: vec![a],
+            max_redemptions: None,
+            redemptions: 0,
+            expires_at: None,
+            is_active: true,
+        };
+
+        let quote = build_quote(&items, &tiers, Some(&promo)).unwrap();
+
+        let kinds: Vec<&str> = quote.lines.iter().map(|l| l.kind.as_str()).collect();
+        assert_eq!(
+            kinds,
+            [
+                LINE_KIND_HARDWARE,
+                LINE_KIND_ADDON,
+                LINE_KIND_HARDWARE,
+                LINE_KIND_VOLUME_DISCOUNT,
+                LINE_KIND_PROMO
+            ]
+        );
+        // Hardware 2000 + 500 and two backups at 50
+        assert_eq!(quote.subtotal_usdc, Money::from_usdc(2_600));
+        assert_eq!(quote.lines[1].quantity, 2);
+        // 10% volume discount, then 20% of the 1800 left of package a
+        assert_eq!(quote.lines[3].amount_usdc, Money::from_usdc(-260));
+        assert_eq!(quote.lines[4].amount_usdc, Money::from_usdc(-360));
+        assert_eq!(quote.discount_usdc, Money::from_usdc(620));
+        assert_eq!(quote.total_usdc, Money::from_usdc(1_980));
+        assert_eq!(quote.monthly_usdc, Money::from_usdc(320));
+        assert_eq!(quote.promo_code.as_deref(), Some("LAUNCH"));
+    }
+
+    #[test]
+    fn promo_codes_must_apply_to_a_quoted_package() {
+        let promo = PromoCode {
+            code: "OTHER".to_string(),
+            description: String::new(),
+            discount: PromoDiscount::Fixed {
+                amount_usdc: Money::from_usdc(100),
+            },
+            package_ids: vec![Uuid::new_v4()],
+            max_redemptions: None,
+            redemptions: 0,
+            expires_at: None,
+            is_active: true,
+        };
+
+        assert_eq!(
+            build_quote(&[item(Uuid::new_v4(), 1, 1_000)], &[], Some(&promo)).unwrap_err(),
+            "promo code OTHER is not valid for these packages"
+        );
+    }
+}
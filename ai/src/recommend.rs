@@ -0,0 +1,263 @@
+use crate::gpu::GpuMemory;
+use crate::{GpuClass, Money, Package};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// Largest model, context and concurrency an estimate is made for
+pub const MAX_PARAMETERS_B: u32 = 2_000;
+pub const MAX_CONTEXT_LENGTH: u32 = 1_048_576;
+pub const MAX_CONCURRENCY: u32 = 1_024;
+
+/// Largest attention layout accepted, which keeps the KV cache size in range
+pub const MAX_LAYERS: u32 = 1_024;
+pub const MAX_KV_HEADS: u32 = 1_024;
+pub const MAX_HEAD_DIM: u32 = 4_096;
+
+/// Runtime memory beyond weights and KV cache (CUDA context, activations,
+/// fragmentation): a fixed part plus a share of the weights
+const OVERHEAD_FIXED_GB: Decimal = Decimal::ONE;
+const OVERHEAD_PERCENT_OF_WEIGHTS: i64 = 10;
+
+/// Bytes per KV cache element; caches are kept at FP16
+const KV_CACHE_BYTES: u64 = 2;
+
+/// Weight format of a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantization {
+    Fp16,
+    Fp8,
+    /// GGUF-style integer quantizations, including their scale overhead
+    Q8,
+    Q6,
+    Q5,
+    Q4,
+    Q3,
+}
+
+impl Quantization {
+    pub fn bits_per_weight(&self) -> Decimal {
+        match self {
+            Quantization::Fp16 => Decimal::from(16),
+            Quantization::Fp8 => Decimal::from(8),
+            Quantization::Q8 => Decimal::new(85, 1),
+            Quantization::Q6 => Decimal::new(66, 1),
+            Quantization::Q5 => Decimal::new(55, 1),
+            Quantization::Q4 => Decimal::new(48, 1),
+            Quantization::Q3 => Decimal::new(39, 1),
+        }
+    }
+}
+
+/// The attention layout that sizes the KV cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelShape {
+    pub layers: u32,
+    pub kv_heads: u32,
+    pub head_dim: u32,
+}
+
+impl ModelShape {
+    /// A typical grouped-query attention layout for a model of the size
+    /// (8 KV heads of 128), with the layer count of common model families
+    pub fn estimate(parameters_b: Decimal) -> Self {
+        let layers = [(4, 28), (10, 32), (20, 48), (40, 64), (80, 80), (150, 88)]
+            .into_iter()
+            .find(|(up_to_b, _)| parameters_b <= Decimal::from(*up_to_b))
+            .map_or(126, |(_, layers)| layers);
+
+        Self {
+            layers,
+            kv_heads: 8,
+            head_dim: 128,
+        }
+    }
+
+    /// KV cache bytes one token takes: keys and values in every layer.
+    /// Shapes within the `MAX_*` bounds cannot overflow it.
+    pub fn kv_bytes_per_token(&self) -> u64 {
+        2 * u64::from(self.layers)
+            * u64::from(self.kv_heads)
+            * u64::from(self.head_dim)
+            * KV_CACHE_BYTES
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelFitRequest {
+    /// Parameter count in billions, e.g. 70
+    #[serde(with = "rust_decimal::serde::float")]
+    pub parameters_b: Decimal,
+    pub quantization: Quantization,
+    /// Tokens of context each sequence holds
+    pub context_length: u32,
+    /// Sequences served at once, each with a full context
+    #[serde(default = "default_concurrency")]
+    pub concurrency: u32,
+    /// Attention layout; estimated from the parameter count if omitted
+    pub shape: Option<ModelShape>,
+}
+
+fn default_concurrency() -> u32 {
+    1
+}
+
+impl ModelFitRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.parameters_b <= Decimal::ZERO || self.parameters_b > Decimal::from(MAX_PARAMETERS_B)
+        {
+            return Err(format!(
+                "parameters_b must be above 0 and at most {MAX_PARAMETERS_B}"
+            ));
+        }
+        if self.context_length == 0 || self.context_length > MAX_CONTEXT_LENGTH {
+            return Err(format!(
+                "context length must be between 1 and {MAX_CONTEXT_LENGTH} tokens"
+            ));
+        }
+        if self.concurrency == 0 || self.concurrency > MAX_CONCURRENCY {
+            return Err(format!(
+                "concurrency must be between 1 and {MAX_CONCURRENCY}"
+            ));
+        }
+        if let Some(shape) = &self.shape {
+            if shape.layers == 0 || shape.layers > MAX_LAYERS {
+                return Err(format!("layers must be between 1 and {MAX_LAYERS}"));
+            }
+            if shape.kv_heads == 0 || shape.kv_heads > MAX_KV_HEADS {
+                return Err(format!("KV heads must be between 1 and {MAX_KV_HEADS}"));
+            }
+            if shape.head_dim == 0 || shape.head_dim > MAX_HEAD_DIM {
+                return Err(format!(
+                    "head dimension must be between 1 and {MAX_HEAD_DIM}"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Memory a model needs to serve the request, in GB (10^9 bytes)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryEstimate {
+    pub shape: ModelShape,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub weights_gb: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub kv_cache_gb: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub overhead_gb: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub total_gb: Decimal,
+}
+
+/// How the model sits on one package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageFit {
+    pub sku: String,
+    pub name: String,
+    pub gpu_class: GpuClass,
+    pub gpu_count: u16,
+    /// GPU memory the model can use: VRAM across all cards, or the GPU's
+    /// share of unified memory
+    pub memory_gb: u16,
+    pub fits: bool,
+    /// Memory left over; negative when the model does not fit
+    #[serde(with = "rust_decimal::serde::float")]
+    pub headroom_gb: Decimal,
+    /// Lowest price the package is sold at
+    pub price_usdc: Money,
+    pub monthly_price_usdc: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recommendation {
+    pub estimate: MemoryEstimate,
+    /// Fitting packages cheapest first, then the rest closest to fitting
+    /// first. Packages without GPU are left out.
+    pub packages: Vec<PackageFit>,
+}
+
+/// Estimate the memory the model needs: weights at the quantization's
+/// bits per weight, an FP16 KV cache for every token of every sequence, and
+/// runtime overhead. Figures are rounded up to 0.1 GB.
+pub fn estimate_memory(req: &ModelFitRequest) -> Result<MemoryEstimate, String> {
+    req.validate()?;
+
+    let shape = req
+        .shape
+        .unwrap_or_else(|| ModelShape::estimate(req.parameters_b));
+    let weights_gb = req.parameters_b * req.quantization.bits_per_weight() / Decimal::from(8);
+    let kv_cache_bytes = Decimal::from(shape.kv_bytes_per_token())
+        * Decimal::from(req.context_length)
+        * Decimal::from(req.concurrency);
+    let kv_cache_gb = kv_cache_bytes / Decimal::from(1_000_000_000u64);
+    let overhead_gb = OVERHEAD_FIXED_GB
+        + weights_gb * Decimal::from(OVERHEAD_PERCENT_OF_WEIGHTS) / Decimal::ONE_HUNDRED;
+
+    let weights_gb = round_up(weights_gb);
+    let kv_cache_gb = round_up(kv_cache_gb);
+    let overhead_gb = round_up(overhead_gb);
+
+    Ok(MemoryEstimate {
+        shape,
+        weights_gb,
+        kv_cache_gb,
+        overhead_gb,
+        total_gb: weights_gb + kv_cache_gb + overhead_gb,
+    })
+}
+
+/// Which of `packages` can serve the request
+pub fn recommend(req: &ModelFitRequest, packages: &[Package]) -> Result<Recommendation, String> {
+    let estimate = estimate_memory(req)?;
+
+    let mut fits: Vec<PackageFit> = packages
+        .iter()
+        .filter_map(|package| {
+            let memory_gb = usable_memory_gb(package)?;
+            let headroom_gb = Decimal::from(memory_gb) - estimate.total_gb;
+            Some(PackageFit {
+                sku: package.sku.clone(),
+                name: package.name.clone(),
+                gpu_class: package.gpu_class.clone(),
+                gpu_count: package.gpu_count,
+                memory_gb,
+                fits: headroom_gb >= Decimal::ZERO,
+                headroom_gb,
+                price_usdc: package.get_min_price(),
+                monthly_price_usdc: package.monthly_price_usdc,
+            })
+        })
+        .collect();
+
+    fits.sort_by(|a, b| {
+        b.fits.cmp(&a.fits).then_with(|| {
+            if a.fits {
+                a.price_usdc.cmp(&b.price_usdc)
+            } else {
+                b.headroom_gb.cmp(&a.headroom_gb)
+            }
+        })
+    });
+
+    Ok(Recommendation {
+        estimate,
+        packages: fits,
+    })
+}
+
+/// GPU memory a model can be loaded into. Dedicated VRAM is pooled across
+/// cards by tensor parallelism; an integrated GPU gets the share of system
+/// memory the package is configured with, which `vram_gb` records.
+fn usable_memory_gb(package: &Package) -> Option<u16> {
+    let memory_gb = match package.gpu_class.spec()?.memory {
+        GpuMemory::Dedicated { .. } => package.vram_gb,
+        GpuMemory::Unified { max_gb } => package.vram_gb.min(max_gb),
+    };
+    (memory_gb > 0).then_some(memory_gb)
+}
+
+fn round_up(gb: Decimal) -> Decimal {
+    gb.round_dp_with_strategy(1, rust_decimal::RoundingStrategy::AwayFromZero)
+}
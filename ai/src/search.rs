@@ -0,0 +1,150 @@
+use crate::{Availability, GpuClass, Money, Package, Provenance, ProvenanceOption};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Filters and order of the public package list. Price filters apply to
+/// the provenance options a package is sold in: a package matches if one of
+/// its options passing the provenance filter is priced within the range.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageQuery {
+    pub gpu_class: Option<GpuClass>,
+    pub min_vram_gb: Option<u16>,
+    pub gpu_count: Option<u16>,
+    pub min_price_usdc: Option<Money>,
+    pub max_price_usdc: Option<Money>,
+    pub availability: Option<AvailabilityFilter>,
+    pub provenance: Option<ProvenanceFilter>,
+    #[serde(default)]
+    pub sort: PackageSort,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AvailabilityFilter {
+    InStock,
+    Preorder,
+    Build,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvenanceFilter {
+    New,
+    Used,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PackageSort {
+    /// Cheapest matching option first
+    #[default]
+    Price,
+    PriceDesc,
+    /// Cheapest matching option per GB of VRAM first; packages without
+    /// VRAM last
+    PricePerVramGb,
+}
+
+impl PackageQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        for price in [self.min_price_usdc, self.max_price_usdc]
+            .into_iter()
+            .flatten()
+        {
+            if price.is_negative() {
+                return Err("prices must not be negative".to_string());
+            }
+        }
+        if let (Some(min), Some(max)) = (self.min_price_usdc, self.max_price_usdc) {
+            if min > max {
+                return Err(format!(
+                    "min_price_usdc ({min}) must not be above max_price_usdc ({max})"
+                ));
+            }
+        }
+        if self.gpu_count == Some(0) && self.min_vram_gb.is_some_and(|v| v > 0) {
+            return Err("packages without GPU have no VRAM".to_string());
+        }
+        Ok(())
+    }
+
+    /// The packages matching the query, in the requested order
+    pub fn apply(&self, packages: Vec<Package>) -> Vec<Package> {
+        let mut matching: Vec<(Money, Package)> = packages
+            .into_iter()
+            .filter(|p| self.matches_hardware(p))
+            .filter_map(|p| Some((self.lowest_matching_price(&p)?, p)))
+            .collect();
+
+        match self.sort {
+            PackageSort::Price => matching.sort_by_key(|(price, _)| *price),
+            PackageSort::PriceDesc => matching.sort_by_key(|(price, _)| std::cmp::Reverse(*price)),
+            PackageSort::PricePerVramGb => matching.sort_by(|a, b| {
+                match (price_per_vram_gb(a.0, &a.1), price_per_vram_gb(b.0, &b.1)) {
+                    (Some(x), Some(y)) => x.cmp(&y),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => a.0.cmp(&b.0),
+                }
+            }),
+        }
+
+        matching.into_iter().map(|(_, p)| p).collect()
+    }
+
+    fn matches_hardware(&self, package: &Package) -> bool {
+        self.gpu_class
+            .as_ref()
+            .is_none_or(|class| *class == package.gpu_class)
+            && self.min_vram_gb.is_none_or(|min| package.vram_gb >= min)
+            && self
+                .gpu_count
+                .is_none_or(|count| package.gpu_count == count)
+            && self.availability.is_none_or(|wanted| {
+                matches!(
+                    (wanted, &package.availability),
+                    (AvailabilityFilter::InStock, Availability::InStock)
+                        | (AvailabilityFilter::Preorder, Availability::Preorder)
+                        | (AvailabilityFilter::Build, Availability::Build { .. })
+                )
+            })
+    }
+
+    /// Price of the cheapest option passing the provenance and price
+    /// filters. A package without provenance rows is sold new at its setup
+    /// price.
+    fn lowest_matching_price(&self, package: &Package) -> Option<Money> {
+        let new_at_setup_price = [ProvenanceOption {
+            provenance_type: Provenance::New,
+            quantity_available: 0,
+            calculated_price: package.setup_price_usdc,
+            discount_percentage: None,
+        }];
+        let options = if package.provenances.is_empty() {
+            &new_at_setup_price[..]
+        } else {
+            &package.provenances[..]
+        };
+
+        options
+            .iter()
+            .filter(|o| {
+                self.provenance.is_none_or(|wanted| {
+                    matches!(
+                        (wanted, &o.provenance_type),
+                        (ProvenanceFilter::New, Provenance::New)
+                            | (ProvenanceFilter::Used, Provenance::Used { .. })
+                    )
+                })
+            })
+            .map(|o| o.calculated_price)
+            .filter(|price| self.min_price_usdc.is_none_or(|min| *price >= min))
+            .filter(|price| self.max_price_usdc.is_none_or(|max| *price <= max))
+            .min()
+    }
+}
+
+fn price_per_vram_gb(price: Money, package: &Package) -> Option<Decimal> {
+    (package.vram_gb > 0).then(|| price.as_decimal() / Decimal::from(package.vram_gb))
+}
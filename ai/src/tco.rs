@@ -0,0 +1,149 @@
+use crate::money::{Money, Rounding};
+use crate::Provenance;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// Average hours in a month (8760 / 12)
+pub const HOURS_PER_MONTH: i64 = 730;
+/// Longest term a TCO report covers
+pub const MAX_TERM_MONTHS: u32 = 120;
+/// Lowest utilization a report is computed for (0.01%, about 4 minutes a
+/// month)
+pub const MIN_UTILIZATION_PERCENT: Decimal = Decimal::from_parts(1, 0, 0, false, 2);
+/// Highest cloud hourly rate compared against, far above any real instance
+pub const MAX_CLOUD_HOURLY_RATE_USDC: i64 = 1_000_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TcoRequest {
+    pub sku: String,
+    pub provenance: Provenance,
+    pub term_months: u32,
+    /// Share of the month the machine is busy, at least 0.01 and at most 100
+    #[serde(with = "rust_decimal::serde::float")]
+    pub utilization_percent: Decimal,
+    /// Hourly price of a comparable cloud GPU instance; defaults to the
+    /// configured comparison rate
+    pub cloud_hourly_rate_usdc: Option<Money>,
+}
+
+impl TcoRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.term_months == 0 || self.term_months > MAX_TERM_MONTHS {
+            return Err(format!(
+                "term must be between 1 and {MAX_TERM_MONTHS} months"
+            ));
+        }
+        if self.utilization_percent < MIN_UTILIZATION_PERCENT
+            || self.utilization_percent > Decimal::ONE_HUNDRED
+        {
+            return Err(format!(
+                "utilization must be at least {MIN_UTILIZATION_PERCENT} and at most 100 percent"
+            ));
+        }
+        if let Some(rate) = self.cloud_hourly_rate_usdc {
+            validate_cloud_hourly_rate(rate)?;
+        }
+        Ok(())
+    }
+}
+
+fn validate_cloud_hourly_rate(rate: Money) -> Result<(), String> {
+    if rate < Money::ZERO || rate > Money::from_usdc(MAX_CLOUD_HOURLY_RATE_USDC) {
+        return Err(format!(
+            "cloud hourly rate must be between 0 and {MAX_CLOUD_HOURLY_RATE_USDC} USDC"
+        ));
+    }
+    Ok(())
+}
+
+/// Cumulative cost at the end of a month; month 0 is the day of purchase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TcoPoint {
+    pub month: u32,
+    pub ownership_usdc: Money,
+    pub cloud_usdc: Money,
+    /// Cloud minus ownership; negative while owning costs more
+    pub savings_usdc: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TcoReport {
+    pub sku: String,
+    pub provenance: Provenance,
+    pub term_months: u32,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub utilization_percent: Decimal,
+    /// One-time hardware and setup price of the chosen provenance
+    pub hardware_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+    pub cloud_hourly_rate_usdc: Money,
+    /// Cloud cost of one month at the given utilization
+    pub cloud_monthly_usdc: Money,
+    pub points: Vec<TcoPoint>,
+    pub total_ownership_usdc: Money,
+    pub total_cloud_usdc: Money,
+    /// First month at whose end owning has cost no more than renting
+    pub break_even_month: Option<u32>,
+    /// Ownership cost per hour actually used over the term
+    pub effective_hourly_rate_usdc: Money,
+}
+
+/// Compare owning a package (hardware up front plus monthly hosting) with
+/// renting cloud capacity for the hours it would actually be used
+pub fn calculate_tco(
+    req: &TcoRequest,
+    hardware_price: Money,
+    monthly_price: Money,
+    cloud_hourly_rate: Money,
+) -> Result<TcoReport, String> {
+    req.validate()?;
+    validate_cloud_hourly_rate(cloud_hourly_rate)?;
+
+    let used_hours_per_month =
+        Decimal::from(HOURS_PER_MONTH) * req.utilization_percent / Decimal::ONE_HUNDRED;
+    let cloud_monthly = cloud_hourly_rate.mul_decimal(used_hours_per_month, Rounding::HalfUp);
+
+    let points: Vec<TcoPoint> = (0..=req.term_months)
+        .map(|month| {
+            let months = month as i64;
+            let ownership = hardware_price + monthly_price.mul_ratio(months, 1, Rounding::HalfUp);
+            let cloud = cloud_monthly.mul_ratio(months, 1, Rounding::HalfUp);
+            TcoPoint {
+                month,
+                ownership_usdc: ownership,
+                cloud_usdc: cloud,
+                savings_usdc: cloud - ownership,
+            }
+        })
+        .collect();
+
+    let last = points.last().expect("term is at least one month");
+    let (total_ownership, total_cloud) = (last.ownership_usdc, last.cloud_usdc);
+    let break_even_month = points
+        .iter()
+        .find(|p| p.month > 0 && p.ownership_usdc <= p.cloud_usdc)
+        .map(|p| p.month);
+
+    let used_hours = used_hours_per_month * Decimal::from(req.term_months);
+    let effective_hourly = total_ownership
+        .as_decimal()
+        .checked_div(used_hours)
+        .map(|rate| Money::from_decimal(rate, Rounding::HalfUp))
+        .ok_or_else(|| "effective hourly rate is out of range".to_string())?;
+
+    Ok(TcoReport {
+        sku: req.sku.clone(),
+        provenance: req.provenance.clone(),
+        term_months: req.term_months,
+        utilization_percent: req.utilization_percent,
+        hardware_price_usdc: hardware_price,
+        monthly_price_usdc: monthly_price,
+        cloud_hourly_rate_usdc: cloud_hourly_rate,
+        cloud_monthly_usdc: cloud_monthly,
+        total_ownership_usdc: total_ownership,
+        total_cloud_usdc: total_cloud,
+        break_even_month,
+        effective_hourly_rate_usdc: effective_hourly,
+        points,
+    })
+}
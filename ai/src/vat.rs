@@ -0,0 +1,246 @@
+use crate::money::{Money, Rounding};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// ISO 3166-1 alpha-2 codes of the EU member states
+pub const EU_MEMBER_STATES: [&str; 27] = [
+    "AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "ES", "FI", "FR", "GR", "HR", "HU", "IE", "IT",
+    "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK",
+];
+
+pub fn is_eu_member(country_code: &str) -> bool {
+    EU_MEMBER_STATES.contains(&country_code)
+}
+
+/// Prefix of VAT identification numbers issued by a member state. Greece
+/// uses `EL` rather than its ISO code.
+pub fn vat_id_prefix(country_code: &str) -> &str {
+    match country_code {
+        "GR" => "EL",
+        other => other,
+    }
+}
+
+/// Strip the spaces, dots and dashes people type into VAT IDs and uppercase
+/// the rest, e.g. `de 123.456.789` becomes `DE123456789`
+pub fn normalize_vat_id(vat_id: &str) -> String {
+    vat_id
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '.' | '-'))
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Offline syntax check of a normalized VAT ID for `country_code`: the
+/// member state prefix followed by 2 to 12 letters or digits. Says nothing
+/// about whether the number is actually registered.
+pub fn check_vat_id_format(country_code: &str, vat_id: &str) -> Result<(), String> {
+    if !is_eu_member(country_code) {
+        return Err(format!("{country_code} is not an EU member state"));
+    }
+
+    let prefix = vat_id_prefix(country_code);
+    let Some(number) = vat_id.strip_prefix(prefix) else {
+        return Err(format!(
+            "VAT ID for {country_code} must start with {prefix}"
+        ));
+    };
+
+    if !(2..=12).contains(&number.len()) || !number.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("VAT ID {vat_id} is not well formed"));
+    }
+
+    Ok(())
+}
+
+/// The legal entity an organization is invoiced as
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BillingProfile {
+    pub org_id: Uuid,
+    pub legal_name: String,
+    pub address: String,
+    pub country_code: String,
+    pub vat_id: Option<String>,
+    /// Whether `vat_id` passed validation when it was last saved
+    pub vat_id_valid: bool,
+}
+
+impl BillingProfile {
+    /// A business customer with a validated EU VAT ID
+    pub fn is_eu_business(&self) -> bool {
+        self.vat_id.is_some() && self.vat_id_valid && is_eu_member(&self.country_code)
+    }
+}
+
+/// Request to create or replace an organization's billing profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BillingProfileRequest {
+    pub legal_name: String,
+    #[serde(default)]
+    pub address: String,
+    pub country_code: String,
+    pub vat_id: Option<String>,
+}
+
+/// A VAT rate for a country, in force from `valid_from` until the day before
+/// `valid_to` (open ended when `None`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VatRate {
+    pub country_code: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub rate_percent: Decimal,
+    pub valid_from: NaiveDate,
+    pub valid_to: Option<NaiveDate>,
+}
+
+impl VatRate {
+    pub fn applies_on(&self, date: NaiveDate) -> bool {
+        self.valid_from <= date && self.valid_to.is_none_or(|to| date < to)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.country_code.len() != 2
+            || !self.country_code.chars().all(|c| c.is_ascii_uppercase())
+        {
+            return Err(format!(
+                "country code {} must be two uppercase letters",
+                self.country_code
+            ));
+        }
+        if self.rate_percent.is_sign_negative() || self.rate_percent >= Decimal::ONE_HUNDRED {
+            return Err(format!(
+                "VAT rate {}% must be between 0 and 100",
+                self.rate_percent
+            ));
+        }
+        if self.valid_to.is_some_and(|to| to <= self.valid_from) {
+            return Err("valid_to must be after valid_from".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Standard VAT rate of a country on a given date
+pub fn rate_on(rates: &[VatRate], country_code: &str, date: NaiveDate) -> Option<Decimal> {
+    rates
+        .iter()
+        .filter(|r| r.country_code == country_code && r.applies_on(date))
+        .max_by_key(|r| r.valid_from)
+        .map(|r| r.rate_percent)
+}
+
+/// How VAT applies to an invoice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VatTreatment {
+    /// VAT charged at the rate of `country_code`
+    Standard,
+    /// B2B supply to another member state; the customer accounts for VAT
+    ReverseCharge,
+    /// Customer outside the EU; no EU VAT is due
+    Export,
+}
+
+impl VatTreatment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VatTreatment::Standard => "standard",
+            VatTreatment::ReverseCharge => "reverse_charge",
+            VatTreatment::Export => "export",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "standard" => Some(VatTreatment::Standard),
+            "reverse_charge" => Some(VatTreatment::ReverseCharge),
+            "export" => Some(VatTreatment::Export),
+            _ => None,
+        }
+    }
+
+    /// Mention that has to be printed on invoices without VAT
+    pub fn invoice_note(&self) -> Option<&'static str> {
+        match self {
+            VatTreatment::Standard => None,
+            VatTreatment::ReverseCharge => {
+                Some("Reverse charge: VAT to be accounted for by the recipient (Art. 196 Directive 2006/112/EC)")
+            }
+            VatTreatment::Export => {
+                Some("Not subject to EU VAT: services supplied outside the EU (Art. 44 / 59 Directive 2006/112/EC)")
+            }
+        }
+    }
+}
+
+/// The VAT outcome for one invoice
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VatAssessment {
+    pub treatment: VatTreatment,
+    /// Country whose VAT applies; the customer's country for zero-rated
+    /// treatments
+    pub country_code: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub rate_percent: Decimal,
+    pub customer_vat_id: Option<String>,
+}
+
+impl VatAssessment {
+    /// VAT due on a net amount
+    pub fn vat_on(&self, net: Money) -> Money {
+        net.percent(self.rate_percent, Rounding::HalfUp)
+    }
+}
+
+/// Decide how VAT applies to a supply of hosting services by a business in
+/// `supplier_country` on `date`:
+///
+/// - customers in the supplier's own country pay local VAT, businesses
+///   included
+/// - EU businesses with a validated VAT ID elsewhere are reverse charged
+/// - other EU customers are treated as consumers and pay the VAT of their
+///   own country
+/// - customers outside the EU are zero rated as exports
+///
+/// Organizations without a billing profile are treated as consumers in the
+/// supplier's country.
+pub fn assess(
+    supplier_country: &str,
+    customer: Option<&BillingProfile>,
+    rates: &[VatRate],
+    date: NaiveDate,
+) -> Result<VatAssessment, String> {
+    let standard = |country_code: &str| {
+        rate_on(rates, country_code, date)
+            .map(|rate_percent| VatAssessment {
+                treatment: VatTreatment::Standard,
+                country_code: country_code.to_string(),
+                rate_percent,
+                customer_vat_id: customer.and_then(|c| c.vat_id.clone()),
+            })
+            .ok_or_else(|| format!("no VAT rate configured for {country_code} on {date}"))
+    };
+
+    let Some(customer) = customer else {
+        return standard(supplier_country);
+    };
+
+    let zero_rated = |treatment| VatAssessment {
+        treatment,
+        country_code: customer.country_code.clone(),
+        rate_percent: Decimal::ZERO,
+        customer_vat_id: customer.vat_id.clone(),
+    };
+
+    if customer.country_code == supplier_country {
+        standard(supplier_country)
+    } else if !is_eu_member(&customer.country_code) {
+        Ok(zero_rated(VatTreatment::Export))
+    } else if customer.is_eu_business() {
+        Ok(zero_rated(VatTreatment::ReverseCharge))
+    } else {
+        standard(&customer.country_code)
+    }
+}
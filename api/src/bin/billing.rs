@@ -0,0 +1,77 @@
+//! Run the monthly billing engine by hand.
+//!
+//!     billing [--dry-run] [--period YYYY-MM]
+//!
+//! Without `--period`, a real run bills the last closed month and a dry run
+//! previews the current month's invoices, which are issued once it ends.
+
+use ai::billing::BillingPeriod;
+use anyhow::{anyhow, bail};
+use chrono::Utc;
+use infra::InfraState;
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    let mut dry_run = false;
+    let mut period = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--period" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--period needs a value"))?;
+                period = Some(
+                    BillingPeriod::parse(&value)
+                        .ok_or_else(|| anyhow!("invalid period '{value}', expected YYYY-MM"))?,
+                );
+            }
+            other => {
+                bail!("unknown argument '{other}'\nusage: billing [--dry-run] [--period YYYY-MM]")
+            }
+        }
+    }
+
+    let current = BillingPeriod::containing(Utc::now().date_naive());
+    let period = period.unwrap_or(if dry_run { current } else { current.previous() });
+
+    let infra = InfraState::new().await?;
+    let invoices = infra.run_billing(period, dry_run).await?;
+
+    let heading = if dry_run { "Dry run" } else { "Billed" };
+    println!("{heading} {period}: {} invoices", invoices.len());
+
+    for invoice in &invoices {
+        println!();
+        println!("org {}  total {} USDC", invoice.org_id, invoice.total_usdc);
+        for line in &invoice.lines {
+            println!(
+                "  {:>20}  {}",
+                line.amount_usdc.to_string(),
+                line.description
+            );
+        }
+        if let Some(vat) = &invoice.vat {
+            println!(
+                "  {:>20}  VAT {} {}% ({})",
+                invoice.vat_usdc.to_string(),
+                vat.country_code,
+                vat.rate_percent,
+                vat.treatment.as_str()
+            );
+            if let Some(note) = vat.treatment.invoice_note() {
+                println!("  {:>20}  {note}", "");
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+//! Sync the package catalog with a catalog file.
+//!
+//!     catalog [--apply] [FILE]
+//!
+//! Prints the changes that would make the database match FILE (default
+//! `catalog.toml`); with `--apply` makes them, all in one transaction.
+
+use anyhow::bail;
+use infra::InfraState;
+use std::path::PathBuf;
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    let mut apply = false;
+    let mut path = None;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--apply" => apply = true,
+            other if other.starts_with('-') || path.is_some() => {
+                bail!("unknown argument '{other}'\nusage: catalog [--apply] [FILE]")
+            }
+            other => path = Some(PathBuf::from(other)),
+        }
+    }
+
+    let path = path.unwrap_or_else(|| PathBuf::from("catalog.toml"));
+    let file = infra::read_catalog_file(&path)?;
+
+    let infra = InfraState::new().await?;
+
+    if apply {
+        let plan = infra.apply_catalog_sync(&file).await?;
+        print!("{plan}");
+        if !plan.is_empty() {
+            println!("Applied.");
+        }
+    } else {
+        let plan = infra.plan_catalog_sync(&file).await?;
+        print!("{plan}");
+        if !plan.is_empty() {
+            println!("Run with --apply to make these changes.");
+        }
+    }
+
+    Ok(())
+}
@@ -1,13 +1,46 @@
+use ai::addons::{CreateAddonRequest, PackageAddon, UpdateAddonRequest};
+use ai::billing::Invoice;
+use ai::buyback::{BuybackOffer, BuybackQuoteRequest};
+use ai::catalog::{
+    CatalogPackage, CreatePackageRequest, PackageImageRequest, ProvenanceRequest,
+    ReorderImagesRequest, UpdatePackageImageRequest, UpdatePackageRequest, UpdateProvenanceRequest,
+};
+use ai::compare::PackageComparison;
+use ai::credit_note::{
+    CreateCreditNoteRequest, CreditNote, PayoutReview, PayoutSettlement, PayoutStatus,
+    RefundPayout, RefundPayoutRequest,
+};
+use ai::depreciation::{
+    DepreciationPreview, DepreciationRuleChange, DepreciationRuleRequest, RepricingImpact,
+    ScheduledDepreciationRule, ScheduledRuleStatus,
+};
+use ai::financing::{
+    CreateFinancingPlanRequest, FinancingAgreement, FinancingPlan, PackageFinancing,
+};
+use ai::fulfilment::{BuildSchedule, PartsStock};
+use ai::images::{ImageUpload, MAX_IMAGE_UPLOAD_BYTES};
+use ai::ledger::{LedgerAdjustmentRequest, LedgerCheck, OrgBalance};
+use ai::locale::{Locale, PackageTranslation, TranslationRequest};
+use ai::price_history::{PriceChangeRequest, PriceHistory};
+use ai::quote::{CreatePromoCodeRequest, PromoCode, Quote, QuoteRequest, VolumeDiscountTier};
+use ai::recommend::{ModelFitRequest, Recommendation};
+use ai::search::PackageQuery;
+use ai::tco::{TcoReport, TcoRequest};
+use ai::vat::{BillingProfile, BillingProfileRequest, VatRate};
 use ai::*;
-use axum::extract::Path;
+use axum::extract::{DefaultBodyLimit, Multipart, Path, Query};
 use axum::{
     extract::State,
-    http::{Method, StatusCode},
-    routing::{get, post},
+    http::{
+        header::{ACCEPT_LANGUAGE, AUTHORIZATION, CONTENT_LANGUAGE, VARY},
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+    },
+    routing::{get, patch, post, put},
     Json, Router,
 };
+use infra::storage::{ImageStorage, LOCAL_IMAGE_ROUTE};
 use infra::InfraState;
-use std::{net::SocketAddr, sync::Arc};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
 use tower_http::{
     cors::{Any, CorsLayer},
     services::ServeDir,
@@ -15,10 +48,14 @@ use tower_http::{
 };
 use tracing::info;
 use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
 
 #[derive(Clone)]
 struct AppState {
     infra: Arc<InfraState>,
+    // Bearer tokens for /api/admin routes and the admin each belongs to; the
+    // admin API is disabled when there are none
+    admin_tokens: Arc<HashMap<String, String>>,
 }
 
 #[tokio::main]
@@ -31,26 +68,218 @@ async fn main() -> anyhow::Result<()> {
         .unwrap_or_else(|_| EnvFilter::new("info,tower_http=info"));
     tracing_subscriber::fmt().with_env_filter(filter).init();
 
-    let infra = InfraState::new().await?;
+    let infra = Arc::new(InfraState::new().await?);
+
+    if std::env::var("BILLING_ENABLED").is_ok_and(|v| v == "true") {
+        let every = std::env::var("BILLING_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3600);
+        info!("Billing scheduler running every {every}s");
+        infra::spawn_billing_scheduler(infra.clone(), Duration::from_secs(every));
+    }
+
+    let every = std::env::var("PRICE_REFRESH_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(60);
+    info!("Price refresher running every {every}s");
+    infra::spawn_price_refresher(infra.clone(), Duration::from_secs(every));
+
+    let admin_tokens = Arc::new(admin_tokens_from_env()?);
+
     let state = AppState {
-        infra: Arc::new(infra),
+        infra,
+        admin_tokens,
     };
 
     let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
         .allow_origin(Any)
         .allow_headers(Any);
 
     // static (dist from web) served at /
     let spa = ServeDir::new("./web/dist");
 
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/api/health", get(health))
         .route("/api/auth/signup", post(signup))
         .route("/api/auth/login", post(login))
+        .route("/api/gpus", get(list_gpus))
         .route("/api/packages", get(list_packages))
+        .route("/api/packages/compare", get(compare_packages))
         .route("/api/packages/:sku", get(get_package_by_sku))
+        .route("/api/packages/:sku/depreciation", get(preview_depreciation))
+        .route("/api/packages/:sku/prices", get(get_price_history))
+        .route("/api/packages/:sku/financing", get(get_package_financing))
+        .route("/api/packages/:sku/addons", get(list_package_addons))
+        .route("/api/financing-plans", get(list_financing_plans))
+        .route("/api/financing-agreements", get(list_financing_agreements))
         .route("/api/orders", get(list_orders).post(create_order))
+        .route("/api/quotes", post(create_quote))
+        .route("/api/tco", post(calculate_tco))
+        .route("/api/recommend", post(recommend_packages))
+        .route(
+            "/api/buyback-offers",
+            get(list_buyback_offers).post(quote_buyback),
+        )
+        .route("/api/buyback-offers/:id/accept", post(accept_buyback_offer))
+        .route("/api/invoices", get(list_invoices))
+        .route("/api/balance", get(get_balance))
+        .route("/api/credit-notes", get(list_credit_notes))
+        .route(
+            "/api/refund-payouts",
+            get(list_refund_payouts).post(request_refund_payout),
+        )
+        .route(
+            "/api/billing-profile",
+            get(get_billing_profile).put(update_billing_profile),
+        )
+        .route(
+            "/api/admin/invoices/:id/payments",
+            post(record_invoice_payment),
+        )
+        .route("/api/admin/orgs/:id/balance", get(get_org_balance))
+        .route("/api/admin/orgs/:id/credits", post(grant_credit))
+        .route("/api/admin/orgs/:id/refunds", post(record_refund))
+        .route(
+            "/api/admin/orgs/:id/billing-profile",
+            put(update_org_billing_profile),
+        )
+        .route("/api/admin/ledger/check", get(check_ledger))
+        .route("/api/admin/pricing/recompute", post(recompute_prices))
+        .route("/api/admin/pricing/check", get(check_prices))
+        .route(
+            "/api/admin/packages",
+            get(list_catalog_packages).post(create_package),
+        )
+        .route(
+            "/api/admin/packages/:sku",
+            get(get_catalog_package).patch(update_package),
+        )
+        .route("/api/admin/packages/:sku/activate", post(activate_package))
+        .route(
+            "/api/admin/packages/:sku/deactivate",
+            post(deactivate_package),
+        )
+        .route(
+            "/api/admin/packages/:sku/images",
+            post(add_package_image).put(reorder_package_images),
+        )
+        .route(
+            "/api/admin/packages/:sku/images/upload",
+            post(upload_package_image)
+                // Room for the form fields around the largest photo
+                .layer(DefaultBodyLimit::max(MAX_IMAGE_UPLOAD_BYTES + 64 * 1024)),
+        )
+        .route(
+            "/api/admin/packages/:sku/images/:id",
+            patch(update_package_image).delete(delete_package_image),
+        )
+        .route(
+            "/api/admin/packages/:sku/translations",
+            get(list_package_translations),
+        )
+        .route(
+            "/api/admin/packages/:sku/translations/:locale",
+            put(save_package_translation).delete(delete_package_translation),
+        )
+        .route(
+            "/api/admin/packages/:sku/build-parts",
+            get(get_build_parts).put(set_build_parts),
+        )
+        .route("/api/admin/packages/:sku/provenances", post(add_provenance))
+        .route(
+            "/api/admin/packages/:sku/provenances/:id",
+            patch(update_provenance),
+        )
+        .route(
+            "/api/admin/packages/:sku/addons",
+            get(list_all_package_addons).post(create_package_addon),
+        )
+        .route(
+            "/api/admin/packages/:sku/addons/:code",
+            patch(update_package_addon),
+        )
+        .route(
+            "/api/admin/packages/:sku/depreciation/preview",
+            post(preview_depreciation_what_if),
+        )
+        .route(
+            "/api/admin/packages/:sku/prices",
+            post(change_package_price),
+        )
+        .route(
+            "/api/admin/packages/:sku/depreciation-rule",
+            put(change_depreciation_rule),
+        )
+        .route(
+            "/api/admin/depreciation-rules",
+            get(list_depreciation_rules),
+        )
+        .route(
+            "/api/admin/depreciation-rules/scheduled",
+            get(list_scheduled_depreciation_rules),
+        )
+        .route(
+            "/api/admin/depreciation-rules/scheduled/:id/cancel",
+            post(cancel_scheduled_depreciation_rule),
+        )
+        .route("/api/admin/build-queue", get(get_build_queue))
+        .route("/api/admin/orders/:id/start", post(start_order_build))
+        .route("/api/admin/orders/:id/complete", post(finish_order_build))
+        .route("/api/admin/credit-notes", post(issue_credit_note))
+        .route("/api/admin/refund-payouts", get(list_payouts_by_status))
+        .route(
+            "/api/admin/refund-payouts/:id/approve",
+            post(approve_refund_payout),
+        )
+        .route(
+            "/api/admin/refund-payouts/:id/reject",
+            post(reject_refund_payout),
+        )
+        .route(
+            "/api/admin/refund-payouts/:id/paid",
+            post(settle_refund_payout),
+        )
+        .route(
+            "/api/admin/promo-codes",
+            get(list_promo_codes).post(create_promo_code),
+        )
+        .route(
+            "/api/admin/promo-codes/:code/deactivate",
+            post(deactivate_promo_code),
+        )
+        .route(
+            "/api/admin/volume-discounts",
+            get(list_volume_discounts).put(update_volume_discounts),
+        )
+        .route(
+            "/api/admin/financing-plans",
+            get(list_all_financing_plans).post(create_financing_plan),
+        )
+        .route(
+            "/api/admin/financing-plans/:id/deactivate",
+            post(deactivate_financing_plan),
+        )
+        .route(
+            "/api/admin/vat-rates",
+            get(list_vat_rates).post(add_vat_rate),
+        );
+
+    // Uploads kept on local disk are served by the API itself
+    if let ImageStorage::Local { dir, .. } = ImageStorage::from_env()? {
+        app = app.nest_service(LOCAL_IMAGE_ROUTE, ServeDir::new(dir));
+    }
+
+    let app = app
         .with_state(state)
         .layer(cors)
         .layer(TraceLayer::new_for_http())
@@ -86,6 +315,8 @@ async fn create_order(
     State(state): State<AppState>,
     Json(req): Json<CreateOrderRequest>,
 ) -> Result<Json<CreateOrderResponse>, (StatusCode, String)> {
+    req.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
     state
         .infra
         .create_order(req)
@@ -105,28 +336,1228 @@ async fn list_orders(
         .map_err(internal_err)
 }
 
-async fn list_packages(
+async fn create_quote(
+    State(state): State<AppState>,
+    Json(req): Json<QuoteRequest>,
+) -> Result<Json<Quote>, (StatusCode, String)> {
+    state.infra.quote(req).await.map(Json).map_err(bad_request)
+}
+
+async fn calculate_tco(
+    State(state): State<AppState>,
+    Json(req): Json<TcoRequest>,
+) -> Result<Json<TcoReport>, (StatusCode, String)> {
+    state.infra.tco(req).await.map(Json).map_err(bad_request)
+}
+
+async fn recommend_packages(
     State(state): State<AppState>,
-) -> Result<Json<Vec<ai::Package>>, (StatusCode, String)> {
+    Json(req): Json<ModelFitRequest>,
+) -> Result<Json<Recommendation>, (StatusCode, String)> {
+    req.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
     state
         .infra
-        .get_packages()
+        .recommend_packages(&req)
         .await
         .map(Json)
         .map_err(internal_err)
 }
 
-async fn get_package_by_sku(
+async fn list_buyback_offers(
     State(state): State<AppState>,
-    Path(sku): Path<String>,
-) -> Result<Json<Package>, (StatusCode, String)> {
-    match state.infra.get_package_by_sku(&sku).await {
-        Ok(Some(package)) => Ok(Json(package)),
-        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+) -> Result<Json<Vec<BuybackOffer>>, (StatusCode, String)> {
+    state
+        .infra
+        .get_buyback_offers()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn quote_buyback(
+    State(state): State<AppState>,
+    Json(req): Json<BuybackQuoteRequest>,
+) -> Result<Json<BuybackOffer>, (StatusCode, String)> {
+    match state.infra.quote_buyback(req).await {
+        Ok(Some(offer)) => Ok(Json(offer)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Server not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn accept_buyback_offer(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<BuybackOffer>, (StatusCode, String)> {
+    match state.infra.accept_buyback_offer(id).await {
+        Ok(Some(offer)) => Ok(Json(offer)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            "Open buyback offer not found".to_string(),
+        )),
         Err(e) => Err(internal_err(e)),
     }
 }
 
-fn internal_err(e: anyhow::Error) -> (StatusCode, String) {
-    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+async fn list_invoices(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Invoice>>, (StatusCode, String)> {
+    state
+        .infra
+        .get_invoices()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn record_invoice_payment(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(invoice_id): Path<Uuid>,
+) -> Result<Json<Invoice>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.record_invoice_payment(invoice_id).await {
+        Ok(Some(invoice)) => Ok(Json(invoice)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Open invoice not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn get_balance(
+    State(state): State<AppState>,
+) -> Result<Json<OrgBalance>, (StatusCode, String)> {
+    state
+        .infra
+        .get_balance()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn get_org_balance(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(org_id): Path<Uuid>,
+) -> Result<Json<OrgBalance>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .get_org_balance(org_id)
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn grant_credit(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(org_id): Path<Uuid>,
+    Json(req): Json<LedgerAdjustmentRequest>,
+) -> Result<Json<OrgBalance>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .grant_credit(org_id, req.amount_usdc, &req.reason)
+        .await
+        .map(Json)
+        .map_err(bad_request)
+}
+
+async fn record_refund(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(org_id): Path<Uuid>,
+    Json(req): Json<LedgerAdjustmentRequest>,
+) -> Result<Json<OrgBalance>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .record_refund(org_id, req.amount_usdc, &req.reason)
+        .await
+        .map(Json)
+        .map_err(bad_request)
+}
+
+async fn list_credit_notes(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<CreditNote>>, (StatusCode, String)> {
+    state
+        .infra
+        .get_credit_notes()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn list_refund_payouts(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<RefundPayout>>, (StatusCode, String)> {
+    state
+        .infra
+        .get_refund_payouts()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn request_refund_payout(
+    State(state): State<AppState>,
+    Json(req): Json<RefundPayoutRequest>,
+) -> Result<Json<RefundPayout>, (StatusCode, String)> {
+    state
+        .infra
+        .request_refund_payout(req)
+        .await
+        .map(Json)
+        .map_err(bad_request)
+}
+
+async fn issue_credit_note(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<CreateCreditNoteRequest>,
+) -> Result<Json<CreditNote>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .issue_credit_note(req)
+        .await
+        .map(Json)
+        .map_err(bad_request)
+}
+
+#[derive(serde::Deserialize)]
+struct PayoutFilter {
+    status: Option<PayoutStatus>,
+}
+
+async fn list_payouts_by_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(filter): Query<PayoutFilter>,
+) -> Result<Json<Vec<RefundPayout>>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .get_refund_payouts_by_status(filter.status.unwrap_or(PayoutStatus::Requested))
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn approve_refund_payout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(payout_id): Path<Uuid>,
+    Json(review): Json<PayoutReview>,
+) -> Result<Json<RefundPayout>, (StatusCode, String)> {
+    let reviewer = require_admin(&state, &headers)?;
+
+    match state
+        .infra
+        .review_refund_payout(payout_id, true, &reviewer, review)
+        .await
+    {
+        Ok(Some(payout)) => Ok(Json(payout)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            "Payout awaiting review not found".to_string(),
+        )),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn reject_refund_payout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(payout_id): Path<Uuid>,
+    Json(review): Json<PayoutReview>,
+) -> Result<Json<RefundPayout>, (StatusCode, String)> {
+    let reviewer = require_admin(&state, &headers)?;
+
+    match state
+        .infra
+        .review_refund_payout(payout_id, false, &reviewer, review)
+        .await
+    {
+        Ok(Some(payout)) => Ok(Json(payout)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            "Payout awaiting review not found".to_string(),
+        )),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn settle_refund_payout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(payout_id): Path<Uuid>,
+    Json(settlement): Json<PayoutSettlement>,
+) -> Result<Json<RefundPayout>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state
+        .infra
+        .settle_refund_payout(payout_id, settlement)
+        .await
+    {
+        Ok(Some(payout)) => Ok(Json(payout)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            "Approved payout not found".to_string(),
+        )),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn check_ledger(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<LedgerCheck>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .check_ledger()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+#[derive(serde::Deserialize)]
+struct RecomputeFilter {
+    package_id: Option<Uuid>,
+}
+
+#[derive(serde::Serialize)]
+struct RecomputeResult {
+    updated: usize,
+}
+
+async fn recompute_prices(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(filter): Query<RecomputeFilter>,
+) -> Result<Json<RecomputeResult>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .recompute_prices(filter.package_id)
+        .await
+        .map(|updated| Json(RecomputeResult { updated }))
+        .map_err(internal_err)
+}
+
+async fn check_prices(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<PriceCheck>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .check_price_consistency()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn get_billing_profile(
+    State(state): State<AppState>,
+) -> Result<Json<BillingProfile>, (StatusCode, String)> {
+    match state.infra.get_billing_profile().await {
+        Ok(Some(profile)) => Ok(Json(profile)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            "Billing profile not set up".to_string(),
+        )),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn update_billing_profile(
+    State(state): State<AppState>,
+    Json(req): Json<BillingProfileRequest>,
+) -> Result<Json<BillingProfile>, (StatusCode, String)> {
+    state
+        .infra
+        .update_billing_profile(req)
+        .await
+        .map(Json)
+        .map_err(bad_request)
+}
+
+async fn update_org_billing_profile(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(org_id): Path<Uuid>,
+    Json(req): Json<BillingProfileRequest>,
+) -> Result<Json<BillingProfile>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .update_org_billing_profile(org_id, req)
+        .await
+        .map(Json)
+        .map_err(bad_request)
+}
+
+async fn list_vat_rates(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<VatRate>>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .get_vat_rates()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn add_vat_rate(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(rate): Json<VatRate>,
+) -> Result<Json<VatRate>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .add_vat_rate(rate)
+        .await
+        .map(Json)
+        .map_err(bad_request)
+}
+
+async fn list_promo_codes(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<PromoCode>>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .get_promo_codes()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn create_promo_code(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<CreatePromoCodeRequest>,
+) -> Result<Json<PromoCode>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .create_promo_code(req)
+        .await
+        .map(Json)
+        .map_err(bad_request)
+}
+
+async fn deactivate_promo_code(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(code): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.deactivate_promo_code(&code).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "Promo code not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn list_volume_discounts(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<VolumeDiscountTier>>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .get_volume_discount_tiers()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn update_volume_discounts(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(tiers): Json<Vec<VolumeDiscountTier>>,
+) -> Result<Json<Vec<VolumeDiscountTier>>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .set_volume_discount_tiers(tiers)
+        .await
+        .map(Json)
+        .map_err(bad_request)
+}
+
+async fn list_catalog_packages(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<CatalogPackage>>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .get_catalog()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn get_catalog_package(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+) -> Result<Json<CatalogPackage>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.get_catalog_package(&sku).await {
+        Ok(Some(package)) => Ok(Json(package)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn create_package(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<CreatePackageRequest>,
+) -> Result<Json<CatalogPackage>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .create_package(req)
+        .await
+        .map(Json)
+        .map_err(bad_request)
+}
+
+async fn update_package(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+    Json(req): Json<UpdatePackageRequest>,
+) -> Result<Json<CatalogPackage>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.update_package(&sku, req).await {
+        Ok(Some(package)) => Ok(Json(package)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn activate_package(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+) -> Result<Json<CatalogPackage>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.set_package_active(&sku, true).await {
+        Ok(Some(package)) => Ok(Json(package)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn deactivate_package(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+) -> Result<Json<CatalogPackage>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.set_package_active(&sku, false).await {
+        Ok(Some(package)) => Ok(Json(package)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn add_package_image(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+    Json(req): Json<PackageImageRequest>,
+) -> Result<Json<CatalogPackage>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.add_package_image(&sku, req).await {
+        Ok(Some(package)) => Ok(Json(package)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+/// Multipart form with the photo in a `file` part and `title` and
+/// `description` fields
+async fn upload_package_image(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+    mut multipart: Multipart,
+) -> Result<Json<CatalogPackage>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    let mut upload = ImageUpload {
+        title: String::new(),
+        description: String::new(),
+        bytes: Vec::new(),
+    };
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| (e.status(), e.body_text()))?
+    {
+        match field.name() {
+            Some("file") => {
+                upload.bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| (e.status(), e.body_text()))?
+                    .to_vec()
+            }
+            Some("title") => {
+                upload.title = field
+                    .text()
+                    .await
+                    .map_err(|e| (e.status(), e.body_text()))?
+            }
+            Some("description") => {
+                upload.description = field
+                    .text()
+                    .await
+                    .map_err(|e| (e.status(), e.body_text()))?
+            }
+            _ => {}
+        }
+    }
+
+    match state.infra.upload_package_image(&sku, upload).await {
+        Ok(Some(package)) => Ok(Json(package)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn reorder_package_images(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+    Json(req): Json<ReorderImagesRequest>,
+) -> Result<Json<CatalogPackage>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.reorder_package_images(&sku, req).await {
+        Ok(Some(package)) => Ok(Json(package)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn update_package_image(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((sku, id)): Path<(String, Uuid)>,
+    Json(req): Json<UpdatePackageImageRequest>,
+) -> Result<Json<CatalogPackage>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.update_package_image(&sku, id, req).await {
+        Ok(Some(package)) => Ok(Json(package)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Image not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn delete_package_image(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((sku, id)): Path<(String, Uuid)>,
+) -> Result<Json<CatalogPackage>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.delete_package_image(&sku, id).await {
+        Ok(Some(package)) => Ok(Json(package)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Image not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn list_package_translations(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+) -> Result<Json<Vec<PackageTranslation>>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.get_package_translations(&sku).await {
+        Ok(Some(translations)) => Ok(Json(translations)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn save_package_translation(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((sku, locale)): Path<(String, String)>,
+    Json(req): Json<TranslationRequest>,
+) -> Result<Json<PackageTranslation>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+    let locale: Locale = locale.parse().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    match state
+        .infra
+        .save_package_translation(&sku, locale, req)
+        .await
+    {
+        Ok(Some(translation)) => Ok(Json(translation)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn delete_package_translation(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((sku, locale)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+    let locale: Locale = locale.parse().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    match state.infra.delete_package_translation(&sku, locale).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "Translation not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn get_build_parts(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+) -> Result<Json<PartsStock>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.get_build_parts(&sku).await {
+        Ok(Some(parts)) => Ok(Json(parts)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn set_build_parts(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+    Json(stock): Json<PartsStock>,
+) -> Result<Json<PartsStock>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.set_build_parts(&sku, stock).await {
+        Ok(Some(parts)) => Ok(Json(parts)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn get_build_queue(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<BuildSchedule>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .get_build_schedule()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn start_order_build(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<BuildSchedule>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.start_order_build(order_id).await {
+        Ok(Some(schedule)) => Ok(Json(schedule)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Queued order not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn finish_order_build(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<BuildSchedule>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.finish_order_build(order_id).await {
+        Ok(Some(schedule)) => Ok(Json(schedule)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            "Order being provisioned not found".to_string(),
+        )),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn add_provenance(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+    Json(req): Json<ProvenanceRequest>,
+) -> Result<Json<CatalogPackage>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.add_provenance(&sku, req).await {
+        Ok(Some(package)) => Ok(Json(package)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn update_provenance(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((sku, id)): Path<(String, i32)>,
+    Json(req): Json<UpdateProvenanceRequest>,
+) -> Result<Json<CatalogPackage>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.update_provenance(&sku, id, req).await {
+        Ok(Some(package)) => Ok(Json(package)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Provenance not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn list_package_addons(
+    State(state): State<AppState>,
+    Path(sku): Path<String>,
+) -> Result<Json<Vec<PackageAddon>>, (StatusCode, String)> {
+    match state.infra.get_package_addons(&sku, false).await {
+        Ok(Some(addons)) => Ok(Json(addons)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn list_all_package_addons(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+) -> Result<Json<Vec<PackageAddon>>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.get_package_addons(&sku, true).await {
+        Ok(Some(addons)) => Ok(Json(addons)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn create_package_addon(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+    Json(req): Json<CreateAddonRequest>,
+) -> Result<Json<PackageAddon>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.create_package_addon(&sku, req).await {
+        Ok(Some(addon)) => Ok(Json(addon)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn update_package_addon(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((sku, code)): Path<(String, String)>,
+    Json(req): Json<UpdateAddonRequest>,
+) -> Result<Json<PackageAddon>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.update_package_addon(&sku, &code, req).await {
+        Ok(Some(addon)) => Ok(Json(addon)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Add-on not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn list_financing_plans(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<FinancingPlan>>, (StatusCode, String)> {
+    state
+        .infra
+        .get_financing_plans(true)
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn get_package_financing(
+    State(state): State<AppState>,
+    Path(sku): Path<String>,
+) -> Result<Json<PackageFinancing>, (StatusCode, String)> {
+    match state.infra.package_financing(&sku).await {
+        Ok(Some(financing)) => Ok(Json(financing)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn list_financing_agreements(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<FinancingAgreement>>, (StatusCode, String)> {
+    state
+        .infra
+        .get_financing_agreements()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn list_all_financing_plans(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<FinancingPlan>>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .get_financing_plans(false)
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn create_financing_plan(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<CreateFinancingPlanRequest>,
+) -> Result<Json<FinancingPlan>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .create_financing_plan(req)
+        .await
+        .map(Json)
+        .map_err(bad_request)
+}
+
+async fn deactivate_financing_plan(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.deactivate_financing_plan(id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            "Financing plan not found".to_string(),
+        )),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+/// Hardware figures of every GPU model the catalog can use
+async fn list_gpus() -> Json<Vec<&'static GpuSpec>> {
+    Json(ai::gpu::gpu_specs())
+}
+
+#[derive(serde::Deserialize)]
+struct LocaleQuery {
+    locale: Option<String>,
+}
+
+/// Language of a catalog response: `?locale=` if given, otherwise the
+/// client's `Accept-Language`
+fn negotiate_locale(
+    query: &LocaleQuery,
+    headers: &HeaderMap,
+) -> Result<Locale, (StatusCode, String)> {
+    let accept_language = headers.get(ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok());
+    Locale::negotiate(query.locale.as_deref(), accept_language)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// Headers naming the language of a response, which varies with the
+/// client's `Accept-Language`
+fn content_language(locale: Locale) -> [(HeaderName, HeaderValue); 2] {
+    [
+        (CONTENT_LANGUAGE, HeaderValue::from_static(locale.as_str())),
+        (VARY, HeaderValue::from_static("accept-language")),
+    ]
+}
+
+async fn list_packages(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<PackageQuery>,
+    Query(locale): Query<LocaleQuery>,
+) -> Result<([(HeaderName, HeaderValue); 2], Json<Vec<ai::Package>>), (StatusCode, String)> {
+    query.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let locale = negotiate_locale(&locale, &headers)?;
+
+    state
+        .infra
+        .search_packages(&query, locale)
+        .await
+        .map(|packages| (content_language(locale), Json(packages)))
+        .map_err(internal_err)
+}
+
+#[derive(serde::Deserialize)]
+struct CompareQuery {
+    skus: String,
+}
+
+async fn compare_packages(
+    State(state): State<AppState>,
+    Query(query): Query<CompareQuery>,
+) -> Result<Json<PackageComparison>, (StatusCode, String)> {
+    let skus = ai::compare::parse_skus(&query.skus).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    match state.infra.compare_packages(&skus).await {
+        Ok(Some(comparison)) => Ok(Json(comparison)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn get_package_by_sku(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+    Query(locale): Query<LocaleQuery>,
+) -> Result<([(HeaderName, HeaderValue); 2], Json<Package>), (StatusCode, String)> {
+    let locale = negotiate_locale(&locale, &headers)?;
+
+    match state.infra.get_localized_package(&sku, locale).await {
+        Ok(Some(package)) => Ok((content_language(locale), Json(package))),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct DepreciationRange {
+    from: Option<u32>,
+    to: Option<u32>,
+    step: Option<u32>,
+}
+
+async fn preview_depreciation(
+    State(state): State<AppState>,
+    Path(sku): Path<String>,
+    Query(range): Query<DepreciationRange>,
+) -> Result<Json<DepreciationPreview>, (StatusCode, String)> {
+    match state
+        .infra
+        .depreciation_preview(&sku, range.from, range.to, range.step, None)
+        .await
+    {
+        Ok(Some(preview)) => Ok(Json(preview)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn preview_depreciation_what_if(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+    Query(range): Query<DepreciationRange>,
+    Json(rule): Json<DepreciationRuleRequest>,
+) -> Result<Json<DepreciationPreview>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state
+        .infra
+        .depreciation_preview(&sku, range.from, range.to, range.step, Some(rule))
+        .await
+    {
+        Ok(Some(preview)) => Ok(Json(preview)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+async fn list_depreciation_rules(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<DepreciationRule>>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .get_depreciation_rules()
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn change_depreciation_rule(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+    Json(change): Json<DepreciationRuleChange>,
+) -> Result<Json<RepricingImpact>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.change_depreciation_rule(&sku, change).await {
+        Ok(Some(impact)) => Ok(Json(impact)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ScheduledRuleFilter {
+    status: Option<ScheduledRuleStatus>,
+}
+
+async fn list_scheduled_depreciation_rules(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(filter): Query<ScheduledRuleFilter>,
+) -> Result<Json<Vec<ScheduledDepreciationRule>>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    state
+        .infra
+        .get_scheduled_depreciation_rules(filter.status.unwrap_or(ScheduledRuleStatus::Pending))
+        .await
+        .map(Json)
+        .map_err(internal_err)
+}
+
+async fn cancel_scheduled_depreciation_rule(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<Json<ScheduledDepreciationRule>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.cancel_scheduled_depreciation_rule(id).await {
+        Ok(Some(cancelled)) => Ok(Json(cancelled)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            "Pending rule change not found".to_string(),
+        )),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn get_price_history(
+    State(state): State<AppState>,
+    Path(sku): Path<String>,
+) -> Result<Json<PriceHistory>, (StatusCode, String)> {
+    match state.infra.get_price_history(&sku).await {
+        Ok(Some(history)) => Ok(Json(history)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(internal_err(e)),
+    }
+}
+
+async fn change_package_price(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(sku): Path<String>,
+    Json(req): Json<PriceChangeRequest>,
+) -> Result<Json<PriceHistory>, (StatusCode, String)> {
+    require_admin(&state, &headers)?;
+
+    match state.infra.change_package_price(&sku, req).await {
+        Ok(Some(history)) => Ok(Json(history)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Package not found".to_string())),
+        Err(e) => Err(bad_request(e)),
+    }
+}
+
+/// Read the admin tokens from `ADMIN_API_TOKENS` (`name:token` pairs,
+/// comma separated) and `ADMIN_API_TOKEN`, a single token for `admin`
+fn admin_tokens_from_env() -> anyhow::Result<HashMap<String, String>> {
+    let mut tokens = HashMap::new();
+
+    if let Ok(token) = std::env::var("ADMIN_API_TOKEN") {
+        tokens.insert(token, "admin".to_string());
+    }
+
+    if let Ok(pairs) = std::env::var("ADMIN_API_TOKENS") {
+        for pair in pairs.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, token) = pair
+                .split_once(':')
+                .map(|(name, token)| (name.trim(), token.trim()))
+                .filter(|(name, token)| !name.is_empty() && !token.is_empty())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("invalid ADMIN_API_TOKENS entry, expected name:token")
+                })?;
+            if tokens.insert(token.to_string(), name.to_string()).is_some() {
+                anyhow::bail!("ADMIN_API_TOKENS gives the same token to more than one admin");
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Reject the request unless it carries `Authorization: Bearer <token>` with
+/// one of the admin tokens. Returns the name of the admin it belongs to.
+fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<String, (StatusCode, String)> {
+    if state.admin_tokens.is_empty() {
+        return Err((StatusCode::FORBIDDEN, "Admin API is disabled".to_string()));
+    }
+
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .and_then(|token| state.admin_tokens.get(token))
+        .cloned()
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Admin token required".to_string()))
+}
+
+fn internal_err(e: anyhow::Error) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+fn bad_request(e: anyhow::Error) -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, e.to_string())
 }
@@ -0,0 +1,223 @@
+use crate::InfraState;
+use ai::addons::{
+    self, AddonKind, AddonSelection, CreateAddonRequest, OrderAddon, PackageAddon, SelectedAddon,
+    UpdateAddonRequest,
+};
+use anyhow::{anyhow, Result};
+use persistence::{NewOrderAddon, NewPackageAddon};
+use serde_json::json;
+use uuid::Uuid;
+
+impl InfraState {
+    /// Add-ons of a package; `None` if there is no such package. Customers
+    /// see active add-ons of active packages only.
+    pub async fn get_package_addons(
+        &self,
+        sku: &str,
+        include_inactive: bool,
+    ) -> Result<Option<Vec<PackageAddon>>> {
+        let package = if include_inactive {
+            self.db.get_catalog_package(sku).await?
+        } else {
+            self.db.get_package_by_sku(sku).await?
+        };
+        let Some(package) = package else {
+            return Ok(None);
+        };
+
+        let addons = self
+            .db
+            .get_package_addons(package.id)
+            .await?
+            .into_iter()
+            .map(addon_from_db)
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|a| include_inactive || a.is_active)
+            .collect();
+
+        Ok(Some(addons))
+    }
+
+    /// Returns `None` if there is no such package
+    pub async fn create_package_addon(
+        &self,
+        sku: &str,
+        req: CreateAddonRequest,
+    ) -> Result<Option<PackageAddon>> {
+        req.validate().map_err(|e| anyhow!(e))?;
+
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+        let existing = self.db.get_package_addons(package.id).await?;
+        if existing.iter().any(|a| a.code == req.code) {
+            return Err(anyhow!("{sku} already has an add-on {}", req.code));
+        }
+
+        let saved = self
+            .db
+            .create_package_addon(
+                package.id,
+                &NewPackageAddon {
+                    code: req.code.clone(),
+                    kind: req.kind.as_str().to_string(),
+                    name: req.name.trim().to_string(),
+                    storage_gb: i32::try_from(req.storage_gb)?,
+                    ram_gb: i32::from(req.ram_gb),
+                    setup_price_usdc: req.setup_price_usdc,
+                    monthly_price_usdc: req.monthly_price_usdc,
+                    max_quantity: i32::try_from(req.max_quantity)?,
+                },
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.addon_created",
+                json!({
+                    "package_id": package.id,
+                    "sku": sku,
+                    "addon_id": saved.id,
+                    "code": saved.code,
+                    "setup_price_usdc": saved.setup_price_usdc,
+                    "monthly_price_usdc": saved.monthly_price_usdc,
+                }),
+            )
+            .await?;
+
+        Ok(Some(addon_from_db(saved)?))
+    }
+
+    /// Returns `None` if the package or the add-on does not exist
+    pub async fn update_package_addon(
+        &self,
+        sku: &str,
+        code: &str,
+        req: UpdateAddonRequest,
+    ) -> Result<Option<PackageAddon>> {
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+        let Some(current) = self
+            .db
+            .get_package_addons(package.id)
+            .await?
+            .into_iter()
+            .find(|a| a.code == code)
+        else {
+            return Ok(None);
+        };
+        let current = addon_from_db(current)?;
+
+        let updated = req.apply(&current).map_err(|e| anyhow!(e))?;
+
+        let Some(saved) = self
+            .db
+            .update_package_addon(
+                package.id,
+                code,
+                &updated.name,
+                updated.setup_price_usdc,
+                updated.monthly_price_usdc,
+                i32::try_from(updated.max_quantity)?,
+                updated.is_active,
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.addon_updated",
+                json!({
+                    "package_id": package.id,
+                    "sku": sku,
+                    "addon_id": saved.id,
+                    "code": code,
+                    "before": current,
+                    "after": updated,
+                }),
+            )
+            .await?;
+
+        Ok(Some(addon_from_db(saved)?))
+    }
+
+    /// Check selections against the active add-ons of a package
+    pub(crate) async fn selected_addons(
+        &self,
+        package_id: Uuid,
+        selections: &[AddonSelection],
+    ) -> Result<Vec<SelectedAddon>> {
+        if selections.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let available = self
+            .db
+            .get_package_addons(package_id)
+            .await?
+            .into_iter()
+            .map(addon_from_db)
+            .collect::<Result<Vec<_>>>()?;
+
+        addons::select_addons(&available, selections).map_err(|e| anyhow!(e))
+    }
+}
+
+/// Line items for the add-ons of a new order
+pub(crate) fn new_order_addons(selected: &[SelectedAddon]) -> Result<Vec<NewOrderAddon>> {
+    selected
+        .iter()
+        .map(|s| {
+            Ok(NewOrderAddon {
+                addon_id: s.addon.id,
+                code: s.addon.code.clone(),
+                kind: s.addon.kind.as_str().to_string(),
+                name: s.addon.name.clone(),
+                quantity: i32::try_from(s.quantity)?,
+                storage_gb: i32::try_from(s.addon.storage_gb)?,
+                ram_gb: i32::from(s.addon.ram_gb),
+                setup_price_usdc: s.addon.setup_price_usdc,
+                monthly_price_usdc: s.addon.monthly_price_usdc,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn order_addon_from_db(a: persistence::OrderAddon) -> Result<OrderAddon> {
+    Ok(OrderAddon {
+        kind: addon_kind(&a.kind)?,
+        code: a.code,
+        name: a.name,
+        quantity: a.quantity as u32,
+        setup_price_usdc: a.setup_price_usdc,
+        monthly_price_usdc: a.monthly_price_usdc,
+    })
+}
+
+fn addon_from_db(a: persistence::PackageAddon) -> Result<PackageAddon> {
+    Ok(PackageAddon {
+        id: a.id,
+        package_id: a.package_id,
+        kind: addon_kind(&a.kind)?,
+        code: a.code,
+        name: a.name,
+        storage_gb: a.storage_gb as u32,
+        ram_gb: a.ram_gb as u16,
+        setup_price_usdc: a.setup_price_usdc,
+        monthly_price_usdc: a.monthly_price_usdc,
+        max_quantity: a.max_quantity as u32,
+        is_active: a.is_active,
+    })
+}
+
+fn addon_kind(kind: &str) -> Result<AddonKind> {
+    AddonKind::parse(kind).ok_or_else(|| anyhow!("unknown add-on kind {kind}"))
+}
@@ -0,0 +1,223 @@
+use crate::InfraState;
+use ai::billing::{self, BillableServer, BillingPeriod, DraftInvoice, Invoice, InvoiceLine};
+use ai::ledger::JournalEntry;
+use ai::vat::{VatAssessment, VatTreatment};
+use anyhow::{bail, Result};
+use chrono::{Days, Utc};
+use persistence::{InvoiceVat, NewInvoiceLine};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+impl InfraState {
+    /// Generate the monthly invoices for `period`. Hosting is billed in
+    /// arrears, so only a period that has ended can be invoiced; a dry run
+    /// may preview an open one. Servers already billed for the period are
+    /// skipped, so re-running is safe. With `dry_run` the drafts are returned
+    /// without being persisted.
+    pub async fn run_billing(
+        &self,
+        period: BillingPeriod,
+        dry_run: bool,
+    ) -> Result<Vec<DraftInvoice>> {
+        if !dry_run && period.end > Utc::now().date_naive() {
+            bail!("{period} has not ended yet; hosting is billed in arrears");
+        }
+
+        let servers: Vec<BillableServer> = self
+            .db
+            .get_unbilled_servers(period.start, period.end)
+            .await?
+            .into_iter()
+            .map(|s| BillableServer {
+                server_id: s.server_id,
+                org_id: s.org_id,
+                hostname: s.hostname,
+                package_name: s.package_name,
+                monthly_price_usdc: s.monthly_price_usdc,
+                activated_at: s.activated_at,
+                decommissioned_at: s.decommissioned_at,
+            })
+            .collect();
+
+        let financed = self.financed_servers(&period).await?;
+
+        let mut drafts = billing::draft_invoices(&period, &servers, &financed);
+
+        // VAT follows the rates and customer status in force when the
+        // invoice is issued
+        let issued_on = Utc::now().date_naive();
+        for draft in &mut drafts {
+            let assessment = self.assess_vat(draft.org_id, issued_on).await?;
+            draft.apply_vat(assessment);
+        }
+
+        if dry_run {
+            return Ok(drafts);
+        }
+
+        // Payment is due a fixed number of days after the invoice is issued
+        let due_at = (issued_on + Days::new(self.payment_terms_days as u64))
+            .and_hms_opt(0, 0, 0)
+            .expect("midnight is a valid time")
+            .and_utc();
+
+        for draft in &drafts {
+            let lines: Vec<NewInvoiceLine> = draft
+                .lines
+                .iter()
+                .map(|l| NewInvoiceLine {
+                    server_id: l.server_id,
+                    kind: l.kind.clone(),
+                    description: l.description.clone(),
+                    service_start: l.service_start,
+                    service_end: l.service_end,
+                    billed_days: l.billed_days as i16,
+                    period_days: l.period_days as i16,
+                    amount_usdc: l.amount_usdc,
+                })
+                .collect();
+
+            let vat = draft.vat.as_ref().map(|v| InvoiceVat {
+                treatment: v.treatment.as_str().to_string(),
+                country_code: v.country_code.clone(),
+                rate_percent: v.rate_percent,
+                amount_usdc: draft.vat_usdc,
+                customer_vat_id: v.customer_vat_id.clone(),
+            });
+
+            let invoice_id = Uuid::new_v4();
+            let journal = draft.total_usdc.is_positive().then(|| {
+                JournalEntry::invoice_issued(
+                    draft.org_id,
+                    invoice_id,
+                    draft.subtotal_usdc,
+                    draft.vat_usdc,
+                )
+            });
+
+            let invoice = self
+                .db
+                .create_invoice(
+                    invoice_id,
+                    draft.org_id,
+                    period.start,
+                    period.end,
+                    due_at,
+                    &lines,
+                    vat.as_ref(),
+                    journal.as_ref(),
+                )
+                .await?;
+
+            info!(
+                "Issued invoice {} for org {} ({}): {} USDC",
+                invoice.id, invoice.org_id, period, invoice.total_usdc
+            );
+
+            self.apply_account_credit(&invoice).await?;
+        }
+
+        // Instalments settled from account credit can complete a financing
+        self.complete_paid_financing().await?;
+
+        Ok(drafts)
+    }
+
+    pub async fn get_invoices(&self) -> Result<Vec<Invoice>> {
+        let db_invoices = self.db.get_invoices_for_org(self.demo_org_id).await?;
+
+        let mut invoices = Vec::new();
+        for inv in db_invoices {
+            invoices.push(self.invoice_with_lines(inv).await?);
+        }
+
+        Ok(invoices)
+    }
+
+    /// Convert a persisted invoice, loading its line items
+    pub(crate) async fn invoice_with_lines(&self, inv: persistence::Invoice) -> Result<Invoice> {
+        let lines = self
+            .db
+            .get_invoice_lines(inv.id)
+            .await?
+            .into_iter()
+            .map(|l| InvoiceLine {
+                server_id: l.server_id,
+                kind: l.kind,
+                description: l.description,
+                service_start: l.service_start,
+                service_end: l.service_end,
+                billed_days: l.billed_days as u32,
+                period_days: l.period_days as u32,
+                amount_usdc: l.amount_usdc,
+            })
+            .collect();
+
+        let vat = inv
+            .vat_treatment
+            .as_deref()
+            .and_then(VatTreatment::parse)
+            .map(|treatment| VatAssessment {
+                treatment,
+                country_code: inv.vat_country.clone().unwrap_or_default(),
+                rate_percent: inv.vat_rate_percent,
+                customer_vat_id: inv.customer_vat_id.clone(),
+            });
+
+        Ok(Invoice {
+            id: inv.id,
+            org_id: inv.org_id,
+            period: BillingPeriod {
+                start: inv.period_start,
+                end: inv.period_end,
+            },
+            status: inv.status,
+            subtotal_usdc: inv.subtotal_usdc,
+            vat_usdc: inv.vat_usdc,
+            vat,
+            total_usdc: inv.total_usdc,
+            credit_applied_usdc: inv.credit_applied_usdc,
+            issued_at: inv.issued_at,
+            due_at: inv.due_at,
+            paid_at: inv.paid_at,
+            reminders_sent: inv.reminders_sent as u32,
+            suspended_at: inv.suspended_at,
+            lines,
+        })
+    }
+}
+
+/// Periodically bill the last closed month and chase overdue invoices. The
+/// first tick of a month invoices the one before it, prorated by activation
+/// and decommission dates; later ticks find nothing left to bill.
+pub fn spawn_billing_scheduler(infra: Arc<InfraState>, every: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            interval.tick().await;
+
+            let period = BillingPeriod::containing(Utc::now().date_naive()).previous();
+            match infra.run_billing(period, false).await {
+                Ok(drafts) if !drafts.is_empty() => {
+                    info!(
+                        "Billing run for {} issued {} invoices",
+                        period,
+                        drafts.len()
+                    )
+                }
+                Ok(_) => {}
+                Err(e) => error!("Billing run for {} failed: {e}", period),
+            }
+
+            match infra.run_dunning(Utc::now()).await {
+                Ok(outcomes) if !outcomes.is_empty() => {
+                    info!("Dunning run took {} actions", outcomes.len())
+                }
+                Ok(_) => {}
+                Err(e) => error!("Dunning run failed: {e}"),
+            }
+        }
+    });
+}
@@ -0,0 +1,199 @@
+use crate::InfraState;
+use ai::buyback::{
+    self, BuybackOffer, BuybackPolicy, BuybackQuoteRequest, BuybackStatus, ConditionGrade,
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use persistence::NewBuybackOffer;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+impl InfraState {
+    /// Offer to buy back one of the organization's servers, valued with its
+    /// package's depreciation rule at the hours it has run. Hardware on an
+    /// active financing agreement is refused. Returns `None` if the
+    /// organization has no such server.
+    pub async fn quote_buyback(&self, req: BuybackQuoteRequest) -> Result<Option<BuybackOffer>> {
+        let Some(server) = self.db.get_buyback_server(req.server_id).await? else {
+            return Ok(None);
+        };
+        if server.org_id != self.demo_org_id {
+            return Ok(None);
+        }
+
+        let package_id = server
+            .package_id
+            .ok_or_else(|| anyhow!("server was not bought as a catalog package"))?;
+        let activated_at = server
+            .activated_at
+            .ok_or_else(|| anyhow!("server has not been activated yet"))?;
+        // Until the last instalment is paid the hardware is not the customer's
+        // to sell
+        if server.is_financed {
+            return Err(anyhow!(
+                "server is still being paid off on a financing agreement"
+            ));
+        }
+
+        let already_sold = self
+            .db
+            .get_buyback_offers_for_org(self.demo_org_id)
+            .await?
+            .iter()
+            .any(|o| o.server_id == server.id && o.status == BuybackStatus::Accepted.as_str());
+        if already_sold {
+            return Err(anyhow!("server has already been bought back"));
+        }
+
+        let package = self
+            .db
+            .get_package_by_id(package_id)
+            .await?
+            .ok_or_else(|| anyhow!("package {package_id} is no longer sold"))?;
+        let rule = self.effective_depreciation_rule(package_id).await?;
+
+        let now = Utc::now();
+        let until = server.decommissioned_at.map_or(now, |d| d.min(now));
+        let usage_hours = buyback::recorded_usage_hours(activated_at, until);
+        let valuation = buyback::value_buyback(
+            &rule,
+            package.setup_price_usdc,
+            usage_hours,
+            req.condition,
+            &self.buyback_policy,
+        );
+
+        let offer = self
+            .db
+            .create_buyback_offer(&NewBuybackOffer {
+                org_id: self.demo_org_id,
+                server_id: server.id,
+                package_id,
+                usage_hours: i32::try_from(usage_hours)?,
+                condition: req.condition.as_str().to_string(),
+                resale_price_usdc: valuation.resale_price_usdc,
+                offer_usdc: valuation.offer_usdc,
+                expires_at: now + Duration::days(self.buyback_policy.offer_valid_days.into()),
+            })
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                Some(self.demo_org_id),
+                None,
+                "buyback.offered",
+                json!({
+                    "offer_id": offer.id,
+                    "server_id": offer.server_id,
+                    "usage_hours": offer.usage_hours,
+                    "condition": offer.condition,
+                    "offer_usdc": offer.offer_usdc,
+                }),
+            )
+            .await?;
+
+        Ok(Some(buyback_offer_from_db(offer, now)))
+    }
+
+    pub async fn get_buyback_offers(&self) -> Result<Vec<BuybackOffer>> {
+        let now = Utc::now();
+        Ok(self
+            .db
+            .get_buyback_offers_for_org(self.demo_org_id)
+            .await?
+            .into_iter()
+            .map(|o| buyback_offer_from_db(o, now))
+            .collect())
+    }
+
+    /// Accept an open offer. The server is decommissioned and the box goes
+    /// back into the catalog as a used provenance; the payout is settled
+    /// outside the ledger. Returns `None` if the organization has no open,
+    /// unexpired offer with this id.
+    pub async fn accept_buyback_offer(&self, id: Uuid) -> Result<Option<BuybackOffer>> {
+        match self.db.get_buyback_offer(id).await? {
+            Some(offer) if offer.org_id == self.demo_org_id => {}
+            _ => return Ok(None),
+        }
+
+        let now = Utc::now();
+        let Some(offer) = self.db.accept_buyback_offer(id, now).await? else {
+            return Ok(None);
+        };
+
+        self.db
+            .insert_audit_log(
+                Some(self.demo_org_id),
+                None,
+                "buyback.accepted",
+                json!({
+                    "offer_id": offer.id,
+                    "server_id": offer.server_id,
+                    "provenance_id": offer.provenance_id,
+                    "offer_usdc": offer.offer_usdc,
+                }),
+            )
+            .await?;
+
+        self.refresh_stale_prices().await?;
+
+        info!(
+            "Buyback {} accepted: server {} listed as provenance {:?}",
+            offer.id, offer.server_id, offer.provenance_id
+        );
+
+        Ok(Some(buyback_offer_from_db(offer, now)))
+    }
+}
+
+/// Read the buyback policy from `BUYBACK_MARGIN_PERCENT` and
+/// `BUYBACK_OFFER_VALID_DAYS`, falling back to the defaults
+pub(crate) fn buyback_policy_from_env() -> Result<BuybackPolicy> {
+    let mut policy = BuybackPolicy::default();
+
+    if let Ok(margin) = std::env::var("BUYBACK_MARGIN_PERCENT") {
+        policy.margin_percent = margin
+            .parse::<Decimal>()
+            .map_err(|e| anyhow!("invalid BUYBACK_MARGIN_PERCENT: {e}"))?;
+    }
+
+    if let Ok(days) = std::env::var("BUYBACK_OFFER_VALID_DAYS") {
+        policy.offer_valid_days = days
+            .parse()
+            .map_err(|e| anyhow!("invalid BUYBACK_OFFER_VALID_DAYS: {e}"))?;
+    }
+
+    policy
+        .validate()
+        .map_err(|e| anyhow!("invalid buyback policy: {e}"))?;
+
+    Ok(policy)
+}
+
+fn buyback_offer_from_db(o: persistence::BuybackOffer, now: DateTime<Utc>) -> BuybackOffer {
+    let mut status = BuybackStatus::parse(&o.status).unwrap_or_else(|| {
+        warn!("Unknown buyback status {} on offer {}", o.status, o.id);
+        BuybackStatus::Withdrawn
+    });
+    if status == BuybackStatus::Offered && o.expires_at <= now {
+        status = BuybackStatus::Expired;
+    }
+
+    BuybackOffer {
+        id: o.id,
+        server_id: o.server_id,
+        package_id: o.package_id,
+        sku: o.sku,
+        usage_hours: o.usage_hours.max(0) as u32,
+        condition: ConditionGrade::parse(&o.condition).unwrap_or(ConditionGrade::D),
+        resale_price_usdc: o.resale_price_usdc,
+        offer_usdc: o.offer_usdc,
+        status,
+        expires_at: o.expires_at,
+        created_at: o.created_at,
+        accepted_at: o.accepted_at,
+        provenance_id: o.provenance_id,
+    }
+}
@@ -0,0 +1,519 @@
+use crate::InfraState;
+use ai::catalog::{
+    CatalogImage, CatalogPackage, CatalogProvenance, CreatePackageRequest, PackageDetails,
+    PackageImageRequest, ProvenanceRequest, ReorderImagesRequest, UpdatePackageImageRequest,
+    UpdatePackageRequest, UpdateProvenanceRequest,
+};
+use ai::{Availability, Provenance};
+use anyhow::{anyhow, Result};
+use persistence::PackageFields;
+use serde_json::json;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+impl InfraState {
+    /// Every package, including inactive ones
+    pub async fn get_catalog(&self) -> Result<Vec<CatalogPackage>> {
+        let mut packages = Vec::new();
+        for package in self.db.get_catalog_packages().await? {
+            packages.push(self.catalog_package(package).await?);
+        }
+        Ok(packages)
+    }
+
+    pub async fn get_catalog_package(&self, sku: &str) -> Result<Option<CatalogPackage>> {
+        match self.db.get_catalog_package(sku).await? {
+            Some(package) => Ok(Some(self.catalog_package(package).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Add an inactive package with its first price version
+    pub async fn create_package(&self, req: CreatePackageRequest) -> Result<CatalogPackage> {
+        req.validate().map_err(|e| anyhow!(e))?;
+        self.check_unique_package(Some(&req.sku), &req.details.name, None)
+            .await?;
+
+        let package = self
+            .db
+            .create_package(
+                &req.sku,
+                &package_fields(&req.details),
+                req.setup_price_usdc,
+                req.monthly_price_usdc,
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.package_created",
+                json!({ "package_id": package.id, "sku": req.sku, "package": req }),
+            )
+            .await?;
+
+        self.catalog_package(package).await
+    }
+
+    /// Change a package's details. Returns `None` if there is no package
+    /// with the SKU.
+    pub async fn update_package(
+        &self,
+        sku: &str,
+        req: UpdatePackageRequest,
+    ) -> Result<Option<CatalogPackage>> {
+        let Some(current) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+
+        let before = package_details(&current);
+        let after = req.apply(before.clone());
+        after.validate().map_err(|e| anyhow!(e))?;
+        self.check_unique_package(None, &after.name, Some(current.id))
+            .await?;
+
+        let package = self
+            .db
+            .update_package(current.id, &package_fields(&after))
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.package_updated",
+                json!({ "package_id": package.id, "sku": sku, "before": before, "after": after }),
+            )
+            .await?;
+
+        Ok(Some(self.catalog_package(package).await?))
+    }
+
+    /// List or unlist a package. A package needs an active provenance to be
+    /// listed, since that is what customers buy.
+    pub async fn set_package_active(
+        &self,
+        sku: &str,
+        is_active: bool,
+    ) -> Result<Option<CatalogPackage>> {
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+
+        if is_active {
+            let provenances = self.db.get_catalog_provenances(package.id).await?;
+            if !provenances.iter().any(|p| p.is_active) {
+                return Err(anyhow!(
+                    "add an active provenance to {sku} before activating it"
+                ));
+            }
+        }
+
+        self.db.set_package_active(package.id, is_active).await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                if is_active {
+                    "catalog.package_activated"
+                } else {
+                    "catalog.package_deactivated"
+                },
+                json!({ "package_id": package.id, "sku": sku }),
+            )
+            .await?;
+
+        self.get_catalog_package(sku).await
+    }
+
+    pub async fn add_package_image(
+        &self,
+        sku: &str,
+        req: PackageImageRequest,
+    ) -> Result<Option<CatalogPackage>> {
+        req.validate().map_err(|e| anyhow!(e))?;
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+
+        let image = self
+            .db
+            .add_package_image(
+                package.id,
+                req.filename.trim(),
+                req.title.trim(),
+                req.description.trim(),
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.image_added",
+                json!({ "package_id": package.id, "sku": sku, "image_id": image.id, "filename": image.filename }),
+            )
+            .await?;
+
+        Ok(Some(self.catalog_package(package).await?))
+    }
+
+    /// Returns `None` if the package or the image does not exist
+    pub async fn update_package_image(
+        &self,
+        sku: &str,
+        image_id: Uuid,
+        req: UpdatePackageImageRequest,
+    ) -> Result<Option<CatalogPackage>> {
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+        let images = self.db.get_catalog_images(package.id).await?;
+        let Some(current) = images.into_iter().find(|i| i.id == image_id) else {
+            return Ok(None);
+        };
+
+        let updated = PackageImageRequest {
+            filename: req.filename.unwrap_or(current.filename),
+            title: req.title.unwrap_or(current.title),
+            description: req.description.unwrap_or(current.description),
+        };
+        updated.validate().map_err(|e| anyhow!(e))?;
+
+        self.db
+            .update_package_image(
+                package.id,
+                image_id,
+                updated.filename.trim(),
+                updated.title.trim(),
+                updated.description.trim(),
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.image_updated",
+                json!({ "package_id": package.id, "sku": sku, "image_id": image_id, "image": updated }),
+            )
+            .await?;
+
+        Ok(Some(self.catalog_package(package).await?))
+    }
+
+    /// Returns `None` if the package or the image does not exist
+    pub async fn delete_package_image(
+        &self,
+        sku: &str,
+        image_id: Uuid,
+    ) -> Result<Option<CatalogPackage>> {
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+        let stored = self.db.get_image_storage_keys(package.id, image_id).await?;
+        if !self.db.delete_package_image(package.id, image_id).await? {
+            return Ok(None);
+        }
+        self.delete_stored_images(&stored).await;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.image_removed",
+                json!({ "package_id": package.id, "sku": sku, "image_id": image_id }),
+            )
+            .await?;
+
+        Ok(Some(self.catalog_package(package).await?))
+    }
+
+    /// Put a package's images in the given order, which must list every one
+    /// of them exactly once
+    pub async fn reorder_package_images(
+        &self,
+        sku: &str,
+        req: ReorderImagesRequest,
+    ) -> Result<Option<CatalogPackage>> {
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+
+        let existing: HashSet<Uuid> = self
+            .db
+            .get_catalog_images(package.id)
+            .await?
+            .iter()
+            .map(|i| i.id)
+            .collect();
+        let requested: HashSet<Uuid> = req.image_ids.iter().copied().collect();
+        if requested.len() != req.image_ids.len() || requested != existing {
+            return Err(anyhow!(
+                "image order must list each of the package's {} images exactly once",
+                existing.len()
+            ));
+        }
+
+        self.db
+            .reorder_package_images(package.id, &req.image_ids)
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.images_reordered",
+                json!({ "package_id": package.id, "sku": sku, "image_ids": req.image_ids }),
+            )
+            .await?;
+
+        Ok(Some(self.catalog_package(package).await?))
+    }
+
+    /// Add stock of the package at a number of usage hours
+    pub async fn add_provenance(
+        &self,
+        sku: &str,
+        req: ProvenanceRequest,
+    ) -> Result<Option<CatalogPackage>> {
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+
+        let provenances = self.db.get_catalog_provenances(package.id).await?;
+        if provenances
+            .iter()
+            .any(|p| p.usage_hours as u32 == req.usage_hours)
+        {
+            return Err(anyhow!(
+                "{sku} already has a provenance at {} hours; adjust its quantity instead",
+                req.usage_hours
+            ));
+        }
+
+        let provenance = self
+            .db
+            .create_provenance(
+                package.id,
+                i32::try_from(req.usage_hours)?,
+                i32::try_from(req.quantity_available)?,
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.provenance_added",
+                json!({
+                    "package_id": package.id,
+                    "sku": sku,
+                    "provenance_id": provenance.id,
+                    "usage_hours": provenance.usage_hours,
+                    "quantity_available": provenance.quantity_available,
+                }),
+            )
+            .await?;
+
+        self.refresh_stale_prices().await?;
+
+        Ok(Some(self.catalog_package(package).await?))
+    }
+
+    /// Returns `None` if the package or the provenance does not exist
+    pub async fn update_provenance(
+        &self,
+        sku: &str,
+        provenance_id: i32,
+        req: UpdateProvenanceRequest,
+    ) -> Result<Option<CatalogPackage>> {
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+        let provenances = self.db.get_catalog_provenances(package.id).await?;
+        let Some(current) = provenances.iter().find(|p| p.id == provenance_id) else {
+            return Ok(None);
+        };
+
+        let usage_hours = match req.usage_hours {
+            Some(hours) => i32::try_from(hours)?,
+            None => current.usage_hours,
+        };
+        let quantity_available = match req.quantity_available {
+            Some(quantity) => i32::try_from(quantity)?,
+            None => current.quantity_available,
+        };
+        let is_active = req.is_active.unwrap_or(current.is_active);
+
+        if provenances
+            .iter()
+            .any(|p| p.id != provenance_id && p.usage_hours == usage_hours)
+        {
+            return Err(anyhow!(
+                "{sku} already has a provenance at {usage_hours} hours"
+            ));
+        }
+
+        self.db
+            .update_provenance(
+                package.id,
+                provenance_id,
+                usage_hours,
+                quantity_available,
+                is_active,
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.provenance_updated",
+                json!({
+                    "package_id": package.id,
+                    "sku": sku,
+                    "provenance_id": provenance_id,
+                    "before": {
+                        "usage_hours": current.usage_hours,
+                        "quantity_available": current.quantity_available,
+                        "is_active": current.is_active,
+                    },
+                    "after": {
+                        "usage_hours": usage_hours,
+                        "quantity_available": quantity_available,
+                        "is_active": is_active,
+                    },
+                }),
+            )
+            .await?;
+
+        self.refresh_stale_prices().await?;
+
+        Ok(Some(self.catalog_package(package).await?))
+    }
+
+    /// SKUs and names identify packages to customers, so neither may be
+    /// reused. `sku` is checked when given; `except` is the package being
+    /// edited.
+    async fn check_unique_package(
+        &self,
+        sku: Option<&str>,
+        name: &str,
+        except: Option<Uuid>,
+    ) -> Result<()> {
+        for other in self.db.get_catalog_packages().await? {
+            if Some(other.id) == except {
+                continue;
+            }
+            if sku.is_some() && other.sku.as_deref() == sku {
+                return Err(anyhow!("SKU {} is already used", sku.unwrap_or_default()));
+            }
+            if other.name.trim().eq_ignore_ascii_case(name.trim()) {
+                return Err(anyhow!("package name {} is already used", name.trim()));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn catalog_package(&self, p: persistence::Package) -> Result<CatalogPackage> {
+        let mut variants = self.image_variants(p.id).await?;
+        let images = self
+            .db
+            .get_catalog_images(p.id)
+            .await?
+            .into_iter()
+            .map(|i| CatalogImage {
+                variants: variants.remove(&i.id).unwrap_or_default(),
+                id: i.id,
+                filename: i.filename,
+                title: i.title,
+                description: i.description,
+                sort_order: i.sort_order,
+            })
+            .collect();
+
+        let provenances = self
+            .db
+            .get_catalog_provenances(p.id)
+            .await?
+            .into_iter()
+            .map(|prov| CatalogProvenance {
+                id: prov.id,
+                provenance_type: if prov.usage_hours == 0 {
+                    Provenance::New
+                } else {
+                    Provenance::Used {
+                        hours: prov.usage_hours as u32,
+                    }
+                },
+                quantity_available: prov.quantity_available as u32,
+                is_active: prov.is_active,
+                calculated_price_usdc: prov.calculated_price_usdc,
+            })
+            .collect();
+
+        Ok(CatalogPackage {
+            id: p.id,
+            sku: p.sku.clone().unwrap_or_default(),
+            details: package_details(&p),
+            setup_price_usdc: p.setup_price_usdc,
+            monthly_price_usdc: p.monthly_price_usdc,
+            is_active: p.is_active,
+            created_at: p.created_at,
+            images,
+            provenances,
+        })
+    }
+}
+
+/// Parse the `availability_type`/`availability_value` columns of a package
+pub(crate) fn availability_from_db(availability_type: &str, value: Option<i32>) -> Availability {
+    match availability_type {
+        "preorder" => Availability::Preorder,
+        "in_stock" => Availability::InStock,
+        "build" => Availability::Build {
+            hours: value.unwrap_or(48) as u16,
+        },
+        _ => Availability::InStock,
+    }
+}
+
+fn package_details(p: &persistence::Package) -> PackageDetails {
+    PackageDetails {
+        name: p.name.clone(),
+        description: p.description.clone(),
+        hardware_description: p.hardware_description.clone(),
+        cpu_cores: p.cpu_cores as u16,
+        ram_gb: p.ram_gb as u16,
+        storage_gb: p.storage_gb as u32,
+        gpu_class: p.gpu_class.clone(),
+        gpu_count: p.gpu_count as u16,
+        vram_gb: p.vram_gb as u16,
+        availability: availability_from_db(&p.availability_type, p.availability_value),
+    }
+}
+
+pub(crate) fn package_fields(details: &PackageDetails) -> PackageFields {
+    let (availability_type, availability_value) = match details.availability {
+        Availability::Preorder => ("preorder", None),
+        Availability::InStock => ("in_stock", None),
+        Availability::Build { hours } => ("build", Some(i32::from(hours))),
+    };
+
+    PackageFields {
+        name: details.name.trim().to_string(),
+        description: details.description.trim().to_string(),
+        hardware_description: details.hardware_description.trim().to_string(),
+        cpu_cores: details.cpu_cores as i16,
+        ram_gb: details.ram_gb as i16,
+        storage_gb: details.storage_gb as i32,
+        gpu_class: details.gpu_class.clone(),
+        gpu_count: details.gpu_count as i16,
+        vram_gb: details.vram_gb as i16,
+        availability_type: availability_type.to_string(),
+        availability_value,
+    }
+}
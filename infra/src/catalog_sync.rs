@@ -0,0 +1,196 @@
+use crate::catalog::package_fields;
+use crate::depreciation::new_rule_for_db;
+use crate::InfraState;
+use ai::catalog_sync::{self, CatalogChange, CatalogFile, SyncPlan};
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use persistence::CatalogWrite;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::info;
+use uuid::Uuid;
+
+/// Read a TOML catalog file
+pub fn read_catalog_file(path: &Path) -> Result<CatalogFile> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("reading catalog file {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("parsing catalog file {}", path.display()))
+}
+
+impl InfraState {
+    /// The changes that would make the database match `file`
+    pub async fn plan_catalog_sync(&self, file: &CatalogFile) -> Result<SyncPlan> {
+        let current = self.get_catalog().await?;
+        let rules = self.depreciation_rules_by_package().await?;
+
+        catalog_sync::plan_sync(file, &current, &rules).map_err(|e| anyhow!(e))
+    }
+
+    /// Make the database match `file` in one transaction and return the
+    /// changes made. Cached provenance prices are refreshed afterwards.
+    pub async fn apply_catalog_sync(&self, file: &CatalogFile) -> Result<SyncPlan> {
+        let plan = self.plan_catalog_sync(file).await?;
+        if plan.is_empty() {
+            return Ok(plan);
+        }
+
+        let writes = self.catalog_writes(&plan).await?;
+        // Files of removed uploads are deleted once the removal is committed
+        let mut stored = Vec::new();
+        for write in &writes {
+            if let CatalogWrite::RemoveImage { id, package_id } = write {
+                stored.extend(self.db.get_image_storage_keys(*package_id, *id).await?);
+            }
+        }
+        self.db.apply_catalog_writes(&writes).await?;
+        self.delete_stored_images(&stored).await;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.synced",
+                json!({ "changes": plan.changes }),
+            )
+            .await?;
+
+        info!("Catalog synced: {} changes", plan.changes.len());
+
+        self.refresh_stale_prices().await?;
+
+        Ok(plan)
+    }
+
+    /// Translate a plan into database writes. Packages and images the plan
+    /// creates are given their ids here so later changes can refer to them.
+    async fn catalog_writes(&self, plan: &SyncPlan) -> Result<Vec<CatalogWrite>> {
+        let mut package_ids = HashMap::new();
+        let mut image_ids = HashMap::new();
+        for package in self.get_catalog().await? {
+            for image in &package.images {
+                image_ids.insert((package.id, image.filename.clone()), image.id);
+            }
+            package_ids.insert(package.sku, package.id);
+        }
+
+        let package_id = |sku: &str, ids: &HashMap<String, Uuid>| {
+            ids.get(sku)
+                .copied()
+                .ok_or_else(|| anyhow!("package {sku} not found"))
+        };
+        let image_id = |package_id: Uuid, filename: &str, ids: &HashMap<(Uuid, String), Uuid>| {
+            ids.get(&(package_id, filename.to_string()))
+                .copied()
+                .ok_or_else(|| anyhow!("image {filename} not found"))
+        };
+
+        let mut writes = Vec::new();
+        for change in &plan.changes {
+            let write = match change {
+                CatalogChange::CreatePackage {
+                    sku,
+                    details,
+                    setup_price_usdc,
+                    monthly_price_usdc,
+                } => {
+                    let id = Uuid::new_v4();
+                    package_ids.insert(sku.clone(), id);
+                    CatalogWrite::CreatePackage {
+                        id,
+                        sku: sku.clone(),
+                        fields: package_fields(details),
+                        setup_price_usdc: *setup_price_usdc,
+                        monthly_price_usdc: *monthly_price_usdc,
+                    }
+                }
+                CatalogChange::UpdatePackage { sku, details, .. } => CatalogWrite::UpdatePackage {
+                    id: package_id(sku, &package_ids)?,
+                    fields: package_fields(details),
+                },
+                CatalogChange::ChangePrices {
+                    sku,
+                    setup_price_usdc,
+                    monthly_price_usdc,
+                } => CatalogWrite::ChangePrices {
+                    package_id: package_id(sku, &package_ids)?,
+                    setup_price_usdc: *setup_price_usdc,
+                    monthly_price_usdc: *monthly_price_usdc,
+                    effective_from: Utc::now(),
+                    note: "Catalog sync".to_string(),
+                },
+                CatalogChange::SetDepreciationRule { sku, rule } => {
+                    let rule = rule.clone().into_rule(package_id(sku, &package_ids)?);
+                    CatalogWrite::SaveDepreciationRule(new_rule_for_db(&rule))
+                }
+                CatalogChange::AddImage { sku, image } => {
+                    let package_id = package_id(sku, &package_ids)?;
+                    let id = Uuid::new_v4();
+                    image_ids.insert((package_id, image.filename.trim().to_string()), id);
+                    CatalogWrite::AddImage {
+                        id,
+                        package_id,
+                        filename: image.filename.trim().to_string(),
+                        title: image.title.trim().to_string(),
+                        description: image.description.trim().to_string(),
+                    }
+                }
+                CatalogChange::UpdateImage { sku, image } => {
+                    let package_id = package_id(sku, &package_ids)?;
+                    CatalogWrite::UpdateImage {
+                        id: image_id(package_id, image.filename.trim(), &image_ids)?,
+                        package_id,
+                        filename: image.filename.trim().to_string(),
+                        title: image.title.trim().to_string(),
+                        description: image.description.trim().to_string(),
+                    }
+                }
+                CatalogChange::RemoveImage { sku, filename } => {
+                    let package_id = package_id(sku, &package_ids)?;
+                    CatalogWrite::RemoveImage {
+                        id: image_id(package_id, filename, &image_ids)?,
+                        package_id,
+                    }
+                }
+                CatalogChange::OrderImages { sku, filenames } => {
+                    let package_id = package_id(sku, &package_ids)?;
+                    CatalogWrite::OrderImages {
+                        package_id,
+                        image_ids: filenames
+                            .iter()
+                            .map(|f| image_id(package_id, f, &image_ids))
+                            .collect::<Result<_>>()?,
+                    }
+                }
+                CatalogChange::AddProvenance {
+                    sku,
+                    usage_hours,
+                    quantity_available,
+                } => CatalogWrite::UpsertProvenance {
+                    package_id: package_id(sku, &package_ids)?,
+                    usage_hours: i32::try_from(*usage_hours)?,
+                    quantity_available: i32::try_from(*quantity_available)?,
+                    is_active: true,
+                },
+                CatalogChange::UpdateProvenance {
+                    sku,
+                    usage_hours,
+                    quantity_available,
+                    active,
+                } => CatalogWrite::UpsertProvenance {
+                    package_id: package_id(sku, &package_ids)?,
+                    usage_hours: i32::try_from(*usage_hours)?,
+                    quantity_available: i32::try_from(*quantity_available)?,
+                    is_active: *active,
+                },
+                CatalogChange::SetActive { sku, active } => CatalogWrite::SetPackageActive {
+                    id: package_id(sku, &package_ids)?,
+                    is_active: *active,
+                },
+            };
+            writes.push(write);
+        }
+
+        Ok(writes)
+    }
+}
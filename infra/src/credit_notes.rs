@@ -0,0 +1,328 @@
+use crate::InfraState;
+use ai::credit_note::{
+    self, CreateCreditNoteRequest, CreditNote, CreditNoteReason, PayoutReview, PayoutSettlement,
+    PayoutStatus, RefundPayout, RefundPayoutRequest,
+};
+use ai::ledger::JournalEntry;
+use anyhow::{anyhow, bail, Result};
+use serde_json::json;
+use uuid::Uuid;
+
+impl InfraState {
+    /// Credit part of an issued invoice to the organization's account
+    pub async fn issue_credit_note(&self, req: CreateCreditNoteRequest) -> Result<CreditNote> {
+        if !req.amount_usdc.is_positive() {
+            bail!("credit note amount must be positive");
+        }
+
+        let invoice = self
+            .db
+            .get_invoice(req.invoice_id)
+            .await?
+            .ok_or_else(|| anyhow!("invoice {} not found", req.invoice_id))?;
+        if invoice.status == "void" {
+            bail!("invoice {} is void", invoice.id);
+        }
+
+        let id = Uuid::new_v4();
+        let vat = credit_note::vat_share(req.amount_usdc, invoice.total_usdc, invoice.vat_usdc);
+        let journal =
+            JournalEntry::credit_note_issued(invoice.org_id, id, req.amount_usdc - vat, vat);
+
+        let note = self
+            .db
+            .create_credit_note(
+                id,
+                invoice.id,
+                req.reason.as_str(),
+                req.description.trim(),
+                req.amount_usdc,
+                vat,
+                &journal,
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                Some(note.org_id),
+                None,
+                "credit_note.issued",
+                json!({
+                    "credit_note_id": note.id,
+                    "invoice_id": note.invoice_id,
+                    "reason": note.reason,
+                    "amount_usdc": note.amount_usdc,
+                    "vat_usdc": note.vat_usdc,
+                }),
+            )
+            .await?;
+
+        self.notify_org(
+            note.org_id,
+            "Credit note issued",
+            &format!(
+                "A credit note over {} USDC has been issued against invoice {}. \
+                 It will be applied to your next invoice unless you request a payout.",
+                note.amount_usdc, note.invoice_id
+            ),
+        )
+        .await;
+
+        Ok(credit_note_from_db(note))
+    }
+
+    pub async fn get_credit_notes(&self) -> Result<Vec<CreditNote>> {
+        Ok(self
+            .db
+            .get_credit_notes_for_org(self.demo_org_id)
+            .await?
+            .into_iter()
+            .map(credit_note_from_db)
+            .collect())
+    }
+
+    /// Ask for available account credit to be paid out. Nothing is sent
+    /// until staff approve the request. The credit check and the request are
+    /// one transaction, so concurrent requests cannot overdraw the credit.
+    pub async fn request_refund_payout(&self, req: RefundPayoutRequest) -> Result<RefundPayout> {
+        let org_id = self.demo_org_id;
+        let address = req.destination_address.trim();
+
+        if !req.amount_usdc.is_positive() {
+            bail!("payout amount must be positive");
+        }
+        credit_note::validate_usdc_address(address).map_err(|e| anyhow!(e))?;
+
+        let payout = self
+            .db
+            .create_refund_payout(Uuid::new_v4(), org_id, req.amount_usdc, address)
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                Some(org_id),
+                None,
+                "refund_payout.requested",
+                json!({
+                    "payout_id": payout.id,
+                    "amount_usdc": payout.amount_usdc,
+                    "destination_address": payout.destination_address,
+                }),
+            )
+            .await?;
+
+        Ok(refund_payout_from_db(payout))
+    }
+
+    pub async fn get_refund_payouts(&self) -> Result<Vec<RefundPayout>> {
+        Ok(self
+            .db
+            .get_refund_payouts_for_org(self.demo_org_id)
+            .await?
+            .into_iter()
+            .map(refund_payout_from_db)
+            .collect())
+    }
+
+    pub async fn get_refund_payouts_by_status(
+        &self,
+        status: PayoutStatus,
+    ) -> Result<Vec<RefundPayout>> {
+        Ok(self
+            .db
+            .get_refund_payouts_by_status(status.as_str())
+            .await?
+            .into_iter()
+            .map(refund_payout_from_db)
+            .collect())
+    }
+
+    /// Staff decision on a requested payout, recorded as made by the
+    /// authenticated `reviewer`. Returns `None` if the payout does not exist
+    /// or was already reviewed.
+    pub async fn review_refund_payout(
+        &self,
+        payout_id: Uuid,
+        approve: bool,
+        reviewer: &str,
+        review: PayoutReview,
+    ) -> Result<Option<RefundPayout>> {
+        let status = if approve {
+            PayoutStatus::Approved
+        } else {
+            PayoutStatus::Rejected
+        };
+
+        let Some(payout) = self
+            .db
+            .review_refund_payout(payout_id, status.as_str(), reviewer, review.note.as_deref())
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        self.db
+            .insert_audit_log(
+                Some(payout.org_id),
+                None,
+                &format!("refund_payout.{}", status.as_str()),
+                json!({
+                    "payout_id": payout.id,
+                    "amount_usdc": payout.amount_usdc,
+                    "reviewed_by": reviewer,
+                    "note": review.note,
+                }),
+            )
+            .await?;
+
+        let outcome = if approve {
+            "has been approved and will be sent shortly".to_string()
+        } else {
+            format!(
+                "was declined{}",
+                review
+                    .note
+                    .as_deref()
+                    .map(|n| format!(": {n}"))
+                    .unwrap_or_default()
+            )
+        };
+        self.notify_org(
+            payout.org_id,
+            "Refund payout reviewed",
+            &format!(
+                "Your payout request over {} USDC to {} {outcome}.",
+                payout.amount_usdc, payout.destination_address
+            ),
+        )
+        .await;
+
+        Ok(Some(refund_payout_from_db(payout)))
+    }
+
+    /// Record that an approved payout was sent and post it to the ledger.
+    /// Returns `None` if the payout does not exist or is not approved.
+    pub async fn settle_refund_payout(
+        &self,
+        payout_id: Uuid,
+        settlement: PayoutSettlement,
+    ) -> Result<Option<RefundPayout>> {
+        let tx_hash = settlement.tx_hash.trim();
+        if tx_hash.is_empty() {
+            bail!("tx_hash is required");
+        }
+
+        let Some(payout) = self.db.get_refund_payout(payout_id).await? else {
+            return Ok(None);
+        };
+
+        // The remaining credit is checked under lock as the payout is posted
+        let journal = JournalEntry::refund_paid(
+            payout.org_id,
+            payout.id,
+            payout.amount_usdc,
+            &format!("payout to {}", payout.destination_address),
+        );
+
+        let Some(payout) = self
+            .db
+            .mark_refund_payout_paid(payout_id, tx_hash, &journal)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        self.db
+            .insert_audit_log(
+                Some(payout.org_id),
+                None,
+                "refund_payout.paid",
+                json!({
+                    "payout_id": payout.id,
+                    "amount_usdc": payout.amount_usdc,
+                    "tx_hash": tx_hash,
+                }),
+            )
+            .await?;
+
+        self.notify_org(
+            payout.org_id,
+            "Refund sent",
+            &format!(
+                "{} USDC has been sent to {} (transaction {tx_hash}).",
+                payout.amount_usdc, payout.destination_address
+            ),
+        )
+        .await;
+
+        Ok(Some(refund_payout_from_db(payout)))
+    }
+
+    /// Settle as much of a newly issued invoice as the organization's
+    /// available account credit covers
+    pub(crate) async fn apply_account_credit(&self, invoice: &persistence::Invoice) -> Result<()> {
+        let available = self
+            .get_org_balance(invoice.org_id)
+            .await?
+            .available_credit_usdc();
+        let outstanding = invoice.total_usdc - invoice.credit_applied_usdc;
+        let amount = available.min(outstanding);
+
+        if !amount.is_positive() {
+            return Ok(());
+        }
+
+        let journal = JournalEntry::credit_applied(invoice.org_id, invoice.id, amount);
+        let Some(updated) = self
+            .db
+            .apply_invoice_credit(invoice.id, amount, &journal)
+            .await?
+        else {
+            return Ok(());
+        };
+
+        self.db
+            .insert_audit_log(
+                Some(invoice.org_id),
+                None,
+                "invoice.credit_applied",
+                json!({
+                    "invoice_id": invoice.id,
+                    "amount_usdc": amount,
+                    "status": updated.status,
+                }),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn credit_note_from_db(n: persistence::CreditNote) -> CreditNote {
+    CreditNote {
+        id: n.id,
+        org_id: n.org_id,
+        invoice_id: n.invoice_id,
+        reason: CreditNoteReason::parse(&n.reason).unwrap_or(CreditNoteReason::Goodwill),
+        description: n.description,
+        amount_usdc: n.amount_usdc,
+        vat_usdc: n.vat_usdc,
+        issued_at: n.issued_at,
+    }
+}
+
+fn refund_payout_from_db(p: persistence::RefundPayout) -> RefundPayout {
+    RefundPayout {
+        id: p.id,
+        org_id: p.org_id,
+        amount_usdc: p.amount_usdc,
+        destination_address: p.destination_address,
+        status: PayoutStatus::parse(&p.status).unwrap_or(PayoutStatus::Requested),
+        requested_at: p.requested_at,
+        reviewed_by: p.reviewed_by,
+        reviewed_at: p.reviewed_at,
+        review_note: p.review_note,
+        tx_hash: p.tx_hash,
+        paid_at: p.paid_at,
+    }
+}
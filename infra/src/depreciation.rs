@@ -0,0 +1,388 @@
+use crate::InfraState;
+use ai::depreciation::{
+    self, DepreciationPreview, DepreciationRuleChange, DepreciationRuleRequest,
+    ProvenancePriceImpact, RepricingImpact, RuleChangeOutcome, ScheduledDepreciationRule,
+    ScheduledRuleStatus,
+};
+use ai::{
+    DepreciationCurve, DepreciationRule, DepreciationSchedule, ScheduleBreakpoint,
+    ScheduleInterpolation,
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use persistence::NewDepreciationRule;
+use rust_decimal::Decimal;
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+impl InfraState {
+    /// Depreciation rules of every package that has one, by package
+    pub(crate) async fn depreciation_rules_by_package(
+        &self,
+    ) -> Result<HashMap<Uuid, DepreciationRule>> {
+        Ok(self
+            .get_depreciation_rules()
+            .await?
+            .into_iter()
+            .map(|rule| (rule.package_id, rule))
+            .collect())
+    }
+
+    pub async fn get_depreciation_rules(&self) -> Result<Vec<DepreciationRule>> {
+        Ok(self
+            .db
+            .get_depreciation_rules()
+            .await?
+            .into_iter()
+            .map(depreciation_rule_from_db)
+            .collect())
+    }
+
+    /// The rule a package is priced with, which is the default rule if it
+    /// has none saved
+    pub(crate) async fn effective_depreciation_rule(
+        &self,
+        package_id: Uuid,
+    ) -> Result<DepreciationRule> {
+        Ok(
+            match self
+                .db
+                .get_depreciation_rule_by_package_id(package_id)
+                .await?
+            {
+                Some(dr) => depreciation_rule_from_db(dr),
+                None => DepreciationRule::default_for(package_id),
+            },
+        )
+    }
+
+    /// Validate and save a package's depreciation rule. A custom schedule
+    /// also sets the final percentage and hours its breakpoints end at.
+    pub async fn set_depreciation_rule(&self, rule: DepreciationRule) -> Result<DepreciationRule> {
+        rule.validate().map_err(|e| anyhow!(e))?;
+
+        let saved = self
+            .db
+            .save_depreciation_rule(&new_rule_for_db(&rule))
+            .await?;
+        let saved = depreciation_rule_from_db(saved);
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "depreciation_rule.saved",
+                json!({ "package_id": saved.package_id, "rule": saved }),
+            )
+            .await?;
+
+        Ok(saved)
+    }
+
+    /// Sample a package's depreciation curve for charts. With `what_if` the
+    /// given rule is sampled instead of the saved one, without saving it.
+    /// Returns `None` if there is no package with the SKU.
+    pub async fn depreciation_preview(
+        &self,
+        sku: &str,
+        from: Option<u32>,
+        to: Option<u32>,
+        step: Option<u32>,
+        what_if: Option<DepreciationRuleRequest>,
+    ) -> Result<Option<DepreciationPreview>> {
+        let Some(package) = self.db.get_package_by_sku(sku).await? else {
+            return Ok(None);
+        };
+
+        let what_if_rule = what_if.is_some();
+        let rule = match what_if {
+            Some(req) => {
+                let rule = req.into_rule(package.id);
+                rule.validate().map_err(|e| anyhow!(e))?;
+                rule
+            }
+            None => self.effective_depreciation_rule(package.id).await?,
+        };
+
+        let to = to.unwrap_or_else(|| rule.schedule().full_depreciation_hours());
+        let points = depreciation::sample_curve(
+            &rule,
+            package.setup_price_usdc,
+            from.unwrap_or(0),
+            to,
+            step.unwrap_or(depreciation::DEFAULT_SAMPLE_STEP_HOURS),
+        )
+        .map_err(|e| anyhow!(e))?;
+
+        Ok(Some(DepreciationPreview {
+            sku: sku.to_string(),
+            setup_price_usdc: package.setup_price_usdc,
+            rule,
+            what_if: what_if_rule,
+            points,
+        }))
+    }
+
+    /// Replace a package's depreciation rule, now or at `effective_at`, and
+    /// report how every provenance price moves. A dry run only reports.
+    /// Returns `None` if there is no package with the SKU.
+    pub async fn change_depreciation_rule(
+        &self,
+        sku: &str,
+        change: DepreciationRuleChange,
+    ) -> Result<Option<RepricingImpact>> {
+        let Some(package) = self.db.get_package_by_sku(sku).await? else {
+            return Ok(None);
+        };
+
+        let new_rule = change.rule.into_rule(package.id);
+        new_rule.validate().map_err(|e| anyhow!(e))?;
+        let current_rule = self.effective_depreciation_rule(package.id).await?;
+
+        let provenances = self
+            .db
+            .get_provenance_pricing_inputs(Some(package.id), false)
+            .await?
+            .iter()
+            .map(|input| {
+                ProvenancePriceImpact::between(
+                    &current_rule,
+                    &new_rule,
+                    input.provenance_id,
+                    input.setup_price_usdc,
+                    input.usage_hours as u32,
+                )
+            })
+            .collect();
+
+        // A date that has already passed means now
+        let effective_at = change.effective_at.filter(|at| *at > Utc::now());
+
+        let mut impact = RepricingImpact {
+            sku: sku.to_string(),
+            package_id: package.id,
+            current_rule,
+            new_rule,
+            outcome: RuleChangeOutcome::Previewed,
+            effective_at,
+            scheduled_id: None,
+            provenances,
+        };
+        if change.dry_run {
+            return Ok(Some(impact));
+        }
+
+        match effective_at {
+            Some(at) => {
+                let scheduled = self
+                    .db
+                    .create_scheduled_depreciation_rule(&new_rule_for_db(&impact.new_rule), at)
+                    .await?;
+
+                self.db
+                    .insert_audit_log(
+                        None,
+                        None,
+                        "depreciation_rule.scheduled",
+                        json!({
+                            "scheduled_id": scheduled.id,
+                            "package_id": package.id,
+                            "effective_at": at,
+                            "rule": impact.new_rule,
+                        }),
+                    )
+                    .await?;
+
+                impact.outcome = RuleChangeOutcome::Scheduled;
+                impact.scheduled_id = Some(scheduled.id);
+            }
+            None => {
+                impact.new_rule = self.set_depreciation_rule(impact.new_rule).await?;
+                self.refresh_stale_prices().await?;
+                impact.outcome = RuleChangeOutcome::Applied;
+            }
+        }
+
+        Ok(Some(impact))
+    }
+
+    pub async fn get_scheduled_depreciation_rules(
+        &self,
+        status: ScheduledRuleStatus,
+    ) -> Result<Vec<ScheduledDepreciationRule>> {
+        Ok(self
+            .db
+            .get_scheduled_depreciation_rules(status.as_str())
+            .await?
+            .into_iter()
+            .map(scheduled_rule_from_db)
+            .collect())
+    }
+
+    /// Returns `None` if the change is not pending
+    pub async fn cancel_scheduled_depreciation_rule(
+        &self,
+        id: i32,
+    ) -> Result<Option<ScheduledDepreciationRule>> {
+        let Some(cancelled) = self.db.cancel_scheduled_depreciation_rule(id).await? else {
+            return Ok(None);
+        };
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "depreciation_rule.schedule_cancelled",
+                json!({ "scheduled_id": id, "package_id": cancelled.package_id }),
+            )
+            .await?;
+
+        Ok(Some(scheduled_rule_from_db(cancelled)))
+    }
+
+    /// Put scheduled rule changes whose effective date has passed into
+    /// effect. Returns how many were applied.
+    pub(crate) async fn apply_due_depreciation_rules(&self, now: DateTime<Utc>) -> Result<usize> {
+        let due = self.db.get_due_scheduled_depreciation_rules(now).await?;
+
+        let mut applied = 0;
+        for scheduled in due {
+            // Another request may have applied it in the meantime
+            let Some(scheduled) = self
+                .db
+                .apply_scheduled_depreciation_rule(scheduled.id)
+                .await?
+            else {
+                continue;
+            };
+
+            self.db
+                .insert_audit_log(
+                    None,
+                    None,
+                    "depreciation_rule.applied",
+                    json!({
+                        "scheduled_id": scheduled.id,
+                        "package_id": scheduled.package_id,
+                        "effective_at": scheduled.effective_at,
+                    }),
+                )
+                .await?;
+
+            info!(
+                "Applied scheduled depreciation rule {} for package {}",
+                scheduled.id, scheduled.package_id
+            );
+            applied += 1;
+        }
+
+        Ok(applied)
+    }
+}
+
+pub(crate) fn new_rule_for_db(rule: &DepreciationRule) -> NewDepreciationRule {
+    let (final_pct, full_hours, interpolation, schedule) = match &rule.depreciation_curve {
+        DepreciationCurve::Custom(schedule) => (
+            schedule.final_percentage(),
+            schedule.full_depreciation_hours().max(1),
+            Some(schedule.interpolation.as_str().to_string()),
+            schedule
+                .breakpoints
+                .iter()
+                .map(|b| (b.usage_hours as i32, b.remaining_percentage))
+                .collect(),
+        ),
+        _ => (
+            rule.final_depreciated_percentage,
+            rule.full_depreciation_hours,
+            None,
+            Vec::new(),
+        ),
+    };
+
+    NewDepreciationRule {
+        package_id: rule.package_id,
+        final_depreciated_percentage: final_pct,
+        full_depreciation_hours: full_hours as i32,
+        depreciation_curve: rule.depreciation_curve.as_str().to_string(),
+        schedule_interpolation: interpolation,
+        schedule,
+    }
+}
+
+pub(crate) fn depreciation_rule_from_db(dr: persistence::DepreciationRule) -> DepreciationRule {
+    rule_from_parts(
+        dr.package_id,
+        dr.final_depreciated_percentage,
+        dr.full_depreciation_hours,
+        &dr.depreciation_curve,
+        dr.schedule_interpolation.as_deref(),
+        &dr.schedule_hours,
+        &dr.schedule_percentages,
+    )
+}
+
+fn scheduled_rule_from_db(s: persistence::ScheduledDepreciationRule) -> ScheduledDepreciationRule {
+    ScheduledDepreciationRule {
+        id: s.id,
+        rule: rule_from_parts(
+            s.package_id,
+            s.final_depreciated_percentage,
+            s.full_depreciation_hours,
+            &s.depreciation_curve,
+            s.schedule_interpolation.as_deref(),
+            &s.schedule_hours,
+            &s.schedule_percentages,
+        ),
+        effective_at: s.effective_at,
+        status: ScheduledRuleStatus::parse(&s.status).unwrap_or(ScheduledRuleStatus::Pending),
+        created_at: s.created_at,
+        applied_at: s.applied_at,
+    }
+}
+
+/// A stored custom schedule that fails validation (e.g. edited by hand)
+/// prices as a straight line to the rule's end point instead
+fn rule_from_parts(
+    package_id: Uuid,
+    final_depreciated_percentage: Decimal,
+    full_depreciation_hours: i32,
+    curve: &str,
+    interpolation: Option<&str>,
+    schedule_hours: &[i32],
+    schedule_percentages: &[Decimal],
+) -> DepreciationRule {
+    let depreciation_curve = match curve {
+        "exponential" => DepreciationCurve::Exponential,
+        "stepped" => DepreciationCurve::Stepped,
+        "custom" => {
+            let schedule = DepreciationSchedule {
+                interpolation: interpolation
+                    .and_then(ScheduleInterpolation::parse)
+                    .unwrap_or(ScheduleInterpolation::Linear),
+                breakpoints: schedule_hours
+                    .iter()
+                    .zip(schedule_percentages)
+                    .map(|(hours, pct)| ScheduleBreakpoint::new(*hours as u32, *pct))
+                    .collect(),
+            };
+            match schedule.validate() {
+                Ok(()) => DepreciationCurve::Custom(schedule),
+                Err(e) => {
+                    warn!("Invalid depreciation schedule for package {package_id}: {e}");
+                    DepreciationCurve::Linear
+                }
+            }
+        }
+        _ => DepreciationCurve::Linear,
+    };
+
+    DepreciationRule {
+        package_id,
+        final_depreciated_percentage,
+        full_depreciation_hours: full_depreciation_hours as u32,
+        depreciation_curve,
+    }
+}
@@ -0,0 +1,188 @@
+use crate::InfraState;
+use ai::billing::{BillingPeriod, Invoice};
+use ai::dunning::{DunningAction, DunningOutcome};
+use ai::ledger::JournalEntry;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+impl InfraState {
+    /// Apply the dunning policy to every overdue invoice: send the reminders
+    /// that are due and suspend servers once the grace period has passed.
+    pub async fn run_dunning(&self, now: DateTime<Utc>) -> Result<Vec<DunningOutcome>> {
+        let overdue = self.db.get_overdue_invoices(now).await?;
+
+        let mut outcomes = Vec::new();
+        for invoice in overdue {
+            let action = self.dunning_policy.next_action(
+                invoice.due_at,
+                now,
+                invoice.reminders_sent as u32,
+                invoice.suspended_at.is_some(),
+            );
+
+            let Some(action) = action else {
+                continue;
+            };
+
+            let period = BillingPeriod {
+                start: invoice.period_start,
+                end: invoice.period_end,
+            };
+            let amount = invoice.total_usdc - invoice.credit_applied_usdc;
+
+            let affected_servers = match action {
+                DunningAction::SendReminder { number } => {
+                    self.db
+                        .record_invoice_reminder(invoice.id, number as i16)
+                        .await?;
+
+                    self.notify_org(
+                        invoice.org_id,
+                        &format!("Payment reminder: invoice for {period}"),
+                        &format!(
+                            "Invoice {} for {} over {} USDC was due on {} and is still unpaid. \
+                             Please arrange payment to avoid suspension of your servers.",
+                            invoice.id,
+                            period,
+                            amount,
+                            invoice.due_at.date_naive()
+                        ),
+                    )
+                    .await;
+
+                    self.db
+                        .insert_audit_log(
+                            Some(invoice.org_id),
+                            None,
+                            "invoice.reminder_sent",
+                            json!({ "invoice_id": invoice.id, "reminder": number }),
+                        )
+                        .await?;
+
+                    Vec::new()
+                }
+                DunningAction::Suspend => {
+                    let servers = self.db.suspend_invoice_servers(invoice.id).await?;
+
+                    self.notify_org(
+                        invoice.org_id,
+                        &format!("Servers suspended: invoice for {period} unpaid"),
+                        &format!(
+                            "Invoice {} for {} over {} USDC is past its grace period. \
+                             {} server(s) have been suspended and will be reinstated once it is paid.",
+                            invoice.id,
+                            period,
+                            amount,
+                            servers.len()
+                        ),
+                    )
+                    .await;
+
+                    self.db
+                        .insert_audit_log(
+                            Some(invoice.org_id),
+                            None,
+                            "invoice.servers_suspended",
+                            json!({ "invoice_id": invoice.id, "server_ids": servers }),
+                        )
+                        .await?;
+
+                    servers
+                }
+            };
+
+            info!("Dunning {:?} for invoice {}", action, invoice.id);
+
+            outcomes.push(DunningOutcome {
+                invoice_id: invoice.id,
+                org_id: invoice.org_id,
+                action,
+                affected_servers,
+            });
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Record payment of an open invoice and reinstate any servers it had
+    /// suspended. Returns `None` if the invoice does not exist or is not open.
+    pub async fn record_invoice_payment(&self, invoice_id: Uuid) -> Result<Option<Invoice>> {
+        let Some(open) = self.db.get_invoice(invoice_id).await? else {
+            return Ok(None);
+        };
+
+        // Only the part not already settled from account credit is paid
+        let outstanding = open.total_usdc - open.credit_applied_usdc;
+        let journal = outstanding
+            .is_positive()
+            .then(|| JournalEntry::invoice_paid(open.org_id, open.id, outstanding));
+
+        let Some(invoice) = self
+            .db
+            .mark_invoice_paid(invoice_id, journal.as_ref())
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        self.db
+            .insert_audit_log(
+                Some(invoice.org_id),
+                None,
+                "invoice.paid",
+                json!({ "invoice_id": invoice.id, "total_usdc": invoice.total_usdc }),
+            )
+            .await?;
+
+        self.complete_paid_financing().await?;
+
+        if invoice.suspended_at.is_some() {
+            let reinstated = self.db.reinstate_invoice_servers(invoice.id).await?;
+
+            if !reinstated.is_empty() {
+                self.db
+                    .insert_audit_log(
+                        Some(invoice.org_id),
+                        None,
+                        "invoice.servers_reinstated",
+                        json!({ "invoice_id": invoice.id, "server_ids": reinstated }),
+                    )
+                    .await?;
+
+                self.notify_org(
+                    invoice.org_id,
+                    "Servers reinstated",
+                    &format!(
+                        "Thank you for your payment of invoice {}. {} server(s) have been reinstated.",
+                        invoice.id,
+                        reinstated.len()
+                    ),
+                )
+                .await;
+            }
+        }
+
+        Ok(Some(self.invoice_with_lines(invoice).await?))
+    }
+
+    /// Mail every user of an organization. Delivery failures are logged and
+    /// do not interrupt the caller.
+    pub(crate) async fn notify_org(&self, org_id: Uuid, subject: &str, body: &str) {
+        let recipients = match self.db.get_org_user_emails(org_id).await {
+            Ok(recipients) => recipients,
+            Err(e) => {
+                warn!("Could not load recipients for org {org_id}: {e}");
+                return;
+            }
+        };
+
+        for to in recipients {
+            if let Err(e) = self.mailer.send(&to, subject, body).await {
+                warn!("Failed to mail {to}: {e}");
+            }
+        }
+    }
+}
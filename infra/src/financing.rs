@@ -0,0 +1,233 @@
+use crate::InfraState;
+use ai::billing::{BillingPeriod, FinancedServer};
+use ai::financing::{
+    self, CreateFinancingPlanRequest, FinancingAgreement, FinancingOffer, FinancingPlan,
+    FinancingStatus, PackageFinancing,
+};
+use anyhow::{anyhow, Result};
+use persistence::NewFinancingAgreement;
+use serde_json::json;
+use tracing::{info, warn};
+
+impl InfraState {
+    pub async fn get_financing_plans(&self, active_only: bool) -> Result<Vec<FinancingPlan>> {
+        Ok(self
+            .db
+            .get_financing_plans(active_only)
+            .await?
+            .into_iter()
+            .map(financing_plan_from_db)
+            .collect())
+    }
+
+    pub async fn create_financing_plan(
+        &self,
+        req: CreateFinancingPlanRequest,
+    ) -> Result<FinancingPlan> {
+        req.validate().map_err(|e| anyhow!(e))?;
+
+        let plan = self
+            .db
+            .create_financing_plan(
+                req.name.trim(),
+                req.term_months as i32,
+                req.annual_rate_percent,
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "financing_plan.created",
+                json!({
+                    "plan_id": plan.id,
+                    "term_months": plan.term_months,
+                    "annual_rate_percent": plan.annual_rate_percent,
+                }),
+            )
+            .await?;
+
+        Ok(financing_plan_from_db(plan))
+    }
+
+    pub async fn deactivate_financing_plan(&self, id: i32) -> Result<bool> {
+        let found = self.db.deactivate_financing_plan(id).await?;
+
+        if found {
+            self.db
+                .insert_audit_log(
+                    None,
+                    None,
+                    "financing_plan.deactivated",
+                    json!({ "plan_id": id }),
+                )
+                .await?;
+        }
+
+        Ok(found)
+    }
+
+    /// Amortization schedule of a package's current setup price under every
+    /// active plan. Returns `None` if there is no package with the SKU.
+    pub async fn package_financing(&self, sku: &str) -> Result<Option<PackageFinancing>> {
+        let Some(package) = self.db.get_package_by_sku(sku).await? else {
+            return Ok(None);
+        };
+
+        let offers = self
+            .get_financing_plans(true)
+            .await?
+            .into_iter()
+            .map(|plan| FinancingOffer {
+                schedule: financing::amortize(
+                    package.setup_price_usdc,
+                    plan.annual_rate_percent,
+                    plan.term_months,
+                ),
+                plan,
+            })
+            .collect();
+
+        Ok(Some(PackageFinancing {
+            sku: sku.to_string(),
+            setup_price_usdc: package.setup_price_usdc,
+            offers,
+        }))
+    }
+
+    pub async fn get_financing_agreements(&self) -> Result<Vec<FinancingAgreement>> {
+        Ok(self
+            .db
+            .get_financing_agreements_for_org(self.demo_org_id)
+            .await?
+            .into_iter()
+            .map(financing_agreement_from_db)
+            .collect())
+    }
+
+    /// Terms for an order placed on plan `plan_id`, financing `principal`
+    pub(crate) async fn new_financing_agreement(
+        &self,
+        plan_id: i32,
+        principal: ai::Money,
+    ) -> Result<NewFinancingAgreement> {
+        let plan = self
+            .db
+            .get_financing_plan(plan_id)
+            .await?
+            .filter(|p| p.is_active)
+            .ok_or_else(|| anyhow!("financing plan {plan_id} is not available"))?;
+
+        Ok(NewFinancingAgreement {
+            plan_id: plan.id,
+            principal_usdc: principal,
+            annual_rate_percent: plan.annual_rate_percent,
+            term_months: plan.term_months,
+        })
+    }
+
+    /// Financed servers with their next instalment due in `period`
+    pub(crate) async fn financed_servers(
+        &self,
+        period: &BillingPeriod,
+    ) -> Result<Vec<FinancedServer>> {
+        Ok(self
+            .db
+            .get_unbilled_financed_servers(period.start, period.end)
+            .await?
+            .into_iter()
+            .filter_map(|s| {
+                let term_months = s.term_months as u32;
+                let instalment_number = s.instalments_billed as u32 + 1;
+                let schedule =
+                    financing::amortize(s.principal_usdc, s.annual_rate_percent, term_months);
+
+                Some(FinancedServer {
+                    server_id: s.server_id,
+                    org_id: s.org_id,
+                    hostname: s.hostname,
+                    package_name: s.package_name,
+                    instalment_number,
+                    term_months,
+                    amount_usdc: schedule.payment(instalment_number)?,
+                    activated_at: s.activated_at,
+                })
+            })
+            .collect())
+    }
+
+    /// Transfer ownership of hardware whose instalments are all paid
+    pub(crate) async fn complete_paid_financing(&self) -> Result<()> {
+        for agreement in self.db.complete_paid_financing_agreements().await? {
+            self.db
+                .insert_audit_log(
+                    Some(agreement.org_id),
+                    None,
+                    "financing.ownership_transferred",
+                    json!({
+                        "agreement_id": agreement.id,
+                        "order_id": agreement.order_id,
+                        "server_id": agreement.server_id,
+                    }),
+                )
+                .await?;
+
+            info!(
+                "Financing agreement {} completed; ownership transferred to org {}",
+                agreement.id, agreement.org_id
+            );
+
+            self.notify_org(
+                agreement.org_id,
+                "Your hardware is paid off",
+                &format!(
+                    "The last instalment of {} has been paid. The hardware is now yours.",
+                    agreement.sku.as_deref().unwrap_or("your server")
+                ),
+            )
+            .await;
+        }
+
+        Ok(())
+    }
+}
+
+fn financing_plan_from_db(p: persistence::FinancingPlan) -> FinancingPlan {
+    FinancingPlan {
+        id: p.id,
+        name: p.name,
+        term_months: p.term_months as u32,
+        annual_rate_percent: p.annual_rate_percent,
+        is_active: p.is_active,
+        created_at: p.created_at,
+    }
+}
+
+fn financing_agreement_from_db(a: persistence::FinancingAgreement) -> FinancingAgreement {
+    let status = FinancingStatus::parse(&a.status).unwrap_or_else(|| {
+        warn!(
+            "Unknown financing status {} on agreement {}",
+            a.status, a.id
+        );
+        FinancingStatus::Active
+    });
+
+    FinancingAgreement {
+        id: a.id,
+        order_id: a.order_id,
+        server_id: a.server_id,
+        plan_id: a.plan_id,
+        plan_name: a.plan_name,
+        sku: a.sku.unwrap_or_default(),
+        instalments_billed: a.instalments_billed as u32,
+        status,
+        created_at: a.created_at,
+        ownership_transferred_at: a.ownership_transferred_at,
+        schedule: financing::amortize(
+            a.principal_usdc,
+            a.annual_rate_percent,
+            a.term_months as u32,
+        ),
+    }
+}
@@ -0,0 +1,179 @@
+use crate::InfraState;
+use ai::fulfilment::{BuildCapacity, BuildSchedule, PartsStock, QueuedBuild};
+use ai::{Availability, Package};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+impl InfraState {
+    /// Open builds scheduled on the workshop's capacity as of now
+    pub async fn get_build_schedule(&self) -> Result<BuildSchedule> {
+        let queue = self
+            .db
+            .get_open_builds()
+            .await?
+            .into_iter()
+            .map(|b| QueuedBuild {
+                order_id: b.order_id,
+                package_id: b.package_id,
+                build_hours: b.build_hours.unwrap_or(0).max(0) as u32,
+                queued_at: b.created_at,
+                started_at: b.build_started_at,
+            })
+            .collect();
+        let parts = self
+            .db
+            .get_build_parts()
+            .await?
+            .into_iter()
+            .map(|p| (p.package_id, parts_stock_from_db(&p)))
+            .collect::<HashMap<_, _>>();
+
+        Ok(BuildSchedule::plan(
+            self.build_capacity.clone(),
+            queue,
+            parts,
+            Utc::now(),
+        ))
+    }
+
+    /// Replace the build hours of build packages with when a build ordered
+    /// now would be done
+    pub(crate) async fn estimate_builds(&self, packages: &mut [Package]) -> Result<()> {
+        if !packages
+            .iter()
+            .any(|p| matches!(p.availability, Availability::Build { .. }))
+        {
+            return Ok(());
+        }
+
+        let schedule = self.get_build_schedule().await?;
+        for package in packages {
+            if let Availability::Build { hours } = package.availability {
+                let estimate = schedule.estimate(package.id, u32::from(hours));
+                package.availability = estimate.availability();
+                package.build_estimate = Some(estimate);
+            }
+        }
+        Ok(())
+    }
+
+    /// Parts kits on hand for a package; `None` if there is no such package
+    pub async fn get_build_parts(&self, sku: &str) -> Result<Option<PartsStock>> {
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            self.db
+                .get_package_build_parts(package.id)
+                .await?
+                .as_ref()
+                .map(parts_stock_from_db)
+                .unwrap_or_default(),
+        ))
+    }
+
+    /// Record the parts kits on hand for a package and how long more take.
+    /// Returns `None` if there is no such package.
+    pub async fn set_build_parts(
+        &self,
+        sku: &str,
+        stock: PartsStock,
+    ) -> Result<Option<PartsStock>> {
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+
+        let parts = self
+            .db
+            .set_package_build_parts(
+                package.id,
+                i32::try_from(stock.kits_in_stock)?,
+                i32::try_from(stock.lead_time_hours)?,
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.build_parts_updated",
+                json!({ "package_id": package.id, "sku": sku, "parts": stock }),
+            )
+            .await?;
+
+        Ok(Some(parts_stock_from_db(&parts)))
+    }
+
+    /// A technician starts on a queued order. Returns `None` if the order is
+    /// not queued.
+    pub async fn start_order_build(&self, order_id: Uuid) -> Result<Option<BuildSchedule>> {
+        if !self.db.start_order_build(order_id).await? {
+            return Ok(None);
+        }
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "fulfilment.build_started",
+                json!({ "order_id": order_id }),
+            )
+            .await?;
+
+        Ok(Some(self.get_build_schedule().await?))
+    }
+
+    /// An order's build is done and the server handed over. Returns `None`
+    /// if the order is not being provisioned.
+    pub async fn finish_order_build(&self, order_id: Uuid) -> Result<Option<BuildSchedule>> {
+        if !self.db.finish_order_build(order_id).await? {
+            return Ok(None);
+        }
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "fulfilment.build_finished",
+                json!({ "order_id": order_id }),
+            )
+            .await?;
+
+        Ok(Some(self.get_build_schedule().await?))
+    }
+}
+
+/// Read the workshop's capacity from `BUILD_TECHNICIANS` and
+/// `BUILD_HOURS_PER_DAY`, falling back to the defaults
+pub(crate) fn build_capacity_from_env() -> Result<BuildCapacity> {
+    let mut capacity = BuildCapacity::default();
+
+    if let Ok(technicians) = std::env::var("BUILD_TECHNICIANS") {
+        capacity.technicians = technicians
+            .parse()
+            .map_err(|e| anyhow!("invalid BUILD_TECHNICIANS: {e}"))?;
+    }
+
+    if let Ok(hours) = std::env::var("BUILD_HOURS_PER_DAY") {
+        capacity.hours_per_day = hours
+            .parse()
+            .map_err(|e| anyhow!("invalid BUILD_HOURS_PER_DAY: {e}"))?;
+    }
+
+    capacity
+        .validate()
+        .map_err(|e| anyhow!("invalid build capacity: {e}"))?;
+
+    Ok(capacity)
+}
+
+fn parts_stock_from_db(p: &persistence::BuildParts) -> PartsStock {
+    PartsStock {
+        kits_in_stock: p.kits_in_stock.max(0) as u32,
+        lead_time_hours: p.lead_time_hours.max(0) as u32,
+    }
+}
@@ -0,0 +1,273 @@
+use crate::InfraState;
+use ai::catalog::CatalogPackage;
+use ai::images::{self, ImageFormat, ImageUpload, ImageVariant, MAX_IMAGE_DIMENSION};
+use ai::PackageImage;
+use anyhow::{anyhow, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageReader, Limits};
+use persistence::{NewImageVariant, PackageImageVariant};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::Cursor;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Quality of JPEG renditions and of WebP renditions of photos
+const JPEG_QUALITY: u8 = 85;
+const WEBP_QUALITY: f32 = 80.0;
+
+impl InfraState {
+    /// Store an uploaded photo with its renditions and append it to the
+    /// package's images. Returns `None` if there is no such package.
+    pub async fn upload_package_image(
+        &self,
+        sku: &str,
+        upload: ImageUpload,
+    ) -> Result<Option<CatalogPackage>> {
+        let format = upload.validate().map_err(|e| anyhow!(e))?;
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+
+        let original = upload.bytes;
+        let (original, rendered) = tokio::task::spawn_blocking(move || {
+            render_variants(&original, format).map(|rendered| (original, rendered))
+        })
+        .await??;
+
+        let image_id = Uuid::new_v4();
+        let prefix = format!("packages/{sku}/{image_id}");
+        let original_key = format!("{prefix}/original.{}", format.extension());
+
+        // The full-width rendition in the upload's own format is the upload
+        let mut variants = vec![NewImageVariant {
+            format: format.as_str().to_string(),
+            width: i32::try_from(rendered.width)?,
+            height: i32::try_from(rendered.height)?,
+            storage_key: original_key.clone(),
+            url: self.image_store.url(&original_key),
+            byte_size: i64::try_from(original.len())?,
+        }];
+        let mut objects = vec![(original_key.clone(), original, format)];
+        for rendition in rendered.renditions {
+            let key = format!(
+                "{prefix}/{}w.{}",
+                rendition.width,
+                rendition.format.extension()
+            );
+            variants.push(NewImageVariant {
+                format: rendition.format.as_str().to_string(),
+                width: i32::try_from(rendition.width)?,
+                height: i32::try_from(rendition.height)?,
+                storage_key: key.clone(),
+                url: self.image_store.url(&key),
+                byte_size: i64::try_from(rendition.bytes.len())?,
+            });
+            objects.push((key, rendition.bytes, rendition.format));
+        }
+
+        let keys: Vec<String> = objects.iter().map(|(key, ..)| key.clone()).collect();
+        let saved = async {
+            for (key, bytes, format) in objects {
+                self.image_store
+                    .put(&key, bytes, format.content_type())
+                    .await?;
+            }
+            self.db
+                .add_uploaded_package_image(
+                    image_id,
+                    package.id,
+                    &self.image_store.url(&original_key),
+                    upload.title.trim(),
+                    upload.description.trim(),
+                    &original_key,
+                    &variants,
+                )
+                .await
+        }
+        .await;
+        let image = match saved {
+            Ok(image) => image,
+            Err(e) => {
+                self.delete_stored_images(&keys).await;
+                return Err(e);
+            }
+        };
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.image_uploaded",
+                json!({
+                    "package_id": package.id,
+                    "sku": sku,
+                    "image_id": image.id,
+                    "filename": image.filename,
+                    "variants": variants.len(),
+                }),
+            )
+            .await?;
+
+        Ok(Some(self.catalog_package(package).await?))
+    }
+
+    /// Remove stored files of deleted images. Failures only leave orphaned
+    /// files behind, so they are logged rather than returned.
+    pub(crate) async fn delete_stored_images(&self, keys: &[String]) {
+        for key in keys {
+            if let Err(e) = self.image_store.delete(key).await {
+                warn!("Failed to delete stored image {key}: {e}");
+            }
+        }
+    }
+
+    /// A package's images as customers see them
+    pub(crate) async fn package_images(&self, package_id: Uuid) -> Result<Vec<PackageImage>> {
+        let mut variants = self.image_variants(package_id).await?;
+        Ok(self
+            .db
+            .get_package_images(package_id)
+            .await?
+            .into_iter()
+            .map(|img| PackageImage {
+                variants: variants.remove(&img.id).unwrap_or_default(),
+                id: img.id,
+                filename: img.filename,
+                title: img.title,
+                description: img.description,
+            })
+            .collect())
+    }
+
+    /// Renditions of a package's images by image id
+    pub(crate) async fn image_variants(
+        &self,
+        package_id: Uuid,
+    ) -> Result<HashMap<Uuid, Vec<ImageVariant>>> {
+        let mut by_image: HashMap<Uuid, Vec<ImageVariant>> = HashMap::new();
+        for variant in self.db.get_package_image_variants(package_id).await? {
+            by_image
+                .entry(variant.image_id)
+                .or_default()
+                .push(image_variant_from_db(variant)?);
+        }
+        Ok(by_image)
+    }
+}
+
+/// An upload decoded and resized
+struct RenderedImage {
+    width: u32,
+    height: u32,
+    renditions: Vec<Rendition>,
+}
+
+struct Rendition {
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    bytes: Vec<u8>,
+}
+
+/// Render an upload at every variant width in its own format and as WebP,
+/// except the full-width rendition in its own format, which is the upload.
+/// Photos become lossy WebP; PNGs, usually graphics, stay lossless.
+fn render_variants(bytes: &[u8], format: ImageFormat) -> Result<RenderedImage> {
+    let mut reader = ImageReader::with_format(
+        Cursor::new(bytes),
+        match format {
+            ImageFormat::Png => image::ImageFormat::Png,
+            ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+            ImageFormat::Webp => image::ImageFormat::WebP,
+        },
+    );
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    // Renditions are upright; browsers apply EXIF orientation to the original
+    image.apply_orientation(orientation);
+
+    let (width, height) = (image.width(), image.height());
+    let mut renditions = Vec::new();
+    for target_width in images::variant_widths(width) {
+        let resized_image;
+        let resized = if target_width == width {
+            &image
+        } else {
+            resized_image = image.resize(target_width, u32::MAX, FilterType::Lanczos3);
+            &resized_image
+        };
+        if target_width != width {
+            renditions.push(Rendition {
+                format,
+                width: resized.width(),
+                height: resized.height(),
+                bytes: encode(resized, format)?,
+            });
+        }
+        if format != ImageFormat::Webp {
+            renditions.push(Rendition {
+                format: ImageFormat::Webp,
+                width: resized.width(),
+                height: resized.height(),
+                bytes: encode_webp(resized, format == ImageFormat::Jpeg),
+            });
+        }
+    }
+
+    Ok(RenderedImage {
+        width,
+        height,
+        renditions,
+    })
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match format {
+        ImageFormat::Png => image.write_with_encoder(PngEncoder::new(&mut bytes))?,
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY))?,
+        ImageFormat::Webp => return Ok(encode_webp(image, true)),
+    }
+    Ok(bytes)
+}
+
+fn encode_webp(image: &DynamicImage, lossy: bool) -> Vec<u8> {
+    let encoded = if image.color().has_alpha() {
+        let rgba = image.to_rgba8();
+        let encoder = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height());
+        if lossy {
+            encoder.encode(WEBP_QUALITY)
+        } else {
+            encoder.encode_lossless()
+        }
+    } else {
+        let rgb = image.to_rgb8();
+        let encoder = webp::Encoder::from_rgb(&rgb, rgb.width(), rgb.height());
+        if lossy {
+            encoder.encode(WEBP_QUALITY)
+        } else {
+            encoder.encode_lossless()
+        }
+    };
+    encoded.to_vec()
+}
+
+fn image_variant_from_db(v: PackageImageVariant) -> Result<ImageVariant> {
+    Ok(ImageVariant {
+        format: ImageFormat::parse(&v.format)
+            .ok_or_else(|| anyhow!("unknown image format {}", v.format))?,
+        url: v.url,
+        width: v.width as u32,
+        height: v.height as u32,
+    })
+}
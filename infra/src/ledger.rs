@@ -0,0 +1,124 @@
+use crate::InfraState;
+use ai::ledger::{Account, JournalEntry, LedgerCheck, OrgBalance};
+use ai::Money;
+use anyhow::{bail, Result};
+use serde_json::json;
+use uuid::Uuid;
+
+impl InfraState {
+    /// Exact balance of an organization derived from its ledger accounts
+    pub async fn get_org_balance(&self, org_id: Uuid) -> Result<OrgBalance> {
+        let balances = self.db.get_org_account_balances(org_id).await?;
+        let pending_payouts = self.db.get_pending_payout_total(org_id).await?;
+
+        let net = |code: &str| -> Money {
+            balances
+                .iter()
+                .filter(|b| b.code == code)
+                .map(|b| b.debit_usdc - b.credit_usdc)
+                .sum()
+        };
+
+        let receivable = net(Account::Receivable { org_id }.code());
+        // Credit accounts are liabilities, so their balance is credit-normal
+        let credit = -net(Account::CustomerCredit { org_id }.code());
+
+        Ok(OrgBalance {
+            org_id,
+            receivable_usdc: receivable,
+            credit_usdc: credit,
+            pending_payouts_usdc: pending_payouts,
+            balance_due_usdc: receivable - credit,
+        })
+    }
+
+    pub async fn get_balance(&self) -> Result<OrgBalance> {
+        self.get_org_balance(self.demo_org_id).await
+    }
+
+    /// Verify that total debits equal total credits and that every journal
+    /// entry balances on its own
+    pub async fn check_ledger(&self) -> Result<LedgerCheck> {
+        let totals = self.db.get_ledger_totals().await?;
+        let unbalanced_entries = self.db.get_unbalanced_journal_entries().await?;
+
+        Ok(LedgerCheck {
+            balanced: totals.total_debits_usdc == totals.total_credits_usdc
+                && unbalanced_entries.is_empty(),
+            total_debits_usdc: totals.total_debits_usdc,
+            total_credits_usdc: totals.total_credits_usdc,
+            unbalanced_entries,
+        })
+    }
+
+    /// Grant account credit to an organization
+    pub async fn grant_credit(
+        &self,
+        org_id: Uuid,
+        amount_usdc: Money,
+        reason: &str,
+    ) -> Result<OrgBalance> {
+        if !amount_usdc.is_positive() {
+            bail!("credit amount must be positive");
+        }
+
+        let credit_id = Uuid::new_v4();
+        let entry = JournalEntry::credit_granted(org_id, credit_id, amount_usdc, reason);
+        self.db.post_journal_entry(&entry).await?;
+
+        self.db
+            .insert_audit_log(
+                Some(org_id),
+                None,
+                "ledger.credit_granted",
+                json!({
+                    "credit_id": credit_id,
+                    "amount_usdc": amount_usdc,
+                    "reason": reason,
+                }),
+            )
+            .await?;
+
+        self.get_org_balance(org_id).await
+    }
+
+    /// Pay account credit back out to an organization
+    pub async fn record_refund(
+        &self,
+        org_id: Uuid,
+        amount_usdc: Money,
+        reason: &str,
+    ) -> Result<OrgBalance> {
+        if !amount_usdc.is_positive() {
+            bail!("refund amount must be positive");
+        }
+
+        let available = self.get_org_balance(org_id).await?.available_credit_usdc();
+        if available < amount_usdc {
+            bail!(
+                "refund of {} USDC exceeds available credit of {} USDC",
+                amount_usdc,
+                available
+            );
+        }
+
+        let refund_id = Uuid::new_v4();
+        let entry = JournalEntry::refund_paid(org_id, refund_id, amount_usdc, reason);
+        self.db.post_journal_entry(&entry).await?;
+
+        self.db
+            .insert_audit_log(
+                Some(org_id),
+                None,
+                "ledger.refund_paid",
+                json!({
+                    "refund_id": refund_id,
+                    "amount_usdc": amount_usdc,
+                    "reason": reason,
+                }),
+            )
+            .await?;
+
+        self.get_org_balance(org_id).await
+    }
+}
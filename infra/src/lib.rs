@@ -1,16 +1,66 @@
-use ai::{
-    Availability, CreateOrderRequest, CreateOrderResponse, GpuClass, OrderSummary, Package,
-    PackageImage, Provenance,
-};
-use anyhow::Result;
+use ai::buyback::BuybackPolicy;
+use ai::compare::{self, PackageComparison};
+use ai::dunning::DunningPolicy;
+use ai::fulfilment::BuildCapacity;
+use ai::locale::Locale;
+use ai::quote::{build_quote, PricedItem};
+use ai::recommend::{self, ModelFitRequest, Recommendation};
+use ai::search::PackageQuery;
+use ai::{CreateOrderRequest, CreateOrderResponse, Money, OrderSummary, Package, Provenance};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
 use persistence::Database;
+use std::collections::HashMap;
+use std::sync::Arc;
 use tracing::info;
 use uuid::Uuid;
 
+mod addons;
+mod billing;
+mod buyback;
+mod catalog;
+mod catalog_sync;
+mod credit_notes;
+mod depreciation;
+mod dunning;
+mod financing;
+mod fulfilment;
+mod images;
+mod ledger;
+pub mod mailer;
+mod price_history;
+mod pricing;
+mod quote;
+pub mod storage;
+mod tco;
+mod translations;
+pub mod vat;
+
+pub use billing::spawn_billing_scheduler;
+use catalog::availability_from_db;
+pub use catalog_sync::read_catalog_file;
+use depreciation::depreciation_rule_from_db;
+use mailer::{LogMailer, Mailer};
+pub use pricing::spawn_price_refresher;
+use storage::{ImageStorage, ImageStore};
+use vat::{OfflineVatIdValidator, VatIdValidator};
+
 pub struct InfraState {
     db: Database,
     // TODO: Add demo org_id for now - in real app this would come from JWT
     demo_org_id: Uuid,
+    // Days between an invoice being issued and payment being due
+    payment_terms_days: u32,
+    dunning_policy: DunningPolicy,
+    mailer: Arc<dyn Mailer>,
+    // Member state we invoice from; required to issue invoices
+    vat_supplier_country: Option<String>,
+    vat_validator: Arc<dyn VatIdValidator>,
+    // Default hourly cloud rate TCO reports compare against
+    tco_cloud_hourly_rate: Option<Money>,
+    buyback_policy: BuybackPolicy,
+    image_store: Arc<dyn ImageStore>,
+    build_capacity: BuildCapacity,
 }
 
 impl InfraState {
@@ -26,227 +76,324 @@ impl InfraState {
         let demo_org_id = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000")
             .unwrap_or_else(|_| Uuid::new_v4());
 
-        Ok(Self { db, demo_org_id })
+        let payment_terms_days = std::env::var("BILLING_PAYMENT_TERMS_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(14);
+
+        let dunning_policy = dunning_policy_from_env()?;
+        let vat_supplier_country = vat_supplier_country_from_env()?;
+        let tco_cloud_hourly_rate = match std::env::var("TCO_CLOUD_HOURLY_RATE_USDC") {
+            Ok(rate) => Some(
+                rate.parse::<Money>()
+                    .map_err(|e| anyhow!("invalid TCO_CLOUD_HOURLY_RATE_USDC: {e}"))?,
+            ),
+            Err(_) => None,
+        };
+        let buyback_policy = buyback::buyback_policy_from_env()?;
+        let image_store = ImageStorage::from_env()?.store()?;
+        let build_capacity = fulfilment::build_capacity_from_env()?;
+
+        Ok(Self {
+            db,
+            demo_org_id,
+            payment_terms_days,
+            dunning_policy,
+            mailer: Arc::new(LogMailer),
+            vat_supplier_country,
+            vat_validator: Arc::new(OfflineVatIdValidator),
+            tco_cloud_hourly_rate,
+            buyback_policy,
+            image_store,
+            build_capacity,
+        })
+    }
+
+    /// Deliver customer notifications through `mailer` instead of the log
+    pub fn with_mailer(mut self, mailer: Arc<dyn Mailer>) -> Self {
+        self.mailer = mailer;
+        self
+    }
+
+    /// Validate VAT IDs with `validator` instead of the offline format check
+    pub fn with_vat_validator(mut self, validator: Arc<dyn VatIdValidator>) -> Self {
+        self.vat_validator = validator;
+        self
+    }
+
+    /// Keep uploaded package images in `store` instead of the one
+    /// `IMAGE_STORAGE` configures
+    pub fn with_image_store(mut self, store: Arc<dyn ImageStore>) -> Self {
+        self.image_store = store;
+        self
     }
 
     pub async fn get_packages(&self) -> Result<Vec<Package>> {
-        // Fetch packages from database
         let db_packages = self.db.get_active_packages().await?;
 
         // Fetch all provenance options at once for efficiency
-        let all_provenances = self.db.get_all_package_provenances().await?;
+        let mut provenances: HashMap<Uuid, Vec<persistence::PackageProvenance>> = HashMap::new();
+        for prov in self.db.get_all_package_provenances().await? {
+            provenances.entry(prov.package_id).or_default().push(prov);
+        }
 
-        // Convert from persistence::Package to ai::Package
         let mut packages = Vec::new();
-
         for p in db_packages {
-            // Get depreciation rule for this package
-            let depreciation_rule = self.db.get_depreciation_rule_by_package_id(p.id).await?;
-
-            // Convert depreciation rule
-            let ai_depreciation_rule = depreciation_rule.map(|dr| ai::DepreciationRule {
-                package_id: dr.package_id,
-                final_depreciated_percentage: dr.final_depreciated_percentage,
-                full_depreciation_hours: dr.full_depreciation_hours as u32,
-                depreciation_curve: match dr.depreciation_curve.as_str() {
-                    "exponential" => ai::DepreciationCurve::Exponential,
-                    "stepped" => ai::DepreciationCurve::Stepped,
-                    _ => ai::DepreciationCurve::Linear,
-                },
-            });
+            let package_provenances = provenances.remove(&p.id).unwrap_or_default();
+            packages.push(self.package_from_db(p, package_provenances).await?);
+        }
 
-            // Parse availability
-            let availability = match p.availability_type.as_str() {
-                "preorder" => Availability::Preorder,
-                "in_stock" => Availability::InStock,
-                "build" => Availability::Build {
-                    hours: p.availability_value.unwrap_or(48) as u16,
-                },
-                _ => Availability::InStock,
-            };
-
-            // Get provenance options for this package
-            let package_provenances: Vec<_> = all_provenances
-                .iter()
-                .filter(|prov| prov.package_id == p.id)
-                .collect();
-
-            // Convert provenance options
-            let mut provenance_options = Vec::new();
-            for prov in package_provenances {
-                let provenance_type = match prov.provenance_type.as_str() {
-                    "new" => Provenance::New,
-                    "used" => Provenance::Used {
-                        hours: prov.usage_hours as u32,
-                    },
-                    _ => Provenance::New,
-                };
+        self.estimate_builds(&mut packages).await?;
 
-                provenance_options.push(ai::ProvenanceOption {
-                    provenance_type,
-                    quantity_available: prov.quantity_available as u32,
-                    calculated_price: prov.calculated_price_usdc.unwrap_or(p.setup_price_usdc)
-                        as u32,
-                    discount_percentage: prov.discount_percentage,
-                });
-            }
+        Ok(packages)
+    }
+
+    /// Active packages matching `query`, in its order. The query is
+    /// expected to be validated.
+    pub async fn search_packages(
+        &self,
+        query: &PackageQuery,
+        locale: Locale,
+    ) -> Result<Vec<Package>> {
+        let mut packages = query.apply(self.get_packages().await?);
+        self.localize_packages(&mut packages, locale).await?;
+        Ok(packages)
+    }
+
+    /// Compare active packages in the order of `skus`. Returns `None` if
+    /// one of them is not in the catalog.
+    pub async fn compare_packages(&self, skus: &[String]) -> Result<Option<PackageComparison>> {
+        let packages = self.get_packages().await?;
 
-            // Load package images
-            let db_images = self.db.get_package_images(p.id).await?;
-            let images = db_images
-                .into_iter()
-                .map(|img| PackageImage {
-                    filename: img.filename,
-                    title: img.title,
-                    description: img.description,
-                })
-                .collect();
-
-            // Create the package
-            let gpu_class = p.gpu_class_enum();
-            let mut package = Package {
-                id: p.id,
-                name: p.name,
-                sku: p.sku.unwrap_or_default(),
-                description: p.description,
-                hardware_description: p.hardware_description,
-                cpu_cores: p.cpu_cores as u16,
-                ram_gb: p.ram_gb as u16,
-                storage_gb: p.storage_gb as u32,
-                gpu_class,
-                gpu_count: p.gpu_count as u16,
-                vram_gb: p.vram_gb as u16,
-                setup_price_usdc: p.setup_price_usdc as u32,
-                monthly_price_usdc: p.monthly_price_usdc as u32,
-                depreciation_rule: ai_depreciation_rule,
-                images,
-                availability,
-                provenances: provenance_options,
-                min_price_usdc: None,
-                max_price_usdc: None,
-            };
-
-            // Calculate price range from all provenance options
-            package.calculate_price_range();
-
-            packages.push(package);
+        let mut compared = Vec::new();
+        for sku in skus {
+            match packages.iter().find(|p| &p.sku == sku) {
+                Some(package) => compared.push(package.clone()),
+                None => return Ok(None),
+            }
         }
 
-        Ok(packages)
+        Ok(Some(compare::compare(&compared)))
+    }
+
+    /// Active packages that can serve a model, for an already validated
+    /// request
+    pub async fn recommend_packages(&self, req: &ModelFitRequest) -> Result<Recommendation> {
+        let packages = self.get_packages().await?;
+        recommend::recommend(req, &packages).map_err(|e| anyhow!(e))
+    }
+
+    /// An active package with its text in `locale` where translated
+    pub async fn get_localized_package(
+        &self,
+        sku: &str,
+        locale: Locale,
+    ) -> Result<Option<Package>> {
+        let Some(package) = self.get_package_by_sku(sku).await? else {
+            return Ok(None);
+        };
+        let mut packages = [package];
+        self.localize_packages(&mut packages, locale).await?;
+        let [package] = packages;
+        Ok(Some(package))
     }
 
     pub async fn get_package_by_sku(&self, sku: &str) -> Result<Option<Package>> {
-        let db_package = self.db.get_package_by_sku(sku).await?;
-
-        match db_package {
-            Some(p) => {
-                // Get depreciation rule for this package
-                let depreciation_rule = self.db.get_depreciation_rule_by_package_id(p.id).await?;
-
-                // Convert depreciation rule
-                let ai_depreciation_rule = depreciation_rule.map(|dr| ai::DepreciationRule {
-                    package_id: dr.package_id,
-                    final_depreciated_percentage: dr.final_depreciated_percentage,
-                    full_depreciation_hours: dr.full_depreciation_hours as u32,
-                    depreciation_curve: match dr.depreciation_curve.as_str() {
-                        "exponential" => ai::DepreciationCurve::Exponential,
-                        "stepped" => ai::DepreciationCurve::Stepped,
-                        _ => ai::DepreciationCurve::Linear,
-                    },
-                });
-
-                // Parse availability
-                let availability = match p.availability_type.as_str() {
-                    "preorder" => Availability::Preorder,
-                    "in_stock" => Availability::InStock,
-                    "build" => Availability::Build {
-                        hours: p.availability_value.unwrap_or(48) as u16,
-                    },
-                    _ => Availability::InStock,
-                };
+        let Some(p) = self.db.get_package_by_sku(sku).await? else {
+            return Ok(None);
+        };
 
-                // Get provenance options for this package
-                let package_provenances = self.db.get_package_provenances(p.id).await?;
-
-                // Convert provenance options
-                let mut provenance_options = Vec::new();
-                for prov in package_provenances {
-                    let provenance_type = match prov.provenance_type.as_str() {
-                        "new" => Provenance::New,
-                        "used" => Provenance::Used {
-                            hours: prov.usage_hours as u32,
-                        },
-                        _ => Provenance::New,
-                    };
-
-                    provenance_options.push(ai::ProvenanceOption {
-                        provenance_type,
-                        quantity_available: prov.quantity_available as u32,
-                        calculated_price: prov.calculated_price_usdc.unwrap_or(p.setup_price_usdc)
-                            as u32,
-                        discount_percentage: prov.discount_percentage,
-                    });
-                }
+        let provenances = self.db.get_package_provenances(p.id).await?;
+        let mut package = self.package_from_db(p, provenances).await?;
+        self.estimate_builds(std::slice::from_mut(&mut package))
+            .await?;
 
-                // Load package images
-                let db_images = self.db.get_package_images(p.id).await?;
-                let images = db_images
-                    .into_iter()
-                    .map(|img| PackageImage {
-                        filename: img.filename,
-                        title: img.title,
-                        description: img.description,
-                    })
-                    .collect();
-
-                // Create the package
-                let gpu_class = p.gpu_class_enum();
-                let mut package = Package {
-                    id: p.id,
-                    name: p.name,
-                    sku: p.sku.unwrap_or_default(),
-                    description: p.description,
-                    hardware_description: p.hardware_description,
-                    cpu_cores: p.cpu_cores as u16,
-                    ram_gb: p.ram_gb as u16,
-                    storage_gb: p.storage_gb as u32,
-                    gpu_class,
-                    gpu_count: p.gpu_count as u16,
-                    vram_gb: p.vram_gb as u16,
-                    setup_price_usdc: p.setup_price_usdc as u32,
-                    monthly_price_usdc: p.monthly_price_usdc as u32,
-                    depreciation_rule: ai_depreciation_rule,
-                    images,
-                    availability,
-                    provenances: provenance_options,
-                    min_price_usdc: None,
-                    max_price_usdc: None,
+        Ok(Some(package))
+    }
+
+    /// Convert a package and its provenances for the API. Prices not yet
+    /// cached are computed from the package's rule here; reads never write
+    /// them back, that is left to `refresh_stale_prices`.
+    async fn package_from_db(
+        &self,
+        p: persistence::Package,
+        provenances: Vec<persistence::PackageProvenance>,
+    ) -> Result<Package> {
+        let depreciation_rule = self
+            .db
+            .get_depreciation_rule_by_package_id(p.id)
+            .await?
+            .map(depreciation_rule_from_db);
+
+        let availability = availability_from_db(&p.availability_type, p.availability_value);
+
+        // Prices not yet cached are computed from the same rule
+        let pricing_rule = depreciation_rule
+            .clone()
+            .unwrap_or_else(|| ai::DepreciationRule::default_for(p.id));
+
+        let provenance_options = provenances
+            .into_iter()
+            .map(|prov| {
+                let hours = prov.usage_hours as u32;
+                let provenance_type = match prov.provenance_type.as_str() {
+                    "new" => Provenance::New,
+                    "used" => Provenance::Used { hours },
+                    _ => Provenance::New,
                 };
 
-                // Calculate price range from all provenance options
-                package.calculate_price_range();
+                ai::ProvenanceOption {
+                    provenance_type,
+                    quantity_available: prov.quantity_available as u32,
+                    calculated_price: prov.calculated_price_usdc.unwrap_or_else(|| {
+                        pricing_rule.calculate_depreciated_price(p.setup_price_usdc, hours)
+                    }),
+                    discount_percentage: prov
+                        .discount_percentage
+                        .or_else(|| Some(pricing_rule.discount_percentage(hours))),
+                }
+            })
+            .collect();
 
-                Ok(Some(package))
-            }
-            None => Ok(None),
-        }
+        let images = self.package_images(p.id).await?;
+
+        let mut package = Package {
+            id: p.id,
+            name: p.name,
+            sku: p.sku.unwrap_or_default(),
+            description: p.description,
+            hardware_description: p.hardware_description,
+            cpu_cores: p.cpu_cores as u16,
+            ram_gb: p.ram_gb as u16,
+            storage_gb: p.storage_gb as u32,
+            gpu_class: p.gpu_class,
+            gpu_count: p.gpu_count as u16,
+            vram_gb: p.vram_gb as u16,
+            setup_price_usdc: p.setup_price_usdc,
+            monthly_price_usdc: p.monthly_price_usdc,
+            depreciation_rule,
+            images,
+            availability,
+            provenances: provenance_options,
+            min_price_usdc: None,
+            max_price_usdc: None,
+            build_estimate: None,
+        };
+
+        // Calculate price range from all provenance options
+        package.calculate_price_range();
+
+        Ok(package)
     }
 
     pub async fn create_order(&self, request: CreateOrderRequest) -> Result<CreateOrderResponse> {
-        // Convert GPU class to string for database
-        let gpu_string = format!("{:?}", request.plan.gpu);
+        // Checked up front for a clear error; the redemption itself is
+        // counted atomically with the order
+        let promo = match request.promo_code.as_deref() {
+            Some(code) => Some(self.redeemable_promo_code(code).await?),
+            None => None,
+        };
+
+        let package = match request.sku.as_deref() {
+            Some(sku) => Some(
+                self.db
+                    .get_package_by_sku(sku)
+                    .await?
+                    .ok_or_else(|| anyhow!("package {sku} not found"))?,
+            ),
+            None => None,
+        };
+
+        // The order keeps the price version current when it is placed
+        let package_price = match &package {
+            Some(package) => self.db.get_package_price_at(package.id, Utc::now()).await?,
+            None => None,
+        };
+
+        // Add-ons are kept as line items; storage and RAM upgrades go into
+        // the plan
+        let selected = match &package {
+            Some(package) => self.selected_addons(package.id, &request.addons).await?,
+            None if request.addons.is_empty() => Vec::new(),
+            None => return Err(anyhow!("add-ons require a catalog package (sku)")),
+        };
+        // A catalog package's own specs are the base plan the upgrades go on
+        let base_plan = match &package {
+            Some(package) => ai::Plan {
+                cpu_cores: u16::try_from(package.cpu_cores)?,
+                ram_gb: u16::try_from(package.ram_gb)?,
+                storage_gb: u32::try_from(package.storage_gb)?,
+                gpu: package.gpu_class.clone(),
+            },
+            None => request
+                .plan
+                .clone()
+                .ok_or_else(|| anyhow!("an order needs a catalog package (sku) or a plan"))?,
+        };
+        let plan = ai::addons::upgraded_plan(&base_plan, &selected).map_err(|e| anyhow!(e))?;
+
+        // The promo is priced as in a quote for this one server, which also
+        // checks the code is valid for the package
+        let promo = match (promo, &package) {
+            (Some(promo), Some(package)) => {
+                let setup_price = package_price
+                    .as_ref()
+                    .map_or(package.setup_price_usdc, |p| p.setup_price_usdc);
+                let item = PricedItem {
+                    package_id: package.id,
+                    description: package.name.clone(),
+                    quantity: 1,
+                    unit_price_usdc: setup_price,
+                    discount_percentage: None,
+                    monthly_price_usdc: package.monthly_price_usdc,
+                    addons: selected.clone(),
+                };
+                // A single server is below every volume tier
+                let quote = build_quote(&[item], &[], Some(&promo)).map_err(|e| anyhow!(e))?;
+                Some((promo.code, quote.discount_usdc))
+            }
+            (Some(_), None) => return Err(anyhow!("promo codes require a catalog package (sku)")),
+            (None, _) => None,
+        };
+        let discount = promo
+            .as_ref()
+            .map_or(Money::ZERO, |(_, discount)| *discount);
+
+        // Financing covers the setup price of the version ordered at, less
+        // the promo discount
+        let financing = match request.financing_plan_id {
+            Some(plan_id) => {
+                let price = package_price
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("financing requires a catalog package (sku)"))?;
+                Some(
+                    self.new_financing_agreement(
+                        plan_id,
+                        (price.setup_price_usdc - discount).max(Money::ZERO),
+                    )
+                    .await?,
+                )
+            }
+            None => None,
+        };
 
         // Create the order in the database
         let order = self
             .db
             .create_order(
                 self.demo_org_id,
-                request.plan.cpu_cores as i16,
-                request.plan.ram_gb as i16,
-                request.plan.storage_gb as i32,
-                gpu_string,
+                plan.cpu_cores as i16,
+                i16::try_from(plan.ram_gb)?,
+                i32::try_from(plan.storage_gb)?,
+                plan.gpu,
                 request.pq_enabled,
                 request.notes,
+                promo
+                    .as_ref()
+                    .map(|(code, discount)| (code.as_str(), *discount)),
+                package_price.as_ref(),
+                financing.as_ref(),
+                &addons::new_order_addons(&selected)?,
             )
             .await?;
 
@@ -258,36 +405,73 @@ impl InfraState {
 
     pub async fn get_orders(&self) -> Result<Vec<OrderSummary>> {
         let db_orders = self.db.get_orders_for_org(self.demo_org_id).await?;
+        let mut order_addons = self.db.get_order_addons_for_org(self.demo_org_id).await?;
 
         // Convert from persistence::ServerOrder to ai::OrderSummary
-        let orders = db_orders
-            .into_iter()
-            .map(|o| {
-                let gpu_class = match o.plan_gpu.as_str() {
-                    "None" => GpuClass::None,
-                    "L4" => GpuClass::L4,
-                    "A100_40G" => GpuClass::A100_40G,
-                    "A100_80G" => GpuClass::A100_80G,
-                    "H100_80G" => GpuClass::H100_80G,
-                    "RTX_4090" => GpuClass::RTX_4090,
-                    "RTX_5090" => GpuClass::RTX_5090,
-                    "Radeon_8060S" => GpuClass::Radeon_8060S,
-                    _ => GpuClass::None,
-                };
-
-                OrderSummary {
-                    id: o.id,
-                    plan: ai::Plan {
-                        cpu_cores: o.plan_cpu_cores as u16,
-                        ram_gb: o.plan_ram_gb as u16,
-                        storage_gb: o.plan_storage_gb as u32,
-                        gpu: gpu_class,
-                    },
-                    status: o.status,
-                }
-            })
-            .collect();
+        let mut orders = Vec::new();
+        for o in db_orders {
+            let (line_items, rest) = order_addons.into_iter().partition(|a| a.order_id == o.id);
+            order_addons = rest;
+
+            orders.push(OrderSummary {
+                id: o.id,
+                plan: ai::Plan {
+                    cpu_cores: o.plan_cpu_cores as u16,
+                    ram_gb: o.plan_ram_gb as u16,
+                    storage_gb: o.plan_storage_gb as u32,
+                    gpu: o.plan_gpu,
+                },
+                status: o.status,
+                discount_usdc: o.discount_usdc,
+                addons: line_items
+                    .into_iter()
+                    .map(addons::order_addon_from_db)
+                    .collect::<Result<_>>()?,
+            });
+        }
 
         Ok(orders)
     }
 }
+
+/// Read the dunning policy from `DUNNING_REMINDER_DAYS` (comma separated days
+/// past due) and `DUNNING_GRACE_DAYS`, falling back to the defaults
+fn dunning_policy_from_env() -> Result<DunningPolicy> {
+    let mut policy = DunningPolicy::default();
+
+    if let Ok(days) = std::env::var("DUNNING_REMINDER_DAYS") {
+        policy.reminder_days = days
+            .split(',')
+            .map(|d| d.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|e| anyhow!("invalid DUNNING_REMINDER_DAYS: {e}"))?;
+    }
+
+    if let Ok(days) = std::env::var("DUNNING_GRACE_DAYS") {
+        policy.grace_period_days = days
+            .parse()
+            .map_err(|e| anyhow!("invalid DUNNING_GRACE_DAYS: {e}"))?;
+    }
+
+    policy
+        .validate()
+        .map_err(|e| anyhow!("invalid dunning policy: {e}"))?;
+
+    Ok(policy)
+}
+
+/// Read `VAT_SUPPLIER_COUNTRY`, the EU member state invoices are issued from
+fn vat_supplier_country_from_env() -> Result<Option<String>> {
+    let Ok(country) = std::env::var("VAT_SUPPLIER_COUNTRY") else {
+        return Ok(None);
+    };
+
+    let country = country.trim().to_uppercase();
+    if !ai::vat::is_eu_member(&country) {
+        return Err(anyhow!(
+            "invalid VAT_SUPPLIER_COUNTRY: {country} is not an EU member state"
+        ));
+    }
+
+    Ok(Some(country))
+}
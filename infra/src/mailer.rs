@@ -0,0 +1,21 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use tracing::info;
+
+/// Outgoing customer notifications. Swap the implementation to deliver
+/// through a real mail provider.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<()>;
+}
+
+/// Writes messages to the log instead of delivering them
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<()> {
+        info!("Mail to {to}: {subject}\n{body}");
+        Ok(())
+    }
+}
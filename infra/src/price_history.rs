@@ -0,0 +1,97 @@
+use crate::InfraState;
+use ai::price_history::{self, PriceChangeRequest, PriceHistory, PriceVersion};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tracing::info;
+
+impl InfraState {
+    /// Every price version of a package, newest first. Returns `None` if
+    /// there is no package with the SKU.
+    pub async fn get_price_history(&self, sku: &str) -> Result<Option<PriceHistory>> {
+        let Some(package) = self.db.get_package_by_sku(sku).await? else {
+            return Ok(None);
+        };
+
+        let versions = self.db.get_package_price_versions(package.id).await?;
+        let effective: Vec<DateTime<Utc>> = versions.iter().map(|v| v.effective_from).collect();
+        let states = price_history::version_states(&effective, Utc::now());
+
+        Ok(Some(PriceHistory {
+            sku: sku.to_string(),
+            package_id: package.id,
+            versions: versions
+                .into_iter()
+                .zip(states)
+                .map(|(v, state)| PriceVersion {
+                    id: v.id,
+                    package_id: v.package_id,
+                    setup_price_usdc: v.setup_price_usdc,
+                    monthly_price_usdc: v.monthly_price_usdc,
+                    effective_from: v.effective_from,
+                    note: v.note,
+                    state,
+                    created_at: v.created_at,
+                })
+                .collect(),
+        }))
+    }
+
+    /// Add a price version, effective now or from a future date. Servers
+    /// already bought keep the version they were bought at.
+    pub async fn change_package_price(
+        &self,
+        sku: &str,
+        req: PriceChangeRequest,
+    ) -> Result<Option<PriceHistory>> {
+        let Some(package) = self.db.get_package_by_sku(sku).await? else {
+            return Ok(None);
+        };
+
+        let now = Utc::now();
+        req.validate(now).map_err(|e| anyhow!(e))?;
+        let effective_from = req.effective_from.unwrap_or(now);
+
+        let version = self
+            .db
+            .create_package_price_version(
+                package.id,
+                req.setup_price_usdc,
+                req.monthly_price_usdc,
+                effective_from,
+                req.note.trim(),
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "package_price.created",
+                json!({
+                    "package_id": package.id,
+                    "price_version_id": version.id,
+                    "old_setup_price_usdc": package.setup_price_usdc,
+                    "old_monthly_price_usdc": package.monthly_price_usdc,
+                    "setup_price_usdc": version.setup_price_usdc,
+                    "monthly_price_usdc": version.monthly_price_usdc,
+                    "effective_from": version.effective_from,
+                }),
+            )
+            .await?;
+
+        self.refresh_stale_prices().await?;
+
+        self.get_price_history(sku).await
+    }
+
+    /// List packages at the price version in effect at `now`. Returns how
+    /// many packages changed.
+    pub(crate) async fn apply_due_package_prices(&self, now: DateTime<Utc>) -> Result<usize> {
+        let changed = self.db.apply_due_package_prices(now).await?;
+        for package_id in &changed {
+            info!("Package {package_id} now listed at its new price version");
+        }
+        Ok(changed.len())
+    }
+}
@@ -0,0 +1,144 @@
+use crate::InfraState;
+use ai::{DepreciationRule, Money, PriceCheck, PriceDiscrepancy};
+use anyhow::Result;
+use chrono::Utc;
+use persistence::{ProvenancePrice, ProvenancePricingInput};
+use rust_decimal::Decimal;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+impl InfraState {
+    /// Price provenance rows whose cached price the database marked stale,
+    /// after putting price versions and depreciation rules that are due
+    /// into effect. Returns the number of rows updated.
+    pub async fn refresh_stale_prices(&self) -> Result<usize> {
+        let now = Utc::now();
+        self.apply_due_package_prices(now).await?;
+        self.apply_due_depreciation_rules(now).await?;
+
+        let inputs = self.db.get_provenance_pricing_inputs(None, true).await?;
+        if inputs.is_empty() {
+            return Ok(0);
+        }
+        self.store_prices(&inputs).await
+    }
+
+    /// Recompute every cached provenance price, or only those of one package
+    pub async fn recompute_prices(&self, package_id: Option<Uuid>) -> Result<usize> {
+        let inputs = self
+            .db
+            .get_provenance_pricing_inputs(package_id, false)
+            .await?;
+        let updated = self.store_prices(&inputs).await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "pricing.recomputed",
+                json!({ "package_id": package_id, "rows": updated }),
+            )
+            .await?;
+
+        Ok(updated)
+    }
+
+    /// Compare every cached provenance price with what the depreciation rules
+    /// give, flagging rows that disagree or are stale
+    pub async fn check_price_consistency(&self) -> Result<PriceCheck> {
+        let inputs = self.db.get_provenance_pricing_inputs(None, false).await?;
+        let rules = self.depreciation_rules_by_package().await?;
+
+        let discrepancies: Vec<PriceDiscrepancy> = inputs
+            .iter()
+            .filter_map(|input| {
+                let (price, discount) = expected_price(&rules, input);
+                let matches = input.calculated_price_usdc == Some(price)
+                    && input.discount_percentage == Some(discount);
+
+                (!matches).then_some(PriceDiscrepancy {
+                    provenance_id: input.provenance_id,
+                    package_id: input.package_id,
+                    usage_hours: input.usage_hours as u32,
+                    cached_price_usdc: input.calculated_price_usdc,
+                    expected_price_usdc: price,
+                    cached_discount_percentage: input.discount_percentage,
+                    expected_discount_percentage: discount,
+                })
+            })
+            .collect();
+
+        Ok(PriceCheck {
+            checked: inputs.len(),
+            consistent: discrepancies.is_empty(),
+            discrepancies,
+        })
+    }
+
+    async fn store_prices(&self, inputs: &[ProvenancePricingInput]) -> Result<usize> {
+        let rules = self.depreciation_rules_by_package().await?;
+        let prices: Vec<ProvenancePrice> = inputs
+            .iter()
+            .filter_map(|input| {
+                let (price, discount) = expected_price(&rules, input);
+                let unchanged = input.calculated_price_usdc == Some(price)
+                    && input.discount_percentage == Some(discount);
+
+                (!unchanged).then_some(ProvenancePrice {
+                    provenance_id: input.provenance_id,
+                    calculated_price_usdc: price,
+                    discount_percentage: discount,
+                })
+            })
+            .collect();
+
+        if !prices.is_empty() {
+            self.db.store_provenance_prices(&prices).await?;
+            info!("Recomputed {} provenance prices", prices.len());
+        }
+
+        Ok(prices.len())
+    }
+}
+
+/// Periodically put due price versions and depreciation rules into effect
+/// and cache stale prices, so catalog reads never have to
+pub fn spawn_price_refresher(infra: Arc<InfraState>, every: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            interval.tick().await;
+
+            match infra.refresh_stale_prices().await {
+                Ok(updated) if updated > 0 => info!("Price refresh cached {updated} prices"),
+                Ok(_) => {}
+                Err(e) => error!("Price refresh failed: {e}"),
+            }
+        }
+    });
+}
+
+/// The price and discount the depreciation rule gives a provenance row
+pub(crate) fn expected_price(
+    rules: &HashMap<Uuid, DepreciationRule>,
+    input: &ProvenancePricingInput,
+) -> (Money, Decimal) {
+    let default_rule;
+    let rule = match rules.get(&input.package_id) {
+        Some(rule) => rule,
+        None => {
+            default_rule = DepreciationRule::default_for(input.package_id);
+            &default_rule
+        }
+    };
+
+    let hours = input.usage_hours as u32;
+    (
+        rule.calculate_depreciated_price(input.setup_price_usdc, hours),
+        rule.discount_percentage(hours),
+    )
+}
@@ -0,0 +1,217 @@
+use crate::InfraState;
+use ai::quote::{
+    self, CreatePromoCodeRequest, PricedItem, PromoCode, PromoDiscount, Quote, QuoteRequest,
+    VolumeDiscountTier,
+};
+use ai::Provenance;
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use serde_json::json;
+
+impl InfraState {
+    /// Price packages for an order, applying volume discounts and an
+    /// optional promo code. Nothing is reserved or redeemed.
+    pub async fn quote(&self, req: QuoteRequest) -> Result<Quote> {
+        let promo = match req.promo_code.as_deref() {
+            Some(code) => Some(self.redeemable_promo_code(code).await?),
+            None => None,
+        };
+
+        let mut items = Vec::new();
+        for item in &req.items {
+            let package = self
+                .get_package_by_sku(&item.sku)
+                .await?
+                .ok_or_else(|| anyhow!("unknown package {}", item.sku))?;
+
+            let option = package
+                .provenances
+                .iter()
+                .find(|p| same_provenance(&p.provenance_type, &item.provenance))
+                .ok_or_else(|| anyhow!("{} is not offered in that condition", item.sku))?;
+            if option.quantity_available < item.quantity {
+                bail!(
+                    "only {} of {} available in that condition",
+                    option.quantity_available,
+                    item.sku
+                );
+            }
+
+            let condition = match item.provenance {
+                Provenance::New => "new".to_string(),
+                Provenance::Used { hours } => format!("used, {hours} hours"),
+            };
+
+            items.push(PricedItem {
+                package_id: package.id,
+                description: format!("{} ({condition})", package.name),
+                quantity: item.quantity,
+                unit_price_usdc: option.calculated_price,
+                discount_percentage: option.discount_percentage,
+                monthly_price_usdc: package.monthly_price_usdc,
+                addons: self.selected_addons(package.id, &item.addons).await?,
+            });
+        }
+
+        let tiers = self.get_volume_discount_tiers().await?;
+
+        quote::build_quote(&items, &tiers, promo.as_ref()).map_err(|e| anyhow!(e))
+    }
+
+    /// Look up a promo code and check it can still be redeemed
+    pub(crate) async fn redeemable_promo_code(&self, code: &str) -> Result<PromoCode> {
+        let code = PromoCode::normalize_code(code);
+        let promo = self
+            .db
+            .get_promo_code(&code)
+            .await?
+            .map(promo_code_from_db)
+            .ok_or_else(|| anyhow!("unknown promo code {code}"))?;
+
+        promo.check_redeemable(Utc::now()).map_err(|e| anyhow!(e))?;
+
+        Ok(promo)
+    }
+
+    pub async fn get_promo_codes(&self) -> Result<Vec<PromoCode>> {
+        Ok(self
+            .db
+            .get_promo_codes()
+            .await?
+            .into_iter()
+            .map(promo_code_from_db)
+            .collect())
+    }
+
+    pub async fn create_promo_code(&self, req: CreatePromoCodeRequest) -> Result<PromoCode> {
+        let promo = PromoCode {
+            code: PromoCode::normalize_code(&req.code),
+            description: req.description,
+            discount: req.discount,
+            package_ids: req.package_ids,
+            max_redemptions: req.max_redemptions,
+            redemptions: 0,
+            expires_at: req.expires_at,
+            is_active: true,
+        };
+        promo.validate().map_err(|e| anyhow!(e))?;
+
+        let (discount_type, percent_off, amount_off_usdc) = match &promo.discount {
+            PromoDiscount::Percentage { percent } => ("percentage", Some(*percent), None),
+            PromoDiscount::Fixed { amount_usdc } => ("fixed", None, Some(*amount_usdc)),
+        };
+
+        let saved = self
+            .db
+            .create_promo_code(&persistence::NewPromoCode {
+                code: promo.code.clone(),
+                description: promo.description.clone(),
+                discount_type: discount_type.to_string(),
+                percent_off,
+                amount_off_usdc,
+                max_redemptions: promo.max_redemptions.map(|m| m as i32),
+                expires_at: promo.expires_at,
+                package_ids: promo.package_ids.clone(),
+            })
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "promo_code.created",
+                json!({ "code": saved.code, "discount": promo.discount }),
+            )
+            .await?;
+
+        Ok(promo_code_from_db(saved))
+    }
+
+    /// Returns `false` if there is no such code
+    pub async fn deactivate_promo_code(&self, code: &str) -> Result<bool> {
+        let code = PromoCode::normalize_code(code);
+        let found = self.db.deactivate_promo_code(&code).await?;
+
+        if found {
+            self.db
+                .insert_audit_log(
+                    None,
+                    None,
+                    "promo_code.deactivated",
+                    json!({ "code": code }),
+                )
+                .await?;
+        }
+
+        Ok(found)
+    }
+
+    pub async fn get_volume_discount_tiers(&self) -> Result<Vec<VolumeDiscountTier>> {
+        Ok(self
+            .db
+            .get_volume_discount_tiers()
+            .await?
+            .into_iter()
+            .map(|t| VolumeDiscountTier {
+                min_quantity: t.min_quantity as u32,
+                percent: t.percent_off,
+            })
+            .collect())
+    }
+
+    pub async fn set_volume_discount_tiers(
+        &self,
+        tiers: Vec<VolumeDiscountTier>,
+    ) -> Result<Vec<VolumeDiscountTier>> {
+        quote::validate_volume_tiers(&tiers).map_err(|e| anyhow!(e))?;
+
+        let rows: Vec<persistence::VolumeDiscountTier> = tiers
+            .iter()
+            .map(|t| persistence::VolumeDiscountTier {
+                min_quantity: t.min_quantity as i32,
+                percent_off: t.percent,
+            })
+            .collect();
+        self.db.replace_volume_discount_tiers(&rows).await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "volume_discounts.updated",
+                json!({ "tiers": tiers }),
+            )
+            .await?;
+
+        self.get_volume_discount_tiers().await
+    }
+}
+
+pub(crate) fn same_provenance(a: &Provenance, b: &Provenance) -> bool {
+    match (a, b) {
+        (Provenance::New, Provenance::New) => true,
+        (Provenance::Used { hours: a }, Provenance::Used { hours: b }) => a == b,
+        _ => false,
+    }
+}
+
+fn promo_code_from_db(p: persistence::PromoCode) -> PromoCode {
+    let discount = match (p.percent_off, p.amount_off_usdc) {
+        (Some(percent), _) => PromoDiscount::Percentage { percent },
+        (None, Some(amount_usdc)) => PromoDiscount::Fixed { amount_usdc },
+        (None, None) => PromoDiscount::Fixed {
+            amount_usdc: Default::default(),
+        },
+    };
+
+    PromoCode {
+        code: p.code,
+        description: p.description,
+        discount,
+        package_ids: p.package_ids,
+        max_redemptions: p.max_redemptions.map(|m| m as u32),
+        redemptions: p.redemptions as u32,
+        expires_at: p.expires_at,
+        is_active: p.is_active,
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::path::Path as ObjectPath;
+use object_store::{Attribute, Attributes, ObjectStore, PutOptions, PutPayload};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// Where the API serves a local image directory from
+pub const LOCAL_IMAGE_ROUTE: &str = "/uploads";
+
+/// Storage for uploaded package photos and their renditions. Keys are
+/// relative, `/` separated paths.
+#[async_trait]
+pub trait ImageStore: Send + Sync {
+    async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> Result<()>;
+    async fn delete(&self, key: &str) -> Result<()>;
+    /// Public URL the object is served from
+    fn url(&self, key: &str) -> String;
+}
+
+/// Image storage configured by `IMAGE_STORAGE` (`local` or `s3`)
+#[derive(Debug, Clone)]
+pub enum ImageStorage {
+    /// Files under `dir`, served by the API at [`LOCAL_IMAGE_ROUTE`]
+    Local { dir: PathBuf, base_url: String },
+    /// An S3 bucket or S3-compatible service such as MinIO or R2.
+    /// Credentials come from the usual `AWS_*` variables.
+    S3 {
+        bucket: String,
+        region: String,
+        endpoint: Option<String>,
+        base_url: String,
+    },
+}
+
+impl ImageStorage {
+    pub fn from_env() -> Result<Self> {
+        let base_url = std::env::var("IMAGE_BASE_URL").ok();
+        match std::env::var("IMAGE_STORAGE").as_deref() {
+            Err(_) | Ok("local") => Ok(ImageStorage::Local {
+                dir: std::env::var("IMAGE_STORAGE_DIR")
+                    .unwrap_or_else(|_| "./uploads".to_string())
+                    .into(),
+                base_url: base_url.unwrap_or_else(|| LOCAL_IMAGE_ROUTE.to_string()),
+            }),
+            Ok("s3") => {
+                let bucket = std::env::var("IMAGE_S3_BUCKET")
+                    .map_err(|_| anyhow!("IMAGE_S3_BUCKET is required for S3 image storage"))?;
+                let region =
+                    std::env::var("IMAGE_S3_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+                let endpoint = std::env::var("IMAGE_S3_ENDPOINT").ok();
+                // Path-style URLs work for any S3-compatible service
+                let base_url = base_url.unwrap_or_else(|| match &endpoint {
+                    Some(endpoint) => format!("{}/{bucket}", endpoint.trim_end_matches('/')),
+                    None => format!("https://{bucket}.s3.{region}.amazonaws.com"),
+                });
+                Ok(ImageStorage::S3 {
+                    bucket,
+                    region,
+                    endpoint,
+                    base_url,
+                })
+            }
+            Ok(other) => Err(anyhow!(
+                "invalid IMAGE_STORAGE {other:?}: expected local or s3"
+            )),
+        }
+    }
+
+    pub fn store(&self) -> Result<Arc<dyn ImageStore>> {
+        Ok(match self {
+            ImageStorage::Local { dir, base_url } => Arc::new(LocalImageStore {
+                dir: dir.clone(),
+                base_url: base_url.clone(),
+            }),
+            ImageStorage::S3 {
+                bucket,
+                region,
+                endpoint,
+                base_url,
+            } => {
+                let mut builder = AmazonS3Builder::from_env()
+                    .with_bucket_name(bucket)
+                    .with_region(region);
+                if let Some(endpoint) = endpoint {
+                    builder = builder
+                        .with_endpoint(endpoint)
+                        .with_allow_http(endpoint.starts_with("http://"));
+                }
+                Arc::new(S3ImageStore {
+                    store: builder.build()?,
+                    base_url: base_url.clone(),
+                })
+            }
+        })
+    }
+}
+
+/// Keeps images in a directory on the API host
+pub struct LocalImageStore {
+    dir: PathBuf,
+    base_url: String,
+}
+
+impl LocalImageStore {
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(anyhow!("invalid storage key {key}"));
+        }
+        Ok(self.dir.join(relative))
+    }
+}
+
+#[async_trait]
+impl ImageStore for LocalImageStore {
+    async fn put(&self, key: &str, bytes: Vec<u8>, _content_type: &str) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, bytes).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{key}", self.base_url.trim_end_matches('/'))
+    }
+}
+
+/// Keeps images in an S3 bucket, publicly readable at `base_url`
+pub struct S3ImageStore {
+    store: AmazonS3,
+    base_url: String,
+}
+
+#[async_trait]
+impl ImageStore for S3ImageStore {
+    async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> Result<()> {
+        let mut attributes = Attributes::new();
+        attributes.insert(Attribute::ContentType, content_type.to_string().into());
+        self.store
+            .put_opts(
+                &ObjectPath::from(key),
+                PutPayload::from(bytes),
+                PutOptions {
+                    attributes,
+                    ..Default::default()
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.store.delete(&ObjectPath::from(key)).await?;
+        Ok(())
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{key}", self.base_url.trim_end_matches('/'))
+    }
+}
@@ -0,0 +1,38 @@
+use crate::quote::same_provenance;
+use crate::InfraState;
+use ai::tco::{self, TcoReport, TcoRequest};
+use anyhow::{anyhow, Result};
+
+impl InfraState {
+    /// Total cost of owning a package over a term, against renting cloud
+    /// capacity at the request's or the configured hourly rate
+    pub async fn tco(&self, req: TcoRequest) -> Result<TcoReport> {
+        let package = self
+            .get_package_by_sku(&req.sku)
+            .await?
+            .ok_or_else(|| anyhow!("unknown package {}", req.sku))?;
+
+        let option = package
+            .provenances
+            .iter()
+            .find(|p| same_provenance(&p.provenance_type, &req.provenance))
+            .ok_or_else(|| anyhow!("{} is not offered in that condition", req.sku))?;
+
+        let cloud_rate = req
+            .cloud_hourly_rate_usdc
+            .or(self.tco_cloud_hourly_rate)
+            .ok_or_else(|| {
+                anyhow!(
+                    "cloud_hourly_rate_usdc is required when TCO_CLOUD_HOURLY_RATE_USDC is not set"
+                )
+            })?;
+
+        tco::calculate_tco(
+            &req,
+            option.calculated_price,
+            package.monthly_price_usdc,
+            cloud_rate,
+        )
+        .map_err(|e| anyhow!(e))
+    }
+}
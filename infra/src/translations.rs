@@ -0,0 +1,190 @@
+use crate::InfraState;
+use ai::locale::{self, ImageTranslation, Locale, PackageTranslation, TranslationRequest};
+use ai::Package;
+use anyhow::{anyhow, Result};
+use persistence::{NewImageTranslation, NewPackageTranslation};
+use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+impl InfraState {
+    /// Put packages' text into `locale` where it has been translated
+    pub(crate) async fn localize_packages(
+        &self,
+        packages: &mut [Package],
+        locale: Locale,
+    ) -> Result<()> {
+        if locale.is_default() {
+            return Ok(());
+        }
+
+        let (package_rows, image_rows) = self.db.get_translations(locale.as_str()).await?;
+        let translations = translations_from_db(package_rows, image_rows)?;
+
+        for package in packages {
+            if let Some(translation) = translations.get(&(package.id, locale)) {
+                locale::localize(package, translation);
+            }
+        }
+        Ok(())
+    }
+
+    /// A package's translations into every locale; `None` if there is no
+    /// such package
+    pub async fn get_package_translations(
+        &self,
+        sku: &str,
+    ) -> Result<Option<Vec<PackageTranslation>>> {
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+
+        let (package_rows, image_rows) = self.db.get_package_translations(package.id).await?;
+        let mut translations: Vec<PackageTranslation> =
+            translations_from_db(package_rows, image_rows)?
+                .into_values()
+                .collect();
+        translations.sort_by_key(|t| t.locale.as_str());
+
+        Ok(Some(translations))
+    }
+
+    /// Replace a package's translation into `locale`. Returns `None` if
+    /// there is no such package.
+    pub async fn save_package_translation(
+        &self,
+        sku: &str,
+        locale: Locale,
+        req: TranslationRequest,
+    ) -> Result<Option<PackageTranslation>> {
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(None);
+        };
+        let image_ids: Vec<Uuid> = self
+            .db
+            .get_catalog_images(package.id)
+            .await?
+            .iter()
+            .map(|i| i.id)
+            .collect();
+        req.validate(locale, &image_ids).map_err(|e| anyhow!(e))?;
+
+        let trimmed = |text: &Option<String>| text.as_deref().map(|t| t.trim().to_string());
+        let translation = PackageTranslation {
+            locale,
+            name: trimmed(&req.name),
+            description: trimmed(&req.description),
+            hardware_description: trimmed(&req.hardware_description),
+            images: req
+                .images
+                .iter()
+                .map(|i| ImageTranslation {
+                    image_id: i.image_id,
+                    title: trimmed(&i.title),
+                    description: trimmed(&i.description),
+                })
+                .collect(),
+        };
+
+        self.db
+            .save_package_translation(
+                package.id,
+                locale.as_str(),
+                &NewPackageTranslation {
+                    name: translation.name.clone(),
+                    description: translation.description.clone(),
+                    hardware_description: translation.hardware_description.clone(),
+                    images: translation
+                        .images
+                        .iter()
+                        .map(|i| NewImageTranslation {
+                            image_id: i.image_id,
+                            title: i.title.clone(),
+                            description: i.description.clone(),
+                        })
+                        .collect(),
+                },
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.translation_saved",
+                json!({ "package_id": package.id, "sku": sku, "translation": translation }),
+            )
+            .await?;
+
+        Ok(Some(translation))
+    }
+
+    /// Returns false if the package does not exist or has no translation
+    /// into `locale`
+    pub async fn delete_package_translation(&self, sku: &str, locale: Locale) -> Result<bool> {
+        let Some(package) = self.db.get_catalog_package(sku).await? else {
+            return Ok(false);
+        };
+        if !self
+            .db
+            .delete_package_translation(package.id, locale.as_str())
+            .await?
+        {
+            return Ok(false);
+        }
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "catalog.translation_removed",
+                json!({ "package_id": package.id, "sku": sku, "locale": locale }),
+            )
+            .await?;
+
+        Ok(true)
+    }
+}
+
+/// Group translation rows by package and locale. Image titles may be
+/// translated for a package whose own text is not.
+fn translations_from_db(
+    package_rows: Vec<persistence::PackageTranslation>,
+    image_rows: Vec<persistence::ImageTranslation>,
+) -> Result<HashMap<(Uuid, Locale), PackageTranslation>> {
+    let mut translations = HashMap::new();
+    for row in package_rows {
+        let translation = translation_entry(&mut translations, row.package_id, &row.locale)?;
+        translation.name = row.name;
+        translation.description = row.description;
+        translation.hardware_description = row.hardware_description;
+    }
+    for row in image_rows {
+        translation_entry(&mut translations, row.package_id, &row.locale)?
+            .images
+            .push(ImageTranslation {
+                image_id: row.image_id,
+                title: row.title,
+                description: row.description,
+            });
+    }
+
+    Ok(translations)
+}
+
+fn translation_entry<'a>(
+    translations: &'a mut HashMap<(Uuid, Locale), PackageTranslation>,
+    package_id: Uuid,
+    locale: &str,
+) -> Result<&'a mut PackageTranslation> {
+    let locale = Locale::from_tag(locale).ok_or_else(|| anyhow!("unknown locale {locale}"))?;
+    Ok(translations
+        .entry((package_id, locale))
+        .or_insert_with(|| PackageTranslation {
+            locale,
+            name: None,
+            description: None,
+            hardware_description: None,
+            images: Vec::new(),
+        }))
+}
@@ -0,0 +1,179 @@
+use crate::InfraState;
+use ai::vat::{self, BillingProfile, BillingProfileRequest, VatAssessment, VatRate};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde_json::json;
+use uuid::Uuid;
+
+/// Checks whether a VAT ID is registered. Swap the implementation to query
+/// VIES or a national registry.
+#[async_trait]
+pub trait VatIdValidator: Send + Sync {
+    /// `vat_id` is normalized and starts with the member state prefix
+    async fn is_valid(&self, country_code: &str, vat_id: &str) -> Result<bool>;
+}
+
+/// Accepts every VAT ID that is well formed for its country, without
+/// contacting any registry. Used by default and in tests.
+pub struct OfflineVatIdValidator;
+
+#[async_trait]
+impl VatIdValidator for OfflineVatIdValidator {
+    async fn is_valid(&self, country_code: &str, vat_id: &str) -> Result<bool> {
+        Ok(vat::check_vat_id_format(country_code, vat_id).is_ok())
+    }
+}
+
+impl InfraState {
+    pub async fn get_org_billing_profile(&self, org_id: Uuid) -> Result<Option<BillingProfile>> {
+        Ok(self
+            .db
+            .get_billing_profile(org_id)
+            .await?
+            .map(|p| BillingProfile {
+                org_id: p.org_id,
+                legal_name: p.legal_name,
+                address: p.address,
+                country_code: p.country_code,
+                vat_id: p.vat_id,
+                vat_id_valid: p.vat_id_valid,
+            }))
+    }
+
+    pub async fn get_billing_profile(&self) -> Result<Option<BillingProfile>> {
+        self.get_org_billing_profile(self.demo_org_id).await
+    }
+
+    /// Save an organization's billing profile. A VAT ID is validated when
+    /// saved; one that fails validation is kept but the organization is
+    /// invoiced as a consumer until it is corrected.
+    pub async fn update_org_billing_profile(
+        &self,
+        org_id: Uuid,
+        req: BillingProfileRequest,
+    ) -> Result<BillingProfile> {
+        let country_code = req.country_code.trim().to_uppercase();
+        if country_code.len() != 2 || !country_code.chars().all(|c| c.is_ascii_uppercase()) {
+            bail!("country_code must be an ISO 3166-1 alpha-2 code");
+        }
+        if req.legal_name.trim().is_empty() {
+            bail!("legal_name is required");
+        }
+
+        let vat_id = req
+            .vat_id
+            .as_deref()
+            .map(vat::normalize_vat_id)
+            .filter(|id| !id.is_empty());
+
+        let vat_id_valid = match &vat_id {
+            Some(id) => {
+                if !vat::is_eu_member(&country_code) {
+                    bail!("VAT IDs are only recorded for customers in the EU");
+                }
+                vat::check_vat_id_format(&country_code, id).map_err(|e| anyhow!(e))?;
+                self.vat_validator.is_valid(&country_code, id).await?
+            }
+            None => false,
+        };
+
+        self.db
+            .upsert_billing_profile(
+                org_id,
+                req.legal_name.trim(),
+                req.address.trim(),
+                &country_code,
+                vat_id.as_deref(),
+                vat_id_valid,
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                Some(org_id),
+                None,
+                "billing_profile.updated",
+                json!({
+                    "country_code": country_code,
+                    "vat_id": vat_id,
+                    "vat_id_valid": vat_id_valid,
+                }),
+            )
+            .await?;
+
+        self.get_org_billing_profile(org_id)
+            .await?
+            .ok_or_else(|| anyhow!("billing profile for {org_id} missing after update"))
+    }
+
+    pub async fn update_billing_profile(
+        &self,
+        req: BillingProfileRequest,
+    ) -> Result<BillingProfile> {
+        self.update_org_billing_profile(self.demo_org_id, req).await
+    }
+
+    pub async fn get_vat_rates(&self) -> Result<Vec<VatRate>> {
+        Ok(self
+            .db
+            .get_vat_rates()
+            .await?
+            .into_iter()
+            .map(|r| VatRate {
+                country_code: r.country_code,
+                rate_percent: r.rate_percent,
+                valid_from: r.valid_from,
+                valid_to: r.valid_to,
+            })
+            .collect())
+    }
+
+    /// Configure the VAT rate of a country from a given date
+    pub async fn add_vat_rate(&self, rate: VatRate) -> Result<VatRate> {
+        rate.validate().map_err(|e| anyhow!(e))?;
+
+        let saved = self
+            .db
+            .insert_vat_rate(
+                &rate.country_code,
+                rate.rate_percent,
+                rate.valid_from,
+                rate.valid_to,
+            )
+            .await?;
+
+        self.db
+            .insert_audit_log(
+                None,
+                None,
+                "vat_rate.added",
+                json!({
+                    "country_code": saved.country_code,
+                    "rate_percent": saved.rate_percent,
+                    "valid_from": saved.valid_from,
+                    "valid_to": saved.valid_to,
+                }),
+            )
+            .await?;
+
+        Ok(VatRate {
+            country_code: saved.country_code,
+            rate_percent: saved.rate_percent,
+            valid_from: saved.valid_from,
+            valid_to: saved.valid_to,
+        })
+    }
+
+    /// How VAT applies to an organization's invoices on `date`
+    pub async fn assess_vat(&self, org_id: Uuid, date: NaiveDate) -> Result<VatAssessment> {
+        let Some(supplier_country) = self.vat_supplier_country.as_deref() else {
+            bail!("VAT_SUPPLIER_COUNTRY must be set to the country invoices are issued from");
+        };
+
+        let profile = self.get_org_billing_profile(org_id).await?;
+        let rates = self.get_vat_rates().await?;
+
+        vat::assess(supplier_country, profile.as_ref(), &rates, date).map_err(|e| anyhow!(e))
+    }
+}
@@ -0,0 +1,51 @@
+-- Migration: Add recurring monthly billing
+-- Servers are linked to the package they were built from so the monthly
+-- hosting fee can be invoiced and prorated by activation/decommission dates
+
+ALTER TABLE servers ADD COLUMN IF NOT EXISTS package_id UUID REFERENCES packages(id);
+ALTER TABLE servers ADD COLUMN IF NOT EXISTS activated_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE servers ADD COLUMN IF NOT EXISTS decommissioned_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_servers_activated_at ON servers(activated_at);
+
+CREATE TABLE IF NOT EXISTS invoices (
+    id UUID PRIMARY KEY,
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    period_start DATE NOT NULL,
+    period_end DATE NOT NULL CHECK (period_end > period_start),
+    -- Amounts are in micro-USDC (6 decimals) so prorations are exact
+    total_micro_usdc BIGINT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'paid', 'void')),
+    issued_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    due_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    paid_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_invoices_org_id ON invoices(org_id, period_start);
+CREATE INDEX idx_invoices_status ON invoices(status);
+
+CREATE TABLE IF NOT EXISTS invoice_lines (
+    id BIGSERIAL PRIMARY KEY,
+    invoice_id UUID NOT NULL REFERENCES invoices(id) ON DELETE CASCADE,
+    server_id UUID REFERENCES servers(id),
+    kind VARCHAR(40) NOT NULL DEFAULT 'hosting',
+    description TEXT NOT NULL,
+    -- The billing period this line belongs to
+    period_start DATE NOT NULL,
+    -- The days actually billed within the period (end exclusive)
+    service_start DATE NOT NULL,
+    service_end DATE NOT NULL CHECK (service_end > service_start),
+    billed_days SMALLINT NOT NULL,
+    period_days SMALLINT NOT NULL,
+    amount_micro_usdc BIGINT NOT NULL,
+    -- A server is billed at most once per kind per period, which makes
+    -- re-running the billing engine safe
+    UNIQUE(server_id, period_start, kind)
+);
+
+CREATE INDEX idx_invoice_lines_invoice_id ON invoice_lines(invoice_id);
+
+COMMENT ON TABLE invoices IS 'Monthly invoices generated by the billing engine, one per organization per run';
+COMMENT ON TABLE invoice_lines IS 'Invoice line items; hosting lines are prorated by server activation and decommission dates';
+COMMENT ON COLUMN servers.activated_at IS 'When the server was handed over to the customer and started accruing hosting fees';
+COMMENT ON COLUMN servers.decommissioned_at IS 'When the server stops accruing hosting fees (may be scheduled in the future)';
@@ -0,0 +1,17 @@
+-- Migration: Add dunning for unpaid invoices
+-- Tracks reminders sent per invoice and suspends the servers on an invoice
+-- once the grace period has passed; paying the invoice reinstates them
+
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS reminders_sent SMALLINT NOT NULL DEFAULT 0;
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS last_reminder_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS suspended_at TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE servers ADD COLUMN IF NOT EXISTS suspended_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_invoices_open_due ON invoices(due_at) WHERE status = 'open';
+CREATE INDEX IF NOT EXISTS idx_invoice_lines_server_id ON invoice_lines(server_id);
+CREATE INDEX IF NOT EXISTS idx_audit_log_org_id ON audit_log(org_id, at);
+
+COMMENT ON COLUMN invoices.reminders_sent IS 'Number of payment reminders sent under the dunning policy';
+COMMENT ON COLUMN invoices.suspended_at IS 'When the servers on this invoice were suspended for non-payment';
+COMMENT ON COLUMN servers.suspended_at IS 'When the server was suspended for non-payment (status = suspended)';
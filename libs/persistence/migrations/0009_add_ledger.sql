@@ -0,0 +1,121 @@
+-- Migration: Add double-entry ledger
+-- Invoices, payments, credits and refunds post balanced journal entries so
+-- an organization's balance can be derived exactly. Amounts are integer
+-- minor units (micro-USDC).
+
+CREATE TABLE IF NOT EXISTS ledger_accounts (
+    id SERIAL PRIMARY KEY,
+    code VARCHAR(60) NOT NULL,
+    -- NULL for system accounts, set for per-organization accounts
+    org_id UUID REFERENCES organizations(id) ON DELETE RESTRICT,
+    kind VARCHAR(20) NOT NULL CHECK (kind IN ('asset', 'liability', 'equity', 'revenue', 'expense')),
+    name TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX idx_ledger_accounts_system_code ON ledger_accounts(code) WHERE org_id IS NULL;
+CREATE UNIQUE INDEX idx_ledger_accounts_org_code ON ledger_accounts(code, org_id) WHERE org_id IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS journal_entries (
+    id UUID PRIMARY KEY,
+    description TEXT NOT NULL,
+    -- The business event that produced this entry; each is posted once
+    source_type VARCHAR(40) NOT NULL,
+    source_id UUID NOT NULL,
+    posted_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(source_type, source_id)
+);
+
+CREATE TABLE IF NOT EXISTS journal_lines (
+    id BIGSERIAL PRIMARY KEY,
+    entry_id UUID NOT NULL REFERENCES journal_entries(id) ON DELETE RESTRICT,
+    account_id INTEGER NOT NULL REFERENCES ledger_accounts(id) ON DELETE RESTRICT,
+    debit_micro_usdc BIGINT NOT NULL DEFAULT 0 CHECK (debit_micro_usdc >= 0),
+    credit_micro_usdc BIGINT NOT NULL DEFAULT 0 CHECK (credit_micro_usdc >= 0),
+    -- Each line is either a debit or a credit
+    CHECK ((debit_micro_usdc = 0) <> (credit_micro_usdc = 0))
+);
+
+CREATE INDEX idx_journal_lines_entry_id ON journal_lines(entry_id);
+CREATE INDEX idx_journal_lines_account_id ON journal_lines(account_id);
+
+-- Enforce the double-entry invariant at commit time, after all lines of an
+-- entry have been written
+CREATE OR REPLACE FUNCTION check_journal_entry_balanced()
+RETURNS TRIGGER AS $$
+DECLARE
+    v_debits BIGINT;
+    v_credits BIGINT;
+BEGIN
+    SELECT COALESCE(SUM(debit_micro_usdc), 0), COALESCE(SUM(credit_micro_usdc), 0)
+    INTO v_debits, v_credits
+    FROM journal_lines
+    WHERE entry_id = NEW.entry_id;
+
+    IF v_debits <> v_credits THEN
+        RAISE EXCEPTION 'journal entry % is unbalanced: debits % <> credits %',
+            NEW.entry_id, v_debits, v_credits;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE CONSTRAINT TRIGGER trigger_journal_entry_balanced
+    AFTER INSERT OR UPDATE ON journal_lines
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW
+    EXECUTE FUNCTION check_journal_entry_balanced();
+
+-- System accounts
+INSERT INTO ledger_accounts (code, org_id, kind, name) VALUES
+    ('cash:usdc', NULL, 'asset', 'USDC holdings'),
+    ('revenue:hosting', NULL, 'revenue', 'Hosting revenue'),
+    ('revenue:adjustments', NULL, 'revenue', 'Credits and adjustments')
+ON CONFLICT DO NOTHING;
+
+-- Backfill: receivable accounts and entries for invoices issued before the ledger existed
+INSERT INTO ledger_accounts (code, org_id, kind, name)
+SELECT DISTINCT 'receivable', org_id, 'asset', 'Accounts receivable'
+FROM invoices
+ON CONFLICT DO NOTHING;
+
+INSERT INTO journal_entries (id, description, source_type, source_id, posted_at)
+SELECT gen_random_uuid(), 'Invoice ' || id || ' issued', 'invoice', id, issued_at
+FROM invoices
+WHERE total_micro_usdc > 0;
+
+INSERT INTO journal_lines (entry_id, account_id, debit_micro_usdc, credit_micro_usdc)
+SELECT je.id, la.id, i.total_micro_usdc, 0
+FROM journal_entries je
+JOIN invoices i ON i.id = je.source_id
+JOIN ledger_accounts la ON la.code = 'receivable' AND la.org_id = i.org_id
+WHERE je.source_type = 'invoice'
+UNION ALL
+SELECT je.id, la.id, 0, i.total_micro_usdc
+FROM journal_entries je
+JOIN invoices i ON i.id = je.source_id
+JOIN ledger_accounts la ON la.code = 'revenue:hosting' AND la.org_id IS NULL
+WHERE je.source_type = 'invoice';
+
+INSERT INTO journal_entries (id, description, source_type, source_id, posted_at)
+SELECT gen_random_uuid(), 'Payment for invoice ' || id, 'invoice_payment', id, paid_at
+FROM invoices
+WHERE status = 'paid' AND total_micro_usdc > 0;
+
+INSERT INTO journal_lines (entry_id, account_id, debit_micro_usdc, credit_micro_usdc)
+SELECT je.id, la.id, i.total_micro_usdc, 0
+FROM journal_entries je
+JOIN invoices i ON i.id = je.source_id
+JOIN ledger_accounts la ON la.code = 'cash:usdc' AND la.org_id IS NULL
+WHERE je.source_type = 'invoice_payment'
+UNION ALL
+SELECT je.id, la.id, 0, i.total_micro_usdc
+FROM journal_entries je
+JOIN invoices i ON i.id = je.source_id
+JOIN ledger_accounts la ON la.code = 'receivable' AND la.org_id = i.org_id
+WHERE je.source_type = 'invoice_payment';
+
+COMMENT ON TABLE ledger_accounts IS 'Chart of accounts; system accounts have no org_id, customer accounts are per organization';
+COMMENT ON TABLE journal_entries IS 'Balanced journal entries, one per business event (invoice, payment, credit, refund)';
+COMMENT ON TABLE journal_lines IS 'Debit or credit lines of a journal entry in micro-USDC; each entry must balance';
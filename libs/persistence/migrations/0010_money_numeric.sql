@@ -0,0 +1,116 @@
+-- Migration: Store all money as exact NUMERIC(20,6)
+-- USDC has 6 decimals. Package prices were whole-USDC integers and invoice
+-- and ledger amounts were integer micro-USDC; both now use the same exact
+-- decimal representation as the Rust `Money` type.
+
+-- The view selects packages.*, so it has to be dropped while the column
+-- types change
+DROP VIEW IF EXISTS package_with_provenance;
+
+ALTER TABLE packages
+    ALTER COLUMN setup_price_usdc TYPE NUMERIC(20,6),
+    ALTER COLUMN monthly_price_usdc TYPE NUMERIC(20,6);
+
+ALTER TABLE package_provenance
+    ALTER COLUMN calculated_price_usdc TYPE NUMERIC(20,6);
+
+ALTER TABLE invoices RENAME COLUMN total_micro_usdc TO total_usdc;
+ALTER TABLE invoices
+    ALTER COLUMN total_usdc TYPE NUMERIC(20,6) USING total_usdc / 1000000.0;
+
+ALTER TABLE invoice_lines RENAME COLUMN amount_micro_usdc TO amount_usdc;
+ALTER TABLE invoice_lines
+    ALTER COLUMN amount_usdc TYPE NUMERIC(20,6) USING amount_usdc / 1000000.0;
+
+ALTER TABLE journal_lines RENAME COLUMN debit_micro_usdc TO debit_usdc;
+ALTER TABLE journal_lines RENAME COLUMN credit_micro_usdc TO credit_usdc;
+ALTER TABLE journal_lines
+    ALTER COLUMN debit_usdc TYPE NUMERIC(20,6) USING debit_usdc / 1000000.0,
+    ALTER COLUMN credit_usdc TYPE NUMERIC(20,6) USING credit_usdc / 1000000.0;
+
+CREATE OR REPLACE FUNCTION check_journal_entry_balanced()
+RETURNS TRIGGER AS $$
+DECLARE
+    v_debits NUMERIC(20,6);
+    v_credits NUMERIC(20,6);
+BEGIN
+    SELECT COALESCE(SUM(debit_usdc), 0), COALESCE(SUM(credit_usdc), 0)
+    INTO v_debits, v_credits
+    FROM journal_lines
+    WHERE entry_id = NEW.entry_id;
+
+    IF v_debits <> v_credits THEN
+        RAISE EXCEPTION 'journal entry % is unbalanced: debits % <> credits %',
+            NEW.entry_id, v_debits, v_credits;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- The cached provenance price no longer truncates to whole USDC
+DROP FUNCTION IF EXISTS calculate_depreciated_price(UUID, INTEGER);
+
+CREATE FUNCTION calculate_depreciated_price(
+    p_package_id UUID,
+    p_usage_hours INTEGER
+) RETURNS TABLE (
+    calculated_price NUMERIC(20,6),
+    discount_pct DECIMAL(5,2)
+) AS $$
+DECLARE
+    v_original_price NUMERIC(20,6);
+    v_final_pct DECIMAL(5,2);
+    v_full_hours INTEGER;
+    v_remaining_pct NUMERIC;
+BEGIN
+    SELECT setup_price_usdc INTO v_original_price
+    FROM packages
+    WHERE id = p_package_id;
+
+    SELECT final_depreciated_percentage, full_depreciation_hours
+    INTO v_final_pct, v_full_hours
+    FROM package_depreciation_rules
+    WHERE package_id = p_package_id;
+
+    IF v_final_pct IS NULL THEN
+        v_final_pct := 25.00;
+        v_full_hours := 26280; -- 3 years
+    END IF;
+
+    IF p_usage_hours = 0 THEN
+        v_remaining_pct := 100;
+    ELSIF p_usage_hours >= v_full_hours THEN
+        v_remaining_pct := v_final_pct;
+    ELSE
+        -- Linear depreciation calculation
+        v_remaining_pct := 100 - (100 - v_final_pct) * p_usage_hours::NUMERIC / v_full_hours;
+    END IF;
+
+    RETURN QUERY SELECT
+        ROUND(v_original_price * v_remaining_pct / 100, 6)::NUMERIC(20,6),
+        ROUND(100 - v_remaining_pct, 2)::DECIMAL(5,2);
+END;
+$$ LANGUAGE plpgsql;
+
+-- Refresh cached prices with full precision (the trigger recomputes them)
+UPDATE package_provenance SET usage_hours = usage_hours;
+
+CREATE OR REPLACE VIEW package_with_provenance AS
+SELECT
+    p.*,
+    pp.id as provenance_id,
+    pp.provenance_type,
+    pp.usage_hours,
+    pp.quantity_available,
+    pp.calculated_price_usdc,
+    pp.discount_percentage,
+    pp.is_active as provenance_active
+FROM packages p
+LEFT JOIN package_provenance pp ON p.id = pp.package_id
+WHERE p.is_active = true AND pp.is_active = true
+ORDER BY p.setup_price_usdc, pp.usage_hours;
+
+COMMENT ON COLUMN packages.setup_price_usdc IS 'Hardware and setup price in USDC (6 decimals)';
+COMMENT ON COLUMN packages.monthly_price_usdc IS 'Monthly hosting price in USDC (6 decimals)';
+COMMENT ON COLUMN invoices.total_usdc IS 'Invoice total in USDC (6 decimals)';
@@ -0,0 +1,85 @@
+-- Migration: EU VAT
+-- Organizations get a billing profile (country and VAT ID) that decides how
+-- VAT applies to their invoices, and VAT rates are configured per country
+-- with the date range they are in force.
+
+CREATE TABLE IF NOT EXISTS org_billing_profiles (
+    org_id UUID PRIMARY KEY REFERENCES organizations(id) ON DELETE CASCADE,
+    legal_name VARCHAR(255) NOT NULL,
+    address TEXT NOT NULL DEFAULT '',
+    country_code CHAR(2) NOT NULL CHECK (country_code ~ '^[A-Z]{2}$'),
+    vat_id VARCHAR(20),
+    -- Result of validating vat_id when it was last saved
+    vat_id_valid BOOLEAN NOT NULL DEFAULT false,
+    vat_id_checked_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS vat_rates (
+    id SERIAL PRIMARY KEY,
+    country_code CHAR(2) NOT NULL CHECK (country_code ~ '^[A-Z]{2}$'),
+    rate_percent NUMERIC(5,2) NOT NULL CHECK (rate_percent >= 0 AND rate_percent < 100),
+    valid_from DATE NOT NULL,
+    -- Exclusive; NULL while the rate is current
+    valid_to DATE CHECK (valid_to > valid_from),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(country_code, valid_from)
+);
+
+CREATE INDEX idx_vat_rates_country ON vat_rates(country_code, valid_from);
+
+-- Standard rates of the member states
+INSERT INTO vat_rates (country_code, rate_percent, valid_from, valid_to) VALUES
+    ('AT', 20.00, '2024-01-01', NULL),
+    ('BE', 21.00, '2024-01-01', NULL),
+    ('BG', 20.00, '2024-01-01', NULL),
+    ('CY', 19.00, '2024-01-01', NULL),
+    ('CZ', 21.00, '2024-01-01', NULL),
+    ('DE', 19.00, '2024-01-01', NULL),
+    ('DK', 25.00, '2024-01-01', NULL),
+    ('EE', 22.00, '2024-01-01', '2025-07-01'),
+    ('EE', 24.00, '2025-07-01', NULL),
+    ('ES', 21.00, '2024-01-01', NULL),
+    ('FI', 24.00, '2024-01-01', '2024-09-01'),
+    ('FI', 25.50, '2024-09-01', NULL),
+    ('FR', 20.00, '2024-01-01', NULL),
+    ('GR', 24.00, '2024-01-01', NULL),
+    ('HR', 25.00, '2024-01-01', NULL),
+    ('HU', 27.00, '2024-01-01', NULL),
+    ('IE', 23.00, '2024-01-01', NULL),
+    ('IT', 22.00, '2024-01-01', NULL),
+    ('LT', 21.00, '2024-01-01', NULL),
+    ('LU', 17.00, '2024-01-01', NULL),
+    ('LV', 21.00, '2024-01-01', NULL),
+    ('MT', 18.00, '2024-01-01', NULL),
+    ('NL', 21.00, '2024-01-01', NULL),
+    ('PL', 23.00, '2024-01-01', NULL),
+    ('PT', 23.00, '2024-01-01', NULL),
+    ('RO', 19.00, '2024-01-01', '2025-08-01'),
+    ('RO', 21.00, '2025-08-01', NULL),
+    ('SE', 25.00, '2024-01-01', NULL),
+    ('SI', 22.00, '2024-01-01', NULL),
+    ('SK', 20.00, '2024-01-01', '2025-01-01'),
+    ('SK', 23.00, '2025-01-01', NULL)
+ON CONFLICT DO NOTHING;
+
+-- Invoices record the VAT decision they were issued with
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS subtotal_usdc NUMERIC(20,6);
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS vat_usdc NUMERIC(20,6) NOT NULL DEFAULT 0;
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS vat_rate_percent NUMERIC(5,2) NOT NULL DEFAULT 0;
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS vat_treatment VARCHAR(20)
+    CHECK (vat_treatment IN ('standard', 'reverse_charge', 'export'));
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS vat_country CHAR(2);
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS customer_vat_id VARCHAR(20);
+
+UPDATE invoices SET subtotal_usdc = total_usdc WHERE subtotal_usdc IS NULL;
+ALTER TABLE invoices ALTER COLUMN subtotal_usdc SET NOT NULL;
+
+INSERT INTO ledger_accounts (code, org_id, kind, name) VALUES
+    ('liability:vat', NULL, 'liability', 'VAT payable')
+ON CONFLICT DO NOTHING;
+
+COMMENT ON TABLE org_billing_profiles IS 'Legal entity, country and VAT ID an organization is invoiced as';
+COMMENT ON TABLE vat_rates IS 'Standard VAT rate per country and the dates it is in force';
+COMMENT ON COLUMN invoices.vat_treatment IS 'standard, reverse_charge or export; NULL for invoices issued before VAT';
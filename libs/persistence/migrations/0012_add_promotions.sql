@@ -0,0 +1,54 @@
+-- Migration: Promo codes and volume discounts
+-- Both are applied to the one-time hardware and setup price when quoting and
+-- show up as their own negative line items.
+
+CREATE TABLE IF NOT EXISTS promo_codes (
+    id SERIAL PRIMARY KEY,
+    -- Stored uppercase; codes are matched case-insensitively
+    code VARCHAR(40) NOT NULL UNIQUE CHECK (code = UPPER(code)),
+    description TEXT NOT NULL DEFAULT '',
+    discount_type VARCHAR(20) NOT NULL CHECK (discount_type IN ('percentage', 'fixed')),
+    percent_off NUMERIC(5,2) CHECK (percent_off > 0 AND percent_off <= 100),
+    amount_off_usdc NUMERIC(20,6) CHECK (amount_off_usdc > 0),
+    max_redemptions INTEGER CHECK (max_redemptions > 0),
+    redemptions INTEGER NOT NULL DEFAULT 0,
+    expires_at TIMESTAMP WITH TIME ZONE,
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CHECK (
+        (discount_type = 'percentage' AND percent_off IS NOT NULL AND amount_off_usdc IS NULL)
+        OR (discount_type = 'fixed' AND amount_off_usdc IS NOT NULL AND percent_off IS NULL)
+    ),
+    CHECK (max_redemptions IS NULL OR redemptions <= max_redemptions)
+);
+
+-- Packages a promo code is restricted to; none means every package
+CREATE TABLE IF NOT EXISTS promo_code_packages (
+    promo_code_id INTEGER NOT NULL REFERENCES promo_codes(id) ON DELETE CASCADE,
+    package_id UUID NOT NULL REFERENCES packages(id) ON DELETE CASCADE,
+    PRIMARY KEY (promo_code_id, package_id)
+);
+
+CREATE TABLE IF NOT EXISTS promo_redemptions (
+    id BIGSERIAL PRIMARY KEY,
+    promo_code_id INTEGER NOT NULL REFERENCES promo_codes(id),
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    order_id UUID REFERENCES server_orders(id) ON DELETE SET NULL,
+    redeemed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_promo_redemptions_code ON promo_redemptions(promo_code_id);
+
+CREATE TABLE IF NOT EXISTS volume_discount_tiers (
+    min_quantity INTEGER PRIMARY KEY CHECK (min_quantity >= 2),
+    percent_off NUMERIC(5,2) NOT NULL CHECK (percent_off > 0 AND percent_off < 100)
+);
+
+INSERT INTO volume_discount_tiers (min_quantity, percent_off) VALUES
+    (3, 3.00),
+    (5, 5.00),
+    (10, 8.00)
+ON CONFLICT DO NOTHING;
+
+COMMENT ON TABLE promo_codes IS 'Percentage or fixed promotions on hardware and setup, optionally limited by package, redemptions and expiry';
+COMMENT ON TABLE volume_discount_tiers IS 'Percentage off the hardware subtotal by number of servers quoted';
@@ -0,0 +1,47 @@
+-- Migration: Credit notes and refund payouts
+-- A credit note credits part of an issued invoice to the organization's
+-- account. Account credit is applied to later invoices or paid out in USDC
+-- once staff approve a payout request.
+
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS credit_applied_usdc NUMERIC(20,6) NOT NULL DEFAULT 0
+    CHECK (credit_applied_usdc >= 0);
+
+CREATE TABLE IF NOT EXISTS credit_notes (
+    id UUID PRIMARY KEY,
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    invoice_id UUID NOT NULL REFERENCES invoices(id),
+    reason VARCHAR(20) NOT NULL
+        CHECK (reason IN ('cancellation', 'sla_breach', 'billing_error', 'goodwill')),
+    description TEXT NOT NULL DEFAULT '',
+    -- Gross amount credited and the share of the invoice's VAT it reverses
+    amount_usdc NUMERIC(20,6) NOT NULL CHECK (amount_usdc > 0),
+    vat_usdc NUMERIC(20,6) NOT NULL DEFAULT 0 CHECK (vat_usdc >= 0 AND vat_usdc <= amount_usdc),
+    issued_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_credit_notes_org_id ON credit_notes(org_id, issued_at);
+CREATE INDEX idx_credit_notes_invoice_id ON credit_notes(invoice_id);
+
+CREATE TABLE IF NOT EXISTS refund_payouts (
+    id UUID PRIMARY KEY,
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    amount_usdc NUMERIC(20,6) NOT NULL CHECK (amount_usdc > 0),
+    destination_address VARCHAR(64) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'requested'
+        CHECK (status IN ('requested', 'approved', 'rejected', 'paid')),
+    requested_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    reviewed_by VARCHAR(255),
+    reviewed_at TIMESTAMP WITH TIME ZONE,
+    review_note TEXT,
+    tx_hash VARCHAR(128),
+    paid_at TIMESTAMP WITH TIME ZONE,
+    CHECK (status = 'requested' OR reviewed_at IS NOT NULL),
+    CHECK (status <> 'paid' OR tx_hash IS NOT NULL)
+);
+
+CREATE INDEX idx_refund_payouts_org_id ON refund_payouts(org_id, requested_at);
+CREATE INDEX idx_refund_payouts_status ON refund_payouts(status);
+
+COMMENT ON TABLE credit_notes IS 'Credits against issued invoices for cancellations, SLA breaches and corrections';
+COMMENT ON TABLE refund_payouts IS 'Requests to pay account credit out in USDC; staff approve before anything is sent';
+COMMENT ON COLUMN invoices.credit_applied_usdc IS 'Part of the invoice total settled from account credit';
@@ -0,0 +1,69 @@
+-- Migration: Depreciation pricing is computed in Rust
+-- The SQL calculate_depreciated_price only implemented the linear curve, so
+-- cached prices disagreed with ai::DepreciationRule for exponential and
+-- stepped rules. The database now only marks cached prices stale (NULL)
+-- when an input changes; the application recomputes and stores them.
+
+DROP TRIGGER IF EXISTS trigger_update_provenance_price ON package_provenance;
+DROP FUNCTION IF EXISTS update_provenance_calculated_price();
+DROP FUNCTION IF EXISTS calculate_depreciated_price(UUID, INTEGER);
+
+-- Provenance rows: a new row or changed usage hours invalidate the price
+CREATE OR REPLACE FUNCTION update_package_provenance()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT'
+        OR NEW.usage_hours IS DISTINCT FROM OLD.usage_hours
+        OR NEW.package_id IS DISTINCT FROM OLD.package_id THEN
+        NEW.calculated_price_usdc := NULL;
+        NEW.discount_percentage := NULL;
+    END IF;
+
+    NEW.updated_at = CURRENT_TIMESTAMP;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_update_package_provenance
+    BEFORE INSERT OR UPDATE ON package_provenance
+    FOR EACH ROW
+    EXECUTE FUNCTION update_package_provenance();
+
+-- Packages: a new base price invalidates every provenance of the package
+CREATE OR REPLACE FUNCTION mark_package_prices_stale()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.setup_price_usdc IS DISTINCT FROM OLD.setup_price_usdc THEN
+        UPDATE package_provenance
+        SET calculated_price_usdc = NULL, discount_percentage = NULL
+        WHERE package_id = NEW.id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_mark_package_prices_stale
+    AFTER UPDATE OF setup_price_usdc ON packages
+    FOR EACH ROW
+    EXECUTE FUNCTION mark_package_prices_stale();
+
+-- Depreciation rules: any change invalidates the package's provenances
+CREATE OR REPLACE FUNCTION mark_rule_prices_stale()
+RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE package_provenance
+    SET calculated_price_usdc = NULL, discount_percentage = NULL
+    WHERE package_id = CASE WHEN TG_OP = 'DELETE' THEN OLD.package_id ELSE NEW.package_id END;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_mark_rule_prices_stale
+    AFTER INSERT OR UPDATE OR DELETE ON package_depreciation_rules
+    FOR EACH ROW
+    EXECUTE FUNCTION mark_rule_prices_stale();
+
+-- Everything cached so far came from the SQL implementation
+UPDATE package_provenance SET calculated_price_usdc = NULL, discount_percentage = NULL;
+
+COMMENT ON COLUMN package_provenance.calculated_price_usdc IS 'Depreciated price cached by the application from ai::DepreciationRule; NULL while stale';
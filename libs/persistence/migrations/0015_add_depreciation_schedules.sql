@@ -0,0 +1,36 @@
+-- Migration: Custom depreciation schedules
+-- A rule with the 'custom' curve takes its shape from a table of breakpoints
+-- (usage hours, remaining percentage), either interpolated linearly or held
+-- as steps. The built-in curves keep using final_depreciated_percentage and
+-- full_depreciation_hours. Breakpoints are validated in ai::DepreciationSchedule.
+
+ALTER TABLE package_depreciation_rules
+    DROP CONSTRAINT IF EXISTS package_depreciation_rules_depreciation_curve_check;
+
+ALTER TABLE package_depreciation_rules
+    ADD CONSTRAINT package_depreciation_rules_depreciation_curve_check
+    CHECK (depreciation_curve IN ('linear', 'exponential', 'stepped', 'custom'));
+
+ALTER TABLE package_depreciation_rules
+    ADD COLUMN schedule_interpolation VARCHAR(20)
+        CHECK (schedule_interpolation IN ('linear', 'step'));
+
+ALTER TABLE package_depreciation_rules
+    ADD CONSTRAINT package_depreciation_rules_schedule_check
+    CHECK ((depreciation_curve = 'custom') = (schedule_interpolation IS NOT NULL));
+
+CREATE TABLE IF NOT EXISTS depreciation_schedule_points (
+    package_id UUID NOT NULL REFERENCES package_depreciation_rules(package_id) ON DELETE CASCADE,
+    usage_hours INTEGER NOT NULL CHECK (usage_hours >= 0),
+    remaining_percentage DECIMAL(5,2) NOT NULL CHECK (remaining_percentage >= 0 AND remaining_percentage <= 100),
+    PRIMARY KEY (package_id, usage_hours)
+);
+
+-- Changing a schedule invalidates the package's cached prices
+CREATE TRIGGER trigger_mark_schedule_prices_stale
+    AFTER INSERT OR UPDATE OR DELETE ON depreciation_schedule_points
+    FOR EACH ROW
+    EXECUTE FUNCTION mark_rule_prices_stale();
+
+COMMENT ON TABLE depreciation_schedule_points IS 'Breakpoints of custom depreciation schedules: remaining percentage of the original price once usage_hours is reached';
+COMMENT ON COLUMN package_depreciation_rules.schedule_interpolation IS 'For custom curves: linear (interpolate between breakpoints) or step (hold each value until the next breakpoint)';
@@ -0,0 +1,26 @@
+-- Migration: Depreciation rule changes scheduled for a future date
+-- A pending row replaces the package's rule once effective_at has passed.
+-- The rule is stored the same way as in package_depreciation_rules, with a
+-- custom schedule's breakpoints held in two parallel arrays.
+
+CREATE TABLE IF NOT EXISTS scheduled_depreciation_rules (
+    id SERIAL PRIMARY KEY,
+    package_id UUID NOT NULL REFERENCES packages(id) ON DELETE CASCADE,
+    final_depreciated_percentage DECIMAL(5,2) NOT NULL CHECK (final_depreciated_percentage >= 0 AND final_depreciated_percentage <= 100),
+    full_depreciation_hours INTEGER NOT NULL CHECK (full_depreciation_hours > 0),
+    depreciation_curve VARCHAR(20) NOT NULL CHECK (depreciation_curve IN ('linear', 'exponential', 'stepped', 'custom')),
+    schedule_interpolation VARCHAR(20) CHECK (schedule_interpolation IN ('linear', 'step')),
+    schedule_hours INTEGER[] NOT NULL DEFAULT '{}',
+    schedule_percentages DECIMAL(5,2)[] NOT NULL DEFAULT '{}',
+    effective_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'applied', 'cancelled')),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    applied_at TIMESTAMP WITH TIME ZONE,
+    CHECK ((depreciation_curve = 'custom') = (schedule_interpolation IS NOT NULL)),
+    CHECK (cardinality(schedule_hours) = cardinality(schedule_percentages))
+);
+
+CREATE INDEX idx_scheduled_depreciation_rules_due
+    ON scheduled_depreciation_rules(effective_at) WHERE status = 'pending';
+
+COMMENT ON TABLE scheduled_depreciation_rules IS 'Depreciation rule changes that take effect at effective_at';
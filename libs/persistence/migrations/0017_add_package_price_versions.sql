@@ -0,0 +1,80 @@
+-- Migration: Versioned package prices
+-- Every setup/monthly price a package has had or will have is a row in
+-- package_prices, effective from a given moment. packages.setup_price_usdc and
+-- monthly_price_usdc hold the version currently in effect. Orders and servers
+-- record the version they were bought at, and servers are billed at that
+-- version's monthly price for as long as they run (grandfathering).
+
+CREATE TABLE IF NOT EXISTS package_prices (
+    id SERIAL PRIMARY KEY,
+    package_id UUID NOT NULL REFERENCES packages(id) ON DELETE CASCADE,
+    setup_price_usdc NUMERIC(20,6) NOT NULL CHECK (setup_price_usdc > 0),
+    monthly_price_usdc NUMERIC(20,6) NOT NULL CHECK (monthly_price_usdc >= 0),
+    effective_from TIMESTAMP WITH TIME ZONE NOT NULL,
+    note TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(package_id, effective_from)
+);
+
+CREATE INDEX idx_package_prices_package_id ON package_prices(package_id, effective_from DESC);
+
+-- The prices packages have today are their first version
+INSERT INTO package_prices (package_id, setup_price_usdc, monthly_price_usdc, effective_from, note)
+SELECT id, setup_price_usdc, monthly_price_usdc, created_at, 'Initial price'
+FROM packages;
+
+-- New packages start with a version at their listed price
+CREATE OR REPLACE FUNCTION create_initial_package_price()
+RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO package_prices (package_id, setup_price_usdc, monthly_price_usdc, effective_from, note)
+    VALUES (NEW.id, NEW.setup_price_usdc, NEW.monthly_price_usdc, NEW.created_at, 'Initial price');
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_create_initial_package_price
+    AFTER INSERT ON packages
+    FOR EACH ROW
+    EXECUTE FUNCTION create_initial_package_price();
+
+ALTER TABLE server_orders ADD COLUMN IF NOT EXISTS package_id UUID REFERENCES packages(id);
+ALTER TABLE server_orders ADD COLUMN IF NOT EXISTS package_price_id INTEGER REFERENCES package_prices(id);
+ALTER TABLE servers ADD COLUMN IF NOT EXISTS package_price_id INTEGER REFERENCES package_prices(id);
+
+-- Servers running today keep today's price
+UPDATE servers s
+SET package_price_id = pp.id
+FROM package_prices pp
+WHERE pp.package_id = s.package_id AND s.package_price_id IS NULL;
+
+-- A server takes the price version of its order, or else the version of its
+-- package in effect when it is created
+CREATE OR REPLACE FUNCTION assign_server_price_version()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.package_price_id IS NULL AND NEW.order_id IS NOT NULL THEN
+        SELECT package_price_id INTO NEW.package_price_id
+        FROM server_orders WHERE id = NEW.order_id;
+    END IF;
+
+    IF NEW.package_price_id IS NULL AND NEW.package_id IS NOT NULL THEN
+        SELECT id INTO NEW.package_price_id
+        FROM package_prices
+        WHERE package_id = NEW.package_id AND effective_from <= CURRENT_TIMESTAMP
+        ORDER BY effective_from DESC
+        LIMIT 1;
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_assign_server_price_version
+    BEFORE INSERT ON servers
+    FOR EACH ROW
+    EXECUTE FUNCTION assign_server_price_version();
+
+COMMENT ON TABLE package_prices IS 'Price versions of each package; the latest one effective before now is current';
+COMMENT ON COLUMN servers.package_price_id IS 'Price version the server is billed at, kept when the package price changes';
+COMMENT ON COLUMN server_orders.package_price_id IS 'Price version in effect when the order was placed';
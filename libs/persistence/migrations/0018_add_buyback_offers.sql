@@ -0,0 +1,32 @@
+-- Migration: Hardware buyback
+-- A customer can sell back a server bought through us. The offer is the
+-- package's depreciated price for the hours the server actually ran, scaled
+-- by condition grade, less our margin. Accepting it decommissions the server
+-- and lists the box as a used provenance with those hours.
+
+CREATE TABLE IF NOT EXISTS buyback_offers (
+    id UUID PRIMARY KEY,
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    server_id UUID NOT NULL REFERENCES servers(id),
+    package_id UUID NOT NULL REFERENCES packages(id),
+    usage_hours INTEGER NOT NULL CHECK (usage_hours >= 0),
+    condition VARCHAR(1) NOT NULL CHECK (condition IN ('a', 'b', 'c', 'd')),
+    resale_price_usdc NUMERIC(20,6) NOT NULL CHECK (resale_price_usdc >= 0),
+    offer_usdc NUMERIC(20,6) NOT NULL CHECK (offer_usdc >= 0),
+    status VARCHAR(20) NOT NULL DEFAULT 'offered'
+        CHECK (status IN ('offered', 'accepted', 'withdrawn')),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    accepted_at TIMESTAMP WITH TIME ZONE,
+    provenance_id INTEGER REFERENCES package_provenance(id)
+);
+
+CREATE INDEX idx_buyback_offers_org_id ON buyback_offers(org_id, created_at DESC);
+
+-- A server can only be bought back once
+CREATE UNIQUE INDEX idx_buyback_offers_accepted_server
+    ON buyback_offers(server_id) WHERE status = 'accepted';
+
+COMMENT ON TABLE buyback_offers IS 'Offers to buy back customer hardware; offered rows past expires_at have expired';
+COMMENT ON COLUMN buyback_offers.resale_price_usdc IS 'Depreciated catalog price for usage_hours, before condition and margin';
+COMMENT ON COLUMN buyback_offers.provenance_id IS 'Used provenance the box re-entered the catalog as';
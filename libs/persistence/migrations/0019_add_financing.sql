@@ -0,0 +1,40 @@
+-- Migration: Lease-to-own financing
+-- A financing plan splits a package's setup price into monthly instalments
+-- at a fixed annual rate. An order placed on a plan gets an agreement that
+-- copies the plan's terms, so later plan changes do not affect it. The
+-- billing engine adds one instalment line per month to the server's invoice
+-- and ownership transfers once every instalment is paid.
+
+CREATE TABLE IF NOT EXISTS financing_plans (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(100) NOT NULL,
+    term_months INTEGER NOT NULL CHECK (term_months BETWEEN 2 AND 60),
+    annual_rate_percent DECIMAL(5,2) NOT NULL CHECK (annual_rate_percent BETWEEN 0 AND 100),
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO financing_plans (name, term_months, annual_rate_percent) VALUES
+    ('12 months interest free', 12, 0),
+    ('24 months', 24, 7.9),
+    ('36 months', 36, 9.9);
+
+CREATE TABLE IF NOT EXISTS financing_agreements (
+    id UUID PRIMARY KEY,
+    org_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    order_id UUID NOT NULL UNIQUE REFERENCES server_orders(id) ON DELETE CASCADE,
+    plan_id INTEGER NOT NULL REFERENCES financing_plans(id),
+    principal_usdc NUMERIC(20,6) NOT NULL CHECK (principal_usdc > 0),
+    annual_rate_percent DECIMAL(5,2) NOT NULL,
+    term_months INTEGER NOT NULL CHECK (term_months > 0),
+    status VARCHAR(20) NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'completed')),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    ownership_transferred_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_financing_agreements_org_id ON financing_agreements(org_id, created_at DESC);
+CREATE INDEX idx_financing_agreements_active ON financing_agreements(status) WHERE status = 'active';
+
+COMMENT ON TABLE financing_plans IS 'Instalment terms offered for package setup prices; deactivated plans keep their agreements';
+COMMENT ON TABLE financing_agreements IS 'Orders paid in instalments; the schedule is recomputed from principal, rate and term';
+COMMENT ON COLUMN financing_agreements.ownership_transferred_at IS 'When the last instalment was paid and the hardware became the customer''s';
@@ -0,0 +1,63 @@
+-- Migration: Package add-ons
+-- Optional extras sold with a package (extra NVMe, RAM upgrades, public
+-- IPs, bandwidth tiers, managed backup), each with a per-unit setup and
+-- monthly price. An order keeps the add-ons chosen as line items with the
+-- prices and sizes of the moment, and its plan includes the storage and RAM
+-- upgrades. Monthly add-on fees are billed with the server's hosting.
+
+CREATE TABLE IF NOT EXISTS package_addons (
+    id SERIAL PRIMARY KEY,
+    package_id UUID NOT NULL REFERENCES packages(id) ON DELETE CASCADE,
+    code VARCHAR(50) NOT NULL,
+    kind VARCHAR(20) NOT NULL
+        CHECK (kind IN ('storage', 'ram', 'public_ip', 'bandwidth', 'backup')),
+    name VARCHAR(100) NOT NULL,
+    storage_gb INTEGER NOT NULL DEFAULT 0 CHECK (storage_gb >= 0),
+    ram_gb INTEGER NOT NULL DEFAULT 0 CHECK (ram_gb >= 0),
+    setup_price_usdc NUMERIC(20,6) NOT NULL CHECK (setup_price_usdc >= 0),
+    monthly_price_usdc NUMERIC(20,6) NOT NULL CHECK (monthly_price_usdc >= 0),
+    max_quantity INTEGER NOT NULL DEFAULT 1 CHECK (max_quantity >= 1),
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (package_id, code)
+);
+
+CREATE TABLE IF NOT EXISTS order_addons (
+    id BIGSERIAL PRIMARY KEY,
+    order_id UUID NOT NULL REFERENCES server_orders(id) ON DELETE CASCADE,
+    addon_id INTEGER NOT NULL REFERENCES package_addons(id),
+    code VARCHAR(50) NOT NULL,
+    kind VARCHAR(20) NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    quantity INTEGER NOT NULL CHECK (quantity >= 1),
+    storage_gb INTEGER NOT NULL DEFAULT 0,
+    ram_gb INTEGER NOT NULL DEFAULT 0,
+    setup_price_usdc NUMERIC(20,6) NOT NULL,
+    monthly_price_usdc NUMERIC(20,6) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (order_id, addon_id)
+);
+
+CREATE INDEX idx_order_addons_order_id ON order_addons(order_id);
+
+-- Extras every package can take
+INSERT INTO package_addons
+    (package_id, code, kind, name, storage_gb, setup_price_usdc, monthly_price_usdc, max_quantity)
+SELECT id, 'nvme-2tb', 'storage', 'Extra 2TB NVMe', 2000, 250, 10, 4 FROM packages;
+
+INSERT INTO package_addons
+    (package_id, code, kind, name, setup_price_usdc, monthly_price_usdc, max_quantity)
+SELECT id, 'ipv4', 'public_ip', 'Extra public IPv4 address', 0, 5, 8 FROM packages
+UNION ALL
+SELECT id, 'bw-10g', 'bandwidth', '10 Gbit/s unmetered bandwidth', 0, 150, 1 FROM packages
+UNION ALL
+SELECT id, 'backup', 'backup', 'Managed nightly backup', 50, 40, 1 FROM packages;
+
+-- Integrated GPUs share soldered memory; RAM upgrades are for the rest
+INSERT INTO package_addons
+    (package_id, code, kind, name, ram_gb, setup_price_usdc, monthly_price_usdc, max_quantity)
+SELECT id, 'ram-64', 'ram', '64GB RAM upgrade', 64, 300, 0, 2
+FROM packages WHERE gpu_class <> 'Radeon_8060S';
+
+COMMENT ON TABLE package_addons IS 'Optional extras per package; prices are per unit, setup once and monthly with hosting';
+COMMENT ON TABLE order_addons IS 'Add-ons chosen with an order, priced and sized as of the order';
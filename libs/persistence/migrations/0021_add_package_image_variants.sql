@@ -0,0 +1,26 @@
+-- Migration: Uploaded package photos
+-- Package images used to be static SVGs under web/public/packages. Photos
+-- uploaded by admins are kept on the configured storage backend; the image
+-- row points at the original and records its storage key, and every resized
+-- or WebP rendition is a variant row for building srcset attributes.
+
+ALTER TABLE package_images ADD COLUMN storage_key TEXT;
+
+COMMENT ON COLUMN package_images.storage_key IS 'Storage key of an uploaded original; NULL for static assets';
+
+CREATE TABLE IF NOT EXISTS package_image_variants (
+    id BIGSERIAL PRIMARY KEY,
+    image_id UUID NOT NULL REFERENCES package_images(id) ON DELETE CASCADE,
+    format VARCHAR(10) NOT NULL CHECK (format IN ('png', 'jpeg', 'webp')),
+    width INTEGER NOT NULL CHECK (width > 0),
+    height INTEGER NOT NULL CHECK (height > 0),
+    storage_key TEXT NOT NULL,
+    url TEXT NOT NULL,
+    byte_size BIGINT NOT NULL CHECK (byte_size > 0),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (image_id, format, width)
+);
+
+CREATE INDEX idx_package_image_variants_image_id ON package_image_variants(image_id);
+
+COMMENT ON TABLE package_image_variants IS 'Renditions of uploaded package photos, one per format and width';
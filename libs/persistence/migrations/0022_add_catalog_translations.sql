@@ -0,0 +1,29 @@
+-- Migration: Localized catalog content
+-- Package names, descriptions and image titles are written in English on
+-- the packages and package_images rows. Translations into other locales
+-- (ISO 639-1 codes) live here; a NULL column falls back to the English.
+
+CREATE TABLE IF NOT EXISTS package_translations (
+    package_id UUID NOT NULL REFERENCES packages(id) ON DELETE CASCADE,
+    locale VARCHAR(8) NOT NULL CHECK (locale <> 'en'),
+    name TEXT,
+    description TEXT,
+    hardware_description TEXT,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (package_id, locale)
+);
+
+CREATE TABLE IF NOT EXISTS package_image_translations (
+    image_id UUID NOT NULL REFERENCES package_images(id) ON DELETE CASCADE,
+    locale VARCHAR(8) NOT NULL CHECK (locale <> 'en'),
+    title TEXT,
+    description TEXT,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (image_id, locale)
+);
+
+CREATE INDEX idx_package_translations_locale ON package_translations(locale);
+CREATE INDEX idx_package_image_translations_locale ON package_image_translations(locale);
+
+COMMENT ON TABLE package_translations IS 'Package text per locale; NULL columns fall back to the English on packages';
+COMMENT ON TABLE package_image_translations IS 'Image titles and descriptions per locale; NULL columns fall back to the English';
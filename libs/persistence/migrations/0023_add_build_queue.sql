@@ -0,0 +1,20 @@
+-- Migration: Build queue
+-- Build ETAs are worked out from the orders of build packages still queued
+-- or being built, each package's hands-on build hours (availability_value),
+-- the technicians' capacity and the parts kits on hand. Starting a build
+-- records when and takes one of the package's kits.
+
+ALTER TABLE server_orders ADD COLUMN IF NOT EXISTS build_started_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_server_orders_open
+    ON server_orders(created_at) WHERE status IN ('queued', 'provisioning');
+
+CREATE TABLE IF NOT EXISTS package_build_parts (
+    package_id UUID PRIMARY KEY REFERENCES packages(id) ON DELETE CASCADE,
+    kits_in_stock INTEGER NOT NULL DEFAULT 0 CHECK (kits_in_stock >= 0),
+    lead_time_hours INTEGER NOT NULL DEFAULT 0 CHECK (lead_time_hours >= 0),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+COMMENT ON COLUMN server_orders.build_started_at IS 'When a technician started the build, moving the order to provisioning';
+COMMENT ON TABLE package_build_parts IS 'Parts kits on hand for building a package; builds beyond them wait lead_time_hours';
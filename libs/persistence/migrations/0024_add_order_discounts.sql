@@ -0,0 +1,14 @@
+-- Migration: Order discounts
+-- An order placed with a promo code keeps the discount it was priced with,
+-- and so does the redemption. The discount is taken off the setup price,
+-- including the principal of a financed order.
+
+ALTER TABLE server_orders
+    ADD COLUMN IF NOT EXISTS discount_usdc NUMERIC(20,6) NOT NULL DEFAULT 0
+        CHECK (discount_usdc >= 0);
+
+ALTER TABLE promo_redemptions
+    ADD COLUMN IF NOT EXISTS discount_usdc NUMERIC(20,6) NOT NULL DEFAULT 0
+        CHECK (discount_usdc >= 0);
+
+COMMENT ON COLUMN server_orders.discount_usdc IS 'Promo discount off the setup price, as quoted when the order was placed';
@@ -0,0 +1,12 @@
+-- Migration: Exponential rules are priced from their breakpoint table
+-- ai::DepreciationRule now prices every built-in curve from the schedule it
+-- shows, so exponential prices move from the exact decay to its straight
+-- segments. Their cached prices are marked stale for the application to
+-- recompute.
+
+UPDATE package_provenance
+SET calculated_price_usdc = NULL, discount_percentage = NULL
+WHERE package_id IN (
+    SELECT package_id FROM package_depreciation_rules
+    WHERE depreciation_curve = 'exponential'
+);
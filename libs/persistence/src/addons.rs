@@ -0,0 +1,233 @@
+use crate::Database;
+use ai::Money;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PackageAddon {
+    pub id: i32,
+    pub package_id: Uuid,
+    pub code: String,
+    pub kind: String,
+    pub name: String,
+    pub storage_gb: i32,
+    pub ram_gb: i32,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+    pub max_quantity: i32,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewPackageAddon {
+    pub code: String,
+    pub kind: String,
+    pub name: String,
+    pub storage_gb: i32,
+    pub ram_gb: i32,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+    pub max_quantity: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OrderAddon {
+    pub id: i64,
+    pub order_id: Uuid,
+    pub addon_id: i32,
+    pub code: String,
+    pub kind: String,
+    pub name: String,
+    pub quantity: i32,
+    pub storage_gb: i32,
+    pub ram_gb: i32,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Add-on line item to be written with a new order, copying the add-on as
+/// it is sold now
+#[derive(Debug, Clone)]
+pub struct NewOrderAddon {
+    pub addon_id: i32,
+    pub code: String,
+    pub kind: String,
+    pub name: String,
+    pub quantity: i32,
+    pub storage_gb: i32,
+    pub ram_gb: i32,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+}
+
+const ADDON_COLUMNS: &str = r#"
+    id, package_id, code, kind, name, storage_gb, ram_gb,
+    setup_price_usdc, monthly_price_usdc, max_quantity, is_active, created_at
+"#;
+
+const ORDER_ADDON_COLUMNS: &str = r#"
+    id, order_id, addon_id, code, kind, name, quantity, storage_gb, ram_gb,
+    setup_price_usdc, monthly_price_usdc, created_at
+"#;
+
+impl Database {
+    /// Every add-on of a package, including inactive ones
+    pub async fn get_package_addons(&self, package_id: Uuid) -> Result<Vec<PackageAddon>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {ADDON_COLUMNS} FROM package_addons WHERE package_id = $1 ORDER BY kind, id"
+        ))
+        .bind(package_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(addon_from_row).collect())
+    }
+
+    pub async fn create_package_addon(
+        &self,
+        package_id: Uuid,
+        addon: &NewPackageAddon,
+    ) -> Result<PackageAddon> {
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO package_addons
+            (package_id, code, kind, name, storage_gb, ram_gb, setup_price_usdc, monthly_price_usdc, max_quantity)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING {ADDON_COLUMNS}
+            "#
+        ))
+        .bind(package_id)
+        .bind(&addon.code)
+        .bind(&addon.kind)
+        .bind(&addon.name)
+        .bind(addon.storage_gb)
+        .bind(addon.ram_gb)
+        .bind(addon.setup_price_usdc)
+        .bind(addon.monthly_price_usdc)
+        .bind(addon.max_quantity)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(addon_from_row(&row))
+    }
+
+    /// Returns `None` if the package has no add-on with that code. Orders
+    /// already placed keep the terms they were placed on.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_package_addon(
+        &self,
+        package_id: Uuid,
+        code: &str,
+        name: &str,
+        setup_price_usdc: Money,
+        monthly_price_usdc: Money,
+        max_quantity: i32,
+        is_active: bool,
+    ) -> Result<Option<PackageAddon>> {
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE package_addons SET
+                name = $3, setup_price_usdc = $4, monthly_price_usdc = $5,
+                max_quantity = $6, is_active = $7
+            WHERE package_id = $1 AND code = $2
+            RETURNING {ADDON_COLUMNS}
+            "#
+        ))
+        .bind(package_id)
+        .bind(code)
+        .bind(name)
+        .bind(setup_price_usdc)
+        .bind(monthly_price_usdc)
+        .bind(max_quantity)
+        .bind(is_active)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(addon_from_row))
+    }
+
+    /// Add-on line items of every order of an organization
+    pub async fn get_order_addons_for_org(&self, org_id: Uuid) -> Result<Vec<OrderAddon>> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {ORDER_ADDON_COLUMNS}
+            FROM order_addons
+            WHERE order_id IN (SELECT id FROM server_orders WHERE org_id = $1)
+            ORDER BY order_id, id
+            "#
+        ))
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(order_addon_from_row).collect())
+    }
+}
+
+pub(crate) async fn insert_order_addons(
+    conn: &mut PgConnection,
+    order_id: Uuid,
+    addons: &[NewOrderAddon],
+) -> Result<()> {
+    for addon in addons {
+        sqlx::query(
+            r#"
+            INSERT INTO order_addons
+            (order_id, addon_id, code, kind, name, quantity, storage_gb, ram_gb, setup_price_usdc, monthly_price_usdc)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+        )
+        .bind(order_id)
+        .bind(addon.addon_id)
+        .bind(&addon.code)
+        .bind(&addon.kind)
+        .bind(&addon.name)
+        .bind(addon.quantity)
+        .bind(addon.storage_gb)
+        .bind(addon.ram_gb)
+        .bind(addon.setup_price_usdc)
+        .bind(addon.monthly_price_usdc)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+fn addon_from_row(row: &sqlx::postgres::PgRow) -> PackageAddon {
+    PackageAddon {
+        id: row.get("id"),
+        package_id: row.get("package_id"),
+        code: row.get("code"),
+        kind: row.get("kind"),
+        name: row.get("name"),
+        storage_gb: row.get("storage_gb"),
+        ram_gb: row.get("ram_gb"),
+        setup_price_usdc: row.get("setup_price_usdc"),
+        monthly_price_usdc: row.get("monthly_price_usdc"),
+        max_quantity: row.get("max_quantity"),
+        is_active: row.get("is_active"),
+        created_at: row.get("created_at"),
+    }
+}
+
+fn order_addon_from_row(row: &sqlx::postgres::PgRow) -> OrderAddon {
+    OrderAddon {
+        id: row.get("id"),
+        order_id: row.get("order_id"),
+        addon_id: row.get("addon_id"),
+        code: row.get("code"),
+        kind: row.get("kind"),
+        name: row.get("name"),
+        quantity: row.get("quantity"),
+        storage_gb: row.get("storage_gb"),
+        ram_gb: row.get("ram_gb"),
+        setup_price_usdc: row.get("setup_price_usdc"),
+        monthly_price_usdc: row.get("monthly_price_usdc"),
+        created_at: row.get("created_at"),
+    }
+}
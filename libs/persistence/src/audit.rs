@@ -0,0 +1,41 @@
+use crate::Database;
+use anyhow::Result;
+use sqlx::Row;
+use uuid::Uuid;
+
+impl Database {
+    pub async fn insert_audit_log(
+        &self,
+        org_id: Option<Uuid>,
+        actor_user_id: Option<Uuid>,
+        action: &str,
+        details: serde_json::Value,
+    ) -> Result<i64> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO audit_log (org_id, actor_user_id, action, details)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+        )
+        .bind(org_id)
+        .bind(actor_user_id)
+        .bind(action)
+        .bind(details)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("id"))
+    }
+
+    pub async fn get_org_user_emails(&self, org_id: Uuid) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT email::text as email FROM users WHERE org_id = $1 ORDER BY created_at",
+        )
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.get("email")).collect())
+    }
+}
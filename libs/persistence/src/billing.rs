@@ -0,0 +1,483 @@
+use crate::ledger::insert_journal_entry;
+use crate::Database;
+use ai::ledger::JournalEntry;
+use ai::Money;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BillableServer {
+    pub server_id: Uuid,
+    pub org_id: Uuid,
+    pub hostname: String,
+    pub package_name: String,
+    pub monthly_price_usdc: Money,
+    pub activated_at: DateTime<Utc>,
+    pub decommissioned_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Invoice {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub subtotal_usdc: Money,
+    pub vat_usdc: Money,
+    pub vat_rate_percent: Decimal,
+    pub vat_treatment: Option<String>,
+    pub vat_country: Option<String>,
+    pub customer_vat_id: Option<String>,
+    pub total_usdc: Money,
+    pub credit_applied_usdc: Money,
+    pub status: String,
+    pub issued_at: DateTime<Utc>,
+    pub due_at: DateTime<Utc>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub reminders_sent: i16,
+    pub last_reminder_at: Option<DateTime<Utc>>,
+    pub suspended_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct InvoiceLine {
+    pub id: i64,
+    pub invoice_id: Uuid,
+    pub server_id: Option<Uuid>,
+    pub kind: String,
+    pub description: String,
+    pub period_start: NaiveDate,
+    pub service_start: NaiveDate,
+    pub service_end: NaiveDate,
+    pub billed_days: i16,
+    pub period_days: i16,
+    pub amount_usdc: Money,
+}
+
+/// Line item to be written with a new invoice
+#[derive(Debug, Clone)]
+pub struct NewInvoiceLine {
+    pub server_id: Option<Uuid>,
+    pub kind: String,
+    pub description: String,
+    pub service_start: NaiveDate,
+    pub service_end: NaiveDate,
+    pub billed_days: i16,
+    pub period_days: i16,
+    pub amount_usdc: Money,
+}
+
+/// VAT charged on a new invoice
+#[derive(Debug, Clone)]
+pub struct InvoiceVat {
+    pub treatment: String,
+    pub country_code: String,
+    pub rate_percent: Decimal,
+    pub amount_usdc: Money,
+    pub customer_vat_id: Option<String>,
+}
+
+impl Database {
+    /// Servers active at some point in `[period_start, period_end)` whose
+    /// hosting fee has not been invoiced for that period yet, with the
+    /// monthly price of the version each server was bought at plus the
+    /// monthly fees of the add-ons ordered with it
+    pub async fn get_unbilled_servers(
+        &self,
+        period_start: NaiveDate,
+        period_end: NaiveDate,
+    ) -> Result<Vec<BillableServer>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                s.id as server_id, s.org_id, s.hostname, p.name as package_name,
+                COALESCE(pv.monthly_price_usdc, p.monthly_price_usdc)
+                    + COALESCE((
+                        SELECT SUM(oa.monthly_price_usdc * oa.quantity)
+                        FROM order_addons oa
+                        WHERE oa.order_id = s.order_id
+                    ), 0) as monthly_price_usdc,
+                s.activated_at, s.decommissioned_at
+            FROM servers s
+            JOIN packages p ON p.id = s.package_id
+            LEFT JOIN package_prices pv ON pv.id = s.package_price_id
+            WHERE s.activated_at IS NOT NULL
+              AND s.activated_at < $2::date
+              AND (s.decommissioned_at IS NULL OR s.decommissioned_at >= $1::date)
+              AND NOT EXISTS (
+                  SELECT 1 FROM invoice_lines il
+                  WHERE il.server_id = s.id
+                    AND il.period_start = $1
+                    AND il.kind = 'hosting'
+              )
+            ORDER BY s.org_id, s.activated_at
+            "#,
+        )
+        .bind(period_start)
+        .bind(period_end)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let servers = rows
+            .into_iter()
+            .map(|row| BillableServer {
+                server_id: row.get("server_id"),
+                org_id: row.get("org_id"),
+                hostname: row.get("hostname"),
+                package_name: row.get("package_name"),
+                monthly_price_usdc: row.get("monthly_price_usdc"),
+                activated_at: row.get("activated_at"),
+                decommissioned_at: row.get("decommissioned_at"),
+            })
+            .collect();
+
+        Ok(servers)
+    }
+
+    /// Insert an invoice, its lines and its journal entry in one transaction.
+    /// The unique `(server_id, period_start, kind)` constraint on lines makes
+    /// a concurrent or repeated run fail instead of billing twice.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_invoice(
+        &self,
+        invoice_id: Uuid,
+        org_id: Uuid,
+        period_start: NaiveDate,
+        period_end: NaiveDate,
+        due_at: DateTime<Utc>,
+        lines: &[NewInvoiceLine],
+        vat: Option<&InvoiceVat>,
+        journal: Option<&JournalEntry>,
+    ) -> Result<Invoice> {
+        let subtotal: Money = lines.iter().map(|l| l.amount_usdc).sum();
+        let vat_amount = vat.map(|v| v.amount_usdc).unwrap_or_default();
+
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO invoices
+            (id, org_id, period_start, period_end, subtotal_usdc, vat_usdc, total_usdc, status, due_at,
+             vat_rate_percent, vat_treatment, vat_country, customer_vat_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, 'open', $8, $9, $10, $11, $12)
+            RETURNING id, org_id, period_start, period_end, total_usdc, status, issued_at, due_at, paid_at,
+                reminders_sent, last_reminder_at, suspended_at, subtotal_usdc, vat_usdc, vat_rate_percent,
+                vat_treatment, vat_country, customer_vat_id, credit_applied_usdc
+            "#,
+        )
+        .bind(invoice_id)
+        .bind(org_id)
+        .bind(period_start)
+        .bind(period_end)
+        .bind(subtotal)
+        .bind(vat_amount)
+        .bind(subtotal + vat_amount)
+        .bind(due_at)
+        .bind(vat.map(|v| v.rate_percent).unwrap_or_default())
+        .bind(vat.map(|v| v.treatment.as_str()))
+        .bind(vat.map(|v| v.country_code.as_str()))
+        .bind(vat.and_then(|v| v.customer_vat_id.as_deref()))
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for line in lines {
+            sqlx::query(
+                r#"
+                INSERT INTO invoice_lines
+                (invoice_id, server_id, kind, description, period_start, service_start, service_end, billed_days, period_days, amount_usdc)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+            )
+            .bind(invoice_id)
+            .bind(line.server_id)
+            .bind(&line.kind)
+            .bind(&line.description)
+            .bind(period_start)
+            .bind(line.service_start)
+            .bind(line.service_end)
+            .bind(line.billed_days)
+            .bind(line.period_days)
+            .bind(line.amount_usdc)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        if let Some(entry) = journal {
+            insert_journal_entry(&mut tx, entry).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(invoice_from_row(&row))
+    }
+
+    pub async fn get_invoices_for_org(&self, org_id: Uuid) -> Result<Vec<Invoice>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, org_id, period_start, period_end, total_usdc, status, issued_at, due_at, paid_at,
+                reminders_sent, last_reminder_at, suspended_at, subtotal_usdc, vat_usdc, vat_rate_percent,
+                vat_treatment, vat_country, customer_vat_id, credit_applied_usdc
+            FROM invoices
+            WHERE org_id = $1
+            ORDER BY period_start DESC, issued_at DESC
+            "#,
+        )
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(invoice_from_row).collect())
+    }
+
+    pub async fn get_invoice_lines(&self, invoice_id: Uuid) -> Result<Vec<InvoiceLine>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                id, invoice_id, server_id, kind, description, period_start,
+                service_start, service_end, billed_days, period_days, amount_usdc
+            FROM invoice_lines
+            WHERE invoice_id = $1
+            ORDER BY id ASC
+            "#,
+        )
+        .bind(invoice_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let lines = rows
+            .into_iter()
+            .map(|row| InvoiceLine {
+                id: row.get("id"),
+                invoice_id: row.get("invoice_id"),
+                server_id: row.get("server_id"),
+                kind: row.get("kind"),
+                description: row.get("description"),
+                period_start: row.get("period_start"),
+                service_start: row.get("service_start"),
+                service_end: row.get("service_end"),
+                billed_days: row.get("billed_days"),
+                period_days: row.get("period_days"),
+                amount_usdc: row.get("amount_usdc"),
+            })
+            .collect();
+
+        Ok(lines)
+    }
+
+    pub async fn get_invoice(&self, invoice_id: Uuid) -> Result<Option<Invoice>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, org_id, period_start, period_end, total_usdc, status, issued_at, due_at, paid_at,
+                reminders_sent, last_reminder_at, suspended_at, subtotal_usdc, vat_usdc, vat_rate_percent,
+                vat_treatment, vat_country, customer_vat_id, credit_applied_usdc
+            FROM invoices
+            WHERE id = $1
+            "#,
+        )
+        .bind(invoice_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(invoice_from_row))
+    }
+
+    /// Open invoices whose due date has passed
+    pub async fn get_overdue_invoices(&self, now: DateTime<Utc>) -> Result<Vec<Invoice>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, org_id, period_start, period_end, total_usdc, status, issued_at, due_at, paid_at,
+                reminders_sent, last_reminder_at, suspended_at, subtotal_usdc, vat_usdc, vat_rate_percent,
+                vat_treatment, vat_country, customer_vat_id, credit_applied_usdc
+            FROM invoices
+            WHERE status = 'open' AND due_at < $1
+            ORDER BY due_at ASC
+            "#,
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(invoice_from_row).collect())
+    }
+
+    pub async fn record_invoice_reminder(
+        &self,
+        invoice_id: Uuid,
+        reminders_sent: i16,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE invoices
+            SET reminders_sent = $2, last_reminder_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            "#,
+        )
+        .bind(invoice_id)
+        .bind(reminders_sent)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Mark the invoice as the reason for a suspension and suspend every
+    /// active server billed on it. Returns the suspended server ids.
+    pub async fn suspend_invoice_servers(&self, invoice_id: Uuid) -> Result<Vec<Uuid>> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE invoices SET suspended_at = CURRENT_TIMESTAMP WHERE id = $1")
+            .bind(invoice_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let rows = sqlx::query(
+            r#"
+            UPDATE servers
+            SET status = 'suspended', suspended_at = CURRENT_TIMESTAMP
+            WHERE status = 'active'
+              AND id IN (SELECT server_id FROM invoice_lines WHERE invoice_id = $1)
+            RETURNING id
+            "#,
+        )
+        .bind(invoice_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(rows.into_iter().map(|row| row.get("id")).collect())
+    }
+
+    /// Mark an open invoice as paid and post the payment to the ledger in
+    /// the same transaction. Returns `None` if it was not open.
+    pub async fn mark_invoice_paid(
+        &self,
+        invoice_id: Uuid,
+        journal: Option<&JournalEntry>,
+    ) -> Result<Option<Invoice>> {
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query(
+            r#"
+            UPDATE invoices
+            SET status = 'paid', paid_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND status = 'open'
+            RETURNING id, org_id, period_start, period_end, total_usdc, status, issued_at, due_at, paid_at,
+                reminders_sent, last_reminder_at, suspended_at, subtotal_usdc, vat_usdc, vat_rate_percent,
+                vat_treatment, vat_country, customer_vat_id, credit_applied_usdc
+            "#,
+        )
+        .bind(invoice_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        if let Some(entry) = journal {
+            insert_journal_entry(&mut tx, entry).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Some(invoice_from_row(&row)))
+    }
+
+    /// Settle part or all of an open invoice from account credit, posting the
+    /// journal entry in the same transaction. An invoice covered in full is
+    /// marked paid. Returns `None` if credit was already applied to it.
+    pub async fn apply_invoice_credit(
+        &self,
+        invoice_id: Uuid,
+        amount_usdc: Money,
+        journal: &JournalEntry,
+    ) -> Result<Option<Invoice>> {
+        let mut tx = self.pool.begin().await?;
+
+        if insert_journal_entry(&mut tx, journal).await?.is_none() {
+            return Ok(None);
+        }
+
+        let row = sqlx::query(
+            r#"
+            UPDATE invoices
+            SET credit_applied_usdc = credit_applied_usdc + $2,
+                status = CASE WHEN credit_applied_usdc + $2 >= total_usdc THEN 'paid' ELSE status END,
+                paid_at = CASE WHEN credit_applied_usdc + $2 >= total_usdc THEN CURRENT_TIMESTAMP ELSE paid_at END
+            WHERE id = $1 AND status = 'open' AND credit_applied_usdc + $2 <= total_usdc
+            RETURNING id, org_id, period_start, period_end, total_usdc, status, issued_at, due_at, paid_at,
+                reminders_sent, last_reminder_at, suspended_at, subtotal_usdc, vat_usdc, vat_rate_percent,
+                vat_treatment, vat_country, customer_vat_id, credit_applied_usdc
+            "#,
+        )
+        .bind(invoice_id)
+        .bind(amount_usdc)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        tx.commit().await?;
+
+        Ok(Some(invoice_from_row(&row)))
+    }
+
+    /// Reinstate the suspended servers on an invoice, unless another unpaid
+    /// invoice still holds them suspended. Returns the reinstated server ids.
+    pub async fn reinstate_invoice_servers(&self, invoice_id: Uuid) -> Result<Vec<Uuid>> {
+        let rows = sqlx::query(
+            r#"
+            UPDATE servers s
+            SET status = 'active', suspended_at = NULL
+            WHERE s.status = 'suspended'
+              AND s.id IN (SELECT server_id FROM invoice_lines WHERE invoice_id = $1)
+              AND NOT EXISTS (
+                  SELECT 1
+                  FROM invoice_lines il
+                  JOIN invoices i ON i.id = il.invoice_id
+                  WHERE il.server_id = s.id
+                    AND i.id <> $1
+                    AND i.status = 'open'
+                    AND i.suspended_at IS NOT NULL
+              )
+            RETURNING s.id
+            "#,
+        )
+        .bind(invoice_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.get("id")).collect())
+    }
+}
+
+fn invoice_from_row(row: &sqlx::postgres::PgRow) -> Invoice {
+    Invoice {
+        id: row.get("id"),
+        org_id: row.get("org_id"),
+        period_start: row.get("period_start"),
+        period_end: row.get("period_end"),
+        subtotal_usdc: row.get("subtotal_usdc"),
+        vat_usdc: row.get("vat_usdc"),
+        vat_rate_percent: row.get("vat_rate_percent"),
+        vat_treatment: row.get("vat_treatment"),
+        vat_country: row.get("vat_country"),
+        customer_vat_id: row.get("customer_vat_id"),
+        total_usdc: row.get("total_usdc"),
+        credit_applied_usdc: row.get("credit_applied_usdc"),
+        status: row.get("status"),
+        issued_at: row.get("issued_at"),
+        due_at: row.get("due_at"),
+        paid_at: row.get("paid_at"),
+        reminders_sent: row.get("reminders_sent"),
+        last_reminder_at: row.get("last_reminder_at"),
+        suspended_at: row.get("suspended_at"),
+    }
+}
@@ -0,0 +1,238 @@
+use crate::Database;
+use ai::Money;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+/// The parts of a server a buyback is valued from
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BuybackServer {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub package_id: Option<Uuid>,
+    pub activated_at: Option<DateTime<Utc>>,
+    pub decommissioned_at: Option<DateTime<Utc>>,
+    /// The hardware is still being paid off on an active financing agreement
+    pub is_financed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BuybackOffer {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub server_id: Uuid,
+    pub package_id: Uuid,
+    pub sku: String,
+    pub usage_hours: i32,
+    pub condition: String,
+    pub resale_price_usdc: Money,
+    pub offer_usdc: Money,
+    pub status: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub provenance_id: Option<i32>,
+}
+
+/// Offer to be stored
+#[derive(Debug, Clone)]
+pub struct NewBuybackOffer {
+    pub org_id: Uuid,
+    pub server_id: Uuid,
+    pub package_id: Uuid,
+    pub usage_hours: i32,
+    pub condition: String,
+    pub resale_price_usdc: Money,
+    pub offer_usdc: Money,
+    pub expires_at: DateTime<Utc>,
+}
+
+const BUYBACK_OFFER_COLUMNS: &str = r#"
+    b.id, b.org_id, b.server_id, b.package_id,
+    (SELECT sku FROM packages WHERE id = b.package_id) as sku,
+    b.usage_hours, b.condition, b.resale_price_usdc, b.offer_usdc, b.status,
+    b.expires_at, b.created_at, b.accepted_at, b.provenance_id
+"#;
+
+impl Database {
+    pub async fn get_buyback_server(&self, server_id: Uuid) -> Result<Option<BuybackServer>> {
+        let row = sqlx::query(
+            r#"
+            SELECT s.id, s.org_id, s.package_id, s.activated_at, s.decommissioned_at,
+                   EXISTS (
+                       SELECT 1 FROM financing_agreements fa
+                       WHERE fa.order_id = s.order_id AND fa.status = 'active'
+                   ) as is_financed
+            FROM servers s
+            WHERE s.id = $1
+            "#,
+        )
+        .bind(server_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| BuybackServer {
+            id: r.get("id"),
+            org_id: r.get("org_id"),
+            package_id: r.get("package_id"),
+            activated_at: r.get("activated_at"),
+            decommissioned_at: r.get("decommissioned_at"),
+            is_financed: r.get("is_financed"),
+        }))
+    }
+
+    pub async fn create_buyback_offer(&self, offer: &NewBuybackOffer) -> Result<BuybackOffer> {
+        let row = sqlx::query(&format!(
+            r#"
+            WITH b AS (
+                INSERT INTO buyback_offers
+                (id, org_id, server_id, package_id, usage_hours, condition,
+                 resale_price_usdc, offer_usdc, expires_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                RETURNING *
+            )
+            SELECT {BUYBACK_OFFER_COLUMNS} FROM b
+            "#
+        ))
+        .bind(Uuid::new_v4())
+        .bind(offer.org_id)
+        .bind(offer.server_id)
+        .bind(offer.package_id)
+        .bind(offer.usage_hours)
+        .bind(&offer.condition)
+        .bind(offer.resale_price_usdc)
+        .bind(offer.offer_usdc)
+        .bind(offer.expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(buyback_offer_from_row(&row))
+    }
+
+    pub async fn get_buyback_offer(&self, id: Uuid) -> Result<Option<BuybackOffer>> {
+        let row = sqlx::query(&format!(
+            "SELECT {BUYBACK_OFFER_COLUMNS} FROM buyback_offers b WHERE b.id = $1"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(buyback_offer_from_row))
+    }
+
+    /// Newest first
+    pub async fn get_buyback_offers_for_org(&self, org_id: Uuid) -> Result<Vec<BuybackOffer>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {BUYBACK_OFFER_COLUMNS} FROM buyback_offers b WHERE b.org_id = $1 ORDER BY b.created_at DESC"
+        ))
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(buyback_offer_from_row).collect())
+    }
+
+    /// Accept an open, unexpired offer: the box is added to the catalog as a
+    /// used provenance with the offer's hours (one more unit if that
+    /// provenance already exists), the server is decommissioned and any other
+    /// open offers for it are withdrawn. Returns `None` if the offer is not
+    /// open or has expired.
+    pub async fn accept_buyback_offer(
+        &self,
+        id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Option<BuybackOffer>> {
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query(
+            r#"
+            UPDATE buyback_offers
+            SET status = 'accepted', accepted_at = $2
+            WHERE id = $1 AND status = 'offered' AND expires_at > $2
+            RETURNING server_id, package_id, usage_hours
+            "#,
+        )
+        .bind(id)
+        .bind(now)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let server_id: Uuid = row.get("server_id");
+        let package_id: Uuid = row.get("package_id");
+        let usage_hours: i32 = row.get("usage_hours");
+
+        // The price is left stale for the next catalog read to compute
+        let provenance_id: i32 = sqlx::query_scalar(
+            r#"
+            INSERT INTO package_provenance
+            (package_id, provenance_type, usage_hours, quantity_available, is_active)
+            VALUES ($1, CASE WHEN $2 = 0 THEN 'new' ELSE 'used' END, $2, 1, true)
+            ON CONFLICT (package_id, usage_hours) DO UPDATE SET
+                quantity_available = package_provenance.quantity_available + 1,
+                is_active = true
+            RETURNING id
+            "#,
+        )
+        .bind(package_id)
+        .bind(usage_hours)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE buyback_offers SET provenance_id = $2 WHERE id = $1")
+            .bind(id)
+            .bind(provenance_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE buyback_offers SET status = 'withdrawn'
+            WHERE server_id = $1 AND id <> $2 AND status = 'offered'
+            "#,
+        )
+        .bind(server_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE servers
+            SET decommissioned_at = LEAST(COALESCE(decommissioned_at, $2), $2)
+            WHERE id = $1
+            "#,
+        )
+        .bind(server_id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        self.get_buyback_offer(id).await
+    }
+}
+
+fn buyback_offer_from_row(row: &sqlx::postgres::PgRow) -> BuybackOffer {
+    BuybackOffer {
+        id: row.get("id"),
+        org_id: row.get("org_id"),
+        server_id: row.get("server_id"),
+        package_id: row.get("package_id"),
+        sku: row.get("sku"),
+        usage_hours: row.get("usage_hours"),
+        condition: row.get("condition"),
+        resale_price_usdc: row.get("resale_price_usdc"),
+        offer_usdc: row.get("offer_usdc"),
+        status: row.get("status"),
+        expires_at: row.get("expires_at"),
+        created_at: row.get("created_at"),
+        accepted_at: row.get("accepted_at"),
+        provenance_id: row.get("provenance_id"),
+    }
+}
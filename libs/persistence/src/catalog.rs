@@ -0,0 +1,626 @@
+use crate::depreciation::write_depreciation_rule;
+use crate::{Database, NewDepreciationRule, Package, PackageProvenance};
+use ai::{GpuClass, Money};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+/// Editable columns of a package
+#[derive(Debug, Clone)]
+pub struct PackageFields {
+    pub name: String,
+    pub description: String,
+    pub hardware_description: String,
+    pub cpu_cores: i16,
+    pub ram_gb: i16,
+    pub storage_gb: i32,
+    pub gpu_class: GpuClass,
+    pub gpu_count: i16,
+    pub vram_gb: i16,
+    pub availability_type: String,
+    pub availability_value: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CatalogImage {
+    pub id: Uuid,
+    pub package_id: Uuid,
+    pub filename: String,
+    pub title: String,
+    pub description: String,
+    pub sort_order: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One write of a catalog sync. Packages and images are created with ids
+/// chosen by the caller so later writes can refer to them.
+#[derive(Debug, Clone)]
+pub enum CatalogWrite {
+    CreatePackage {
+        id: Uuid,
+        sku: String,
+        fields: PackageFields,
+        setup_price_usdc: Money,
+        monthly_price_usdc: Money,
+    },
+    UpdatePackage {
+        id: Uuid,
+        fields: PackageFields,
+    },
+    SetPackageActive {
+        id: Uuid,
+        is_active: bool,
+    },
+    /// A price version effective at `effective_from`, which also becomes the
+    /// listed price
+    ChangePrices {
+        package_id: Uuid,
+        setup_price_usdc: Money,
+        monthly_price_usdc: Money,
+        effective_from: DateTime<Utc>,
+        note: String,
+    },
+    SaveDepreciationRule(NewDepreciationRule),
+    AddImage {
+        id: Uuid,
+        package_id: Uuid,
+        filename: String,
+        title: String,
+        description: String,
+    },
+    UpdateImage {
+        id: Uuid,
+        package_id: Uuid,
+        filename: String,
+        title: String,
+        description: String,
+    },
+    RemoveImage {
+        id: Uuid,
+        package_id: Uuid,
+    },
+    OrderImages {
+        package_id: Uuid,
+        image_ids: Vec<Uuid>,
+    },
+    /// Create the provenance at `usage_hours` or update the existing one
+    UpsertProvenance {
+        package_id: Uuid,
+        usage_hours: i32,
+        quantity_available: i32,
+        is_active: bool,
+    },
+}
+
+const PACKAGE_COLUMNS: &str = r#"
+    id, name, sku, description, hardware_description,
+    cpu_cores, ram_gb, storage_gb, gpu_class,
+    gpu_count, vram_gb, setup_price_usdc, monthly_price_usdc,
+    availability_type, availability_value,
+    is_active, created_at
+"#;
+
+const IMAGE_COLUMNS: &str = "id, package_id, filename, title, description, sort_order, created_at";
+
+const PROVENANCE_COLUMNS: &str = r#"
+    id, package_id, provenance_type, usage_hours,
+    quantity_available, is_active, calculated_price_usdc,
+    discount_percentage, created_at, updated_at
+"#;
+
+impl Database {
+    /// Every package, active or not, in the order they were added
+    pub async fn get_catalog_packages(&self) -> Result<Vec<Package>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {PACKAGE_COLUMNS} FROM packages ORDER BY created_at, sku"
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(package_from_row).collect())
+    }
+
+    /// A package by SKU whether or not it is active
+    pub async fn get_catalog_package(&self, sku: &str) -> Result<Option<Package>> {
+        let row = sqlx::query(&format!(
+            "SELECT {PACKAGE_COLUMNS} FROM packages WHERE sku = $1"
+        ))
+        .bind(sku)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(package_from_row))
+    }
+
+    /// Insert an inactive package. Its first price version is created by
+    /// trigger.
+    pub async fn create_package(
+        &self,
+        sku: &str,
+        fields: &PackageFields,
+        setup_price_usdc: Money,
+        monthly_price_usdc: Money,
+    ) -> Result<Package> {
+        let mut conn = self.pool.acquire().await?;
+        insert_package(
+            &mut conn,
+            Uuid::new_v4(),
+            sku,
+            fields,
+            setup_price_usdc,
+            monthly_price_usdc,
+        )
+        .await
+    }
+
+    pub async fn update_package(&self, id: Uuid, fields: &PackageFields) -> Result<Package> {
+        let mut conn = self.pool.acquire().await?;
+        write_package_fields(&mut conn, id, fields).await
+    }
+
+    pub async fn set_package_active(&self, id: Uuid, is_active: bool) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        write_package_active(&mut conn, id, is_active).await
+    }
+
+    /// A package's images in display order
+    pub async fn get_catalog_images(&self, package_id: Uuid) -> Result<Vec<CatalogImage>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {IMAGE_COLUMNS} FROM package_images WHERE package_id = $1 ORDER BY sort_order, created_at"
+        ))
+        .bind(package_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(catalog_image_from_row).collect())
+    }
+
+    /// Append an image after the package's existing ones
+    pub async fn add_package_image(
+        &self,
+        package_id: Uuid,
+        filename: &str,
+        title: &str,
+        description: &str,
+    ) -> Result<CatalogImage> {
+        let mut conn = self.pool.acquire().await?;
+        insert_package_image(
+            &mut conn,
+            Uuid::new_v4(),
+            package_id,
+            filename,
+            title,
+            description,
+        )
+        .await
+    }
+
+    /// Returns `None` if the package has no such image
+    pub async fn update_package_image(
+        &self,
+        package_id: Uuid,
+        id: Uuid,
+        filename: &str,
+        title: &str,
+        description: &str,
+    ) -> Result<Option<CatalogImage>> {
+        let mut conn = self.pool.acquire().await?;
+        write_package_image(&mut conn, package_id, id, filename, title, description).await
+    }
+
+    pub async fn delete_package_image(&self, package_id: Uuid, id: Uuid) -> Result<bool> {
+        let mut conn = self.pool.acquire().await?;
+        remove_package_image(&mut conn, package_id, id).await
+    }
+
+    /// Number the images 1.. in the given order
+    pub async fn reorder_package_images(&self, package_id: Uuid, image_ids: &[Uuid]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        write_image_order(&mut tx, package_id, image_ids).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Every provenance row of a package, including inactive ones
+    pub async fn get_catalog_provenances(
+        &self,
+        package_id: Uuid,
+    ) -> Result<Vec<PackageProvenance>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {PROVENANCE_COLUMNS} FROM package_provenance WHERE package_id = $1 ORDER BY usage_hours"
+        ))
+        .bind(package_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(provenance_from_row).collect())
+    }
+
+    /// Its price is computed on the next catalog read
+    pub async fn create_provenance(
+        &self,
+        package_id: Uuid,
+        usage_hours: i32,
+        quantity_available: i32,
+    ) -> Result<PackageProvenance> {
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO package_provenance (package_id, provenance_type, usage_hours, quantity_available)
+            VALUES ($1, CASE WHEN $2 = 0 THEN 'new' ELSE 'used' END, $2, $3)
+            RETURNING {PROVENANCE_COLUMNS}
+            "#
+        ))
+        .bind(package_id)
+        .bind(usage_hours)
+        .bind(quantity_available)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(provenance_from_row(&row))
+    }
+
+    /// Returns `None` if the package has no such provenance. Changing the
+    /// hours marks the cached price stale by trigger.
+    pub async fn update_provenance(
+        &self,
+        package_id: Uuid,
+        id: i32,
+        usage_hours: i32,
+        quantity_available: i32,
+        is_active: bool,
+    ) -> Result<Option<PackageProvenance>> {
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE package_provenance SET
+                provenance_type = CASE WHEN $3 = 0 THEN 'new' ELSE 'used' END,
+                usage_hours = $3, quantity_available = $4, is_active = $5
+            WHERE package_id = $1 AND id = $2
+            RETURNING {PROVENANCE_COLUMNS}
+            "#
+        ))
+        .bind(package_id)
+        .bind(id)
+        .bind(usage_hours)
+        .bind(quantity_available)
+        .bind(is_active)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(provenance_from_row))
+    }
+
+    /// Apply the writes of a catalog sync in order, all or none
+    pub async fn apply_catalog_writes(&self, writes: &[CatalogWrite]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for write in writes {
+            match write {
+                CatalogWrite::CreatePackage {
+                    id,
+                    sku,
+                    fields,
+                    setup_price_usdc,
+                    monthly_price_usdc,
+                } => {
+                    insert_package(
+                        &mut tx,
+                        *id,
+                        sku,
+                        fields,
+                        *setup_price_usdc,
+                        *monthly_price_usdc,
+                    )
+                    .await?;
+                }
+                CatalogWrite::UpdatePackage { id, fields } => {
+                    write_package_fields(&mut tx, *id, fields).await?;
+                }
+                CatalogWrite::SetPackageActive { id, is_active } => {
+                    write_package_active(&mut tx, *id, *is_active).await?;
+                }
+                CatalogWrite::ChangePrices {
+                    package_id,
+                    setup_price_usdc,
+                    monthly_price_usdc,
+                    effective_from,
+                    note,
+                } => {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO package_prices (package_id, setup_price_usdc, monthly_price_usdc, effective_from, note)
+                        VALUES ($1, $2, $3, $4, $5)
+                        "#,
+                    )
+                    .bind(package_id)
+                    .bind(setup_price_usdc)
+                    .bind(monthly_price_usdc)
+                    .bind(effective_from)
+                    .bind(note)
+                    .execute(&mut *tx)
+                    .await?;
+
+                    sqlx::query(
+                        "UPDATE packages SET setup_price_usdc = $2, monthly_price_usdc = $3 WHERE id = $1",
+                    )
+                    .bind(package_id)
+                    .bind(setup_price_usdc)
+                    .bind(monthly_price_usdc)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                CatalogWrite::SaveDepreciationRule(rule) => {
+                    write_depreciation_rule(&mut tx, rule).await?;
+                }
+                CatalogWrite::AddImage {
+                    id,
+                    package_id,
+                    filename,
+                    title,
+                    description,
+                } => {
+                    insert_package_image(&mut tx, *id, *package_id, filename, title, description)
+                        .await?;
+                }
+                CatalogWrite::UpdateImage {
+                    id,
+                    package_id,
+                    filename,
+                    title,
+                    description,
+                } => {
+                    write_package_image(&mut tx, *package_id, *id, filename, title, description)
+                        .await?;
+                }
+                CatalogWrite::RemoveImage { id, package_id } => {
+                    remove_package_image(&mut tx, *package_id, *id).await?;
+                }
+                CatalogWrite::OrderImages {
+                    package_id,
+                    image_ids,
+                } => {
+                    write_image_order(&mut tx, *package_id, image_ids).await?;
+                }
+                CatalogWrite::UpsertProvenance {
+                    package_id,
+                    usage_hours,
+                    quantity_available,
+                    is_active,
+                } => {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO package_provenance (package_id, provenance_type, usage_hours, quantity_available, is_active)
+                        VALUES ($1, CASE WHEN $2 = 0 THEN 'new' ELSE 'used' END, $2, $3, $4)
+                        ON CONFLICT (package_id, usage_hours) DO UPDATE SET
+                            quantity_available = EXCLUDED.quantity_available,
+                            is_active = EXCLUDED.is_active
+                        "#,
+                    )
+                    .bind(package_id)
+                    .bind(usage_hours)
+                    .bind(quantity_available)
+                    .bind(is_active)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+async fn insert_package(
+    conn: &mut PgConnection,
+    id: Uuid,
+    sku: &str,
+    fields: &PackageFields,
+    setup_price_usdc: Money,
+    monthly_price_usdc: Money,
+) -> Result<Package> {
+    let row = sqlx::query(&format!(
+        r#"
+        INSERT INTO packages
+        (id, sku, name, description, hardware_description, cpu_cores, ram_gb, storage_gb,
+         gpu_class, gpu_count, vram_gb, availability_type, availability_value,
+         setup_price_usdc, monthly_price_usdc, is_active)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, false)
+        RETURNING {PACKAGE_COLUMNS}
+        "#
+    ))
+    .bind(id)
+    .bind(sku)
+    .bind(&fields.name)
+    .bind(&fields.description)
+    .bind(&fields.hardware_description)
+    .bind(fields.cpu_cores)
+    .bind(fields.ram_gb)
+    .bind(fields.storage_gb)
+    .bind(&fields.gpu_class)
+    .bind(fields.gpu_count)
+    .bind(fields.vram_gb)
+    .bind(&fields.availability_type)
+    .bind(fields.availability_value)
+    .bind(setup_price_usdc)
+    .bind(monthly_price_usdc)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(package_from_row(&row))
+}
+
+async fn write_package_fields(
+    conn: &mut PgConnection,
+    id: Uuid,
+    fields: &PackageFields,
+) -> Result<Package> {
+    let row = sqlx::query(&format!(
+        r#"
+        UPDATE packages SET
+            name = $2, description = $3, hardware_description = $4, cpu_cores = $5,
+            ram_gb = $6, storage_gb = $7, gpu_class = $8, gpu_count = $9,
+            vram_gb = $10, availability_type = $11, availability_value = $12
+        WHERE id = $1
+        RETURNING {PACKAGE_COLUMNS}
+        "#
+    ))
+    .bind(id)
+    .bind(&fields.name)
+    .bind(&fields.description)
+    .bind(&fields.hardware_description)
+    .bind(fields.cpu_cores)
+    .bind(fields.ram_gb)
+    .bind(fields.storage_gb)
+    .bind(&fields.gpu_class)
+    .bind(fields.gpu_count)
+    .bind(fields.vram_gb)
+    .bind(&fields.availability_type)
+    .bind(fields.availability_value)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(package_from_row(&row))
+}
+
+async fn write_package_active(conn: &mut PgConnection, id: Uuid, is_active: bool) -> Result<()> {
+    sqlx::query("UPDATE packages SET is_active = $2 WHERE id = $1")
+        .bind(id)
+        .bind(is_active)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+pub(crate) async fn insert_package_image(
+    conn: &mut PgConnection,
+    id: Uuid,
+    package_id: Uuid,
+    filename: &str,
+    title: &str,
+    description: &str,
+) -> Result<CatalogImage> {
+    let row = sqlx::query(&format!(
+        r#"
+        INSERT INTO package_images (id, package_id, filename, title, description, sort_order)
+        VALUES ($1, $2, $3, $4, $5,
+            (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM package_images WHERE package_id = $2))
+        RETURNING {IMAGE_COLUMNS}
+        "#
+    ))
+    .bind(id)
+    .bind(package_id)
+    .bind(filename)
+    .bind(title)
+    .bind(description)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(catalog_image_from_row(&row))
+}
+
+async fn write_package_image(
+    conn: &mut PgConnection,
+    package_id: Uuid,
+    id: Uuid,
+    filename: &str,
+    title: &str,
+    description: &str,
+) -> Result<Option<CatalogImage>> {
+    let row = sqlx::query(&format!(
+        r#"
+        UPDATE package_images SET filename = $3, title = $4, description = $5
+        WHERE package_id = $1 AND id = $2
+        RETURNING {IMAGE_COLUMNS}
+        "#
+    ))
+    .bind(package_id)
+    .bind(id)
+    .bind(filename)
+    .bind(title)
+    .bind(description)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(row.as_ref().map(catalog_image_from_row))
+}
+
+async fn remove_package_image(conn: &mut PgConnection, package_id: Uuid, id: Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM package_images WHERE package_id = $1 AND id = $2")
+        .bind(package_id)
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+async fn write_image_order(
+    conn: &mut PgConnection,
+    package_id: Uuid,
+    image_ids: &[Uuid],
+) -> Result<()> {
+    for (position, id) in image_ids.iter().enumerate() {
+        sqlx::query("UPDATE package_images SET sort_order = $3 WHERE package_id = $1 AND id = $2")
+            .bind(package_id)
+            .bind(id)
+            .bind(position as i32 + 1)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn package_from_row(row: &sqlx::postgres::PgRow) -> Package {
+    Package {
+        id: row.get("id"),
+        name: row.get("name"),
+        sku: row.get("sku"),
+        description: row.get("description"),
+        hardware_description: row.get("hardware_description"),
+        cpu_cores: row.get("cpu_cores"),
+        ram_gb: row.get("ram_gb"),
+        storage_gb: row.get("storage_gb"),
+        gpu_class: row.get("gpu_class"),
+        gpu_count: row.get("gpu_count"),
+        vram_gb: row.get("vram_gb"),
+        setup_price_usdc: row.get("setup_price_usdc"),
+        monthly_price_usdc: row.get("monthly_price_usdc"),
+        availability_type: row.get("availability_type"),
+        availability_value: row.get("availability_value"),
+        is_active: row.get("is_active"),
+        created_at: row.get("created_at"),
+    }
+}
+
+fn catalog_image_from_row(row: &sqlx::postgres::PgRow) -> CatalogImage {
+    CatalogImage {
+        id: row.get("id"),
+        package_id: row.get("package_id"),
+        filename: row.get("filename"),
+        title: row.get("title"),
+        description: row.get("description"),
+        sort_order: row.get("sort_order"),
+        created_at: row.get("created_at"),
+    }
+}
+
+fn provenance_from_row(row: &sqlx::postgres::PgRow) -> PackageProvenance {
+    PackageProvenance {
+        id: row.get("id"),
+        package_id: row.get("package_id"),
+        provenance_type: row.get("provenance_type"),
+        usage_hours: row.get("usage_hours"),
+        quantity_available: row.get("quantity_available"),
+        is_active: row.get("is_active"),
+        calculated_price_usdc: row.get("calculated_price_usdc"),
+        discount_percentage: row.get("discount_percentage"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
@@ -0,0 +1,357 @@
+use crate::ledger::insert_journal_entry;
+use crate::Database;
+use ai::ledger::{Account, JournalEntry};
+use ai::Money;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CreditNote {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub invoice_id: Uuid,
+    pub reason: String,
+    pub description: String,
+    pub amount_usdc: Money,
+    pub vat_usdc: Money,
+    pub issued_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RefundPayout {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub amount_usdc: Money,
+    pub destination_address: String,
+    pub status: String,
+    pub requested_at: DateTime<Utc>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub review_note: Option<String>,
+    pub tx_hash: Option<String>,
+    pub paid_at: Option<DateTime<Utc>>,
+}
+
+const REFUND_PAYOUT_COLUMNS: &str = "id, org_id, amount_usdc, destination_address, status, \
+    requested_at, reviewed_by, reviewed_at, review_note, tx_hash, paid_at";
+
+impl Database {
+    /// Record a credit note and post it to the ledger in one transaction.
+    /// The invoice row is locked so concurrent credit notes cannot credit
+    /// more than the invoice total between them.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_credit_note(
+        &self,
+        id: Uuid,
+        invoice_id: Uuid,
+        reason: &str,
+        description: &str,
+        amount_usdc: Money,
+        vat_usdc: Money,
+        journal: &JournalEntry,
+    ) -> Result<CreditNote> {
+        let mut tx = self.pool.begin().await?;
+
+        let invoice =
+            sqlx::query("SELECT org_id, total_usdc FROM invoices WHERE id = $1 FOR UPDATE")
+                .bind(invoice_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+        let Some(invoice) = invoice else {
+            bail!("invoice {invoice_id} not found");
+        };
+        let org_id: Uuid = invoice.get("org_id");
+        let total: Money = invoice.get("total_usdc");
+
+        let credited: Money = sqlx::query(
+            "SELECT COALESCE(SUM(amount_usdc), 0)::numeric(20,6) as credited FROM credit_notes WHERE invoice_id = $1",
+        )
+        .bind(invoice_id)
+        .fetch_one(&mut *tx)
+        .await?
+        .get("credited");
+
+        if credited + amount_usdc > total {
+            bail!(
+                "invoice {invoice_id} has {} USDC left to credit",
+                total - credited
+            );
+        }
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO credit_notes (id, org_id, invoice_id, reason, description, amount_usdc, vat_usdc)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, org_id, invoice_id, reason, description, amount_usdc, vat_usdc, issued_at
+            "#,
+        )
+        .bind(id)
+        .bind(org_id)
+        .bind(invoice_id)
+        .bind(reason)
+        .bind(description)
+        .bind(amount_usdc)
+        .bind(vat_usdc)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        insert_journal_entry(&mut tx, journal).await?;
+
+        tx.commit().await?;
+
+        Ok(credit_note_from_row(&row))
+    }
+
+    pub async fn get_credit_notes_for_org(&self, org_id: Uuid) -> Result<Vec<CreditNote>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, org_id, invoice_id, reason, description, amount_usdc, vat_usdc, issued_at
+            FROM credit_notes
+            WHERE org_id = $1
+            ORDER BY issued_at DESC
+            "#,
+        )
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(credit_note_from_row).collect())
+    }
+
+    /// Request a payout of account credit. The organization's ledger
+    /// accounts are locked so concurrent requests cannot hold back more
+    /// credit than is available between them.
+    pub async fn create_refund_payout(
+        &self,
+        id: Uuid,
+        org_id: Uuid,
+        amount_usdc: Money,
+        destination_address: &str,
+    ) -> Result<RefundPayout> {
+        let mut tx = self.pool.begin().await?;
+
+        let credit = lock_org_credit(&mut tx, org_id).await?;
+        let pending: Money = sqlx::query(
+            r#"
+            SELECT COALESCE(SUM(amount_usdc), 0)::numeric(20,6) as pending
+            FROM refund_payouts
+            WHERE org_id = $1 AND status IN ('requested', 'approved')
+            "#,
+        )
+        .bind(org_id)
+        .fetch_one(&mut *tx)
+        .await?
+        .get("pending");
+
+        let available = credit - pending;
+        if amount_usdc > available {
+            bail!("payout of {amount_usdc} USDC exceeds available credit of {available} USDC");
+        }
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO refund_payouts (id, org_id, amount_usdc, destination_address)
+            VALUES ($1, $2, $3, $4)
+            RETURNING {REFUND_PAYOUT_COLUMNS}
+            "#
+        ))
+        .bind(id)
+        .bind(org_id)
+        .bind(amount_usdc)
+        .bind(destination_address)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(refund_payout_from_row(&row))
+    }
+
+    pub async fn get_refund_payout(&self, id: Uuid) -> Result<Option<RefundPayout>> {
+        let row = sqlx::query(&format!(
+            "SELECT {REFUND_PAYOUT_COLUMNS} FROM refund_payouts WHERE id = $1"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(refund_payout_from_row))
+    }
+
+    pub async fn get_refund_payouts_for_org(&self, org_id: Uuid) -> Result<Vec<RefundPayout>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {REFUND_PAYOUT_COLUMNS} FROM refund_payouts WHERE org_id = $1 ORDER BY requested_at DESC"
+        ))
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(refund_payout_from_row).collect())
+    }
+
+    /// Payouts in a given status across all organizations, oldest first
+    pub async fn get_refund_payouts_by_status(&self, status: &str) -> Result<Vec<RefundPayout>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {REFUND_PAYOUT_COLUMNS} FROM refund_payouts WHERE status = $1 ORDER BY requested_at ASC"
+        ))
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(refund_payout_from_row).collect())
+    }
+
+    /// Account credit held back by payouts not yet paid or rejected
+    pub async fn get_pending_payout_total(&self, org_id: Uuid) -> Result<Money> {
+        let row = sqlx::query(
+            r#"
+            SELECT COALESCE(SUM(amount_usdc), 0)::numeric(20,6) as pending
+            FROM refund_payouts
+            WHERE org_id = $1 AND status IN ('requested', 'approved')
+            "#,
+        )
+        .bind(org_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("pending"))
+    }
+
+    /// Approve or reject a requested payout. Returns `None` if it is not
+    /// awaiting review.
+    pub async fn review_refund_payout(
+        &self,
+        id: Uuid,
+        status: &str,
+        reviewer: &str,
+        note: Option<&str>,
+    ) -> Result<Option<RefundPayout>> {
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE refund_payouts
+            SET status = $2, reviewed_by = $3, review_note = $4, reviewed_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND status = 'requested'
+            RETURNING {REFUND_PAYOUT_COLUMNS}
+            "#
+        ))
+        .bind(id)
+        .bind(status)
+        .bind(reviewer)
+        .bind(note)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(refund_payout_from_row))
+    }
+
+    /// Mark an approved payout as sent and post it to the ledger in the same
+    /// transaction. The payout and the organization's ledger accounts are
+    /// locked, and the credit must still cover the payout; it may have been
+    /// applied to an invoice since the payout was requested. Returns `None`
+    /// if it is not approved.
+    pub async fn mark_refund_payout_paid(
+        &self,
+        id: Uuid,
+        tx_hash: &str,
+        journal: &JournalEntry,
+    ) -> Result<Option<RefundPayout>> {
+        let mut tx = self.pool.begin().await?;
+
+        let payout = sqlx::query(
+            "SELECT org_id, amount_usdc FROM refund_payouts WHERE id = $1 AND status = 'approved' FOR UPDATE",
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(payout) = payout else {
+            return Ok(None);
+        };
+        let org_id: Uuid = payout.get("org_id");
+        let amount: Money = payout.get("amount_usdc");
+
+        let credit = lock_org_credit(&mut tx, org_id).await?;
+        if credit < amount {
+            bail!("payout of {amount} USDC exceeds remaining credit of {credit} USDC");
+        }
+
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE refund_payouts
+            SET status = 'paid', tx_hash = $2, paid_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND status = 'approved'
+            RETURNING {REFUND_PAYOUT_COLUMNS}
+            "#
+        ))
+        .bind(id)
+        .bind(tx_hash)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        insert_journal_entry(&mut tx, journal).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(refund_payout_from_row(&row)))
+    }
+}
+
+/// Lock the organization's ledger accounts until the transaction ends and
+/// return the balance of its customer credit account. Credit accounts are
+/// liabilities, so their balance is credit-normal.
+async fn lock_org_credit(conn: &mut PgConnection, org_id: Uuid) -> Result<Money> {
+    sqlx::query("SELECT id FROM ledger_accounts WHERE org_id = $1 ORDER BY id FOR UPDATE")
+        .bind(org_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT COALESCE(SUM(jl.credit_usdc - jl.debit_usdc), 0)::numeric(20,6) as credit
+        FROM ledger_accounts la
+        JOIN journal_lines jl ON jl.account_id = la.id
+        WHERE la.org_id = $1 AND la.code = $2
+        "#,
+    )
+    .bind(org_id)
+    .bind(Account::CustomerCredit { org_id }.code())
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(row.get("credit"))
+}
+
+fn credit_note_from_row(row: &sqlx::postgres::PgRow) -> CreditNote {
+    CreditNote {
+        id: row.get("id"),
+        org_id: row.get("org_id"),
+        invoice_id: row.get("invoice_id"),
+        reason: row.get("reason"),
+        description: row.get("description"),
+        amount_usdc: row.get("amount_usdc"),
+        vat_usdc: row.get("vat_usdc"),
+        issued_at: row.get("issued_at"),
+    }
+}
+
+fn refund_payout_from_row(row: &sqlx::postgres::PgRow) -> RefundPayout {
+    RefundPayout {
+        id: row.get("id"),
+        org_id: row.get("org_id"),
+        amount_usdc: row.get("amount_usdc"),
+        destination_address: row.get("destination_address"),
+        status: row.get("status"),
+        requested_at: row.get("requested_at"),
+        reviewed_by: row.get("reviewed_by"),
+        reviewed_at: row.get("reviewed_at"),
+        review_note: row.get("review_note"),
+        tx_hash: row.get("tx_hash"),
+        paid_at: row.get("paid_at"),
+    }
+}
@@ -0,0 +1,330 @@
+use crate::Database;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DepreciationRule {
+    pub id: i32,
+    pub package_id: Uuid,
+    pub final_depreciated_percentage: Decimal,
+    pub full_depreciation_hours: i32,
+    pub depreciation_curve: String,
+    /// Set for custom curves only
+    pub schedule_interpolation: Option<String>,
+    /// Breakpoints of a custom curve, ordered by hours; the two vectors
+    /// are parallel
+    pub schedule_hours: Vec<i32>,
+    pub schedule_percentages: Vec<Decimal>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Depreciation rule to be saved for a package
+#[derive(Debug, Clone)]
+pub struct NewDepreciationRule {
+    pub package_id: Uuid,
+    pub final_depreciated_percentage: Decimal,
+    pub full_depreciation_hours: i32,
+    pub depreciation_curve: String,
+    pub schedule_interpolation: Option<String>,
+    /// (usage hours, remaining percentage)
+    pub schedule: Vec<(i32, Decimal)>,
+}
+
+/// A rule change waiting for its effective date
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ScheduledDepreciationRule {
+    pub id: i32,
+    pub package_id: Uuid,
+    pub final_depreciated_percentage: Decimal,
+    pub full_depreciation_hours: i32,
+    pub depreciation_curve: String,
+    pub schedule_interpolation: Option<String>,
+    pub schedule_hours: Vec<i32>,
+    pub schedule_percentages: Vec<Decimal>,
+    pub effective_at: DateTime<Utc>,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+impl ScheduledDepreciationRule {
+    fn to_new_rule(&self) -> NewDepreciationRule {
+        NewDepreciationRule {
+            package_id: self.package_id,
+            final_depreciated_percentage: self.final_depreciated_percentage,
+            full_depreciation_hours: self.full_depreciation_hours,
+            depreciation_curve: self.depreciation_curve.clone(),
+            schedule_interpolation: self.schedule_interpolation.clone(),
+            schedule: self
+                .schedule_hours
+                .iter()
+                .copied()
+                .zip(self.schedule_percentages.iter().copied())
+                .collect(),
+        }
+    }
+}
+
+const SCHEDULED_RULE_COLUMNS: &str = "id, package_id, final_depreciated_percentage, \
+    full_depreciation_hours, depreciation_curve, schedule_interpolation, schedule_hours, \
+    schedule_percentages, effective_at, status, created_at, applied_at";
+
+const DEPRECIATION_RULE_COLUMNS: &str = r#"
+    dr.id, dr.package_id, dr.final_depreciated_percentage, dr.full_depreciation_hours,
+    dr.depreciation_curve, dr.schedule_interpolation, dr.created_at, dr.updated_at,
+    ARRAY(
+        SELECT usage_hours FROM depreciation_schedule_points
+        WHERE package_id = dr.package_id ORDER BY usage_hours
+    ) as schedule_hours,
+    ARRAY(
+        SELECT remaining_percentage FROM depreciation_schedule_points
+        WHERE package_id = dr.package_id ORDER BY usage_hours
+    ) as schedule_percentages
+"#;
+
+impl Database {
+    pub async fn get_depreciation_rules(&self) -> Result<Vec<DepreciationRule>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {DEPRECIATION_RULE_COLUMNS} FROM package_depreciation_rules dr ORDER BY dr.package_id"
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(depreciation_rule_from_row).collect())
+    }
+
+    pub async fn get_depreciation_rule_by_package_id(
+        &self,
+        package_id: Uuid,
+    ) -> Result<Option<DepreciationRule>> {
+        let row = sqlx::query(&format!(
+            "SELECT {DEPRECIATION_RULE_COLUMNS} FROM package_depreciation_rules dr WHERE dr.package_id = $1"
+        ))
+        .bind(package_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(depreciation_rule_from_row))
+    }
+
+    /// Create or replace a package's rule together with its schedule
+    /// breakpoints. Cached prices of the package are marked stale by trigger.
+    pub async fn save_depreciation_rule(
+        &self,
+        rule: &NewDepreciationRule,
+    ) -> Result<DepreciationRule> {
+        let mut tx = self.pool.begin().await?;
+        write_depreciation_rule(&mut tx, rule).await?;
+        tx.commit().await?;
+
+        self.get_depreciation_rule_by_package_id(rule.package_id)
+            .await?
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "depreciation rule for {} missing after save",
+                    rule.package_id
+                )
+            })
+    }
+
+    pub async fn create_scheduled_depreciation_rule(
+        &self,
+        rule: &NewDepreciationRule,
+        effective_at: DateTime<Utc>,
+    ) -> Result<ScheduledDepreciationRule> {
+        let (hours, percentages): (Vec<i32>, Vec<Decimal>) = rule.schedule.iter().copied().unzip();
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO scheduled_depreciation_rules
+            (package_id, final_depreciated_percentage, full_depreciation_hours, depreciation_curve,
+             schedule_interpolation, schedule_hours, schedule_percentages, effective_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING {SCHEDULED_RULE_COLUMNS}
+            "#
+        ))
+        .bind(rule.package_id)
+        .bind(rule.final_depreciated_percentage)
+        .bind(rule.full_depreciation_hours)
+        .bind(&rule.depreciation_curve)
+        .bind(&rule.schedule_interpolation)
+        .bind(&hours)
+        .bind(&percentages)
+        .bind(effective_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(scheduled_rule_from_row(&row))
+    }
+
+    /// Scheduled rule changes in a given status, soonest first
+    pub async fn get_scheduled_depreciation_rules(
+        &self,
+        status: &str,
+    ) -> Result<Vec<ScheduledDepreciationRule>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {SCHEDULED_RULE_COLUMNS} FROM scheduled_depreciation_rules WHERE status = $1 ORDER BY effective_at, id"
+        ))
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(scheduled_rule_from_row).collect())
+    }
+
+    /// Pending changes whose effective date has passed, in the order they
+    /// took effect
+    pub async fn get_due_scheduled_depreciation_rules(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<ScheduledDepreciationRule>> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {SCHEDULED_RULE_COLUMNS} FROM scheduled_depreciation_rules
+            WHERE status = 'pending' AND effective_at <= $1
+            ORDER BY effective_at, id
+            "#
+        ))
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(scheduled_rule_from_row).collect())
+    }
+
+    /// Make a pending scheduled change the package's rule. Returns `None` if
+    /// it is no longer pending, e.g. another request applied it first.
+    pub async fn apply_scheduled_depreciation_rule(
+        &self,
+        id: i32,
+    ) -> Result<Option<ScheduledDepreciationRule>> {
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE scheduled_depreciation_rules
+            SET status = 'applied', applied_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND status = 'pending'
+            RETURNING {SCHEDULED_RULE_COLUMNS}
+            "#
+        ))
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let scheduled = scheduled_rule_from_row(&row);
+
+        write_depreciation_rule(&mut tx, &scheduled.to_new_rule()).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(scheduled))
+    }
+
+    /// Returns `None` if the change is not pending
+    pub async fn cancel_scheduled_depreciation_rule(
+        &self,
+        id: i32,
+    ) -> Result<Option<ScheduledDepreciationRule>> {
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE scheduled_depreciation_rules
+            SET status = 'cancelled'
+            WHERE id = $1 AND status = 'pending'
+            RETURNING {SCHEDULED_RULE_COLUMNS}
+            "#
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(scheduled_rule_from_row))
+    }
+}
+
+pub(crate) async fn write_depreciation_rule(
+    conn: &mut PgConnection,
+    rule: &NewDepreciationRule,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO package_depreciation_rules
+        (package_id, final_depreciated_percentage, full_depreciation_hours,
+         depreciation_curve, schedule_interpolation)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (package_id) DO UPDATE SET
+            final_depreciated_percentage = EXCLUDED.final_depreciated_percentage,
+            full_depreciation_hours = EXCLUDED.full_depreciation_hours,
+            depreciation_curve = EXCLUDED.depreciation_curve,
+            schedule_interpolation = EXCLUDED.schedule_interpolation
+        "#,
+    )
+    .bind(rule.package_id)
+    .bind(rule.final_depreciated_percentage)
+    .bind(rule.full_depreciation_hours)
+    .bind(&rule.depreciation_curve)
+    .bind(&rule.schedule_interpolation)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("DELETE FROM depreciation_schedule_points WHERE package_id = $1")
+        .bind(rule.package_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for (usage_hours, remaining_percentage) in &rule.schedule {
+        sqlx::query(
+            r#"
+            INSERT INTO depreciation_schedule_points (package_id, usage_hours, remaining_percentage)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(rule.package_id)
+        .bind(usage_hours)
+        .bind(remaining_percentage)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+fn depreciation_rule_from_row(row: &sqlx::postgres::PgRow) -> DepreciationRule {
+    DepreciationRule {
+        id: row.get("id"),
+        package_id: row.get("package_id"),
+        final_depreciated_percentage: row.get("final_depreciated_percentage"),
+        full_depreciation_hours: row.get("full_depreciation_hours"),
+        depreciation_curve: row.get("depreciation_curve"),
+        schedule_interpolation: row.get("schedule_interpolation"),
+        schedule_hours: row.get("schedule_hours"),
+        schedule_percentages: row.get("schedule_percentages"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn scheduled_rule_from_row(row: &sqlx::postgres::PgRow) -> ScheduledDepreciationRule {
+    ScheduledDepreciationRule {
+        id: row.get("id"),
+        package_id: row.get("package_id"),
+        final_depreciated_percentage: row.get("final_depreciated_percentage"),
+        full_depreciation_hours: row.get("full_depreciation_hours"),
+        depreciation_curve: row.get("depreciation_curve"),
+        schedule_interpolation: row.get("schedule_interpolation"),
+        schedule_hours: row.get("schedule_hours"),
+        schedule_percentages: row.get("schedule_percentages"),
+        effective_at: row.get("effective_at"),
+        status: row.get("status"),
+        created_at: row.get("created_at"),
+        applied_at: row.get("applied_at"),
+    }
+}
@@ -0,0 +1,309 @@
+use crate::Database;
+use ai::Money;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct FinancingPlan {
+    pub id: i32,
+    pub name: String,
+    pub term_months: i32,
+    pub annual_rate_percent: Decimal,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct FinancingAgreement {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub order_id: Uuid,
+    pub server_id: Option<Uuid>,
+    pub plan_id: i32,
+    pub plan_name: String,
+    pub sku: Option<String>,
+    pub principal_usdc: Money,
+    pub annual_rate_percent: Decimal,
+    pub term_months: i32,
+    pub instalments_billed: i64,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub ownership_transferred_at: Option<DateTime<Utc>>,
+}
+
+/// Agreement to be written with a new order, on a plan's current terms
+#[derive(Debug, Clone)]
+pub struct NewFinancingAgreement {
+    pub plan_id: i32,
+    pub principal_usdc: Money,
+    pub annual_rate_percent: Decimal,
+    pub term_months: i32,
+}
+
+/// An activated server on an active agreement with no instalment billed for
+/// the period yet
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct FinancedServer {
+    pub server_id: Uuid,
+    pub org_id: Uuid,
+    pub hostname: String,
+    pub package_name: String,
+    pub principal_usdc: Money,
+    pub annual_rate_percent: Decimal,
+    pub term_months: i32,
+    pub instalments_billed: i64,
+    pub activated_at: DateTime<Utc>,
+}
+
+const FINANCING_PLAN_COLUMNS: &str =
+    "id, name, term_months, annual_rate_percent, is_active, created_at";
+
+const FINANCING_AGREEMENT_COLUMNS: &str = r#"
+    fa.id, fa.org_id, fa.order_id, s.id as server_id, fa.plan_id, fp.name as plan_name,
+    p.sku, fa.principal_usdc, fa.annual_rate_percent, fa.term_months,
+    (SELECT COUNT(*) FROM invoice_lines il
+     WHERE il.server_id = s.id AND il.kind = 'instalment') as instalments_billed,
+    fa.status, fa.created_at, fa.ownership_transferred_at
+"#;
+
+const FINANCING_AGREEMENT_JOINS: &str = r#"
+    JOIN financing_plans fp ON fp.id = fa.plan_id
+    JOIN server_orders o ON o.id = fa.order_id
+    LEFT JOIN packages p ON p.id = o.package_id
+    LEFT JOIN servers s ON s.order_id = fa.order_id
+"#;
+
+impl Database {
+    pub async fn get_financing_plans(&self, active_only: bool) -> Result<Vec<FinancingPlan>> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {FINANCING_PLAN_COLUMNS} FROM financing_plans
+            WHERE is_active OR NOT $1
+            ORDER BY term_months, id
+            "#
+        ))
+        .bind(active_only)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(financing_plan_from_row).collect())
+    }
+
+    pub async fn get_financing_plan(&self, id: i32) -> Result<Option<FinancingPlan>> {
+        let row = sqlx::query(&format!(
+            "SELECT {FINANCING_PLAN_COLUMNS} FROM financing_plans WHERE id = $1"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(financing_plan_from_row))
+    }
+
+    pub async fn create_financing_plan(
+        &self,
+        name: &str,
+        term_months: i32,
+        annual_rate_percent: Decimal,
+    ) -> Result<FinancingPlan> {
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO financing_plans (name, term_months, annual_rate_percent)
+            VALUES ($1, $2, $3)
+            RETURNING {FINANCING_PLAN_COLUMNS}
+            "#
+        ))
+        .bind(name)
+        .bind(term_months)
+        .bind(annual_rate_percent)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(financing_plan_from_row(&row))
+    }
+
+    /// Stop offering a plan; existing agreements keep running on it
+    pub async fn deactivate_financing_plan(&self, id: i32) -> Result<bool> {
+        let result = sqlx::query("UPDATE financing_plans SET is_active = false WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Newest first
+    pub async fn get_financing_agreements_for_org(
+        &self,
+        org_id: Uuid,
+    ) -> Result<Vec<FinancingAgreement>> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {FINANCING_AGREEMENT_COLUMNS}
+            FROM financing_agreements fa {FINANCING_AGREEMENT_JOINS}
+            WHERE fa.org_id = $1
+            ORDER BY fa.created_at DESC
+            "#
+        ))
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(financing_agreement_from_row).collect())
+    }
+
+    /// Servers on active agreements, activated before `period_end`, with
+    /// instalments left and none billed for the period yet. Decommissioned
+    /// servers stay in until the hardware is paid off.
+    pub async fn get_unbilled_financed_servers(
+        &self,
+        period_start: NaiveDate,
+        period_end: NaiveDate,
+    ) -> Result<Vec<FinancedServer>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM (
+                SELECT
+                    s.id as server_id, s.org_id, s.hostname, p.name as package_name,
+                    fa.principal_usdc, fa.annual_rate_percent, fa.term_months,
+                    (SELECT COUNT(*) FROM invoice_lines il
+                     WHERE il.server_id = s.id AND il.kind = 'instalment') as instalments_billed,
+                    s.activated_at
+                FROM financing_agreements fa
+                JOIN servers s ON s.order_id = fa.order_id
+                JOIN packages p ON p.id = s.package_id
+                WHERE fa.status = 'active'
+                  AND s.activated_at IS NOT NULL
+                  AND s.activated_at < $2::date
+                  AND NOT EXISTS (
+                      SELECT 1 FROM invoice_lines il
+                      WHERE il.server_id = s.id
+                        AND il.period_start = $1
+                        AND il.kind = 'instalment'
+                  )
+            ) f
+            WHERE f.instalments_billed < f.term_months
+            ORDER BY f.org_id, f.activated_at
+            "#,
+        )
+        .bind(period_start)
+        .bind(period_end)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| FinancedServer {
+                server_id: row.get("server_id"),
+                org_id: row.get("org_id"),
+                hostname: row.get("hostname"),
+                package_name: row.get("package_name"),
+                principal_usdc: row.get("principal_usdc"),
+                annual_rate_percent: row.get("annual_rate_percent"),
+                term_months: row.get("term_months"),
+                instalments_billed: row.get("instalments_billed"),
+                activated_at: row.get("activated_at"),
+            })
+            .collect())
+    }
+
+    /// Complete the active agreements whose every instalment is on a paid
+    /// invoice, transferring ownership. Returns the completed agreements.
+    pub async fn complete_paid_financing_agreements(&self) -> Result<Vec<FinancingAgreement>> {
+        let ids: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            UPDATE financing_agreements fa
+            SET status = 'completed', ownership_transferred_at = CURRENT_TIMESTAMP
+            FROM servers s
+            WHERE s.order_id = fa.order_id
+              AND fa.status = 'active'
+              AND (
+                  SELECT COUNT(*) FROM invoice_lines il
+                  JOIN invoices i ON i.id = il.invoice_id
+                  WHERE il.server_id = s.id AND il.kind = 'instalment' AND i.status = 'paid'
+              ) >= fa.term_months
+            RETURNING fa.id
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {FINANCING_AGREEMENT_COLUMNS}
+            FROM financing_agreements fa {FINANCING_AGREEMENT_JOINS}
+            WHERE fa.id = ANY($1)
+            "#
+        ))
+        .bind(&ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(financing_agreement_from_row).collect())
+    }
+}
+
+/// Write the agreement of an order placed on a financing plan
+pub(crate) async fn insert_financing_agreement(
+    conn: &mut PgConnection,
+    org_id: Uuid,
+    order_id: Uuid,
+    agreement: &NewFinancingAgreement,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO financing_agreements
+        (id, org_id, order_id, plan_id, principal_usdc, annual_rate_percent, term_months)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(org_id)
+    .bind(order_id)
+    .bind(agreement.plan_id)
+    .bind(agreement.principal_usdc)
+    .bind(agreement.annual_rate_percent)
+    .bind(agreement.term_months)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+fn financing_plan_from_row(row: &sqlx::postgres::PgRow) -> FinancingPlan {
+    FinancingPlan {
+        id: row.get("id"),
+        name: row.get("name"),
+        term_months: row.get("term_months"),
+        annual_rate_percent: row.get("annual_rate_percent"),
+        is_active: row.get("is_active"),
+        created_at: row.get("created_at"),
+    }
+}
+
+fn financing_agreement_from_row(row: &sqlx::postgres::PgRow) -> FinancingAgreement {
+    FinancingAgreement {
+        id: row.get("id"),
+        org_id: row.get("org_id"),
+        order_id: row.get("order_id"),
+        server_id: row.get("server_id"),
+        plan_id: row.get("plan_id"),
+        plan_name: row.get("plan_name"),
+        sku: row.get("sku"),
+        principal_usdc: row.get("principal_usdc"),
+        annual_rate_percent: row.get("annual_rate_percent"),
+        term_months: row.get("term_months"),
+        instalments_billed: row.get("instalments_billed"),
+        status: row.get("status"),
+        created_at: row.get("created_at"),
+        ownership_transferred_at: row.get("ownership_transferred_at"),
+    }
+}
@@ -0,0 +1,164 @@
+use crate::Database;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+/// An order of a build package that is queued or being built
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OpenBuild {
+    pub order_id: Uuid,
+    pub package_id: Uuid,
+    /// The package's build hours
+    pub build_hours: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub build_started_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BuildParts {
+    pub package_id: Uuid,
+    pub kits_in_stock: i32,
+    pub lead_time_hours: i32,
+    pub updated_at: DateTime<Utc>,
+}
+
+const BUILD_PARTS_COLUMNS: &str = "package_id, kits_in_stock, lead_time_hours, updated_at";
+
+impl Database {
+    /// Open builds in the order they were placed
+    pub async fn get_open_builds(&self) -> Result<Vec<OpenBuild>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT o.id AS order_id, o.package_id, p.availability_value AS build_hours,
+                   o.created_at, o.build_started_at
+            FROM server_orders o
+            JOIN packages p ON p.id = o.package_id
+            WHERE o.status IN ('queued', 'provisioning')
+              AND p.availability_type = 'build'
+            ORDER BY o.created_at, o.id
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| OpenBuild {
+                order_id: row.get("order_id"),
+                package_id: row.get("package_id"),
+                build_hours: row.get("build_hours"),
+                created_at: row.get("created_at"),
+                build_started_at: row.get("build_started_at"),
+            })
+            .collect())
+    }
+
+    pub async fn get_build_parts(&self) -> Result<Vec<BuildParts>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {BUILD_PARTS_COLUMNS} FROM package_build_parts"
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(build_parts_from_row).collect())
+    }
+
+    pub async fn get_package_build_parts(&self, package_id: Uuid) -> Result<Option<BuildParts>> {
+        let row = sqlx::query(&format!(
+            "SELECT {BUILD_PARTS_COLUMNS} FROM package_build_parts WHERE package_id = $1"
+        ))
+        .bind(package_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(build_parts_from_row))
+    }
+
+    pub async fn set_package_build_parts(
+        &self,
+        package_id: Uuid,
+        kits_in_stock: i32,
+        lead_time_hours: i32,
+    ) -> Result<BuildParts> {
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO package_build_parts (package_id, kits_in_stock, lead_time_hours)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (package_id) DO UPDATE SET
+                kits_in_stock = EXCLUDED.kits_in_stock,
+                lead_time_hours = EXCLUDED.lead_time_hours,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING {BUILD_PARTS_COLUMNS}
+            "#
+        ))
+        .bind(package_id)
+        .bind(kits_in_stock)
+        .bind(lead_time_hours)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(build_parts_from_row(&row))
+    }
+
+    /// Move a queued order to provisioning, taking a parts kit of its
+    /// package if there is one. Returns false if the order is not queued.
+    pub async fn start_order_build(&self, order_id: Uuid) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query(
+            r#"
+            UPDATE server_orders
+            SET status = 'provisioning', build_started_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND status = 'queued'
+            RETURNING package_id
+            "#,
+        )
+        .bind(order_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(row) = row else {
+            return Ok(false);
+        };
+
+        let package_id: Option<Uuid> = row.get("package_id");
+        if let Some(package_id) = package_id {
+            sqlx::query(
+                r#"
+                UPDATE package_build_parts
+                SET kits_in_stock = GREATEST(kits_in_stock - 1, 0), updated_at = CURRENT_TIMESTAMP
+                WHERE package_id = $1
+                "#,
+            )
+            .bind(package_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Move an order being provisioned to active. Returns false if it is
+    /// not being provisioned.
+    pub async fn finish_order_build(&self, order_id: Uuid) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE server_orders SET status = 'active' WHERE id = $1 AND status = 'provisioning'",
+        )
+        .bind(order_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+fn build_parts_from_row(row: &sqlx::postgres::PgRow) -> BuildParts {
+    BuildParts {
+        package_id: row.get("package_id"),
+        kits_in_stock: row.get("kits_in_stock"),
+        lead_time_hours: row.get("lead_time_hours"),
+        updated_at: row.get("updated_at"),
+    }
+}
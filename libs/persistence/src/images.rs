@@ -0,0 +1,141 @@
+use crate::catalog::insert_package_image;
+use crate::{CatalogImage, Database};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PackageImageVariant {
+    pub id: i64,
+    pub image_id: Uuid,
+    pub format: String,
+    pub width: i32,
+    pub height: i32,
+    pub storage_key: String,
+    pub url: String,
+    pub byte_size: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A rendition already written to storage, to be recorded with its image
+#[derive(Debug, Clone)]
+pub struct NewImageVariant {
+    pub format: String,
+    pub width: i32,
+    pub height: i32,
+    pub storage_key: String,
+    pub url: String,
+    pub byte_size: i64,
+}
+
+const VARIANT_COLUMNS: &str = r#"
+    v.id, v.image_id, v.format, v.width, v.height,
+    v.storage_key, v.url, v.byte_size, v.created_at
+"#;
+
+impl Database {
+    /// Append an uploaded photo after the package's existing images, with
+    /// its renditions
+    #[allow(clippy::too_many_arguments)]
+    pub async fn add_uploaded_package_image(
+        &self,
+        id: Uuid,
+        package_id: Uuid,
+        filename: &str,
+        title: &str,
+        description: &str,
+        storage_key: &str,
+        variants: &[NewImageVariant],
+    ) -> Result<CatalogImage> {
+        let mut tx = self.pool.begin().await?;
+
+        let image =
+            insert_package_image(&mut tx, id, package_id, filename, title, description).await?;
+        sqlx::query("UPDATE package_images SET storage_key = $2 WHERE id = $1")
+            .bind(id)
+            .bind(storage_key)
+            .execute(&mut *tx)
+            .await?;
+
+        for variant in variants {
+            sqlx::query(
+                r#"
+                INSERT INTO package_image_variants
+                (image_id, format, width, height, storage_key, url, byte_size)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+            )
+            .bind(id)
+            .bind(&variant.format)
+            .bind(variant.width)
+            .bind(variant.height)
+            .bind(&variant.storage_key)
+            .bind(&variant.url)
+            .bind(variant.byte_size)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(image)
+    }
+
+    /// Renditions of every image of a package, narrowest first
+    pub async fn get_package_image_variants(
+        &self,
+        package_id: Uuid,
+    ) -> Result<Vec<PackageImageVariant>> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {VARIANT_COLUMNS}
+            FROM package_image_variants v
+            JOIN package_images i ON i.id = v.image_id
+            WHERE i.package_id = $1
+            ORDER BY v.image_id, v.format, v.width
+            "#
+        ))
+        .bind(package_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(variant_from_row).collect())
+    }
+
+    /// Storage keys of an uploaded image's original and renditions, each
+    /// once; empty for static assets
+    pub async fn get_image_storage_keys(&self, package_id: Uuid, id: Uuid) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT storage_key FROM package_images
+            WHERE package_id = $1 AND id = $2 AND storage_key IS NOT NULL
+            UNION
+            SELECT v.storage_key
+            FROM package_image_variants v
+            JOIN package_images i ON i.id = v.image_id
+            WHERE i.package_id = $1 AND i.id = $2
+            "#,
+        )
+        .bind(package_id)
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| row.get("storage_key")).collect())
+    }
+}
+
+fn variant_from_row(row: &sqlx::postgres::PgRow) -> PackageImageVariant {
+    PackageImageVariant {
+        id: row.get("id"),
+        image_id: row.get("image_id"),
+        format: row.get("format"),
+        width: row.get("width"),
+        height: row.get("height"),
+        storage_key: row.get("storage_key"),
+        url: row.get("url"),
+        byte_size: row.get("byte_size"),
+        created_at: row.get("created_at"),
+    }
+}
@@ -0,0 +1,192 @@
+use crate::Database;
+use ai::ledger::{Account, JournalEntry, Side};
+use ai::Money;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AccountBalance {
+    pub account_id: i32,
+    pub code: String,
+    pub org_id: Option<Uuid>,
+    pub kind: String,
+    pub debit_usdc: Money,
+    pub credit_usdc: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LedgerTotals {
+    pub total_debits_usdc: Money,
+    pub total_credits_usdc: Money,
+}
+
+impl Database {
+    /// Post a journal entry in its own transaction. Returns the entry id, or
+    /// `None` if an entry for the same source was already posted.
+    pub async fn post_journal_entry(&self, entry: &JournalEntry) -> Result<Option<Uuid>> {
+        let mut tx = self.pool.begin().await?;
+        let id = insert_journal_entry(&mut tx, entry).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Debit and credit totals of every account belonging to an organization
+    pub async fn get_org_account_balances(&self, org_id: Uuid) -> Result<Vec<AccountBalance>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                la.id as account_id, la.code, la.org_id, la.kind,
+                COALESCE(SUM(jl.debit_usdc), 0)::numeric(20,6) as debit_usdc,
+                COALESCE(SUM(jl.credit_usdc), 0)::numeric(20,6) as credit_usdc
+            FROM ledger_accounts la
+            LEFT JOIN journal_lines jl ON jl.account_id = la.id
+            WHERE la.org_id = $1
+            GROUP BY la.id
+            ORDER BY la.code
+            "#,
+        )
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let balances = rows
+            .into_iter()
+            .map(|row| AccountBalance {
+                account_id: row.get("account_id"),
+                code: row.get("code"),
+                org_id: row.get("org_id"),
+                kind: row.get("kind"),
+                debit_usdc: row.get("debit_usdc"),
+                credit_usdc: row.get("credit_usdc"),
+            })
+            .collect();
+
+        Ok(balances)
+    }
+
+    /// Totals across the whole ledger
+    pub async fn get_ledger_totals(&self) -> Result<LedgerTotals> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                COALESCE(SUM(debit_usdc), 0)::numeric(20,6) as total_debits_usdc,
+                COALESCE(SUM(credit_usdc), 0)::numeric(20,6) as total_credits_usdc
+            FROM journal_lines
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(LedgerTotals {
+            total_debits_usdc: row.get("total_debits_usdc"),
+            total_credits_usdc: row.get("total_credits_usdc"),
+        })
+    }
+
+    /// Journal entries whose lines do not balance
+    pub async fn get_unbalanced_journal_entries(&self) -> Result<Vec<Uuid>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT je.id
+            FROM journal_entries je
+            LEFT JOIN journal_lines jl ON jl.entry_id = je.id
+            GROUP BY je.id
+            HAVING COALESCE(SUM(jl.debit_usdc), 0) <> COALESCE(SUM(jl.credit_usdc), 0)
+                OR COUNT(jl.id) < 2
+            ORDER BY je.id
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.get("id")).collect())
+    }
+}
+
+/// Write a validated journal entry on an open connection so it can share a
+/// transaction with the event it records. Returns `None` without writing
+/// anything if the source was already posted.
+pub(crate) async fn insert_journal_entry(
+    conn: &mut PgConnection,
+    entry: &JournalEntry,
+) -> Result<Option<Uuid>> {
+    entry
+        .validate()
+        .map_err(|e| anyhow!("refusing to post journal entry: {e}"))?;
+
+    let entry_id = Uuid::new_v4();
+
+    let inserted = sqlx::query(
+        r#"
+        INSERT INTO journal_entries (id, description, source_type, source_id)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (source_type, source_id) DO NOTHING
+        "#,
+    )
+    .bind(entry_id)
+    .bind(&entry.description)
+    .bind(&entry.source_type)
+    .bind(entry.source_id)
+    .execute(&mut *conn)
+    .await?;
+
+    if inserted.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    for posting in &entry.postings {
+        let account_id = ensure_account(&mut *conn, &posting.account).await?;
+        let (debit, credit) = match posting.side {
+            Side::Debit => (posting.amount_usdc, Money::ZERO),
+            Side::Credit => (Money::ZERO, posting.amount_usdc),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO journal_lines (entry_id, account_id, debit_usdc, credit_usdc)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(entry_id)
+        .bind(account_id)
+        .bind(debit)
+        .bind(credit)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(Some(entry_id))
+}
+
+/// Look up an account, creating per-organization accounts on first use
+async fn ensure_account(conn: &mut PgConnection, account: &Account) -> Result<i32> {
+    let existing = sqlx::query(
+        "SELECT id FROM ledger_accounts WHERE code = $1 AND org_id IS NOT DISTINCT FROM $2",
+    )
+    .bind(account.code())
+    .bind(account.org_id())
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some(row) = existing {
+        return Ok(row.get("id"));
+    }
+
+    let row = sqlx::query(
+        r#"
+        INSERT INTO ledger_accounts (code, org_id, kind, name)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+    )
+    .bind(account.code())
+    .bind(account.org_id())
+    .bind(account.kind().as_str())
+    .bind(account.name())
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(row.get("id"))
+}
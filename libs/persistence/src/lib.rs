@@ -1,20 +1,43 @@
-use ai::GpuClass;
+use ai::{GpuClass, Money};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
-pub struct DepreciationRule {
-    pub id: i32,
-    pub package_id: Uuid,
-    pub final_depreciated_percentage: f64,
-    pub full_depreciation_hours: i32,
-    pub depreciation_curve: String,
-    pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
-}
+mod addons;
+mod audit;
+mod billing;
+mod buyback;
+mod catalog;
+mod credit_notes;
+mod depreciation;
+mod financing;
+mod fulfilment;
+mod images;
+mod ledger;
+mod price_history;
+mod pricing;
+mod promotions;
+mod translations;
+mod vat;
+
+pub use addons::*;
+pub use billing::*;
+pub use buyback::*;
+pub use catalog::*;
+pub use credit_notes::*;
+pub use depreciation::*;
+pub use financing::*;
+pub use fulfilment::*;
+pub use images::*;
+pub use ledger::*;
+pub use price_history::*;
+pub use pricing::*;
+pub use promotions::*;
+pub use translations::*;
+pub use vat::*;
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct PackageProvenance {
@@ -24,14 +47,15 @@ pub struct PackageProvenance {
     pub usage_hours: i32,
     pub quantity_available: i32,
     pub is_active: bool,
-    pub calculated_price_usdc: Option<i32>,
-    pub discount_percentage: Option<f64>,
+    pub calculated_price_usdc: Option<Money>,
+    pub discount_percentage: Option<Decimal>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct PackageImage {
+    pub id: Uuid,
     pub package_id: Uuid,
     pub filename: String,
     pub title: String,
@@ -48,33 +72,17 @@ pub struct Package {
     pub cpu_cores: i16,
     pub ram_gb: i16,
     pub storage_gb: i32,
-    pub gpu_class: String, // We'll convert to/from GpuClass
+    pub gpu_class: GpuClass,
     pub gpu_count: i16,
     pub vram_gb: i16,
-    pub setup_price_usdc: i32,
-    pub monthly_price_usdc: i32,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
     pub availability_type: String,
     pub availability_value: Option<i32>,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
 }
 
-impl Package {
-    pub fn gpu_class_enum(&self) -> GpuClass {
-        match self.gpu_class.as_str() {
-            "None" => GpuClass::None,
-            "L4" => GpuClass::L4,
-            "A100_40G" => GpuClass::A100_40G,
-            "A100_80G" => GpuClass::A100_80G,
-            "H100_80G" => GpuClass::H100_80G,
-            "RTX_4090" => GpuClass::RTX_4090,
-            "RTX_5090" => GpuClass::RTX_5090,
-            "Radeon_8060S" => GpuClass::Radeon_8060S,
-            _ => GpuClass::None,
-        }
-    }
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct ServerOrder {
     pub id: Uuid,
@@ -82,10 +90,15 @@ pub struct ServerOrder {
     pub plan_cpu_cores: i16,
     pub plan_ram_gb: i16,
     pub plan_storage_gb: i32,
-    pub plan_gpu: String,
+    pub plan_gpu: GpuClass,
     pub pq_enabled: bool,
     pub notes: Option<String>,
     pub status: String,
+    pub package_id: Option<Uuid>,
+    /// Price version the order was placed at
+    pub package_price_id: Option<i32>,
+    /// Promo discount off the setup price
+    pub discount_usdc: Money,
     pub created_at: DateTime<Utc>,
 }
 
@@ -104,7 +117,7 @@ impl Database {
             r#"
             SELECT
                 id, name, sku, description, hardware_description,
-                cpu_cores, ram_gb, storage_gb, gpu_class::text as gpu_class,
+                cpu_cores, ram_gb, storage_gb, gpu_class,
                 gpu_count, vram_gb, setup_price_usdc, monthly_price_usdc,
                 availability_type, availability_value,
                 is_active, created_at
@@ -142,67 +155,6 @@ impl Database {
         Ok(packages)
     }
 
-    pub async fn get_depreciation_rules(&self) -> Result<Vec<DepreciationRule>> {
-        let rows = sqlx::query(
-            r#"
-            SELECT
-                id, package_id, final_depreciated_percentage::float8 as final_depreciated_percentage,
-                full_depreciation_hours, depreciation_curve,
-                created_at, updated_at
-            FROM package_depreciation_rules
-            ORDER BY package_id
-            "#,
-        )
-        .fetch_all(&self.pool)
-        .await?;
-
-        let rules = rows
-            .into_iter()
-            .map(|row| DepreciationRule {
-                id: row.get("id"),
-                package_id: row.get("package_id"),
-                final_depreciated_percentage: row.get("final_depreciated_percentage"),
-                full_depreciation_hours: row.get("full_depreciation_hours"),
-                depreciation_curve: row.get("depreciation_curve"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            })
-            .collect();
-
-        Ok(rules)
-    }
-
-    pub async fn get_depreciation_rule_by_package_id(
-        &self,
-        package_id: Uuid,
-    ) -> Result<Option<DepreciationRule>> {
-        let row = sqlx::query(
-            r#"
-            SELECT
-                id, package_id, final_depreciated_percentage::float8 as final_depreciated_percentage,
-                full_depreciation_hours, depreciation_curve,
-                created_at, updated_at
-            FROM package_depreciation_rules
-            WHERE package_id = $1
-            "#,
-        )
-        .bind(package_id)
-        .fetch_optional(&self.pool)
-        .await?;
-
-        let rule = row.map(|r| DepreciationRule {
-            id: r.get("id"),
-            package_id: r.get("package_id"),
-            final_depreciated_percentage: r.get("final_depreciated_percentage"),
-            full_depreciation_hours: r.get("full_depreciation_hours"),
-            depreciation_curve: r.get("depreciation_curve"),
-            created_at: r.get("created_at"),
-            updated_at: r.get("updated_at"),
-        });
-
-        Ok(rule)
-    }
-
     pub async fn get_package_provenances(
         &self,
         package_id: Uuid,
@@ -212,7 +164,7 @@ impl Database {
             SELECT
                 id, package_id, provenance_type, usage_hours,
                 quantity_available, is_active, calculated_price_usdc,
-                discount_percentage::float8 as discount_percentage, created_at, updated_at
+                discount_percentage, created_at, updated_at
             FROM package_provenance
             WHERE package_id = $1 AND is_active = true
             ORDER BY usage_hours ASC
@@ -247,7 +199,7 @@ impl Database {
             SELECT
                 id, package_id, provenance_type, usage_hours,
                 quantity_available, is_active, calculated_price_usdc,
-                discount_percentage::float8 as discount_percentage, created_at, updated_at
+                discount_percentage, created_at, updated_at
             FROM package_provenance
             WHERE is_active = true
             ORDER BY package_id, usage_hours ASC
@@ -280,7 +232,7 @@ impl Database {
             r#"
             SELECT
                 id, name, sku, description, hardware_description,
-                cpu_cores, ram_gb, storage_gb, gpu_class::text as gpu_class,
+                cpu_cores, ram_gb, storage_gb, gpu_class,
                 gpu_count, vram_gb, setup_price_usdc, monthly_price_usdc,
                 availability_type, availability_value,
                 is_active, created_at
@@ -320,7 +272,7 @@ impl Database {
             r#"
             SELECT
                 id, name, sku, description, hardware_description,
-                cpu_cores, ram_gb, storage_gb, gpu_class::text as gpu_class,
+                cpu_cores, ram_gb, storage_gb, gpu_class,
                 gpu_count, vram_gb, setup_price_usdc, monthly_price_usdc,
                 availability_type, availability_value,
                 is_active, created_at
@@ -358,7 +310,7 @@ impl Database {
     pub async fn get_package_images(&self, package_id: Uuid) -> Result<Vec<PackageImage>> {
         let rows = sqlx::query(
             r#"
-            SELECT filename, title, description
+            SELECT id, filename, title, description
             FROM package_images
             WHERE package_id = $1
             ORDER BY sort_order ASC
@@ -371,6 +323,7 @@ impl Database {
         let images = rows
             .into_iter()
             .map(|row| PackageImage {
+                id: row.get("id"),
                 package_id,
                 filename: row.get::<String, _>("filename"),
                 title: row.get::<String, _>("title"),
@@ -381,23 +334,30 @@ impl Database {
         Ok(images)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_order(
         &self,
         org_id: Uuid,
         cpu_cores: i16,
         ram_gb: i16,
         storage_gb: i32,
-        gpu: String,
+        gpu: GpuClass,
         pq_enabled: bool,
         notes: Option<String>,
+        promo: Option<(&str, Money)>,
+        package_price: Option<&PackagePriceVersion>,
+        financing: Option<&NewFinancingAgreement>,
+        addons: &[NewOrderAddon],
     ) -> Result<ServerOrder> {
         let order_id = Uuid::new_v4();
 
+        let mut tx = self.pool.begin().await?;
+
         let query = sqlx::query(
             r#"
-            INSERT INTO server_orders (id, org_id, plan_cpu_cores, plan_ram_gb, plan_storage_gb, plan_gpu, pq_enabled, notes, status)
-            VALUES ($1, $2, $3, $4, $5, $6::gpu_class, $7, $8, 'queued')
-            RETURNING id, org_id, plan_cpu_cores, plan_ram_gb, plan_storage_gb, plan_gpu::text as plan_gpu, pq_enabled, notes, status, created_at
+            INSERT INTO server_orders (id, org_id, plan_cpu_cores, plan_ram_gb, plan_storage_gb, plan_gpu, pq_enabled, notes, status, package_id, package_price_id, discount_usdc)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'queued', $9, $10, $11)
+            RETURNING id, org_id, plan_cpu_cores, plan_ram_gb, plan_storage_gb, plan_gpu, pq_enabled, notes, status, package_id, package_price_id, discount_usdc, created_at
             "#,
         )
         .bind(order_id)
@@ -408,9 +368,26 @@ impl Database {
         .bind(gpu)
         .bind(pq_enabled)
         .bind(notes)
-        .fetch_one(&self.pool)
+        .bind(package_price.map(|v| v.package_id))
+        .bind(package_price.map(|v| v.id))
+        .bind(promo.map_or(Money::ZERO, |(_, discount)| discount))
+        .fetch_one(&mut *tx)
         .await?;
 
+        if let Some((code, discount)) = promo {
+            if !promotions::redeem_promo_code(&mut tx, code, org_id, order_id, discount).await? {
+                anyhow::bail!("promo code {code} can no longer be redeemed");
+            }
+        }
+
+        if let Some(agreement) = financing {
+            financing::insert_financing_agreement(&mut tx, org_id, order_id, agreement).await?;
+        }
+
+        addons::insert_order_addons(&mut tx, order_id, addons).await?;
+
+        tx.commit().await?;
+
         Ok(ServerOrder {
             id: query.get("id"),
             org_id: query.get("org_id"),
@@ -421,6 +398,9 @@ impl Database {
             pq_enabled: query.get("pq_enabled"),
             notes: query.get("notes"),
             status: query.get("status"),
+            package_id: query.get("package_id"),
+            package_price_id: query.get("package_price_id"),
+            discount_usdc: query.get("discount_usdc"),
             created_at: query.get("created_at"),
         })
     }
@@ -438,7 +418,7 @@ impl Database {
             r#"
             INSERT INTO server_orders
             (id, org_id, plan_cpu_cores, plan_ram_gb, plan_storage_gb, plan_gpu, pq_enabled, notes, status)
-            VALUES ($1, $2, $3, $4, $5, $6::gpu_class, $7, $8, 'queued')
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'queued')
             "#
         )
         .bind(order_id)
@@ -460,7 +440,8 @@ impl Database {
             r#"
             SELECT
                 id, org_id, plan_cpu_cores, plan_ram_gb, plan_storage_gb,
-                plan_gpu::text as plan_gpu, pq_enabled, notes, status, created_at
+                plan_gpu, pq_enabled, notes, status,
+                package_id, package_price_id, discount_usdc, created_at
             FROM server_orders
             WHERE org_id = $1
             ORDER BY created_at DESC
@@ -482,6 +463,9 @@ impl Database {
                 pq_enabled: row.get("pq_enabled"),
                 notes: row.get("notes"),
                 status: row.get("status"),
+                package_id: row.get("package_id"),
+                package_price_id: row.get("package_price_id"),
+                discount_usdc: row.get("discount_usdc"),
                 created_at: row.get("created_at"),
             })
             .collect();
@@ -0,0 +1,124 @@
+use crate::Database;
+use ai::Money;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PackagePriceVersion {
+    pub id: i32,
+    pub package_id: Uuid,
+    pub setup_price_usdc: Money,
+    pub monthly_price_usdc: Money,
+    pub effective_from: DateTime<Utc>,
+    pub note: String,
+    pub created_at: DateTime<Utc>,
+}
+
+const PRICE_VERSION_COLUMNS: &str =
+    "id, package_id, setup_price_usdc, monthly_price_usdc, effective_from, note, created_at";
+
+impl Database {
+    /// Every price version of a package, newest first
+    pub async fn get_package_price_versions(
+        &self,
+        package_id: Uuid,
+    ) -> Result<Vec<PackagePriceVersion>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {PRICE_VERSION_COLUMNS} FROM package_prices WHERE package_id = $1 ORDER BY effective_from DESC"
+        ))
+        .bind(package_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(price_version_from_row).collect())
+    }
+
+    /// The version of a package's price in effect at `at`
+    pub async fn get_package_price_at(
+        &self,
+        package_id: Uuid,
+        at: DateTime<Utc>,
+    ) -> Result<Option<PackagePriceVersion>> {
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT {PRICE_VERSION_COLUMNS} FROM package_prices
+            WHERE package_id = $1 AND effective_from <= $2
+            ORDER BY effective_from DESC
+            LIMIT 1
+            "#
+        ))
+        .bind(package_id)
+        .bind(at)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(price_version_from_row))
+    }
+
+    pub async fn create_package_price_version(
+        &self,
+        package_id: Uuid,
+        setup_price_usdc: Money,
+        monthly_price_usdc: Money,
+        effective_from: DateTime<Utc>,
+        note: &str,
+    ) -> Result<PackagePriceVersion> {
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO package_prices (package_id, setup_price_usdc, monthly_price_usdc, effective_from, note)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING {PRICE_VERSION_COLUMNS}
+            "#
+        ))
+        .bind(package_id)
+        .bind(setup_price_usdc)
+        .bind(monthly_price_usdc)
+        .bind(effective_from)
+        .bind(note)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(price_version_from_row(&row))
+    }
+
+    /// Copy the version in effect at `now` into each package whose listed
+    /// prices differ from it. Returns the packages that changed.
+    pub async fn apply_due_package_prices(&self, now: DateTime<Utc>) -> Result<Vec<Uuid>> {
+        let rows = sqlx::query(
+            r#"
+            UPDATE packages p
+            SET setup_price_usdc = v.setup_price_usdc, monthly_price_usdc = v.monthly_price_usdc
+            FROM (
+                SELECT DISTINCT ON (package_id) package_id, setup_price_usdc, monthly_price_usdc
+                FROM package_prices
+                WHERE effective_from <= $1
+                ORDER BY package_id, effective_from DESC
+            ) v
+            WHERE p.id = v.package_id
+              AND (p.setup_price_usdc, p.monthly_price_usdc)
+                  IS DISTINCT FROM (v.setup_price_usdc, v.monthly_price_usdc)
+            RETURNING p.id
+            "#,
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| row.get("id")).collect())
+    }
+}
+
+fn price_version_from_row(row: &sqlx::postgres::PgRow) -> PackagePriceVersion {
+    PackagePriceVersion {
+        id: row.get("id"),
+        package_id: row.get("package_id"),
+        setup_price_usdc: row.get("setup_price_usdc"),
+        monthly_price_usdc: row.get("monthly_price_usdc"),
+        effective_from: row.get("effective_from"),
+        note: row.get("note"),
+        created_at: row.get("created_at"),
+    }
+}
@@ -0,0 +1,90 @@
+use crate::Database;
+use ai::Money;
+use anyhow::Result;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+/// A provenance row with everything needed to price it
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ProvenancePricingInput {
+    pub provenance_id: i32,
+    pub package_id: Uuid,
+    pub usage_hours: i32,
+    pub calculated_price_usdc: Option<Money>,
+    pub discount_percentage: Option<Decimal>,
+    pub setup_price_usdc: Money,
+}
+
+/// A freshly computed cached price for a provenance row
+#[derive(Debug, Clone)]
+pub struct ProvenancePrice {
+    pub provenance_id: i32,
+    pub calculated_price_usdc: Money,
+    pub discount_percentage: Decimal,
+}
+
+impl Database {
+    /// Pricing inputs of provenance rows, optionally limited to one package
+    /// and to rows whose cached price is stale
+    pub async fn get_provenance_pricing_inputs(
+        &self,
+        package_id: Option<Uuid>,
+        stale_only: bool,
+    ) -> Result<Vec<ProvenancePricingInput>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                pp.id as provenance_id, pp.package_id, pp.usage_hours,
+                pp.calculated_price_usdc, pp.discount_percentage, p.setup_price_usdc
+            FROM package_provenance pp
+            JOIN packages p ON p.id = pp.package_id
+            WHERE ($1::uuid IS NULL OR pp.package_id = $1)
+              AND (NOT $2 OR pp.calculated_price_usdc IS NULL OR pp.discount_percentage IS NULL)
+            ORDER BY pp.package_id, pp.usage_hours
+            "#,
+        )
+        .bind(package_id)
+        .bind(stale_only)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let inputs = rows
+            .into_iter()
+            .map(|row| ProvenancePricingInput {
+                provenance_id: row.get("provenance_id"),
+                package_id: row.get("package_id"),
+                usage_hours: row.get("usage_hours"),
+                calculated_price_usdc: row.get("calculated_price_usdc"),
+                discount_percentage: row.get("discount_percentage"),
+                setup_price_usdc: row.get("setup_price_usdc"),
+            })
+            .collect();
+
+        Ok(inputs)
+    }
+
+    /// Write computed prices to the provenance cache in one transaction
+    pub async fn store_provenance_prices(&self, prices: &[ProvenancePrice]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for price in prices {
+            sqlx::query(
+                r#"
+                UPDATE package_provenance
+                SET calculated_price_usdc = $2, discount_percentage = $3
+                WHERE id = $1
+                "#,
+            )
+            .bind(price.provenance_id)
+            .bind(price.calculated_price_usdc)
+            .bind(price.discount_percentage)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use crate::Database;
+use ai::Money;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PromoCode {
+    pub id: i32,
+    pub code: String,
+    pub description: String,
+    pub discount_type: String,
+    pub percent_off: Option<Decimal>,
+    pub amount_off_usdc: Option<Money>,
+    pub max_redemptions: Option<i32>,
+    pub redemptions: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub is_active: bool,
+    pub package_ids: Vec<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Promo code to be created
+#[derive(Debug, Clone)]
+pub struct NewPromoCode {
+    pub code: String,
+    pub description: String,
+    pub discount_type: String,
+    pub percent_off: Option<Decimal>,
+    pub amount_off_usdc: Option<Money>,
+    pub max_redemptions: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub package_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct VolumeDiscountTier {
+    pub min_quantity: i32,
+    pub percent_off: Decimal,
+}
+
+const PROMO_CODE_COLUMNS: &str = r#"
+    pc.id, pc.code, pc.description, pc.discount_type, pc.percent_off, pc.amount_off_usdc,
+    pc.max_redemptions, pc.redemptions, pc.expires_at, pc.is_active, pc.created_at,
+    ARRAY(
+        SELECT package_id FROM promo_code_packages WHERE promo_code_id = pc.id ORDER BY package_id
+    ) as package_ids
+"#;
+
+impl Database {
+    pub async fn get_promo_codes(&self) -> Result<Vec<PromoCode>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {PROMO_CODE_COLUMNS} FROM promo_codes pc ORDER BY pc.created_at DESC"
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(promo_code_from_row).collect())
+    }
+
+    /// Look up a promo code; `code` must already be uppercase
+    pub async fn get_promo_code(&self, code: &str) -> Result<Option<PromoCode>> {
+        let row = sqlx::query(&format!(
+            "SELECT {PROMO_CODE_COLUMNS} FROM promo_codes pc WHERE pc.code = $1"
+        ))
+        .bind(code)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(promo_code_from_row))
+    }
+
+    pub async fn create_promo_code(&self, promo: &NewPromoCode) -> Result<PromoCode> {
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO promo_codes
+            (code, description, discount_type, percent_off, amount_off_usdc, max_redemptions, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id
+            "#,
+        )
+        .bind(&promo.code)
+        .bind(&promo.description)
+        .bind(&promo.discount_type)
+        .bind(promo.percent_off)
+        .bind(promo.amount_off_usdc)
+        .bind(promo.max_redemptions)
+        .bind(promo.expires_at)
+        .fetch_one(&mut *tx)
+        .await?;
+        let promo_code_id: i32 = row.get("id");
+
+        for package_id in &promo.package_ids {
+            sqlx::query(
+                "INSERT INTO promo_code_packages (promo_code_id, package_id) VALUES ($1, $2)",
+            )
+            .bind(promo_code_id)
+            .bind(package_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        self.get_promo_code(&promo.code)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("promo code {} missing after insert", promo.code))
+    }
+
+    /// Stop a promo code from being redeemed any further
+    pub async fn deactivate_promo_code(&self, code: &str) -> Result<bool> {
+        let result = sqlx::query("UPDATE promo_codes SET is_active = false WHERE code = $1")
+            .bind(code)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_volume_discount_tiers(&self) -> Result<Vec<VolumeDiscountTier>> {
+        let rows = sqlx::query(
+            "SELECT min_quantity, percent_off FROM volume_discount_tiers ORDER BY min_quantity",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| VolumeDiscountTier {
+                min_quantity: row.get("min_quantity"),
+                percent_off: row.get("percent_off"),
+            })
+            .collect())
+    }
+
+    /// Replace all volume discount tiers
+    pub async fn replace_volume_discount_tiers(&self, tiers: &[VolumeDiscountTier]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM volume_discount_tiers")
+            .execute(&mut *tx)
+            .await?;
+
+        for tier in tiers {
+            sqlx::query(
+                "INSERT INTO volume_discount_tiers (min_quantity, percent_off) VALUES ($1, $2)",
+            )
+            .bind(tier.min_quantity)
+            .bind(tier.percent_off)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// Count a redemption of `code` against an order. Returns `false` without
+/// writing anything if the code is inactive, expired or used up, which the
+/// conditional update decides atomically.
+pub(crate) async fn redeem_promo_code(
+    conn: &mut PgConnection,
+    code: &str,
+    org_id: Uuid,
+    order_id: Uuid,
+    discount_usdc: Money,
+) -> Result<bool> {
+    let row = sqlx::query(
+        r#"
+        UPDATE promo_codes
+        SET redemptions = redemptions + 1
+        WHERE code = $1
+          AND is_active
+          AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
+          AND (max_redemptions IS NULL OR redemptions < max_redemptions)
+        RETURNING id
+        "#,
+    )
+    .bind(code)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(false);
+    };
+    let promo_code_id: i32 = row.get("id");
+
+    sqlx::query(
+        r#"
+        INSERT INTO promo_redemptions (promo_code_id, org_id, order_id, discount_usdc)
+        VALUES ($1, $2, $3, $4)
+        "#,
+    )
+    .bind(promo_code_id)
+    .bind(org_id)
+    .bind(order_id)
+    .bind(discount_usdc)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(true)
+}
+
+fn promo_code_from_row(row: &sqlx::postgres::PgRow) -> PromoCode {
+    PromoCode {
+        id: row.get("id"),
+        code: row.get("code"),
+        description: row.get("description"),
+        discount_type: row.get("discount_type"),
+        percent_off: row.get("percent_off"),
+        amount_off_usdc: row.get("amount_off_usdc"),
+        max_redemptions: row.get("max_redemptions"),
+        redemptions: row.get("redemptions"),
+        expires_at: row.get("expires_at"),
+        is_active: row.get("is_active"),
+        package_ids: row.get("package_ids"),
+        created_at: row.get("created_at"),
+    }
+}
@@ -0,0 +1,218 @@
+use crate::Database;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PackageTranslation {
+    pub package_id: Uuid,
+    pub locale: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub hardware_description: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ImageTranslation {
+    pub image_id: Uuid,
+    pub package_id: Uuid,
+    pub locale: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A package's translation into one locale, replacing any it had
+#[derive(Debug, Clone)]
+pub struct NewPackageTranslation {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub hardware_description: Option<String>,
+    pub images: Vec<NewImageTranslation>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewImageTranslation {
+    pub image_id: Uuid,
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+const TRANSLATION_COLUMNS: &str =
+    "package_id, locale, name, description, hardware_description, updated_at";
+
+const IMAGE_TRANSLATION_COLUMNS: &str =
+    "t.image_id, i.package_id, t.locale, t.title, t.description, t.updated_at";
+
+impl Database {
+    /// Every package's and image's translation into `locale`
+    pub async fn get_translations(
+        &self,
+        locale: &str,
+    ) -> Result<(Vec<PackageTranslation>, Vec<ImageTranslation>)> {
+        let packages = sqlx::query(&format!(
+            "SELECT {TRANSLATION_COLUMNS} FROM package_translations WHERE locale = $1"
+        ))
+        .bind(locale)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let images = sqlx::query(&format!(
+            r#"
+            SELECT {IMAGE_TRANSLATION_COLUMNS}
+            FROM package_image_translations t
+            JOIN package_images i ON i.id = t.image_id
+            WHERE t.locale = $1
+            "#
+        ))
+        .bind(locale)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok((
+            packages.iter().map(translation_from_row).collect(),
+            images.iter().map(image_translation_from_row).collect(),
+        ))
+    }
+
+    /// A package's translations into every locale
+    pub async fn get_package_translations(
+        &self,
+        package_id: Uuid,
+    ) -> Result<(Vec<PackageTranslation>, Vec<ImageTranslation>)> {
+        let packages = sqlx::query(&format!(
+            "SELECT {TRANSLATION_COLUMNS} FROM package_translations WHERE package_id = $1 ORDER BY locale"
+        ))
+        .bind(package_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let images = sqlx::query(&format!(
+            r#"
+            SELECT {IMAGE_TRANSLATION_COLUMNS}
+            FROM package_image_translations t
+            JOIN package_images i ON i.id = t.image_id
+            WHERE i.package_id = $1
+            ORDER BY t.locale, i.sort_order
+            "#
+        ))
+        .bind(package_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok((
+            packages.iter().map(translation_from_row).collect(),
+            images.iter().map(image_translation_from_row).collect(),
+        ))
+    }
+
+    /// Replace a package's translation into `locale`, image titles included
+    pub async fn save_package_translation(
+        &self,
+        package_id: Uuid,
+        locale: &str,
+        translation: &NewPackageTranslation,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO package_translations
+            (package_id, locale, name, description, hardware_description)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (package_id, locale) DO UPDATE SET
+                name = EXCLUDED.name,
+                description = EXCLUDED.description,
+                hardware_description = EXCLUDED.hardware_description,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(package_id)
+        .bind(locale)
+        .bind(&translation.name)
+        .bind(&translation.description)
+        .bind(&translation.hardware_description)
+        .execute(&mut *tx)
+        .await?;
+
+        delete_image_translations(&mut tx, package_id, locale).await?;
+        for image in &translation.images {
+            sqlx::query(
+                r#"
+                INSERT INTO package_image_translations (image_id, locale, title, description)
+                VALUES ($1, $2, $3, $4)
+                "#,
+            )
+            .bind(image.image_id)
+            .bind(locale)
+            .bind(&image.title)
+            .bind(&image.description)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Returns false if the package had no translation into `locale`
+    pub async fn delete_package_translation(&self, package_id: Uuid, locale: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let result =
+            sqlx::query("DELETE FROM package_translations WHERE package_id = $1 AND locale = $2")
+                .bind(package_id)
+                .bind(locale)
+                .execute(&mut *tx)
+                .await?;
+        let images = delete_image_translations(&mut tx, package_id, locale).await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0 || images > 0)
+    }
+}
+
+async fn delete_image_translations(
+    conn: &mut sqlx::PgConnection,
+    package_id: Uuid,
+    locale: &str,
+) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM package_image_translations
+        WHERE locale = $2
+          AND image_id IN (SELECT id FROM package_images WHERE package_id = $1)
+        "#,
+    )
+    .bind(package_id)
+    .bind(locale)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+fn translation_from_row(row: &sqlx::postgres::PgRow) -> PackageTranslation {
+    PackageTranslation {
+        package_id: row.get("package_id"),
+        locale: row.get("locale"),
+        name: row.get("name"),
+        description: row.get("description"),
+        hardware_description: row.get("hardware_description"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn image_translation_from_row(row: &sqlx::postgres::PgRow) -> ImageTranslation {
+    ImageTranslation {
+        image_id: row.get("image_id"),
+        package_id: row.get("package_id"),
+        locale: row.get("locale"),
+        title: row.get("title"),
+        description: row.get("description"),
+        updated_at: row.get("updated_at"),
+    }
+}
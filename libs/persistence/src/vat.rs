@@ -0,0 +1,167 @@
+use crate::Database;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BillingProfile {
+    pub org_id: Uuid,
+    pub legal_name: String,
+    pub address: String,
+    pub country_code: String,
+    pub vat_id: Option<String>,
+    pub vat_id_valid: bool,
+    pub vat_id_checked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct VatRate {
+    pub id: i32,
+    pub country_code: String,
+    pub rate_percent: Decimal,
+    pub valid_from: NaiveDate,
+    pub valid_to: Option<NaiveDate>,
+}
+
+impl Database {
+    pub async fn get_billing_profile(&self, org_id: Uuid) -> Result<Option<BillingProfile>> {
+        let row = sqlx::query(
+            r#"
+            SELECT org_id, legal_name, address, country_code, vat_id, vat_id_valid,
+                vat_id_checked_at, created_at, updated_at
+            FROM org_billing_profiles
+            WHERE org_id = $1
+            "#,
+        )
+        .bind(org_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| BillingProfile {
+            org_id: row.get("org_id"),
+            legal_name: row.get("legal_name"),
+            address: row.get("address"),
+            country_code: row.get("country_code"),
+            vat_id: row.get("vat_id"),
+            vat_id_valid: row.get("vat_id_valid"),
+            vat_id_checked_at: row.get("vat_id_checked_at"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        }))
+    }
+
+    /// Create or replace the billing profile of an organization
+    pub async fn upsert_billing_profile(
+        &self,
+        org_id: Uuid,
+        legal_name: &str,
+        address: &str,
+        country_code: &str,
+        vat_id: Option<&str>,
+        vat_id_valid: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO org_billing_profiles
+            (org_id, legal_name, address, country_code, vat_id, vat_id_valid, vat_id_checked_at)
+            VALUES ($1, $2, $3, $4, $5, $6, CASE WHEN $5 IS NULL THEN NULL ELSE CURRENT_TIMESTAMP END)
+            ON CONFLICT (org_id) DO UPDATE SET
+                legal_name = EXCLUDED.legal_name,
+                address = EXCLUDED.address,
+                country_code = EXCLUDED.country_code,
+                vat_id = EXCLUDED.vat_id,
+                vat_id_valid = EXCLUDED.vat_id_valid,
+                vat_id_checked_at = EXCLUDED.vat_id_checked_at,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(org_id)
+        .bind(legal_name)
+        .bind(address)
+        .bind(country_code)
+        .bind(vat_id)
+        .bind(vat_id_valid)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_vat_rates(&self) -> Result<Vec<VatRate>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, country_code, rate_percent, valid_from, valid_to
+            FROM vat_rates
+            ORDER BY country_code, valid_from
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let rates = rows
+            .into_iter()
+            .map(|row| VatRate {
+                id: row.get("id"),
+                country_code: row.get("country_code"),
+                rate_percent: row.get("rate_percent"),
+                valid_from: row.get("valid_from"),
+                valid_to: row.get("valid_to"),
+            })
+            .collect();
+
+        Ok(rates)
+    }
+
+    /// Add a rate for a country from `valid_from`. The open-ended rate it
+    /// supersedes is closed on the same day.
+    pub async fn insert_vat_rate(
+        &self,
+        country_code: &str,
+        rate_percent: Decimal,
+        valid_from: NaiveDate,
+        valid_to: Option<NaiveDate>,
+    ) -> Result<VatRate> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE vat_rates
+            SET valid_to = $2
+            WHERE country_code = $1 AND valid_to IS NULL AND valid_from < $2
+            "#,
+        )
+        .bind(country_code)
+        .bind(valid_from)
+        .execute(&mut *tx)
+        .await?;
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO vat_rates (country_code, rate_percent, valid_from, valid_to)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, country_code, rate_percent, valid_from, valid_to
+            "#,
+        )
+        .bind(country_code)
+        .bind(rate_percent)
+        .bind(valid_from)
+        .bind(valid_to)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(VatRate {
+            id: row.get("id"),
+            country_code: row.get("country_code"),
+            rate_percent: row.get("rate_percent"),
+            valid_from: row.get("valid_from"),
+            valid_to: row.get("valid_to"),
+        })
+    }
+}
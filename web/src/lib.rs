@@ -13,11 +13,67 @@ fn api_base() -> String {
     }
 }
 
+/// A GPU class as shown to customers, e.g. `RTX_4090` as "RTX 4090"
+fn gpu_label(gpu_class: &str) -> String {
+    gpu_class.replace('_', " ")
+}
+
+/// A USDC amount as sent by the API: an exact decimal string
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+struct Usdc(String);
+
+impl std::fmt::Display for Usdc {
+    /// Whole amounts without decimals, everything else to the cent
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.parse::<f64>() {
+            Ok(value) if value.fract() == 0.0 => write!(f, "{value:.0}"),
+            Ok(value) => write!(f, "{value:.2}"),
+            Err(_) => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl Usdc {
+    /// For totals shown in the browser; the API does the exact sums
+    fn as_f64(&self) -> f64 {
+        self.0.parse().unwrap_or(0.0)
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 struct PackageImage {
     filename: String,
     title: String,
     description: String,
+    /// Resized and WebP renditions of uploaded photos
+    #[serde(default)]
+    variants: Vec<ImageVariant>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ImageVariant {
+    url: String,
+    format: String,
+    width: u32,
+}
+
+impl PackageImage {
+    /// `srcset` of the renditions in `format`, narrowest first; empty for
+    /// static assets
+    fn srcset(&self, format: &str) -> String {
+        let mut variants: Vec<_> = self
+            .variants
+            .iter()
+            .filter(|v| v.format == format)
+            .collect();
+        variants.sort_by_key(|v| v.width);
+        variants
+            .iter()
+            .map(|v| format!("{} {}w", v.url, v.width))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -27,6 +83,19 @@ enum Availability {
     Build { hours: u16 },
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+struct BuildEstimate {
+    ships_at: String,
+    waiting_for_parts: bool,
+}
+
+impl BuildEstimate {
+    /// The ship date without the time of day
+    fn ship_date(&self) -> &str {
+        self.ships_at.get(..10).unwrap_or(&self.ships_at)
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 enum Provenance {
     New,
@@ -37,7 +106,7 @@ enum Provenance {
 struct ProvenanceOption {
     provenance_type: Provenance,
     quantity_available: u32,
-    calculated_price: u32,
+    calculated_price: Usdc,
     discount_percentage: Option<f64>,
 }
 
@@ -54,25 +123,198 @@ struct Package {
     gpu_class: String,
     gpu_count: u16,
     vram_gb: u16,
-    setup_price_usdc: u32,
-    monthly_price_usdc: u32,
+    setup_price_usdc: Usdc,
+    monthly_price_usdc: Usdc,
     images: Vec<PackageImage>,
     availability: Availability,
     provenances: Vec<ProvenanceOption>,
-    min_price_usdc: Option<u32>,
-    max_price_usdc: Option<u32>,
+    min_price_usdc: Option<Usdc>,
+    max_price_usdc: Option<Usdc>,
+    #[serde(default)]
+    build_estimate: Option<BuildEstimate>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct FinancingPlan {
+    name: String,
+    term_months: u32,
+    annual_rate_percent: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct AmortizationSchedule {
+    instalment_usdc: Usdc,
+    total_interest_usdc: Usdc,
+    total_paid_usdc: Usdc,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct FinancingOffer {
+    plan: FinancingPlan,
+    schedule: AmortizationSchedule,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct PackageFinancing {
+    offers: Vec<FinancingOffer>,
+}
+
+/// An optional extra sold with a package; prices are per unit
+#[derive(Clone, Serialize, Deserialize)]
+struct PackageAddon {
+    code: String,
+    name: String,
+    setup_price_usdc: Usdc,
+    monthly_price_usdc: Usdc,
+    max_quantity: u32,
+}
+
+/// A GPU model from the hardware registry; the other figures are unused here
+#[derive(Clone, Serialize, Deserialize)]
+struct GpuSpec {
+    class: String,
+    name: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ComparedPackage {
+    sku: String,
+    name: String,
+    cpu_cores: u16,
+    ram_gb: u16,
+    storage_gb: u32,
+    gpu_class: String,
+    gpu_count: u16,
+    vram_gb: u16,
+    availability: Availability,
+    setup_price_usdc: Usdc,
+    monthly_price_usdc: Usdc,
+    cheapest_provenance: Provenance,
+    cheapest_price_usdc: Usdc,
+    price_per_vram_gb_usdc: Option<Usdc>,
+    price_per_core_usdc: Usdc,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ComparisonLeaders {
+    cheapest: Option<String>,
+    lowest_price_per_vram_gb: Option<String>,
+    lowest_price_per_core: Option<String>,
+    most_vram: Option<String>,
+    most_cores: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct PackageComparison {
+    packages: Vec<ComparedPackage>,
+    leaders: ComparisonLeaders,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ModelFitRequest {
+    parameters_b: f64,
+    quantization: String,
+    context_length: u32,
+    concurrency: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct MemoryEstimate {
+    weights_gb: f64,
+    kv_cache_gb: f64,
+    overhead_gb: f64,
+    total_gb: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct PackageFit {
+    sku: String,
+    name: String,
+    memory_gb: u16,
+    fits: bool,
+    headroom_gb: f64,
+    price_usdc: Usdc,
+    monthly_price_usdc: Usdc,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Recommendation {
+    estimate: MemoryEstimate,
+    packages: Vec<PackageFit>,
+}
+
+/// Most packages the comparison page takes, as enforced by the API
+const MAX_COMPARED_PACKAGES: usize = 4;
+
+/// Rendered width of a package detail image: one grid column of three, or
+/// the full width on phones
+const PACKAGE_IMAGE_SIZES: &str = "(max-width: 768px) 100vw, 33vw";
+
+/// Catalog filters as entered on the landing page; empty means any
+#[derive(Clone, Default, PartialEq)]
+struct PackageFilters {
+    gpu_class: String,
+    min_vram_gb: String,
+    gpu_count: String,
+    min_price_usdc: String,
+    max_price_usdc: String,
+    availability: String,
+    provenance: String,
+    sort: String,
+}
+
+impl PackageFilters {
+    /// Query parameters of the filters that are set
+    fn params(&self) -> Vec<(&'static str, String)> {
+        [
+            ("gpu_class", &self.gpu_class),
+            ("min_vram_gb", &self.min_vram_gb),
+            ("gpu_count", &self.gpu_count),
+            ("min_price_usdc", &self.min_price_usdc),
+            ("max_price_usdc", &self.max_price_usdc),
+            ("availability", &self.availability),
+            ("provenance", &self.provenance),
+            ("sort", &self.sort),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.trim().is_empty())
+        .map(|(name, value)| (name, value.trim().to_string()))
+        .collect()
+    }
 }
 
 #[component]
 fn Landing() -> impl IntoView {
-    let packages = create_resource(
+    let filters = create_rw_signal(PackageFilters::default());
+    // GPU models to filter by
+    let gpus = create_resource(
         || (),
         |_| async move {
+            let url = format!("{}/api/gpus", api_base());
+            match gloo_net::http::Request::get(&url).send().await {
+                Ok(r) if r.status() == 200 => r.json::<Vec<GpuSpec>>().await.unwrap_or_default(),
+                _ => Vec::new(),
+            }
+        },
+    );
+    // SKUs picked for comparison, in the order picked
+    let compared = create_rw_signal(Vec::<String>::new());
+
+    let packages = create_resource(
+        move || filters.get(),
+        |filters| async move {
             let url = format!("{}/api/packages", api_base());
-            let resp = gloo_net::http::Request::get(&url).send().await;
+            let resp = gloo_net::http::Request::get(&url)
+                .query(filters.params())
+                .send()
+                .await;
             match resp {
-                Ok(r) if r.status() == 200 => r.json::<Vec<Package>>().await.ok(),
-                _ => None,
+                Ok(r) if r.status() == 200 => r
+                    .json::<Vec<Package>>()
+                    .await
+                    .map_err(|_| "Failed to load packages. Please try again later.".to_string()),
+                Ok(r) if r.status() == 400 => Err(r.text().await.unwrap_or_default()),
+                _ => Err("Failed to load packages. Please try again later.".to_string()),
             }
         },
     );
@@ -134,6 +376,123 @@ fn Landing() -> impl IntoView {
                     </p>
                 </div>
 
+                <div class="package-filters">
+                    <label>
+                        <span>"GPU"</span>
+                        <select
+                            prop:value=move || filters.with(|f| f.gpu_class.clone())
+                            on:change=move |e| filters.update(|f| f.gpu_class = event_target_value(&e))
+                        >
+                            <option value="">"Any"</option>
+                            {move || gpus.get().unwrap_or_default().into_iter().map(|gpu| view! {
+                                <option value=gpu.class>{gpu.name}</option>
+                            }).collect::<Vec<_>>()}
+                            <option value="None">"No GPU"</option>
+                        </select>
+                    </label>
+                    <label>
+                        <span>"GPUs"</span>
+                        <input
+                            type="number"
+                            min="0"
+                            placeholder="Any"
+                            prop:value=move || filters.with(|f| f.gpu_count.clone())
+                            on:change=move |e| filters.update(|f| f.gpu_count = event_target_value(&e))
+                        />
+                    </label>
+                    <label>
+                        <span>"Min VRAM (GB)"</span>
+                        <input
+                            type="number"
+                            min="0"
+                            placeholder="Any"
+                            prop:value=move || filters.with(|f| f.min_vram_gb.clone())
+                            on:change=move |e| filters.update(|f| f.min_vram_gb = event_target_value(&e))
+                        />
+                    </label>
+                    <label>
+                        <span>"Price from (USDC)"</span>
+                        <input
+                            type="number"
+                            min="0"
+                            placeholder="Any"
+                            prop:value=move || filters.with(|f| f.min_price_usdc.clone())
+                            on:change=move |e| filters.update(|f| f.min_price_usdc = event_target_value(&e))
+                        />
+                    </label>
+                    <label>
+                        <span>"Price to (USDC)"</span>
+                        <input
+                            type="number"
+                            min="0"
+                            placeholder="Any"
+                            prop:value=move || filters.with(|f| f.max_price_usdc.clone())
+                            on:change=move |e| filters.update(|f| f.max_price_usdc = event_target_value(&e))
+                        />
+                    </label>
+                    <label>
+                        <span>"Availability"</span>
+                        <select
+                            prop:value=move || filters.with(|f| f.availability.clone())
+                            on:change=move |e| filters.update(|f| f.availability = event_target_value(&e))
+                        >
+                            <option value="">"Any"</option>
+                            <option value="in_stock">"In stock"</option>
+                            <option value="build">"Built to order"</option>
+                            <option value="preorder">"Preorder"</option>
+                        </select>
+                    </label>
+                    <label>
+                        <span>"Condition"</span>
+                        <select
+                            prop:value=move || filters.with(|f| f.provenance.clone())
+                            on:change=move |e| filters.update(|f| f.provenance = event_target_value(&e))
+                        >
+                            <option value="">"New or used"</option>
+                            <option value="new">"New"</option>
+                            <option value="used">"Used"</option>
+                        </select>
+                    </label>
+                    <label>
+                        <span>"Sort by"</span>
+                        <select
+                            prop:value=move || filters.with(|f| f.sort.clone())
+                            on:change=move |e| filters.update(|f| f.sort = event_target_value(&e))
+                        >
+                            <option value="">"Price: low to high"</option>
+                            <option value="price_desc">"Price: high to low"</option>
+                            <option value="price_per_vram_gb">"Price per GB of VRAM"</option>
+                        </select>
+                    </label>
+                    <button
+                        class="cta-button secondary"
+                        on:click=move |_| filters.set(PackageFilters::default())
+                    >
+                        "Reset"
+                    </button>
+                </div>
+
+                {move || {
+                    let skus = compared.get();
+                    (!skus.is_empty()).then(|| view! {
+                        <div class="compare-bar">
+                            <span>{format!("{} of {} packages selected", skus.len(), MAX_COMPARED_PACKAGES)}</span>
+                            {if skus.len() >= 2 {
+                                view! {
+                                    <A href={format!("/compare?skus={}", skus.join(","))} class="cta-button primary">
+                                        "Compare"
+                                    </A>
+                                }.into_view()
+                            } else {
+                                view! { <span class="compare-hint">"Pick at least two to compare"</span> }.into_view()
+                            }}
+                            <button class="cta-button secondary" on:click=move |_| compared.set(Vec::new())>
+                                "Clear"
+                            </button>
+                        </div>
+                    })
+                }}
+
                 <div class="packages-grid">
                     <Suspense fallback=move || view! {
                         <div class="loading-packages">
@@ -142,10 +501,16 @@ fn Landing() -> impl IntoView {
                         </div>
                     }>
                         {move || {
+                            let unfiltered = filters.with(|f| f.params().is_empty());
                             packages.get().map(|pkgs| match pkgs {
-                                Some(packages) => {
+                                Ok(packages) if packages.is_empty() => view! {
+                                    <div class="empty-state">
+                                        <p>"No packages match these filters."</p>
+                                    </div>
+                                }.into_view(),
+                                Ok(packages) => {
                                     packages.into_iter().enumerate().map(|(idx, pkg)| {
-                                        let is_popular = idx == 1; // Make middle package popular
+                                        let is_popular = unfiltered && idx == 1; // Make middle package popular
                                         let card_class = if is_popular { "package-card popular" } else { "package-card" };
 
                                         view! {
@@ -168,8 +533,8 @@ fn Landing() -> impl IntoView {
                                                             {if pkg.min_price_usdc.is_some() && pkg.max_price_usdc.is_some()
                                                                 && pkg.min_price_usdc != pkg.max_price_usdc {
                                                                 format!("${} ~ ${} USDC",
-                                                                    pkg.min_price_usdc.unwrap_or(pkg.setup_price_usdc),
-                                                                    pkg.max_price_usdc.unwrap_or(pkg.setup_price_usdc))
+                                                                    pkg.min_price_usdc.clone().unwrap_or_else(|| pkg.setup_price_usdc.clone()),
+                                                                    pkg.max_price_usdc.clone().unwrap_or_else(|| pkg.setup_price_usdc.clone()))
                                                             } else {
                                                                 format!("${} USDC", pkg.setup_price_usdc)
                                                             }}
@@ -178,7 +543,7 @@ fn Landing() -> impl IntoView {
                                                     <div class="price-monthly">
                                                         <span class="price-label">"Monthly Hosting"</span>
                                                         <span class="price-amount primary">
-                                                            "$" {pkg.monthly_price_usdc} " USDC/mo"
+                                                            "$" {pkg.monthly_price_usdc.to_string()} " USDC/mo"
                                                         </span>
                                                     </div>
                                                 </div>
@@ -230,7 +595,7 @@ fn Landing() -> impl IntoView {
                                                     </div>
                                                     <div class="spec-item">
                                                         <span class="spec-icon">"🎮"</span>
-                                                        <span>{pkg.gpu_count} "x " {pkg.gpu_class.replace("_", " ")}
+                                                        <span>{pkg.gpu_count} "x " {gpu_label(&pkg.gpu_class)}
                                                             {if pkg.vram_gb > 0 {
                                                                 format!(" ({}GB VRAM)", pkg.vram_gb)
                                                             } else {
@@ -261,6 +626,33 @@ fn Landing() -> impl IntoView {
                                                     </ul>
                                                 </div>
 
+                                                <label class="compare-toggle">
+                                                    <input
+                                                        type="checkbox"
+                                                        prop:checked={
+                                                            let sku = pkg.sku.clone();
+                                                            move || compared.with(|c| c.contains(&sku))
+                                                        }
+                                                        prop:disabled={
+                                                            let sku = pkg.sku.clone();
+                                                            move || compared.with(|c| !c.contains(&sku) && c.len() >= MAX_COMPARED_PACKAGES)
+                                                        }
+                                                        on:change={
+                                                            let sku = pkg.sku.clone();
+                                                            move |e| {
+                                                                let checked = event_target_checked(&e);
+                                                                compared.update(|c| {
+                                                                    c.retain(|s| *s != sku);
+                                                                    if checked {
+                                                                        c.push(sku.clone());
+                                                                    }
+                                                                });
+                                                            }
+                                                        }
+                                                    />
+                                                    <span>"Compare"</span>
+                                                </label>
+
                                                 <A href={format!("/package/{}", pkg.sku)} class="cta-button">
                                                     "Deploy Now →"
                                                 </A>
@@ -268,9 +660,9 @@ fn Landing() -> impl IntoView {
                                         }.into_view()
                                     }).collect::<Vec<_>>().into_view()
                                 },
-                                None => view! {
+                                Err(message) => view! {
                                     <div class="error-state">
-                                        <p>"Failed to load packages. Please try again later."</p>
+                                        <p>{message}</p>
                                     </div>
                                 }.into_view()
                             })
@@ -279,6 +671,8 @@ fn Landing() -> impl IntoView {
                 </div>
             </section>
 
+            <ModelFit />
+
             {/* Security Section */}
             <section class="security-section">
                 <div class="security-content">
@@ -403,6 +797,33 @@ fn PackageDetail() -> impl IntoView {
         }
     });
 
+    // Instalment plans for the setup price
+    let financing_resource = create_resource(sku, |sku| async move {
+        if sku.is_empty() {
+            return None;
+        }
+        let url = format!("{}/api/packages/{}/financing", api_base(), sku);
+        let resp = gloo_net::http::Request::get(&url).send().await;
+        match resp {
+            Ok(r) if r.status() == 200 => r.json::<PackageFinancing>().await.ok(),
+            _ => None,
+        }
+    });
+
+    // Optional extras and how many of each are picked
+    let addons_resource = create_resource(sku, |sku| async move {
+        if sku.is_empty() {
+            return None;
+        }
+        let url = format!("{}/api/packages/{}/addons", api_base(), sku);
+        let resp = gloo_net::http::Request::get(&url).send().await;
+        match resp {
+            Ok(r) if r.status() == 200 => r.json::<Vec<PackageAddon>>().await.ok(),
+            _ => None,
+        }
+    });
+    let addon_quantities = create_rw_signal(std::collections::HashMap::<String, u32>::new());
+
     // Fetch all packages for navigation
     let all_packages = create_resource(
         || (),
@@ -454,9 +875,20 @@ fn PackageDetail() -> impl IntoView {
                             let availability_text = match &pkg.availability {
                                 Availability::InStock => "In Stock - Ships within 24 hours".to_string(),
                                 Availability::Preorder => "Preorder - Ships when available".to_string(),
-                                Availability::Build { hours } => format!("Custom Build - {} hour delivery", hours),
+                                Availability::Build { hours } => match &pkg.build_estimate {
+                                    Some(estimate) => format!(
+                                        "Custom Build - ships by {} ({} hours{})",
+                                        estimate.ship_date(),
+                                        hours,
+                                        if estimate.waiting_for_parts { ", waiting for parts" } else { "" },
+                                    ),
+                                    None => format!("Custom Build - {} hour delivery", hours),
+                                },
                             };
 
+                            let pkg_setup_price = pkg.min_price_usdc.clone().unwrap_or_else(|| pkg.setup_price_usdc.clone());
+                            let pkg_monthly_price = pkg.monthly_price_usdc.clone();
+
                             let payment_policy = match &pkg.availability {
                                 Availability::InStock | Availability::Build { .. } =>
                                     "Full payment required at time of order".to_string(),
@@ -497,9 +929,27 @@ fn PackageDetail() -> impl IntoView {
                                             {
                                                 // Use images from database
                                                 pkg.images.iter().map(|img| {
+                                                    // Uploads come in their own format and as WebP
+                                                    let webp_srcset = img.srcset("webp");
+                                                    let own_format = img.variants.iter()
+                                                        .map(|v| v.format.clone())
+                                                        .find(|f| f != "webp")
+                                                        .unwrap_or_else(|| "webp".to_string());
+                                                    let srcset = img.srcset(&own_format);
                                                     view! {
                                                         <div class="package-image">
-                                                            <img src={img.filename.clone()} alt={img.title.clone()} />
+                                                            <picture>
+                                                                {(!webp_srcset.is_empty() && own_format != "webp").then(|| view! {
+                                                                    <source type="image/webp" srcset=webp_srcset sizes=PACKAGE_IMAGE_SIZES />
+                                                                })}
+                                                                <img
+                                                                    src={img.filename.clone()}
+                                                                    srcset=(!srcset.is_empty()).then_some(srcset)
+                                                                    sizes=PACKAGE_IMAGE_SIZES
+                                                                    alt={img.title.clone()}
+                                                                    loading="lazy"
+                                                                />
+                                                            </picture>
                                                             <div class="image-info">
                                                                 <h3>{img.title.clone()}</h3>
                                                                 <p>{img.description.clone()}</p>
@@ -533,7 +983,7 @@ fn PackageDetail() -> impl IntoView {
                                                     </div>
                                                     <div class="spec-item">
                                                         <span class="spec-label">"GPU"</span>
-                                                        <span class="spec-value">{format!("{:?}", pkg.gpu_class)}</span>
+                                                        <span class="spec-value">{gpu_label(&pkg.gpu_class)}</span>
                                                     </div>
                                                     <div class="spec-item">
                                                         <span class="spec-label">"GPU Count"</span>
@@ -579,7 +1029,7 @@ fn PackageDetail() -> impl IntoView {
                                                                 </div>
                                                                 <div class="provenance-pricing">
                                                                     <span class="provenance-price">
-                                                                        "$" {prov.calculated_price} " USDC"
+                                                                        "$" {prov.calculated_price.to_string()} " USDC"
                                                                     </span>
                                                                     {prov.discount_percentage.map(|discount| {
                                                                         view! {
@@ -604,8 +1054,8 @@ fn PackageDetail() -> impl IntoView {
                                                             {if pkg.min_price_usdc.is_some() && pkg.max_price_usdc.is_some()
                                                                 && pkg.min_price_usdc != pkg.max_price_usdc {
                                                                 format!("${} ~ ${} USDC",
-                                                                    pkg.min_price_usdc.unwrap_or(pkg.setup_price_usdc),
-                                                                    pkg.max_price_usdc.unwrap_or(pkg.setup_price_usdc))
+                                                                    pkg.min_price_usdc.clone().unwrap_or_else(|| pkg.setup_price_usdc.clone()),
+                                                                    pkg.max_price_usdc.clone().unwrap_or_else(|| pkg.setup_price_usdc.clone()))
                                                             } else {
                                                                 format!("${} USDC", pkg.setup_price_usdc)
                                                             }}
@@ -613,13 +1063,97 @@ fn PackageDetail() -> impl IntoView {
                                                     </div>
                                                     <div class="price-item">
                                                         <span class="price-label">"Monthly Hosting"</span>
-                                                        <span class="price-amount">"$"{pkg.monthly_price_usdc}" USDC/mo"</span>
+                                                        <span class="price-amount">"$"{pkg.monthly_price_usdc.to_string()}" USDC/mo"</span>
                                                     </div>
                                                 </div>
                                                 <div class="payment-policy">
                                                     <h3>"Payment Policy"</h3>
                                                     <p>{payment_policy}</p>
                                                 </div>
+                                                {move || {
+                                                    financing_resource.get().flatten()
+                                                        .filter(|f| !f.offers.is_empty())
+                                                        .map(|financing| view! {
+                                                            <div class="financing-options">
+                                                                <h3>"Lease to Own"</h3>
+                                                                {financing.offers.into_iter().map(|offer| {
+                                                                    view! {
+                                                                        <div class="financing-option">
+                                                                            <span class="financing-plan">{offer.plan.name.clone()}</span>
+                                                                            <span class="financing-instalment">
+                                                                                {format!("${} USDC/mo × {}", offer.schedule.instalment_usdc, offer.plan.term_months)}
+                                                                            </span>
+                                                                            <span class="financing-terms">
+                                                                                {format!("{:.2}% APR, ${} USDC interest, ${} USDC total",
+                                                                                    offer.plan.annual_rate_percent,
+                                                                                    offer.schedule.total_interest_usdc,
+                                                                                    offer.schedule.total_paid_usdc)}
+                                                                            </span>
+                                                                        </div>
+                                                                    }
+                                                                }).collect_view()}
+                                                                <p class="financing-note">
+                                                                    "Instalments are added to your monthly invoice. The hardware becomes yours once the last one is paid."
+                                                                </p>
+                                                            </div>
+                                                        })
+                                                }}
+                                                {move || {
+                                                    addons_resource.get().flatten()
+                                                        .filter(|addons| !addons.is_empty())
+                                                        .map(|addons| {
+                                                            let setup_price = pkg_setup_price.as_f64();
+                                                            let monthly_price = pkg_monthly_price.as_f64();
+                                                            let priced = addons.clone();
+                                                            let totals = move || addon_quantities.with(|quantities| {
+                                                                priced.iter().fold((setup_price, monthly_price), |(setup, monthly), addon| {
+                                                                    let quantity = f64::from(quantities.get(&addon.code).copied().unwrap_or(0));
+                                                                    (
+                                                                        setup + addon.setup_price_usdc.as_f64() * quantity,
+                                                                        monthly + addon.monthly_price_usdc.as_f64() * quantity,
+                                                                    )
+                                                                })
+                                                            });
+                                                            view! {
+                                                                <div class="addon-options">
+                                                                    <h3>"Add-ons"</h3>
+                                                                    {addons.into_iter().map(|addon| {
+                                                                        let code = addon.code.clone();
+                                                                        let shown_code = addon.code.clone();
+                                                                        view! {
+                                                                            <label class="addon-option">
+                                                                                <span class="addon-name">{addon.name.clone()}</span>
+                                                                                <span class="addon-price">
+                                                                                    {format!("+${} USDC setup, +${} USDC/mo", addon.setup_price_usdc, addon.monthly_price_usdc)}
+                                                                                </span>
+                                                                                <input
+                                                                                    type="number"
+                                                                                    min="0"
+                                                                                    max=addon.max_quantity.to_string()
+                                                                                    prop:value=move || addon_quantities.with(|q| q.get(&shown_code).copied().unwrap_or(0).to_string())
+                                                                                    on:change=move |e| {
+                                                                                        let quantity = event_target_value(&e)
+                                                                                            .parse::<u32>()
+                                                                                            .unwrap_or(0)
+                                                                                            .min(addon.max_quantity);
+                                                                                        addon_quantities.update(|q| {
+                                                                                            q.insert(code.clone(), quantity);
+                                                                                        });
+                                                                                    }
+                                                                                />
+                                                                            </label>
+                                                                        }
+                                                                    }).collect_view()}
+                                                                    <p class="addon-total">
+                                                                        {move || {
+                                                                            let (setup, monthly) = totals();
+                                                                            format!("Configured: ${setup:.2} USDC setup, ${monthly:.2} USDC/mo")
+                                                                        }}
+                                                                    </p>
+                                                                </div>
+                                                            }
+                                                        })
+                                                }}
                                             </div>
 
                                             <div class="package-actions">
@@ -675,6 +1209,251 @@ fn PackageDetail() -> impl IntoView {
     }
 }
 
+/// "Which box runs my model?": memory estimate and the packages that fit
+#[component]
+fn ModelFit() -> impl IntoView {
+    let (parameters_b, set_parameters_b) = create_signal("70".to_string());
+    let (quantization, set_quantization) = create_signal("q4".to_string());
+    let (context_length, set_context_length) = create_signal("32768".to_string());
+    let (concurrency, set_concurrency) = create_signal("1".to_string());
+    let (result, set_result) = create_signal(None::<Result<Recommendation, String>>);
+
+    let submit = move |_| {
+        let body = ModelFitRequest {
+            parameters_b: parameters_b.get().trim().parse().unwrap_or(0.0),
+            quantization: quantization.get(),
+            context_length: context_length.get().trim().parse().unwrap_or(0),
+            concurrency: concurrency.get().trim().parse().unwrap_or(0),
+        };
+        wasm_bindgen_futures::spawn_local(async move {
+            let url = format!("{}/api/recommend", api_base());
+            let resp = gloo_net::http::Request::post(&url)
+                .header("content-type", "application/json")
+                .body(serde_json::to_string(&body).unwrap())
+                .unwrap()
+                .send()
+                .await;
+
+            let outcome = match resp {
+                Ok(r) if r.status() == 200 => r.json::<Recommendation>().await.map_err(|_| {
+                    "Failed to load recommendations. Please try again later.".to_string()
+                }),
+                Ok(r) if r.status() == 400 => Err(r.text().await.unwrap_or_default()),
+                _ => Err("Failed to load recommendations. Please try again later.".to_string()),
+            };
+            set_result.set(Some(outcome));
+        });
+    };
+
+    view! {
+        <section class="model-fit-section">
+            <div class="section-header">
+                <h2 class="section-title">"Which Box Runs My Model?"</h2>
+                <p class="section-subtitle">
+                    "Estimate the memory a model needs for your context and concurrency, and see which packages fit."
+                </p>
+            </div>
+
+            <div class="model-fit-form">
+                <label>
+                    <span>"Parameters (billions)"</span>
+                    <input
+                        type="number"
+                        min="0"
+                        step="any"
+                        prop:value=parameters_b
+                        on:input=move |e| set_parameters_b.set(event_target_value(&e))
+                    />
+                </label>
+                <label>
+                    <span>"Quantization"</span>
+                    <select
+                        prop:value=quantization
+                        on:change=move |e| set_quantization.set(event_target_value(&e))
+                    >
+                        <option value="fp16">"FP16"</option>
+                        <option value="fp8">"FP8"</option>
+                        <option value="q8">"Q8"</option>
+                        <option value="q6">"Q6"</option>
+                        <option value="q5">"Q5"</option>
+                        <option value="q4">"Q4"</option>
+                        <option value="q3">"Q3"</option>
+                    </select>
+                </label>
+                <label>
+                    <span>"Context (tokens)"</span>
+                    <input
+                        type="number"
+                        min="1"
+                        prop:value=context_length
+                        on:input=move |e| set_context_length.set(event_target_value(&e))
+                    />
+                </label>
+                <label>
+                    <span>"Concurrent requests"</span>
+                    <input
+                        type="number"
+                        min="1"
+                        prop:value=concurrency
+                        on:input=move |e| set_concurrency.set(event_target_value(&e))
+                    />
+                </label>
+                <button class="cta-button primary" on:click=submit>"Find packages"</button>
+            </div>
+
+            {move || result.get().map(|outcome| match outcome {
+                Ok(recommendation) => {
+                    let estimate = recommendation.estimate;
+                    view! {
+                        <div class="model-fit-result">
+                            <p class="model-fit-estimate">
+                                {format!(
+                                    "Needs about {:.1} GB: {:.1} GB weights, {:.1} GB KV cache, {:.1} GB overhead.",
+                                    estimate.total_gb, estimate.weights_gb, estimate.kv_cache_gb, estimate.overhead_gb,
+                                )}
+                            </p>
+                            {if recommendation.packages.iter().any(|p| p.fits) {
+                                view! {}.into_view()
+                            } else {
+                                view! { <p class="empty-state">"No package has enough memory for this model."</p> }.into_view()
+                            }}
+                            <ul class="model-fit-packages">
+                                {recommendation.packages.into_iter().map(|fit| {
+                                    let class = if fit.fits { "model-fit-package fits" } else { "model-fit-package" };
+                                    view! {
+                                        <li class=class>
+                                            <A href={format!("/package/{}", fit.sku)}>{fit.name}</A>
+                                            <span>{format!("{} GB", fit.memory_gb)}</span>
+                                            <span>
+                                                {if fit.fits {
+                                                    format!("✅ {:.1} GB to spare", fit.headroom_gb)
+                                                } else {
+                                                    format!("❌ {:.1} GB short", -fit.headroom_gb)
+                                                }}
+                                            </span>
+                                            <span>{format!("from ${} USDC + ${} USDC/mo", fit.price_usdc, fit.monthly_price_usdc)}</span>
+                                        </li>
+                                    }
+                                }).collect::<Vec<_>>()}
+                            </ul>
+                        </div>
+                    }.into_view()
+                }
+                Err(message) => view! {
+                    <div class="error-state">
+                        <p>{message}</p>
+                    </div>
+                }.into_view(),
+            })}
+        </section>
+    }
+}
+
+#[component]
+fn Compare() -> impl IntoView {
+    let query = use_query_map();
+    let skus = move || query.with(|q| q.get("skus").cloned().unwrap_or_default());
+
+    let comparison = create_resource(skus, |skus| async move {
+        let url = format!("{}/api/packages/compare", api_base());
+        let resp = gloo_net::http::Request::get(&url)
+            .query([("skus", skus)])
+            .send()
+            .await;
+        match resp {
+            Ok(r) if r.status() == 200 => r
+                .json::<PackageComparison>()
+                .await
+                .map_err(|_| "Failed to load the comparison. Please try again later.".to_string()),
+            Ok(r) if r.status() == 400 => Err(r.text().await.unwrap_or_default()),
+            Ok(r) if r.status() == 404 => {
+                Err("One of the packages could not be found.".to_string())
+            }
+            _ => Err("Failed to load the comparison. Please try again later.".to_string()),
+        }
+    });
+
+    view! {
+        <div class="compare-container">
+            <h1 class="section-title">"Compare Packages"</h1>
+            <Suspense fallback=move || view! {
+                <div class="loading">
+                    <div class="spinner"></div>
+                    <p>"Loading comparison..."</p>
+                </div>
+            }>
+                {move || comparison.get().map(|result| match result {
+                    Ok(comparison) => {
+                        let leaders = comparison.leaders.clone();
+                        let packages = comparison.packages.clone();
+                        // One table row: a label and a cell per package, the
+                        // leader on that measure highlighted
+                        let row = move |label: &'static str,
+                                        leader: Option<String>,
+                                        cell: fn(&ComparedPackage) -> String| {
+                            view! {
+                                <tr>
+                                    <th scope="row">{label}</th>
+                                    {packages.iter().map(|pkg| {
+                                        let class = if leader.as_deref() == Some(pkg.sku.as_str()) { "leader" } else { "" };
+                                        view! { <td class=class>{cell(pkg)}</td> }
+                                    }).collect::<Vec<_>>()}
+                                </tr>
+                            }
+                        };
+
+                        view! {
+                            <table class="compare-table">
+                                <thead>
+                                    <tr>
+                                        <th></th>
+                                        {comparison.packages.iter().map(|pkg| view! {
+                                            <th scope="col">
+                                                <A href={format!("/package/{}", pkg.sku)}>{pkg.name.clone()}</A>
+                                            </th>
+                                        }).collect::<Vec<_>>()}
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    {row("CPU cores", leaders.most_cores.clone(), |p| p.cpu_cores.to_string())}
+                                    {row("RAM", None, |p| format!("{}GB", p.ram_gb))}
+                                    {row("Storage", None, |p| format!("{:.1}TB NVMe", p.storage_gb as f32 / 1000.0))}
+                                    {row("GPU", None, |p| format!("{}x {}", p.gpu_count, gpu_label(&p.gpu_class)))}
+                                    {row("VRAM", leaders.most_vram.clone(), |p| format!("{}GB", p.vram_gb))}
+                                    {row("Availability", None, |p| match &p.availability {
+                                        Availability::InStock => "In Stock".to_string(),
+                                        Availability::Preorder => "Preorder".to_string(),
+                                        Availability::Build { hours } => format!("{}h Build", hours),
+                                    })}
+                                    {row("Cheapest option", None, |p| match &p.cheapest_provenance {
+                                        Provenance::New => "New".to_string(),
+                                        Provenance::Used { hours } => format!("Used ({}h)", hours),
+                                    })}
+                                    {row("Hardware & Setup", leaders.cheapest.clone(), |p| format!("${} USDC", p.cheapest_price_usdc))}
+                                    {row("Monthly Hosting", None, |p| format!("${} USDC/mo", p.monthly_price_usdc))}
+                                    {row("Per GB of VRAM", leaders.lowest_price_per_vram_gb.clone(), |p| {
+                                        p.price_per_vram_gb_usdc
+                                            .as_ref()
+                                            .map(|price| format!("${} USDC", price))
+                                            .unwrap_or_else(|| "—".to_string())
+                                    })}
+                                    {row("Per CPU core", leaders.lowest_price_per_core.clone(), |p| format!("${} USDC", p.price_per_core_usdc))}
+                                </tbody>
+                            </table>
+                        }.into_view()
+                    }
+                    Err(message) => view! {
+                        <div class="error-state">
+                            <p>{message}</p>
+                        </div>
+                    }.into_view(),
+                })}
+            </Suspense>
+            <A href="/" class="back-link-center">"Back to Packages"</A>
+        </div>
+    }
+}
+
 fn Dashboard() -> impl IntoView {
     use serde::{Deserialize, Serialize};
 
@@ -743,6 +1522,7 @@ fn App() -> impl IntoView {
                         <Route path="/signup" view=Signup />
                         <Route path="/dashboard" view=Dashboard />
                         <Route path="/package/:sku" view=PackageDetail />
+                        <Route path="/compare" view=Compare />
                     </Routes>
                 </main>
             </Router>